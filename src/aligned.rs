@@ -0,0 +1,188 @@
+use std::alloc::{self, Layout};
+use std::io;
+use std::mem::{self, MaybeUninit};
+use std::ops::{Deref, DerefMut, RangeBounds};
+use std::ptr::NonNull;
+
+use watto::Pod;
+
+use crate::{make_range, Decompressor};
+
+/// A buffer of decompressed bytes starting at an aligned address, returned by
+/// [`Decompressor::get_aligned`].
+///
+/// The bytes can be reinterpreted as a slice of any [`watto::Pod`] type whose
+/// alignment does not exceed the alignment of the buffer, without copying them.
+pub struct AlignedBuf {
+    ptr: NonNull<u8>,
+    len: usize,
+    /// The layout of the allocation, which is at least one byte large.
+    layout: Layout,
+}
+
+// SAFETY: the buffer exclusively owns its allocation, like a `Vec<u8>`.
+unsafe impl Send for AlignedBuf {}
+// SAFETY: shared access only hands out shared references to the bytes.
+unsafe impl Sync for AlignedBuf {}
+
+impl AlignedBuf {
+    /// Allocates an uninitialized buffer of `len` bytes aligned to `align`.
+    fn new_uninit(len: usize, align: usize) -> io::Result<Self> {
+        let layout = Layout::from_size_align(len.max(1), align).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the alignment must be a power of two",
+            )
+        })?;
+        // SAFETY: the layout has a non-zero size.
+        let ptr = unsafe { alloc::alloc(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        Ok(Self { ptr, len, layout })
+    }
+
+    /// The alignment of the start of the buffer.
+    pub fn align(&self) -> usize {
+        self.layout.align()
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        // SAFETY: the allocation was created with this layout.
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the first `len` bytes are initialized once the buffer is handed out.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the first `len` bytes are initialized once the buffer is handed out.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl AsRef<[u8]> for AlignedBuf {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl std::fmt::Debug for AlignedBuf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AlignedBuf")
+            .field("len", &self.len)
+            .field("align", &self.align())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Decompressor<'_> {
+    /// Decompresses `range` into a new buffer starting at a multiple of `align` bytes.
+    ///
+    /// A [`Vec<u8>`] returned by [`Decompressor::get`] only guarantees an alignment
+    /// of one byte, so casting its contents to wider [`watto::Pod`] types may
+    /// fail, or require another copy. With an `align` of 8 or 16, the output can
+    /// be cast to slices of `u64` or `u128` directly. An error is returned if the
+    /// `range` is out of bounds, or `align` is not a power of two.
+    pub fn get_aligned<R>(&mut self, range: R, align: usize) -> io::Result<AlignedBuf>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.archive.input_len)?;
+        if range.start > range.end || range.end > self.archive.input_len {
+            return Err(self.out_of_bounds(range));
+        }
+        self.check_output_len(range.len())?;
+
+        let buf = AlignedBuf::new_uninit(range.len(), align)?;
+        // SAFETY: the allocation holds at least `len` bytes.
+        let uninit = unsafe { std::slice::from_raw_parts_mut(buf.ptr.as_ptr().cast(), buf.len) };
+        self.get_uninit(range, uninit)?;
+        // the buffer is only handed out once `get_uninit` initialized all of it
+        Ok(buf)
+    }
+
+    /// Reads the [`Pod`] value stored at `offset` of the uncompressed contents.
+    ///
+    /// This only decompresses the frames covering `offset..offset + size_of::<T>()`,
+    /// and thus allows random access into compressed binary tables. The value
+    /// is read in its native byte order, just like a [`Pod`] cast of the bytes,
+    /// but does not need to be aligned within the archive. An error is returned
+    /// if the value extends past the end of the archive.
+    pub fn get_as<T: Pod>(&mut self, offset: usize) -> io::Result<T> {
+        let end = offset.checked_add(mem::size_of::<T>());
+        let range = offset..end.unwrap_or(usize::MAX);
+        let mut value = MaybeUninit::<T>::uninit();
+        // SAFETY: the bytes of the value are only read after `get_uninit` initialized them.
+        let bytes = unsafe {
+            std::slice::from_raw_parts_mut(value.as_mut_ptr().cast(), mem::size_of::<T>())
+        };
+        self.get_uninit(range, bytes)?;
+        // SAFETY: all bytes are initialized, and any bit pattern is a valid `Pod`.
+        Ok(unsafe { value.assume_init() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Compressor;
+
+    #[test]
+    fn test_get_aligned() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+
+        for align in [1, 8, 16, 64, 4096] {
+            for range in [0..0, 1..2, 999..1001, 2501..7500, 0..10_000] {
+                let buf = d.get_aligned(range.clone(), align).unwrap();
+                assert_eq!(buf.as_ptr() as usize % align, 0);
+                assert_eq!(buf.align(), align);
+                assert_eq!(&*buf, &input[range]);
+            }
+        }
+
+        let buf = d.get_aligned(8..808, 8).unwrap();
+        let values = u64::slice_from_bytes(&buf).unwrap();
+        assert_eq!(values.len(), 100);
+        assert_eq!(values[0].to_le_bytes(), input[8..16]);
+
+        assert!(d.get_aligned(0..10, 3).is_err());
+        assert!(d.get_aligned(9_000..10_001, 8).is_err());
+        let mut d = d.max_output_bytes(100);
+        assert!(d.get_aligned(0..101, 8).is_err());
+    }
+
+    #[test]
+    fn test_get_as() {
+        let table: Vec<u64> = (0..10_000u64).map(|i| i * i).collect();
+        let input: Vec<u8> = table.iter().flat_map(|value| value.to_ne_bytes()).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+
+        // values spanning a frame boundary, as 1000 is not a multiple of 8
+        for idx in [0, 124, 125, 5_000, 9_999] {
+            assert_eq!(d.get_as::<u64>(idx * 8).unwrap(), table[idx]);
+        }
+        assert_eq!(
+            d.get_as::<[u32; 3]>(4).unwrap(),
+            [table[0] >> 32, table[1], table[1] >> 32].map(|value| value as u32)
+        );
+        assert_eq!(
+            d.get_as::<u8>(input.len() - 1).unwrap(),
+            input[input.len() - 1]
+        );
+
+        assert!(d.get_as::<u64>(input.len() - 4).is_err());
+        assert!(d.get_as::<u64>(usize::MAX - 2).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+//! A container bundling multiple named archives into a single buffer.
+//!
+//! The container starts with a header, followed by a directory of all the
+//! members and the members themselves:
+//!
+//! - `magic: [u8; 4]`: The [`MAGIC`] bytes identifying a container.
+//! - `version: u32`: The container [`VERSION`].
+//! - `num_members: u32`, followed by 4 bytes of padding.
+//! - For each member, its `u64` offset relative to the start of the container,
+//!   its `u64` length, and its name as a `u32` length and the UTF-8 bytes,
+//!   padded to a multiple of 8 bytes.
+//!
+//! Each member is a complete archive, starting at an offset that is a multiple
+//! of 8 bytes, so that it is aligned just like a standalone archive.
+//!
+//! All integers are stored in little-endian byte order.
+
+use std::mem;
+
+use crate::{Compressor, Decompressor, Error};
+
+/// The magic bytes at the start of every container.
+const MAGIC: [u8; 4] = *b"SeeA";
+/// The current container version.
+const VERSION: u32 = 1;
+/// The alignment of the directory entries and members.
+const ALIGN: usize = mem::size_of::<u64>();
+
+/// Bundles multiple named archives into a single buffer, which can be read
+/// with an [`ArchiveReader`].
+///
+/// Each member has its own frame offset table, and can use different
+/// compression settings.
+#[derive(Debug, Default)]
+pub struct ArchiveBuilder {
+    members: Vec<(String, Vec<u8>)>,
+}
+
+impl ArchiveBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compresses `input` with the given `compressor`, and adds it as the member `name`.
+    ///
+    /// A previously added member of the same name is replaced.
+    pub fn add(
+        &mut self,
+        name: impl Into<String>,
+        compressor: &mut Compressor<'_>,
+        input: &[u8],
+    ) -> Result<(), Error> {
+        let archive = compressor.compress(input)?;
+        self.add_archive(name, archive);
+        Ok(())
+    }
+
+    /// Adds an already compressed `archive` as the member `name`.
+    ///
+    /// A previously added member of the same name is replaced.
+    pub fn add_archive(&mut self, name: impl Into<String>, archive: Vec<u8>) {
+        let name = name.into();
+        match self.members.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, existing)) => *existing = archive,
+            None => self.members.push((name, archive)),
+        }
+    }
+
+    /// Writes all the members into a new container.
+    pub fn finish(self) -> Vec<u8> {
+        let directory_size: usize = self.members.iter().map(|(name, _)| entry_size(name)).sum();
+        let mut offset = 4 * mem::size_of::<u32>() + directory_size;
+
+        let mut buf = Vec::with_capacity(
+            offset
+                + self
+                    .members
+                    .iter()
+                    .map(|(_, archive)| archive.len().next_multiple_of(ALIGN))
+                    .sum::<usize>(),
+        );
+        buf.extend_from_slice(&MAGIC);
+        buf.extend_from_slice(&VERSION.to_le_bytes());
+        buf.extend_from_slice(&(self.members.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes());
+
+        for (name, archive) in &self.members {
+            buf.extend_from_slice(&(offset as u64).to_le_bytes());
+            buf.extend_from_slice(&(archive.len() as u64).to_le_bytes());
+            buf.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            buf.extend_from_slice(name.as_bytes());
+            buf.resize(buf.len().next_multiple_of(ALIGN), 0);
+            offset += archive.len().next_multiple_of(ALIGN);
+        }
+        for (_, archive) in &self.members {
+            buf.extend_from_slice(archive);
+            buf.resize(buf.len().next_multiple_of(ALIGN), 0);
+        }
+        buf
+    }
+}
+
+/// The size of the directory entry for the member `name`, including padding.
+fn entry_size(name: &str) -> usize {
+    (2 * mem::size_of::<u64>() + mem::size_of::<u32>() + name.len()).next_multiple_of(ALIGN)
+}
+
+/// Provides access to the members of a container created by an [`ArchiveBuilder`].
+#[derive(Debug)]
+pub struct ArchiveReader<'b> {
+    members: Vec<(&'b str, &'b [u8])>,
+}
+
+impl<'b> ArchiveReader<'b> {
+    /// Parses the directory of the container, returning `None` if it is invalid.
+    pub fn new(bytes: &'b [u8]) -> Option<Self> {
+        let rest = bytes.strip_prefix(&MAGIC)?;
+        let (version, rest) = read_u32(rest)?;
+        if version != VERSION {
+            return None;
+        }
+        let (num_members, rest) = read_u32(rest)?;
+        let (_padding, mut rest) = read_u32(rest)?;
+
+        let mut members = Vec::with_capacity((num_members as usize).min(rest.len() / ALIGN));
+        for _ in 0..num_members {
+            let entry = rest;
+            let (offset, tail) = read_u64(entry)?;
+            let (len, tail) = read_u64(tail)?;
+            let (name_len, tail) = read_u32(tail)?;
+            let name = tail.get(..name_len as usize)?;
+            let name = std::str::from_utf8(name).ok()?;
+            rest = entry.get(entry_size(name)..)?;
+
+            let offset: usize = offset.try_into().ok()?;
+            let end = offset.checked_add(len.try_into().ok()?)?;
+            if !offset.is_multiple_of(ALIGN) {
+                return None;
+            }
+            members.push((name, bytes.get(offset..end)?));
+        }
+        Some(Self { members })
+    }
+
+    /// Returns a [`Decompressor`] for the member `name`, or `None` if there is
+    /// no such member, or it is not a valid archive.
+    pub fn member(&self, name: &str) -> Option<Decompressor<'b>> {
+        let archive = self.member_bytes(name)?;
+        Decompressor::new(archive).ok()
+    }
+
+    /// Returns the raw archive of the member `name`.
+    pub fn member_bytes(&self, name: &str) -> Option<&'b [u8]> {
+        self.members
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, archive)| *archive)
+    }
+
+    /// Iterates over the names of all members, in the order they were added.
+    pub fn names(&self) -> impl Iterator<Item = &'b str> + '_ {
+        self.members.iter().map(|(name, _)| *name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> Option<(u32, &[u8])> {
+    let (value, bytes) = bytes.split_first_chunk::<4>()?;
+    Some((u32::from_le_bytes(*value), bytes))
+}
+
+fn read_u64(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let (value, bytes) = bytes.split_first_chunk::<8>()?;
+    Some((u64::from_le_bytes(*value), bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Checksum;
+
+    #[test]
+    fn test_members() {
+        let text: Vec<u8> = (0..10_000u32).map(|i| (i % 13) as u8).collect();
+        let binary: Vec<u8> = (0..1_000u32).flat_map(|i| i.to_le_bytes()).collect();
+
+        let mut builder = ArchiveBuilder::new();
+        builder
+            .add("text", &mut Compressor::new().frame_size(1000), &text)
+            .unwrap();
+        let mut compressor = Compressor::new()
+            .frame_size(512)
+            .wide_offsets(true)
+            .checksum(Checksum::Crc32);
+        builder.add("binary", &mut compressor, &binary).unwrap();
+        builder.add("empty", &mut compressor, &[]).unwrap();
+        builder.add("replaced", &mut compressor, &text).unwrap();
+        builder.add("replaced", &mut compressor, &binary).unwrap();
+        let container = builder.finish();
+
+        let reader = ArchiveReader::new(&container).unwrap();
+        assert_eq!(reader.len(), 4);
+        let names: Vec<_> = reader.names().collect();
+        assert_eq!(names, ["text", "binary", "empty", "replaced"]);
+
+        let mut d = reader.member("text").unwrap();
+        assert_eq!(d.frame_size(), 1000);
+        assert_eq!(d.get(1234..5678).unwrap(), &text[1234..5678]);
+        let mut d = reader.member("binary").unwrap();
+        assert_eq!(d.get(..).unwrap(), binary);
+        let mut d = reader.member("empty").unwrap();
+        assert_eq!(d.get(..).unwrap(), &[]);
+        let mut d = reader.member("replaced").unwrap();
+        assert_eq!(d.get(..).unwrap(), binary);
+        assert!(reader.member("missing").is_none());
+
+        let empty = ArchiveBuilder::new().finish();
+        assert!(ArchiveReader::new(&empty).unwrap().is_empty());
+
+        assert!(ArchiveReader::new(&container[..100]).is_none());
+        assert!(ArchiveReader::new(&container[4..]).is_none());
+    }
+}
@@ -0,0 +1,1348 @@
+use std::future::Future;
+use std::io::{self, SeekFrom};
+use std::mem::ManuallyDrop;
+use std::ops::{Range, RangeBounds};
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+use crate::cache::FrameCache;
+use crate::format::Archive;
+use crate::{eof, make_range, CompressWriter, Decompressor, Storage};
+
+/// The asynchronous counterpart of [`Storage`], holding the bytes of an archive.
+///
+/// This only depends on [`Future`] of the standard library, so it can be
+/// implemented on top of any async runtime, for example using `tokio::fs::File`
+/// or an async HTTP or object store client. Readers implementing the `AsyncRead`
+/// and `AsyncSeek` traits of `futures-io`, as used by `smol` and others, only
+/// need to seek to the start of the range and read it to the end.
+///
+/// With the `tokio` or `futures-io` features, an [`AsyncCursor`] implements the
+/// `AsyncRead` and `AsyncSeek` traits of `tokio` or `futures-io`.
+///
+/// The futures of [`AsyncDecompressor`] are [`Send`], and do not rely on a
+/// runtime themselves, so they can be polled by any executor.
+pub trait AsyncStorage: Send + Sync {
+    /// The total size of the archive in bytes.
+    fn len(&self) -> u64;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads the bytes in `range`.
+    ///
+    /// Ranges that are out of bounds result in an
+    /// [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) error.
+    fn read(&self, range: Range<u64>) -> impl Future<Output = io::Result<Vec<u8>>> + Send;
+}
+
+impl<S: AsyncStorage + ?Sized> AsyncStorage for &S {
+    fn len(&self) -> u64 {
+        (**self).len()
+    }
+
+    fn read(&self, range: Range<u64>) -> impl Future<Output = io::Result<Vec<u8>>> + Send {
+        (**self).read(range)
+    }
+}
+
+impl<S: AsyncStorage + ?Sized> AsyncStorage for Arc<S> {
+    fn len(&self) -> u64 {
+        (**self).len()
+    }
+
+    fn read(&self, range: Range<u64>) -> impl Future<Output = io::Result<Vec<u8>>> + Send {
+        (**self).read(range)
+    }
+}
+
+/// An asynchronous sink of bytes, which an [`AsyncCompressWriter`] writes the
+/// archive to.
+///
+/// Like [`AsyncStorage`], this only depends on [`Future`] of the standard library,
+/// and is implemented on top of the `AsyncWrite` of any async runtime.
+pub trait AsyncSink: Send {
+    /// Writes all of `bytes` to the sink.
+    fn write_all(&mut self, bytes: &[u8]) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Flushes any bytes buffered by the sink. The default does nothing.
+    fn flush(&mut self) -> impl Future<Output = io::Result<()>> + Send {
+        async { Ok(()) }
+    }
+}
+
+impl AsyncSink for Vec<u8> {
+    async fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+impl<W: AsyncSink + ?Sized> AsyncSink for &mut W {
+    fn write_all(&mut self, bytes: &[u8]) -> impl Future<Output = io::Result<()>> + Send {
+        (**self).write_all(bytes)
+    }
+
+    fn flush(&mut self) -> impl Future<Output = io::Result<()>> + Send {
+        (**self).flush()
+    }
+}
+
+/// The asynchronous counterpart of [`CompressWriter`], writing the archive to
+/// an [`AsyncSink`].
+///
+/// Created via [`Compressor::async_writer`](crate::Compressor::async_writer).
+/// Input chunks are compressed as soon as they complete a frame. With a
+/// [`trailer_index`](crate::Compressor::trailer_index), the compressed frames
+/// are written to the sink right away, so an upload can start while later input
+/// is still being produced. Otherwise, the whole archive is written on
+/// [`AsyncCompressWriter::finish`].
+pub struct AsyncCompressWriter<'d, W> {
+    /// The writer compressing into a buffer, which is drained into the sink.
+    writer: CompressWriter<'d, Vec<u8>>,
+    sink: W,
+}
+
+impl<'d, W: AsyncSink> AsyncCompressWriter<'d, W> {
+    pub(crate) fn new(writer: CompressWriter<'d, Vec<u8>>, sink: W) -> Self {
+        Self { writer, sink }
+    }
+
+    /// Compresses `input`, writing any completed frames to the sink.
+    pub async fn write(&mut self, input: &[u8]) -> io::Result<()> {
+        io::Write::write_all(&mut self.writer, input)?;
+        let buf = self.writer.get_mut();
+        if !buf.is_empty() {
+            self.sink.write_all(buf).await?;
+            buf.clear();
+        }
+        Ok(())
+    }
+
+    /// Compresses any remaining input, and writes the rest of the archive to
+    /// the sink, which is flushed and returned.
+    pub async fn finish(self) -> io::Result<W> {
+        let Self { writer, mut sink } = self;
+        let buf = writer.finish()?;
+        sink.write_all(&buf).await?;
+        sink.flush().await?;
+        Ok(sink)
+    }
+}
+
+/// The default number of concurrent fetches of an [`AsyncDecompressor`].
+const DEFAULT_CONCURRENT_FETCHES: usize = 8;
+
+/// A [`Decompressor`] reading archives from an [`AsyncStorage`].
+///
+/// All reads of a range first fetch the compressed frames it needs from the
+/// storage asynchronously, and then decompress them. Like with
+/// [`Decompressor::with_storage`], only the index of the archive is read up front.
+///
+/// Adjacent compressed frames are fetched with a single read, and the reads
+/// of frames that are not adjacent, for example because the frames in between
+/// are cached, run concurrently, which minimizes round trips to remote storage.
+///
+/// The decompression itself runs on the thread polling the future, unless it is
+/// moved to a blocking thread pool with [`AsyncDecompressor::offload_blocking`].
+pub struct AsyncDecompressor<'b, S> {
+    storage: S,
+    /// The fetched ranges of the storage, which the decompressor reads from.
+    staged: Arc<Staged>,
+    shared: Arc<Shared<'b>>,
+    concurrent_fetches: usize,
+    offload: Option<Offload<'b>>,
+    /// The offloaded decompression of a cancelled read, which may still hold
+    /// the lock of the decompressor.
+    in_flight: Option<Receiver<io::Result<Vec<u8>>>>,
+    /// The uncompressed size and frame size, which are read without locking.
+    len: usize,
+    frame_size: usize,
+}
+
+/// Decompresses a range on a blocking thread pool.
+type Offload<'b> = Box<dyn Fn(Range<usize>) -> Receiver<io::Result<Vec<u8>>> + Send + Sync + 'b>;
+
+impl<S: std::fmt::Debug> std::fmt::Debug for AsyncDecompressor<'_, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncDecompressor")
+            .field("storage", &self.storage)
+            .field("len", &self.len)
+            .field("frame_size", &self.frame_size)
+            .field("concurrent_fetches", &self.concurrent_fetches)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: AsyncStorage> AsyncDecompressor<'static, S> {
+    /// Opens the archive in `storage`, reading only its index, which is owned
+    /// by the returned decompressor.
+    ///
+    /// In contrast to [`AsyncDecompressor::new`], the decompressor does not
+    /// borrow anything, so it can be stored in long-lived structs and use
+    /// [`AsyncDecompressor::offload_blocking`].
+    pub async fn open(storage: S) -> io::Result<Self> {
+        /// Frees the index, unless opening the archive succeeded.
+        struct Guard(Option<NonNull<Vec<u8>>>);
+
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                if let Some(index) = self.0 {
+                    // SAFETY: the index was allocated below, and is not borrowed anymore
+                    drop(unsafe { Box::from_raw(index.as_ptr()) });
+                }
+            }
+        }
+
+        let index = NonNull::from(Box::leak(Box::default()));
+        let mut guard = Guard(Some(index));
+        // SAFETY: the index is only freed after the decompressor borrowing it,
+        // either by the guard or by `Shared`
+        let mut decompressor = Self::new(storage, unsafe { &mut *index.as_ptr() }).await?;
+        let shared = Arc::get_mut(&mut decompressor.shared).expect("not shared yet");
+        shared.index = guard.0.take();
+        Ok(decompressor)
+    }
+
+    /// Runs the decompression of every read as a task spawned with `spawn_blocking`,
+    /// like [`tokio::task::spawn_blocking`], instead of on the thread polling the future.
+    ///
+    /// Decompressing large ranges is CPU heavy, and would otherwise stall the
+    /// other tasks of the async runtime. This is only available for decompressors
+    /// that do not borrow anything, as the tasks have to be `'static`.
+    ///
+    /// A task keeps running when its read is cancelled, and the next read
+    /// waits for it to complete before it touches the decompressor again.
+    ///
+    /// [`tokio::task::spawn_blocking`]: https://docs.rs/tokio/latest/tokio/task/fn.spawn_blocking.html
+    pub fn offload_blocking<F>(mut self, spawn_blocking: F) -> Self
+    where
+        F: Fn(Box<dyn FnOnce() + Send>) + Send + Sync + 'static,
+    {
+        let shared = Arc::clone(&self.shared);
+        self.offload = Some(Box::new(move |range| {
+            let (sender, receiver) = channel();
+            let shared = Arc::clone(&shared);
+            spawn_blocking(Box::new(move || {
+                let result = shared.lock().get(range);
+                sender.send(result);
+            }));
+            receiver
+        }));
+        self
+    }
+}
+
+impl<'b, S: AsyncStorage> AsyncDecompressor<'b, S> {
+    /// Opens the archive in `storage`, reading only its index into `index`.
+    ///
+    /// An invalid archive results in an error wrapping the [`Error`](crate::Error)
+    /// that describes the problem.
+    pub async fn new(storage: S, index: &'b mut Vec<u8>) -> io::Result<Self> {
+        let staged = Arc::new(Staged::new(storage.len()));
+        // the index is parsed again with every fetched range, until all reads succeed
+        loop {
+            let mut scratch = Vec::new();
+            let Err(err) = Archive::read_index(&*staged, &mut scratch) else {
+                break;
+            };
+            let missing = staged.take_missing();
+            if missing.is_empty() {
+                return Err(err);
+            }
+            fetch(&storage, &staged, missing, DEFAULT_CONCURRENT_FETCHES).await?;
+        }
+        let decompressor = Decompressor::with_storage(Arc::clone(&staged), index)?;
+        staged.clear();
+        let (len, frame_size) = (decompressor.len(), decompressor.frame_size());
+        Ok(Self {
+            storage,
+            staged,
+            shared: Arc::new(Shared {
+                decompressor: ManuallyDrop::new(Mutex::new(decompressor)),
+                index: None,
+            }),
+            concurrent_fetches: DEFAULT_CONCURRENT_FETCHES,
+            offload: None,
+            in_flight: None,
+            len,
+            frame_size,
+        })
+    }
+
+    /// Enables a cache of up to `budget` bytes of decompressed frames, whose
+    /// compressed frames are not fetched again. See [`Decompressor::frame_cache`].
+    pub fn frame_cache(self, budget: usize) -> Self {
+        self.shared.lock().cache = FrameCache::new(budget);
+        self
+    }
+
+    /// Limits the number of reads from the storage that run concurrently,
+    /// which defaults to 8.
+    pub fn concurrent_fetches(mut self, limit: usize) -> Self {
+        self.concurrent_fetches = limit.max(1);
+        self
+    }
+
+    /// Returns the wrapped storage.
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+
+    /// The total uncompressed size of the archive.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub async fn get<R>(&mut self, range: R) -> io::Result<Vec<u8>>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.len)?;
+        self.stage(range.clone()).await?;
+        let result = match &self.offload {
+            Some(offload) => {
+                let result = self.in_flight.insert(offload(range)).await;
+                self.in_flight = None;
+                result
+            }
+            None => self.shared.lock().get(range),
+        };
+        self.staged.clear();
+        result
+    }
+
+    /// Reads the uncompressed bytes at `offset` into `buf`, returning how many
+    /// bytes were read, which is only less than requested at the end of the archive.
+    pub async fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> io::Result<usize> {
+        let end = offset.saturating_add(buf.len()).min(self.len());
+        if self.offload.is_some() {
+            let output = self.get(offset.min(end)..end).await?;
+            buf[..output.len()].copy_from_slice(&output);
+            return Ok(output.len());
+        }
+        self.stage(offset.min(end)..end).await?;
+        let result = self.shared.lock().read_at(offset, buf);
+        self.staged.clear();
+        result
+    }
+
+    /// Returns an [`AsyncChunks`] stream decompressing `range` one frame at a time.
+    ///
+    /// Each frame is only fetched and decompressed when the next chunk is
+    /// requested, so the range is never held in memory as a whole, and a slow
+    /// consumer, like an HTTP response body, applies backpressure.
+    pub fn stream<R>(&mut self, range: R) -> AsyncChunks<'_, 'b, S>
+    where
+        R: RangeBounds<usize>,
+    {
+        let (range, error) = match make_range(range, self.len()) {
+            Ok(range) => (range, None),
+            Err(err) => (0..0, Some(err)),
+        };
+        AsyncChunks {
+            decompressor: Some(self),
+            reading: None,
+            range,
+            error,
+        }
+    }
+
+    /// Returns an [`AsyncCursor`] reading and seeking within the uncompressed
+    /// contents of the archive, which takes ownership of the decompressor.
+    pub fn into_cursor(self) -> AsyncCursor<'b, S>
+    where
+        S: 'b,
+    {
+        AsyncCursor {
+            len: self.len(),
+            decompressor: Some(self),
+            reading: None,
+            pos: 0,
+            chunk: Vec::new(),
+            chunk_start: 0,
+        }
+    }
+
+    /// Fetches the compressed frames covering `range` that are not cached.
+    async fn stage(&mut self, range: Range<usize>) -> io::Result<()> {
+        if let Some(in_flight) = &mut self.in_flight {
+            // the result of the cancelled read is not needed anymore
+            let _ = in_flight.await;
+            self.in_flight = None;
+            self.staged.clear();
+        }
+        let mut spans: Vec<Range<u64>> = Vec::new();
+        {
+            let decompressor = self.shared.lock();
+            for (idx, span) in decompressor.compressed_frames_for(range)? {
+                let cached = decompressor.cache.get(idx, |_| ()).is_some();
+                // frames of the same group share their span
+                if !span.is_empty() && !cached && spans.last() != Some(&span) {
+                    spans.push(span);
+                }
+            }
+        }
+        fetch(&self.storage, &self.staged, spans, self.concurrent_fetches).await
+    }
+}
+
+/// A stream of the decompressed frames of a range, created by [`AsyncDecompressor::stream`].
+///
+/// Chunks are pulled with [`AsyncChunks::next`] or [`AsyncChunks::poll_next`].
+/// With the `stream` feature, this implements the `Stream` trait of
+/// `futures-core`, yielding the chunks as `bytes::Bytes`, which the body
+/// streams of HTTP servers like `hyper` or `axum` accept.
+pub struct AsyncChunks<'a, 'b, S> {
+    /// The decompressor, unless it is owned by a read in progress.
+    decompressor: Option<&'a mut AsyncDecompressor<'b, S>>,
+    reading: Option<ReadFrame<'a, 'b, S>>,
+    /// The remaining part of the range.
+    range: Range<usize>,
+    /// The error of a range whose bounds overflow, which is the only chunk.
+    error: Option<crate::Error>,
+}
+
+/// The read of a chunk by an [`AsyncChunks`], which borrows the decompressor
+/// until it completes, and resolves to the end of the chunk.
+type ReadFrame<'a, 'b, S> = Pin<
+    Box<
+        dyn Future<Output = (&'a mut AsyncDecompressor<'b, S>, usize, io::Result<Vec<u8>>)>
+            + Send
+            + 'a,
+    >,
+>;
+
+impl<S> std::fmt::Debug for AsyncChunks<'_, '_, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncChunks")
+            .field("range", &self.range)
+            .field("error", &self.error)
+            .field("reading", &self.reading.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'b, S: AsyncStorage + 'b> AsyncChunks<'_, 'b, S> {
+    /// Returns the next chunk, which extends to the end of its frame, or
+    /// `None` at the end of the range. The stream ends after the first error.
+    pub async fn next(&mut self) -> Option<io::Result<Vec<u8>>> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+
+    /// Polls for the next chunk, like `Stream::poll_next`, see [`AsyncChunks::next`].
+    ///
+    /// A read that is not polled to completion, for example because the future
+    /// of [`next`](Self::next) was dropped, continues with the next poll.
+    pub fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<io::Result<Vec<u8>>>> {
+        let this = self.get_mut();
+        if let Some(err) = this.error.take() {
+            return Poll::Ready(Some(Err(err.into())));
+        }
+        let reading = match &mut this.reading {
+            Some(reading) => reading,
+            None => {
+                if this.range.start >= this.range.end {
+                    return Poll::Ready(None);
+                }
+                let decompressor = this.decompressor.take().expect("not reading");
+                let start = this.range.start;
+                let frame_size = decompressor.frame_size;
+                let frame_end = (start - start % frame_size).saturating_add(frame_size);
+                let end = this.range.end.min(frame_end);
+                this.reading.insert(Box::pin(async move {
+                    let chunk = decompressor.get(start..end).await;
+                    (decompressor, end, chunk)
+                }))
+            }
+        };
+        let Poll::Ready((decompressor, end, chunk)) = reading.as_mut().poll(cx) else {
+            return Poll::Pending;
+        };
+        this.reading = None;
+        this.decompressor = Some(decompressor);
+        this.range.start = if chunk.is_ok() { end } else { this.range.end };
+        Poll::Ready(Some(chunk))
+    }
+}
+
+#[cfg(feature = "stream")]
+impl<'b, S: AsyncStorage + 'b> futures_core::Stream for AsyncChunks<'_, 'b, S> {
+    type Item = io::Result<bytes::Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        AsyncChunks::poll_next(self, cx).map(|chunk| chunk.map(|chunk| chunk.map(Into::into)))
+    }
+}
+
+/// The read of a chunk by an [`AsyncCursor`], which owns the decompressor until
+/// it completes, and resolves to the start of the chunk.
+type ReadChunk<'b, S> = Pin<
+    Box<dyn Future<Output = (AsyncDecompressor<'b, S>, usize, io::Result<Vec<u8>>)> + Send + 'b>,
+>;
+
+/// An asynchronous reader over the uncompressed contents of an archive,
+/// created by [`AsyncDecompressor::into_cursor`].
+///
+/// This is the asynchronous counterpart of [`Cursor`](crate::Cursor). With the
+/// `tokio` or `futures-io` features, it implements the `AsyncRead` and
+/// `AsyncSeek` traits of those crates, which forward to
+/// [`poll_read`](Self::poll_read) and [`poll_seek`](Self::poll_seek). Each read decompresses the rest of the frame
+/// at the current position, which is kept around for the following reads.
+pub struct AsyncCursor<'b, S> {
+    /// The decompressor, unless it is owned by a read in progress.
+    decompressor: Option<AsyncDecompressor<'b, S>>,
+    reading: Option<ReadChunk<'b, S>>,
+    len: usize,
+    pos: u64,
+    /// The most recently read chunk, starting at `chunk_start`.
+    chunk: Vec<u8>,
+    chunk_start: usize,
+}
+
+// the cursor is never pinned structurally, the pending read is boxed
+impl<S> Unpin for AsyncCursor<'_, S> {}
+
+impl<S> std::fmt::Debug for AsyncCursor<'_, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncCursor")
+            .field("len", &self.len)
+            .field("pos", &self.pos)
+            .field("reading", &self.reading.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'b, S: AsyncStorage + 'b> AsyncCursor<'b, S> {
+    /// The current position within the uncompressed contents.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Reads into `buf` at the current position, like `AsyncRead::poll_read`,
+    /// returning how many bytes were read, which is zero at the end of the archive.
+    pub fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            let pos = usize::try_from(this.pos).unwrap_or(usize::MAX);
+            let buffered = pos
+                .checked_sub(this.chunk_start)
+                .and_then(|offset| this.chunk.get(offset..))
+                .filter(|buffered| !buffered.is_empty());
+            if let Some(buffered) = buffered {
+                let len = buffered.len().min(buf.len());
+                buf[..len].copy_from_slice(&buffered[..len]);
+                this.pos += len as u64;
+                return Poll::Ready(Ok(len));
+            }
+            if buf.is_empty() || pos >= this.len {
+                return Poll::Ready(Ok(0));
+            }
+
+            let reading = match &mut this.reading {
+                Some(reading) => reading,
+                None => {
+                    let mut decompressor = this.decompressor.take().expect("not reading");
+                    let frame_size = decompressor.frame_size;
+                    let end = (pos - pos % frame_size)
+                        .saturating_add(frame_size)
+                        .min(this.len);
+                    this.reading.insert(Box::pin(async move {
+                        let result = decompressor.get(pos..end).await;
+                        (decompressor, pos, result)
+                    }))
+                }
+            };
+            let Poll::Ready((decompressor, start, result)) = reading.as_mut().poll(cx) else {
+                return Poll::Pending;
+            };
+            this.reading = None;
+            this.decompressor = Some(decompressor);
+            this.chunk = result?;
+            this.chunk_start = start;
+        }
+    }
+
+    /// Seeks to `pos`, like `AsyncSeek::poll_seek`, which completes right away.
+    ///
+    /// Seeking while a read is pending is allowed, the next read then continues
+    /// at the new position.
+    pub fn poll_seek(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        Poll::Ready(self.get_mut().seek(pos))
+    }
+
+    /// Reads into `buf` at the current position, see [`AsyncCursor::poll_read`].
+    pub async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_read(cx, buf)).await
+    }
+
+    /// Seeks to `pos`, returning the new position.
+    pub fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(pos) => {
+                self.pos = pos;
+                return Ok(pos);
+            }
+            SeekFrom::End(offset) => (self.len as u64, offset),
+            SeekFrom::Current(offset) => (self.pos, offset),
+        };
+        self.pos = base.checked_add_signed(offset).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+
+    /// Returns the decompressor, waiting for a pending read to complete first.
+    pub async fn into_inner(mut self) -> AsyncDecompressor<'b, S> {
+        match self.reading.take() {
+            Some(reading) => reading.await.0,
+            None => self.decompressor.take().expect("not reading"),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<'b, S: AsyncStorage + 'b> tokio::io::AsyncRead for AsyncCursor<'b, S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let len = std::task::ready!(AsyncCursor::poll_read(self, cx, buf.initialize_unfilled()))?;
+        buf.advance(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<'b, S: AsyncStorage + 'b> tokio::io::AsyncSeek for AsyncCursor<'b, S> {
+    fn start_seek(self: Pin<&mut Self>, pos: SeekFrom) -> io::Result<()> {
+        self.get_mut().seek(pos).map(drop)
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(self.pos))
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<'b, S: AsyncStorage + 'b> futures_io::AsyncRead for AsyncCursor<'b, S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        AsyncCursor::poll_read(self, cx, buf)
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<'b, S: AsyncStorage + 'b> futures_io::AsyncSeek for AsyncCursor<'b, S> {
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        AsyncCursor::poll_seek(self, cx, pos)
+    }
+}
+
+/// The decompressor of an [`AsyncDecompressor`], which is shared with the tasks
+/// decompressing off the runtime.
+struct Shared<'b> {
+    decompressor: ManuallyDrop<Mutex<Decompressor<'b>>>,
+    /// The index owned by decompressors created with [`AsyncDecompressor::open`],
+    /// which the decompressor borrows from.
+    index: Option<NonNull<Vec<u8>>>,
+}
+
+// SAFETY: the index is only accessed through the decompressor
+unsafe impl<'b> Send for Shared<'b> where Mutex<Decompressor<'b>>: Send {}
+unsafe impl<'b> Sync for Shared<'b> where Mutex<Decompressor<'b>>: Sync {}
+
+impl<'b> Shared<'b> {
+    /// Locks the decompressor, which stays usable after a panic.
+    fn lock(&self) -> MutexGuard<'_, Decompressor<'b>> {
+        self.decompressor
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Drop for Shared<'_> {
+    fn drop(&mut self) {
+        // SAFETY: the decompressor is dropped before the index it borrows from,
+        // which was allocated in `AsyncDecompressor::open`
+        unsafe {
+            ManuallyDrop::drop(&mut self.decompressor);
+            if let Some(index) = self.index {
+                drop(Box::from_raw(index.as_ptr()));
+            }
+        }
+    }
+}
+
+/// The state shared by the [`Sender`] and [`Receiver`] of a [`channel`].
+struct Channel<T> {
+    value: Option<T>,
+    /// Whether the sender has been dropped.
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+/// Sends the single value of a [`channel`].
+struct Sender<T>(Arc<Mutex<Channel<T>>>);
+
+/// A future resolving to the value sent through a [`channel`].
+struct Receiver<T>(Arc<Mutex<Channel<T>>>);
+
+/// Creates a channel for sending a single value to a future, like the result
+/// of a task on another thread.
+fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let channel = Arc::new(Mutex::new(Channel {
+        value: None,
+        closed: false,
+        waker: None,
+    }));
+    (Sender(Arc::clone(&channel)), Receiver(channel))
+}
+
+impl<T> Sender<T> {
+    fn send(self, value: T) {
+        self.0.lock().unwrap().value = Some(value);
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut channel = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        channel.closed = true;
+        if let Some(waker) = channel.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Future for Receiver<io::Result<T>> {
+    type Output = io::Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        let mut channel = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(value) = channel.value.take() {
+            return Poll::Ready(value);
+        }
+        if channel.closed {
+            // the task panicked, or was dropped without running
+            return Poll::Ready(Err(io::Error::other("the blocking task did not complete")));
+        }
+        channel.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+type Fetch<'a> = Pin<Box<dyn Future<Output = io::Result<(u64, Vec<u8>)>> + Send + 'a>>;
+
+/// Fetches the `ranges` of `storage` into `staged`, coalescing adjacent ranges
+/// and running up to `concurrency` reads at once.
+async fn fetch<S: AsyncStorage>(
+    storage: &S,
+    staged: &Staged,
+    ranges: Vec<Range<u64>>,
+    concurrency: usize,
+) -> io::Result<()> {
+    let mut pending = coalesce(ranges).into_iter();
+    let mut running: Vec<Fetch<'_>> = Vec::new();
+    loop {
+        while running.len() < concurrency {
+            let Some(range) = pending.next() else {
+                break;
+            };
+            running.push(Box::pin(async move {
+                let bytes = storage.read(range.clone()).await?;
+                if bytes.len() as u64 != range.end - range.start {
+                    return Err(eof());
+                }
+                Ok((range.start, bytes))
+            }));
+        }
+        if running.is_empty() {
+            return Ok(());
+        }
+        let (start, bytes) = std::future::poll_fn(|cx| {
+            for i in 0..running.len() {
+                if let Poll::Ready(result) = running[i].as_mut().poll(cx) {
+                    drop(running.swap_remove(i));
+                    return Poll::Ready(result);
+                }
+            }
+            Poll::Pending
+        })
+        .await?;
+        staged.insert(start, bytes);
+    }
+}
+
+/// Sorts `ranges`, and merges the ones that overlap or are adjacent.
+fn coalesce(mut ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+    ranges.sort_by_key(|range| range.start);
+    let mut coalesced: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match coalesced.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => coalesced.push(range),
+        }
+    }
+    coalesced
+}
+
+/// [`Storage`] serving the ranges fetched from an [`AsyncStorage`].
+///
+/// Reads of ranges that have not been fetched fail, and are recorded so that
+/// they can be fetched before trying again.
+#[derive(Debug)]
+struct Staged {
+    len: u64,
+    ranges: Mutex<Vec<(u64, Vec<u8>)>>,
+    missing: Mutex<Vec<Range<u64>>>,
+}
+
+impl Staged {
+    fn new(len: u64) -> Self {
+        Self {
+            len,
+            ranges: Mutex::default(),
+            missing: Mutex::default(),
+        }
+    }
+
+    fn insert(&self, start: u64, bytes: Vec<u8>) {
+        self.ranges.lock().unwrap().push((start, bytes));
+    }
+
+    fn take_missing(&self) -> Vec<Range<u64>> {
+        std::mem::take(&mut *self.missing.lock().unwrap())
+    }
+
+    fn clear(&self) {
+        self.ranges.lock().unwrap().clear();
+        self.missing.lock().unwrap().clear();
+    }
+}
+
+impl Storage for Staged {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+        if range.start > range.end || range.end > self.len {
+            return Err(eof());
+        }
+        buf.clear();
+        let ranges = self.ranges.lock().unwrap();
+        let fetched = ranges.iter().find(|(start, bytes)| {
+            *start <= range.start && range.end <= *start + bytes.len() as u64
+        });
+        let Some((start, bytes)) = fetched else {
+            self.missing.lock().unwrap().push(range);
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "the range has not been fetched",
+            ));
+        };
+        let offset = (range.start - start) as usize;
+        buf.extend_from_slice(&bytes[offset..offset + (range.end - range.start) as usize]);
+        Ok(buf)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::pin::pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::Thread;
+
+    use super::*;
+    use crate::{Checksum, Compressor};
+
+    /// Runs `future` to completion on the current thread.
+    pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+        struct ThreadWaker(Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    /// Async storage yielding once before every read, and counting the reads
+    /// and how many of them ran concurrently.
+    pub(crate) struct Remote {
+        pub(crate) bytes: Vec<u8>,
+        pub(crate) reads: AtomicUsize,
+        pub(crate) in_flight: AtomicUsize,
+        pub(crate) max_in_flight: AtomicUsize,
+    }
+
+    impl Remote {
+        pub(crate) fn new(bytes: Vec<u8>) -> Self {
+            Self {
+                bytes,
+                reads: AtomicUsize::new(0),
+                in_flight: AtomicUsize::new(0),
+                max_in_flight: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl AsyncStorage for Remote {
+        fn len(&self) -> u64 {
+            self.bytes.len() as u64
+        }
+
+        async fn read(&self, range: Range<u64>) -> io::Result<Vec<u8>> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::Relaxed);
+            let mut yielded = false;
+            std::future::poll_fn(|cx| {
+                if yielded {
+                    return Poll::Ready(());
+                }
+                yielded = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            })
+            .await;
+            self.in_flight.fetch_sub(1, Ordering::Relaxed);
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            let mut buf = Vec::new();
+            crate::storage::read_to_vec(&self.bytes, range, &mut buf)?;
+            Ok(buf)
+        }
+    }
+
+    fn assert_send<T: Send>(value: T) -> T {
+        value
+    }
+
+    #[test]
+    fn test_async_executor() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let storage = Remote::new(compressed);
+
+        // the futures can be moved to an executor on another thread
+        let output = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    block_on(assert_send(async {
+                        let mut index = Vec::new();
+                        let mut d = AsyncDecompressor::new(&storage, &mut index).await?;
+                        assert_send(d.get(2500..4500)).await
+                    }))
+                })
+                .join()
+                .unwrap()
+        });
+        assert_eq!(output.unwrap(), &input[2500..4500]);
+    }
+
+    #[test]
+    fn test_async_writer() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+
+        /// Sink recording the size of every write.
+        #[derive(Default)]
+        struct Upload {
+            bytes: Vec<u8>,
+            writes: Vec<usize>,
+        }
+
+        impl AsyncSink for Upload {
+            async fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+                self.bytes.extend_from_slice(bytes);
+                self.writes.push(bytes.len());
+                Ok(())
+            }
+        }
+
+        for trailer_index in [false, true] {
+            let compressor = || {
+                Compressor::new()
+                    .frame_size(1000)
+                    .checksum(Checksum::Crc32)
+                    .trailer_index(trailer_index)
+            };
+            let expected = compressor().compress(&input).unwrap();
+            let upload = block_on(assert_send(async {
+                let mut writer = compressor().async_writer(Upload::default())?;
+                for chunk in input.chunks(700) {
+                    writer.write(chunk).await?;
+                }
+                writer.finish().await
+            }))
+            .unwrap();
+            assert_eq!(upload.bytes, expected);
+            // frames are only uploaded as they complete with a trailer index
+            assert_eq!(upload.writes.len() > 2, trailer_index);
+        }
+
+        let mut sink = Vec::new();
+        let writer = Compressor::new().async_writer(&mut sink).unwrap();
+        block_on(writer.finish()).unwrap();
+        assert_eq!(Decompressor::new(&sink).unwrap().get(..).unwrap(), []);
+    }
+
+    #[test]
+    fn test_coalesced_fetches() {
+        assert_eq!(coalesce(vec![5..8, 0..2, 2..4, 8..8, 7..9]), [0..4, 5..9]);
+
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let storage = Remote::new(compressed);
+        let mut index = Vec::new();
+        let d = block_on(AsyncDecompressor::new(&storage, &mut index)).unwrap();
+        let mut d = d.frame_cache(10_000).concurrent_fetches(2);
+
+        // all adjacent frames are fetched at once
+        let reads = storage.reads.load(Ordering::Relaxed);
+        assert_eq!(block_on(d.get(2500..4500)).unwrap(), &input[2500..4500]);
+        assert_eq!(storage.reads.load(Ordering::Relaxed), reads + 1);
+
+        // the cached frames split the rest into separate, concurrent fetches
+        storage.max_in_flight.store(0, Ordering::Relaxed);
+        assert_eq!(block_on(d.get(..7000)).unwrap(), &input[..7000]);
+        assert_eq!(storage.reads.load(Ordering::Relaxed), reads + 3);
+        assert_eq!(storage.max_in_flight.load(Ordering::Relaxed), 2);
+
+        storage.max_in_flight.store(0, Ordering::Relaxed);
+        let mut d = d.frame_cache(10_000).concurrent_fetches(1);
+        block_on(d.get(2500..4500)).unwrap();
+        assert_eq!(block_on(d.get(..)).unwrap(), input);
+        assert_eq!(storage.max_in_flight.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_async_stream() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let storage = Remote::new(compressed);
+        let mut index = Vec::new();
+        let mut d = block_on(AsyncDecompressor::new(&storage, &mut index)).unwrap();
+
+        let mut stream = d.stream(1500..4200);
+        let reads = storage.reads.load(Ordering::Relaxed);
+        let first = block_on(stream.next()).unwrap().unwrap();
+        assert_eq!(first, &input[1500..2000]);
+        // only the frame of the first chunk has been fetched
+        assert_eq!(storage.reads.load(Ordering::Relaxed), reads + 1);
+        let mut chunks = vec![first];
+        while let Some(chunk) = block_on(stream.next()) {
+            chunks.push(chunk.unwrap());
+        }
+        let lens: Vec<_> = chunks.iter().map(Vec::len).collect();
+        assert_eq!(lens, [500, 1000, 1000, 200]);
+        assert_eq!(chunks.concat(), &input[1500..4200]);
+        drop(stream);
+
+        // a cancelled read continues with the next one
+        let mut stream = d.stream(6000..6100);
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(pin!(stream.next()).poll(&mut cx).is_pending());
+        let reads = storage.reads.load(Ordering::Relaxed);
+        assert_eq!(
+            block_on(stream.next()).unwrap().unwrap(),
+            &input[6000..6100]
+        );
+        assert_eq!(storage.reads.load(Ordering::Relaxed), reads + 1);
+        drop(stream);
+
+        let mut stream = d.stream(10_500..11_000);
+        assert!(block_on(stream.next()).unwrap().is_err());
+        assert!(block_on(stream.next()).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "stream")]
+    fn test_bytes_stream() {
+        use futures_util::StreamExt;
+
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let storage = Remote::new(compressed);
+        let mut index = Vec::new();
+        let mut d = block_on(AsyncDecompressor::new(&storage, &mut index)).unwrap();
+
+        let chunks: Vec<io::Result<bytes::Bytes>> = block_on(d.stream(1500..4200).collect());
+        let chunks: Vec<_> = chunks.into_iter().map(Result::unwrap).collect();
+        let lens: Vec<_> = chunks.iter().map(|chunk| chunk.len()).collect();
+        assert_eq!(lens, [500, 1000, 1000, 200]);
+        assert_eq!(chunks.concat(), &input[1500..4200]);
+
+        // the stream can be mapped and boxed, like for the body of a response
+        let body = d
+            .stream(..)
+            .map(|chunk| chunk.map(|chunk| chunk.len()))
+            .boxed();
+        let lens: Vec<_> = block_on(body.collect());
+        assert_eq!(lens.len(), 10);
+        assert!(lens.into_iter().all(|len| len.unwrap() == 1000));
+        let mut errors = d.stream(10_500..11_000);
+        assert!(block_on(StreamExt::next(&mut errors)).unwrap().is_err());
+        assert!(block_on(StreamExt::next(&mut errors)).is_none());
+    }
+
+    #[test]
+    fn test_async_cursor() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let storage = Remote::new(compressed);
+        let mut index = Vec::new();
+        let d = block_on(AsyncDecompressor::new(&storage, &mut index)).unwrap();
+        let mut cursor = assert_send(d.into_cursor());
+
+        let mut output = Vec::new();
+        let mut buf = [0; 300];
+        loop {
+            let len = block_on(cursor.read(&mut buf)).unwrap();
+            if len == 0 {
+                break;
+            }
+            output.extend_from_slice(&buf[..len]);
+        }
+        assert_eq!(output, input);
+        assert_eq!(cursor.position(), 10_000);
+
+        // each frame is fetched once, and the reads within it are buffered
+        let reads = storage.reads.load(Ordering::Relaxed);
+        assert_eq!(cursor.seek(SeekFrom::Start(1500)).unwrap(), 1500);
+        assert_eq!(block_on(cursor.read(&mut buf)).unwrap(), 300);
+        assert_eq!(buf, input[1500..1800]);
+        assert_eq!(block_on(cursor.read(&mut buf)).unwrap(), 200);
+        assert_eq!(buf[..200], input[1800..2000]);
+        assert_eq!(storage.reads.load(Ordering::Relaxed), reads + 1);
+
+        assert_eq!(cursor.seek(SeekFrom::End(-50)).unwrap(), 9_950);
+        assert_eq!(block_on(cursor.read(&mut buf)).unwrap(), 50);
+        assert_eq!(buf[..50], input[9_950..]);
+        assert_eq!(cursor.seek(SeekFrom::Current(10)).unwrap(), 10_010);
+        assert_eq!(block_on(cursor.read(&mut buf)).unwrap(), 0);
+        assert!(cursor.seek(SeekFrom::Current(-20_000)).is_err());
+
+        // seeking while a read is pending continues at the new position
+        cursor.seek(SeekFrom::Start(4000)).unwrap();
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        assert!(Pin::new(&mut cursor)
+            .poll_read(&mut cx, &mut buf)
+            .is_pending());
+        let seek = Pin::new(&mut cursor).poll_seek(&mut cx, SeekFrom::Start(7000));
+        assert!(matches!(seek, Poll::Ready(Ok(7000))));
+        assert_eq!(block_on(cursor.read(&mut buf)).unwrap(), 300);
+        assert_eq!(buf, input[7000..7300]);
+
+        let mut d = block_on(cursor.into_inner());
+        assert_eq!(block_on(d.get(..10)).unwrap(), &input[..10]);
+    }
+
+    #[test]
+    #[cfg(feature = "futures-io")]
+    fn test_futures_io_cursor() {
+        use futures_util::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt};
+
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let storage = Remote::new(compressed);
+        let mut index = Vec::new();
+        let d = block_on(AsyncDecompressor::new(&storage, &mut index)).unwrap();
+        let mut cursor = d.into_cursor();
+        let mut output = Vec::new();
+        block_on(cursor.read_to_end(&mut output)).unwrap();
+        assert_eq!(output, input);
+
+        // the inherent `seek` takes precedence over the trait method
+        let pos = block_on(AsyncSeekExt::seek(&mut cursor, SeekFrom::End(-5500)));
+        assert_eq!(pos.unwrap(), 4500);
+        let mut buf = [0; 1000];
+        block_on(cursor.read_exact(&mut buf)).unwrap();
+        assert_eq!(buf, input[4500..5500]);
+        let pos = block_on(AsyncSeekExt::seek(&mut cursor, SeekFrom::Current(-20_000)));
+        assert!(pos.is_err());
+
+        // adapters of `futures-io` readers work on top of the cursor
+        let mut buffered = futures_util::io::BufReader::new(cursor.take(1500));
+        let mut line = Vec::new();
+        block_on(buffered.read_until(b'\n', &mut line)).unwrap();
+        let newline = input[5500..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .unwrap();
+        assert_eq!(line.len(), newline + 1);
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn test_tokio_cursor() {
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let storage = Arc::new(Remote::new(compressed));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let d = AsyncDecompressor::open(storage).await.unwrap();
+            let mut cursor = d.into_cursor();
+            let mut output = Vec::new();
+            cursor.read_to_end(&mut output).await.unwrap();
+            assert_eq!(output, input);
+
+            // the inherent `seek` takes precedence over the trait method
+            assert_eq!(
+                AsyncSeekExt::seek(&mut cursor, SeekFrom::Start(4500))
+                    .await
+                    .unwrap(),
+                4500
+            );
+            let mut buf = [0; 1000];
+            cursor.read_exact(&mut buf).await.unwrap();
+            assert_eq!(buf, input[4500..5500]);
+            assert_eq!(
+                AsyncSeekExt::seek(&mut cursor, SeekFrom::Current(-2000))
+                    .await
+                    .unwrap(),
+                3500
+            );
+            assert_eq!(cursor.stream_position().await.unwrap(), 3500);
+            assert!(AsyncSeekExt::seek(&mut cursor, SeekFrom::End(-20_000))
+                .await
+                .is_err());
+
+            // the cursor can be moved into a task of the runtime
+            let rest = tokio::spawn(async move {
+                let mut rest = Vec::new();
+                cursor.read_to_end(&mut rest).await.map(|_| rest)
+            });
+            assert_eq!(rest.await.unwrap().unwrap(), input[3500..]);
+        });
+    }
+
+    #[test]
+    fn test_offload_blocking() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let storage = Arc::new(Remote::new(compressed));
+        let spawned = Arc::new(AtomicUsize::new(0));
+        let d = block_on(AsyncDecompressor::open(Arc::clone(&storage))).unwrap();
+        let mut d = d.offload_blocking({
+            let spawned = Arc::clone(&spawned);
+            move |task| {
+                spawned.fetch_add(1, Ordering::Relaxed);
+                std::thread::spawn(task);
+            }
+        });
+
+        assert_eq!(block_on(d.get(1500..3500)).unwrap(), &input[1500..3500]);
+        let mut buf = [0; 100];
+        assert_eq!(block_on(d.read_at(9_950, &mut buf)).unwrap(), 50);
+        assert_eq!(buf[..50], input[9_950..]);
+        assert_eq!(spawned.load(Ordering::Relaxed), 2);
+        assert!(block_on(d.get(..10_001)).is_err());
+
+        // the next read waits for the task of a cancelled read, without locking
+        let (sender, tasks) = std::sync::mpsc::channel::<Box<dyn FnOnce() + Send>>();
+        let mut d = d.offload_blocking(move |task| sender.send(task).unwrap());
+        let mut cx = Context::from_waker(Waker::noop());
+        let task = {
+            let mut get = pin!(d.get(..100));
+            loop {
+                assert!(get.as_mut().poll(&mut cx).is_pending());
+                if let Ok(task) = tasks.try_recv() {
+                    break task;
+                }
+            }
+        };
+        assert_eq!(d.len(), input.len());
+        {
+            let mut get = pin!(d.get(5000..5100));
+            assert!(get.as_mut().poll(&mut cx).is_pending());
+            assert!(tasks.try_recv().is_err());
+            task();
+            std::thread::spawn(move || tasks.into_iter().for_each(|task| task()));
+            assert_eq!(block_on(get).unwrap(), &input[5000..5100]);
+        }
+
+        // a task that never runs results in an error instead of a hang
+        let mut d = d.offload_blocking(drop);
+        assert!(block_on(d.get(..100)).is_err());
+
+        let storage = Remote::new(vec![0; 100]);
+        assert!(block_on(AsyncDecompressor::open(&storage)).is_err());
+    }
+
+    #[test]
+    fn test_async_decompressor() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        for compressor in [
+            Compressor::new().checksum(Checksum::Crc32),
+            Compressor::new().trailer_index(true),
+            Compressor::new().sparse_index(4),
+        ] {
+            let compressed = compressor.frame_size(1000).compress(&input).unwrap();
+            let storage = Remote::new(compressed);
+            let mut index = Vec::new();
+            let mut d = block_on(AsyncDecompressor::new(&storage, &mut index)).unwrap();
+            assert_eq!(d.len(), input.len());
+
+            let reads = storage.reads.load(Ordering::Relaxed);
+            assert_eq!(block_on(d.get(1500..1600)).unwrap(), &input[1500..1600]);
+            assert_eq!(storage.reads.load(Ordering::Relaxed), reads + 1);
+            assert_eq!(block_on(d.get(..)).unwrap(), input);
+            let mut buf = [0; 100];
+            assert_eq!(block_on(d.read_at(9_950, &mut buf)).unwrap(), 50);
+            assert_eq!(buf[..50], input[9_950..]);
+            assert!(block_on(d.get(..10_001)).is_err());
+        }
+
+        let mut index = Vec::new();
+        let storage = Remote::new(vec![0; 100]);
+        let err = block_on(AsyncDecompressor::new(&storage, &mut index))
+            .map(drop)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
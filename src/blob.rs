@@ -0,0 +1,216 @@
+use std::io;
+use std::ops::RangeBounds;
+use std::sync::Arc;
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Compressor, Decompressor, Error, OwnedDecompressor};
+
+/// A blob that stays compressed at rest, for use as a field of serialized structs.
+///
+/// It serializes as the bytes of its archive, and deserializing it only reads
+/// the index of the archive. Its contents are decompressed on demand, and
+/// [`get`](Self::get) only decompresses the frames covering the requested range.
+/// Cloning is cheap, as the compressed bytes are shared.
+///
+/// This is only available with the `serde` feature.
+#[derive(Clone)]
+pub struct SeezeeBytes {
+    bytes: Arc<[u8]>,
+    /// The decompressor reading from `bytes`.
+    decompressor: OwnedDecompressor,
+}
+
+impl std::fmt::Debug for SeezeeBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SeezeeBytes")
+            .field("len", &self.len())
+            .field("compressed_len", &self.bytes.len())
+            .finish()
+    }
+}
+
+impl PartialEq for SeezeeBytes {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for SeezeeBytes {}
+
+impl SeezeeBytes {
+    /// Compresses `input` using `compressor`.
+    pub fn compress(compressor: &mut Compressor<'_>, input: &[u8]) -> Result<Self, Error> {
+        Self::from_compressed(compressor.compress(input)?)
+    }
+
+    /// Wraps the archive in `bytes`, as returned by [`SeezeeBytes::as_compressed`].
+    ///
+    /// Fails like [`Decompressor::new`] if `bytes` is not a valid archive.
+    pub fn from_compressed(bytes: impl Into<Arc<[u8]>>) -> Result<Self, Error> {
+        let bytes = bytes.into();
+        let decompressor = Decompressor::from_owned(Arc::clone(&bytes))?;
+        Ok(Self {
+            bytes,
+            decompressor,
+        })
+    }
+
+    /// The bytes of the archive.
+    pub fn as_compressed(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The uncompressed size.
+    pub fn len(&self) -> usize {
+        self.decompressor.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Decompresses the bytes in `range`, see [`Decompressor::get`].
+    pub fn get<R>(&mut self, range: R) -> io::Result<Vec<u8>>
+    where
+        R: RangeBounds<usize>,
+    {
+        self.decompressor.get(range)
+    }
+
+    /// Returns the decompressor reading the contents, which shares the
+    /// compressed bytes instead of copying them.
+    pub fn into_decompressor(self) -> OwnedDecompressor {
+        self.decompressor
+    }
+}
+
+impl TryFrom<Vec<u8>> for SeezeeBytes {
+    type Error = Error;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Error> {
+        Self::from_compressed(bytes)
+    }
+}
+
+impl From<SeezeeBytes> for Vec<u8> {
+    fn from(bytes: SeezeeBytes) -> Self {
+        bytes.bytes.to_vec()
+    }
+}
+
+impl Serialize for SeezeeBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.bytes)
+    }
+}
+
+impl<'de> Deserialize<'de> for SeezeeBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = deserializer.deserialize_byte_buf(ArchiveVisitor)?;
+        Self::from_compressed(bytes).map_err(de::Error::custom)
+    }
+}
+
+/// Visits the bytes of an archive, which formats without a native bytes type
+/// represent as a sequence.
+struct ArchiveVisitor;
+
+impl<'de> Visitor<'de> for ArchiveVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("the bytes of an archive")
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(bytes.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(bytes)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        // the size hint comes from the input, so it is not trusted blindly
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(1 << 16));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::value::{BytesDeserializer, Error as ValueError};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Document {
+        name: String,
+        contents: SeezeeBytes,
+    }
+
+    #[test]
+    fn test_seezee_bytes() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let mut bytes =
+            SeezeeBytes::compress(&mut Compressor::new().frame_size(1000), &input).unwrap();
+        assert_eq!(bytes.len(), 10_000);
+        assert!(!bytes.is_empty());
+        assert!(bytes.as_compressed().len() < input.len());
+        assert_eq!(bytes.get(4000..4100).unwrap(), &input[4000..4100]);
+        assert_eq!(bytes.clone().into_decompressor().get(..).unwrap(), input);
+
+        // round trip through the compressed representation
+        let stored: Vec<u8> = bytes.clone().into();
+        assert_eq!(stored, bytes.as_compressed());
+        let mut restored = SeezeeBytes::try_from(stored).unwrap();
+        assert_eq!(restored, bytes);
+        assert_eq!(restored.get(..).unwrap(), input);
+
+        assert!(SeezeeBytes::try_from(vec![0; 100]).is_err());
+        let mut empty = SeezeeBytes::compress(&mut Compressor::new(), &[]).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.get(..).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_seezee_bytes_serde() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let contents =
+            SeezeeBytes::compress(&mut Compressor::new().frame_size(1000), &input).unwrap();
+        let document = Document {
+            name: "test".into(),
+            contents: contents.clone(),
+        };
+
+        // the contents stay compressed in the serialized document
+        let serialized = toml::to_string(&document).unwrap();
+        let table: toml::Table = toml::from_str(&serialized).unwrap();
+        let stored = table["contents"].as_array().unwrap();
+        assert_eq!(stored.len(), contents.as_compressed().len());
+        let mut deserialized: Document = toml::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, document);
+        assert_eq!(deserialized.contents.len(), input.len());
+        assert_eq!(
+            deserialized.contents.get(7000..7100).unwrap(),
+            &input[7000..7100]
+        );
+
+        // formats with a native bytes type
+        let deserializer = BytesDeserializer::<ValueError>::new(contents.as_compressed());
+        let mut deserialized = SeezeeBytes::deserialize(deserializer).unwrap();
+        assert_eq!(deserialized.get(..).unwrap(), input);
+
+        let deserializer = BytesDeserializer::<ValueError>::new(&[0; 100]);
+        let err = SeezeeBytes::deserialize(deserializer).unwrap_err();
+        assert_eq!(err.to_string(), Error::InvalidHeader.to_string());
+        let invalid = "name = \"test\"\ncontents = [1, 2, 3]\n";
+        assert!(toml::from_str::<Document>(invalid).is_err());
+        assert!(toml::from_str::<Document>("name = \"test\"\ncontents = \"text\"\n").is_err());
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+/// An optional least-recently-used cache of decompressed frames, which is
+/// limited to a budget of bytes.
+///
+/// The cache is shared by all reads of a [`Decompressor`](crate::Decompressor),
+/// including concurrent reads through `&self`, so it is behind a [`Mutex`].
+#[derive(Debug, Default)]
+pub struct FrameCache {
+    inner: Option<Mutex<LruFrames>>,
+}
+
+impl FrameCache {
+    /// Creates a cache holding up to `budget` bytes of decompressed frames.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            inner: (budget > 0).then(|| Mutex::new(LruFrames::new(budget))),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Returns the budget the cache was created with, or zero for a poisoned cache.
+    pub fn budget(&self) -> usize {
+        match self.inner.as_ref().map(Mutex::lock) {
+            Some(Ok(frames)) => frames.budget,
+            _ => 0,
+        }
+    }
+
+    /// Calls `f` with the cached frame `idx`, marking it as recently used.
+    ///
+    /// Returns `None` if the frame is not cached.
+    pub fn get<T>(&self, idx: usize, f: impl FnOnce(&[u8]) -> T) -> Option<T> {
+        let mut frames = self.inner.as_ref()?.lock().ok()?;
+        frames.get(idx).map(f)
+    }
+
+    /// Adds a copy of the verified frame `idx` to the cache.
+    pub fn insert(&self, idx: usize, frame: &[u8]) {
+        // a poisoned cache is simply not used anymore
+        if let Some(Ok(mut frames)) = self.inner.as_ref().map(Mutex::lock) {
+            frames.insert(idx, frame);
+        }
+    }
+}
+
+/// A pool of reusable objects, like `zstd` contexts or scratch buffers.
+///
+/// The lock is only held to take and return objects, so concurrent users never
+/// block each other while using them. Instead, the pool grows to the number of
+/// concurrent users.
+#[derive(Debug)]
+pub struct Pool<T> {
+    items: Mutex<Vec<T>>,
+}
+
+impl<T> Default for Pool<T> {
+    fn default() -> Self {
+        Self {
+            items: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl<T> Pool<T> {
+    /// Takes an object out of the pool, or calls `create` if it is empty.
+    pub fn take<E>(&self, create: impl FnOnce() -> Result<T, E>) -> Result<Pooled<'_, T>, E> {
+        let item = match self.items.lock().ok().and_then(|mut items| items.pop()) {
+            Some(item) => item,
+            None => create()?,
+        };
+        Ok(Pooled {
+            pool: self,
+            item: Some(item),
+        })
+    }
+
+    /// Adds an object to the pool.
+    pub fn put(&self, item: T) {
+        // a poisoned pool is simply not used anymore
+        if let Ok(mut items) = self.items.lock() {
+            items.push(item);
+        }
+    }
+
+    /// Takes an object out of the pool, or creates a default one if it is empty.
+    pub fn take_or_default(&self) -> Pooled<'_, T>
+    where
+        T: Default,
+    {
+        match self.take(|| Ok::<_, std::convert::Infallible>(T::default())) {
+            Ok(item) => item,
+        }
+    }
+
+    /// The number of objects in the pool, not counting the ones in use.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.items.lock().map_or(0, |items| items.len())
+    }
+}
+
+/// An object taken out of a [`Pool`], which is returned to it when dropped.
+pub struct Pooled<'a, T> {
+    pool: &'a Pool<T>,
+    item: Option<T>,
+}
+
+impl<T> Pooled<'_, T> {
+    /// Takes the object out of its pool for good.
+    pub fn into_inner(mut self) -> T {
+        self.item.take().unwrap()
+    }
+}
+
+impl<T> Deref for Pooled<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.item.as_ref().unwrap()
+    }
+}
+
+impl<T> DerefMut for Pooled<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.item.as_mut().unwrap()
+    }
+}
+
+impl<T> Drop for Pooled<'_, T> {
+    fn drop(&mut self) {
+        if let Some(item) = self.item.take() {
+            self.pool.put(item);
+        }
+    }
+}
+
+/// A buffer holding a single decompressed frame, along with its index.
+///
+/// This allows subsequent reads to reuse a frame they have in common, like the
+/// frame at the boundary of sequential reads, instead of decompressing it again.
+#[derive(Debug, Default)]
+pub struct FrameBuf {
+    /// The index of the verified frame in `buf`, or `None` if it does not hold
+    /// a complete frame.
+    pub frame: Option<usize>,
+    pub buf: Vec<u8>,
+    /// The compressed frames read from a [`Storage`](crate::Storage) that does
+    /// not hold them in memory.
+    pub compressed: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct LruFrames {
+    budget: usize,
+    /// The total size of all cached frames.
+    size: usize,
+    /// The cached frames by index, along with the tick of their last use.
+    frames: HashMap<usize, (Vec<u8>, u64)>,
+    /// The indices of the cached frames by the tick of their last use.
+    lru: BTreeMap<u64, usize>,
+    tick: u64,
+}
+
+impl LruFrames {
+    fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            size: 0,
+            frames: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    fn get(&mut self, idx: usize) -> Option<&[u8]> {
+        let (frame, last_used) = self.frames.get_mut(&idx)?;
+        self.lru.remove(last_used);
+        self.tick += 1;
+        *last_used = self.tick;
+        self.lru.insert(self.tick, idx);
+        Some(frame)
+    }
+
+    /// Inserts frame `idx`, evicting the least recently used frames to stay
+    /// within the budget. Frames larger than the whole budget are not cached.
+    fn insert(&mut self, idx: usize, frame: &[u8]) {
+        if frame.len() > self.budget || self.frames.contains_key(&idx) {
+            return;
+        }
+        let mut buf = Vec::new();
+        while self.size + frame.len() > self.budget {
+            let Some((_, evicted)) = self.lru.pop_first() else {
+                break;
+            };
+            if let Some((evicted, _)) = self.frames.remove(&evicted) {
+                self.size -= evicted.len();
+                // reuse the allocation of an evicted frame
+                buf = evicted;
+            }
+        }
+        buf.clear();
+        buf.extend_from_slice(frame);
+
+        self.tick += 1;
+        self.size += frame.len();
+        self.frames.insert(idx, (buf, self.tick));
+        self.lru.insert(self.tick, idx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru() {
+        let cache = FrameCache::new(300);
+        cache.insert(0, &[0; 100]);
+        cache.insert(1, &[1; 100]);
+        cache.insert(2, &[2; 100]);
+        // frame 0 is now the most recently used one
+        assert_eq!(cache.get(0, |frame| frame[0]), Some(0));
+
+        cache.insert(3, &[3; 150]);
+        assert_eq!(cache.get(1, |_| ()), None);
+        assert_eq!(cache.get(2, |_| ()), None);
+        assert_eq!(cache.get(0, <[u8]>::len), Some(100));
+        assert_eq!(cache.get(3, <[u8]>::len), Some(150));
+
+        cache.insert(4, &[4; 301]);
+        assert_eq!(cache.get(4, |_| ()), None);
+        assert_eq!(cache.get(3, |_| ()), Some(()));
+
+        let disabled = FrameCache::default();
+        disabled.insert(0, &[0; 100]);
+        assert_eq!(disabled.get(0, |_| ()), None);
+    }
+}
@@ -0,0 +1,95 @@
+/// The algorithm used to checksum each uncompressed frame.
+///
+/// The algorithm is recorded in the archive, and the [`Decompressor`] verifies
+/// frames using the matching algorithm automatically.
+///
+/// [`Decompressor`]: crate::Decompressor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum Checksum {
+    /// Do not store any checksums.
+    #[default]
+    None,
+    /// A 32-bit CRC32 checksum.
+    Crc32,
+    /// The fast 64-bit XXH3 hash, which is less prone to collisions than CRC32.
+    Xxh3,
+    /// The 256-bit BLAKE3 cryptographic hash.
+    Blake3,
+}
+
+impl Checksum {
+    /// The identifier of the algorithm in the archive header.
+    pub(crate) fn id(self) -> u32 {
+        match self {
+            Checksum::None => 0,
+            Checksum::Crc32 => 1,
+            Checksum::Xxh3 => 2,
+            Checksum::Blake3 => 3,
+        }
+    }
+
+    pub(crate) fn from_id(id: u32) -> Option<Self> {
+        Some(match id {
+            0 => Checksum::None,
+            1 => Checksum::Crc32,
+            2 => Checksum::Xxh3,
+            3 => Checksum::Blake3,
+            _ => return None,
+        })
+    }
+
+    /// The size of a single checksum in bytes.
+    pub(crate) fn size(self) -> usize {
+        match self {
+            Checksum::None => 0,
+            Checksum::Crc32 => 4,
+            Checksum::Xxh3 => 8,
+            Checksum::Blake3 => 32,
+        }
+    }
+
+    /// Computes the checksum of `data`, or `None` if checksums are disabled.
+    pub(crate) fn digest(self, data: &[u8]) -> Option<Digest> {
+        let mut digest = Digest {
+            bytes: [0; 32],
+            len: self.size(),
+        };
+        match self {
+            Checksum::None => return None,
+            Checksum::Crc32 => {
+                digest.bytes[..4].copy_from_slice(&crc32fast::hash(data).to_le_bytes())
+            }
+            Checksum::Xxh3 => {
+                digest.bytes[..8].copy_from_slice(&xxhash_rust::xxh3::xxh3_64(data).to_le_bytes())
+            }
+            Checksum::Blake3 => digest.bytes = *blake3::hash(data).as_bytes(),
+        }
+        Some(digest)
+    }
+}
+
+/// The checksum of a single frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Digest {
+    bytes: [u8; 32],
+    len: usize,
+}
+
+impl Digest {
+    /// Creates a digest from the raw checksum `bytes`, of at most 32 bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut digest = Digest {
+            bytes: [0; 32],
+            len: bytes.len(),
+        };
+        digest.bytes[..bytes.len()].copy_from_slice(bytes);
+        digest
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
@@ -0,0 +1,482 @@
+use std::io;
+use std::ops::{Range, RangeBounds};
+
+use crate::checksum::Digest;
+use crate::format::{self, Archive, Frame, FrameInfo, FrameTable};
+use crate::{make_range, Checksum, Decompressor, Error, DEFAULT_FRAME_SIZE};
+
+/// The alignment that writers may pad each archive to.
+const ALIGN: usize = std::mem::size_of::<u64>();
+
+/// Reads multiple archives stored back-to-back in a single buffer.
+///
+/// Like the members of a gzip file, the archives can be read as a single
+/// logical stream of their concatenated contents, or individually via
+/// [`members`](Self::members). This is useful for files that whole archives
+/// are appended to, like log files.
+///
+/// The archives can directly follow each other, just like the output of
+/// [`Compressor::compress`](crate::Compressor::compress) appended to a file.
+/// Zero padding up to a multiple of 8 bytes after each archive, as written by
+/// earlier versions, is skipped when reading.
+///
+/// The length of an archive with a [`trailer_index`] can not be determined
+/// from its start, so such an archive has to be the last one in the buffer.
+///
+/// [`trailer_index`]: crate::Compressor::trailer_index
+#[derive(Debug)]
+pub struct ConcatDecompressor<'b> {
+    /// Each member, along with the offset of its contents within the logical stream.
+    members: Vec<(usize, Decompressor<'b>)>,
+    len: usize,
+}
+
+impl<'b> ConcatDecompressor<'b> {
+    /// Parses all the archives in `bytes`.
+    ///
+    /// Fails with the error of the first invalid archive.
+    pub fn new(mut bytes: &'b [u8]) -> Result<Self, Error> {
+        let mut members = Vec::new();
+        let mut len = 0usize;
+        while !bytes.is_empty() {
+            let (archive, archive_len) = Archive::parse_prefix(bytes)?;
+            let member = &bytes[..archive_len];
+            // skip any zero padding, archives never start with a zero byte
+            let max_padding = archive_len.next_multiple_of(ALIGN).min(bytes.len()) - archive_len;
+            bytes = &bytes[archive_len..];
+            let padding = bytes[..max_padding].iter().take_while(|&&byte| byte == 0);
+            bytes = &bytes[padding.count()..];
+            let input_len = archive.input_len;
+            members.push((len, Decompressor::from_archive(member, archive)));
+            len = len
+                .checked_add(input_len)
+                .ok_or(Error::ImplausibleInputLen(input_len as u64))?;
+        }
+        Ok(Self { members, len })
+    }
+
+    /// The total uncompressed size of all archives.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the individual archives, in the order they appear in the buffer.
+    pub fn members(&mut self) -> impl ExactSizeIterator<Item = &mut Decompressor<'b>> + '_ {
+        self.members.iter_mut().map(|(_, member)| member)
+    }
+
+    /// Turns this into the individual archives.
+    pub fn into_members(self) -> Vec<Decompressor<'b>> {
+        self.members.into_iter().map(|(_, member)| member).collect()
+    }
+
+    pub fn get<R>(&mut self, range: R) -> io::Result<Vec<u8>>
+    where
+        R: RangeBounds<usize>,
+    {
+        let mut buf = Vec::new();
+        self.get_into(&mut buf, range)?;
+        Ok(buf)
+    }
+
+    pub fn get_into<'o, R>(&mut self, buf: &'o mut Vec<u8>, range: R) -> io::Result<&'o [u8]>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.len)?;
+        self.read_into(buf, range)
+    }
+
+    fn read_into<'o>(&mut self, buf: &'o mut Vec<u8>, range: Range<usize>) -> io::Result<&'o [u8]> {
+        if range.start > range.end || range.end > self.len {
+            return Err(Error::RangeOutOfBounds {
+                range,
+                len: self.len,
+            }
+            .into());
+        }
+        buf.clear();
+        buf.reserve(range.len());
+
+        let first = self
+            .members
+            .partition_point(|(start, _)| *start <= range.start)
+            .saturating_sub(1);
+        for (start, member) in &mut self.members[first..] {
+            if *start >= range.end {
+                break;
+            }
+            let member_range = range.start.saturating_sub(*start)
+                ..(range.end - *start).min(member.archive.input_len);
+            if member_range.is_empty() {
+                continue;
+            }
+            member.append_into(buf, member_range)?;
+        }
+        Ok(buf.as_slice())
+    }
+}
+
+/// Merges multiple `archives` into a single archive, without decompressing or
+/// recompressing anything.
+///
+/// The compressed frames of all archives are copied as-is, and a new frame
+/// index is built for them. All archives have to use the same frame size, and
+/// all but the last one have to consist of whole frames only. They also have to
+/// share the same dictionary, if any. Checksums are only kept if all archives
+/// use the same [`Checksum`] algorithm, and the metadata of all archives is
+/// merged, with later archives taking precedence.
+///
+/// The merged archive uses the default layout, with an uncompressed index in
+/// front of unpadded frames.
+pub fn concat(archives: &[&[u8]]) -> Result<Vec<u8>, Error> {
+    let archives = archives
+        .iter()
+        .map(|bytes| Archive::parse(bytes))
+        .collect::<Result<Vec<_>, Error>>()?;
+    // empty archives do not contain any frames, so their settings do not matter
+    let non_empty: Vec<_> = archives
+        .iter()
+        .filter(|archive| archive.input_len > 0)
+        .collect();
+
+    let frame_size = non_empty
+        .first()
+        .map_or(DEFAULT_FRAME_SIZE, |archive| archive.frame_size);
+    let dictionary = non_empty.first().and_then(|archive| archive.dictionary);
+    let dictionary_id = non_empty.first().and_then(|archive| archive.dictionary_id);
+    let checksum = non_empty
+        .first()
+        .map_or(Checksum::None, |archive| archive.checksum);
+    let checksum = if non_empty.iter().all(|archive| archive.checksum == checksum) {
+        checksum
+    } else {
+        Checksum::None
+    };
+    for (i, archive) in non_empty.iter().enumerate() {
+        if archive.frame_size != frame_size {
+            return Err(invalid_input("archives have different frame sizes"));
+        }
+        if i + 1 < non_empty.len() && archive.input_len % frame_size != 0 {
+            return Err(invalid_input("archives have to consist of whole frames"));
+        }
+        if archive.dictionary != dictionary || archive.dictionary_id != dictionary_id {
+            return Err(invalid_input("archives use different dictionaries"));
+        }
+    }
+
+    let mut metadata: Vec<(String, Vec<u8>)> = Vec::new();
+    for (key, value) in archives.iter().flat_map(|archive| archive.metadata) {
+        match metadata.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.into(),
+            None => metadata.push((key.into(), value.into())),
+        }
+    }
+
+    let parts: Vec<_> = non_empty
+        .iter()
+        .map(|archive| (*archive, 0..archive.input_len.div_ceil(frame_size)))
+        .collect();
+    write_archive(
+        &parts,
+        frame_size,
+        checksum,
+        &metadata,
+        dictionary,
+        dictionary_id,
+    )
+}
+
+/// Splits an `archive` into `num_shards` standalone archives, without
+/// decompressing or recompressing anything.
+///
+/// Each shard consists of a contiguous run of whole frames, with the frames
+/// distributed as evenly as possible. Concatenating the contents of all shards
+/// in order yields the contents of the original archive, and [`concat()`] merges
+/// them back into a single archive. Shards are empty if the archive has fewer
+/// frames than `num_shards`.
+///
+/// Every shard keeps the frame size, dictionary, checksums and metadata of the
+/// original archive, and uses the default layout, with an uncompressed index in
+/// front of unpadded frames.
+pub fn split(archive: &[u8], num_shards: usize) -> Result<Vec<Vec<u8>>, Error> {
+    if num_shards == 0 {
+        return Err(invalid_input("the number of shards has to be positive"));
+    }
+    let archive = Archive::parse(archive)?;
+    let num_frames = archive.input_len.div_ceil(archive.frame_size);
+    let metadata: Vec<(String, Vec<u8>)> = archive
+        .metadata
+        .iter()
+        .map(|(key, value)| (key.into(), value.into()))
+        .collect();
+
+    (0..num_shards)
+        .map(|shard| {
+            let frames = shard * num_frames / num_shards..(shard + 1) * num_frames / num_shards;
+            write_archive(
+                &[(&archive, frames)],
+                archive.frame_size,
+                archive.checksum,
+                &metadata,
+                archive.dictionary,
+                archive.dictionary_id,
+            )
+        })
+        .collect()
+}
+
+/// Writes a new archive consisting of the given ranges of frames of each archive.
+fn write_archive(
+    parts: &[(&Archive<'_>, Range<usize>)],
+    frame_size: usize,
+    checksum: Checksum,
+    metadata: &[(String, Vec<u8>)],
+    dictionary: Option<&[u8]>,
+    dictionary_id: Option<u32>,
+) -> Result<Vec<u8>, Error> {
+    let mut frames = Vec::new();
+    let mut table = FrameTable::new();
+    let mut input_len = 0u64;
+    for (archive, range) in parts {
+        table.reserve(range.len());
+        for idx in range.clone() {
+            let frame = archive
+                .frame(idx)
+                .ok_or_else(|| invalid_data("invalid frame"))?;
+            let stored = match frame {
+                Frame::Compressed(bytes) if archive.zstd_frames => {
+                    // the frames of the archive itself do not have magic bytes
+                    frames.extend_from_slice(bytes.get(4..).unwrap_or_default());
+                    false
+                }
+                Frame::Compressed(bytes) => {
+                    frames.extend_from_slice(bytes);
+                    false
+                }
+                Frame::Stored(bytes) => {
+                    frames.extend_from_slice(bytes);
+                    true
+                }
+                Frame::Hole(_) => false,
+            };
+            let checksum = match checksum {
+                Checksum::None => None,
+                _ => archive.frame_checksum(idx).map(Digest::from_bytes),
+            };
+            table.push(frames.len() as u64, FrameInfo { stored, checksum });
+        }
+        let end = (range.end * frame_size).min(archive.input_len);
+        input_len += end.saturating_sub(range.start * frame_size) as u64;
+    }
+
+    let header = format::Header {
+        frame_size,
+        input_len,
+        wide: format::needs_wide_offsets(input_len, frames.len() as u64),
+        compact: false,
+        group_size: 1,
+        frame_alignment: 1,
+        checksum,
+        metadata,
+        dictionary,
+        dictionary_id,
+    };
+    let mut buf = Vec::with_capacity(header.size(table.num_frames()).unwrap_or(0) + frames.len());
+    header.write(&mut buf, &table)?;
+    buf.extend_from_slice(&frames);
+    Ok(buf)
+}
+
+fn invalid_input(message: &'static str) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Checksum, Compressor};
+
+    #[test]
+    fn test_concatenated() {
+        let input: Vec<u8> = (0..30_000u32).map(|i| (i % 251) as u8).collect();
+        let parts = [
+            &input[..10_000],
+            &input[10_000..10_000],
+            &input[10_000..25_000],
+        ];
+
+        // only the first archives are padded, the others follow directly
+        let mut bytes = Vec::new();
+        let mut count = 0;
+        let mut append = |archive: Vec<u8>| {
+            if count < 2 {
+                bytes.resize(bytes.len().next_multiple_of(ALIGN), 0);
+            }
+            bytes.extend(archive);
+            count += 1;
+        };
+        append(
+            Compressor::new()
+                .frame_size(1000)
+                .compress(parts[0])
+                .unwrap(),
+        );
+        append(Compressor::new().compress(parts[1]).unwrap());
+        append(
+            Compressor::new()
+                .frame_size(512)
+                .wide_offsets(true)
+                .frame_alignment(64)
+                .checksum(Checksum::Crc32)
+                .compress(parts[2])
+                .unwrap(),
+        );
+        // an archive with a trailing index has to come last
+        append(
+            Compressor::new()
+                .frame_size(700)
+                .trailer_index(true)
+                .compress(&input[25_000..])
+                .unwrap(),
+        );
+
+        let mut d = ConcatDecompressor::new(&bytes).unwrap();
+        assert_eq!(d.len(), input.len());
+        assert_eq!(d.members().len(), 4);
+        assert_eq!(d.get(..).unwrap(), input);
+        for range in [0..0, 0..1, 9_999..10_001, 5_000..27_000, 25_000..30_000] {
+            assert_eq!(d.get(range.clone()).unwrap(), &input[range]);
+        }
+        assert!(d.get(29_000..30_001).is_err());
+
+        let mut members = d.into_members();
+        assert_eq!(members[2].get(..).unwrap(), parts[2]);
+
+        assert!(ConcatDecompressor::new(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ConcatDecompressor::new(&[]).unwrap().is_empty());
+
+        // appended archives do not need to be aligned
+        let mut appended = Vec::new();
+        let mut starts = Vec::new();
+        for range in [0..333, 333..500, 500..501, 501..1000] {
+            let part = &input[range];
+            starts.push(appended.len());
+            appended.extend(Compressor::new().frame_size(128).compress(part).unwrap());
+        }
+        assert!(starts.iter().any(|start| start % ALIGN != 0));
+        let mut d = ConcatDecompressor::new(&appended).unwrap();
+        assert_eq!(d.members().len(), 4);
+        assert_eq!(d.get(..).unwrap(), &input[..1000]);
+        assert_eq!(d.get(250..950).unwrap(), &input[250..950]);
+
+        // anything but zero padding between archives is invalid
+        let mut garbage = Compressor::new().compress(&input[..3]).unwrap();
+        garbage.extend_from_slice(&[1; 8]);
+        assert!(ConcatDecompressor::new(&garbage).is_err());
+    }
+
+    #[test]
+    fn test_concat() {
+        let mut input: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        input[10_000..13_000].fill(0);
+
+        let a = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .metadata("name", "a")
+            .metadata("a", "1")
+            .compress(&input[..10_000])
+            .unwrap();
+        let b = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .holes(true)
+            .frame_alignment(64)
+            .trailer_index(true)
+            .metadata("name", "b")
+            .compress(&input[10_000..15_000])
+            .unwrap();
+        let c = Compressor::new()
+            .frame_size(1000)
+            .compress_seekable(&input[15_000..])
+            .unwrap();
+        let empty = Compressor::new().compress(&[]).unwrap();
+
+        let merged = concat(&[&a, &empty, &b]).unwrap();
+        let mut d = Decompressor::new(&merged).unwrap();
+        assert_eq!(d.archive.checksum, Checksum::Crc32);
+        assert!(matches!(d.archive.frame(10), Some(Frame::Hole(1000))));
+        let metadata: Vec<_> = d.metadata().iter().collect();
+        assert_eq!(metadata, [("name", &b"b"[..]), ("a", b"1")]);
+        assert_eq!(d.get(..).unwrap(), &input[..15_000]);
+
+        let merged = concat(&[&a, &b, &c]).unwrap();
+        let mut d = Decompressor::new(&merged).unwrap();
+        assert_eq!(d.archive.checksum, Checksum::None);
+        assert_eq!(d.get(..).unwrap(), input);
+        assert_eq!(d.get(9_500..15_500).unwrap(), &input[9_500..15_500]);
+
+        let merged = concat(&[]).unwrap();
+        let mut d = Decompressor::new(&merged).unwrap();
+        assert_eq!(d.get(..).unwrap(), []);
+
+        // only the last archive may end in a partial frame
+        let partial = Compressor::new()
+            .frame_size(1000)
+            .compress(&input[..1500])
+            .unwrap();
+        assert!(concat(&[&a, &partial]).is_ok());
+        assert!(concat(&[&partial, &a]).is_err());
+        let other_frame_size = Compressor::new()
+            .frame_size(500)
+            .compress(&input[..1000])
+            .unwrap();
+        assert!(concat(&[&a, &other_frame_size]).is_err());
+        assert!(concat(&[&a, &a[..100]]).is_err());
+    }
+
+    #[test]
+    fn test_split() {
+        let input: Vec<u8> = (0..10_500u32).map(|i| (i % 251) as u8).collect();
+        let archive = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Xxh3)
+            .compact_index(true)
+            .metadata("name", "input")
+            .compress(&input)
+            .unwrap();
+
+        for num_shards in [1, 3, 11, 20] {
+            let shards = split(&archive, num_shards).unwrap();
+            assert_eq!(shards.len(), num_shards);
+
+            let mut contents = Vec::new();
+            for shard in &shards {
+                let mut d = Decompressor::new(shard).unwrap();
+                assert_eq!(d.metadata().get("name"), Some(&b"input"[..]));
+                if d.archive.input_len > 0 {
+                    assert_eq!(d.archive.checksum, Checksum::Xxh3);
+                }
+                contents.extend(d.get(..).unwrap());
+            }
+            assert_eq!(contents, input);
+
+            let shards: Vec<_> = shards.iter().map(Vec::as_slice).collect();
+            let merged = concat(&shards).unwrap();
+            let mut d = Decompressor::new(&merged).unwrap();
+            assert_eq!(d.get(..).unwrap(), input);
+        }
+
+        assert!(split(&archive, 0).is_err());
+        assert!(split(&archive[..100], 2).is_err());
+    }
+}
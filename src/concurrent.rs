@@ -0,0 +1,105 @@
+use std::io;
+use std::ops::RangeBounds;
+
+use crate::cache::{FrameBuf, Pool};
+use crate::{make_range, Decompressor};
+
+/// A [`Decompressor`] serving reads from many threads at once.
+///
+/// Reads only need `&self`, so the same archive can be shared by many reader
+/// threads, without an outer `Mutex` serializing all reads. Every read uses its
+/// own `zstd` context and scratch buffer, which are taken from a pool and reused
+/// across reads. A [`Decompressor::frame_cache`] is shared by all threads.
+#[derive(Debug)]
+pub struct ConcurrentDecompressor<'b> {
+    decompressor: Decompressor<'b>,
+    /// The scratch buffers for partially read frames.
+    frame_bufs: Pool<FrameBuf>,
+}
+
+impl<'b> ConcurrentDecompressor<'b> {
+    /// Wraps a [`Decompressor`], which can be configured with a dictionary or
+    /// frame cache beforehand.
+    pub fn new(decompressor: Decompressor<'b>) -> Self {
+        Self {
+            decompressor,
+            frame_bufs: Pool::default(),
+        }
+    }
+
+    /// Returns the wrapped [`Decompressor`].
+    pub fn into_inner(self) -> Decompressor<'b> {
+        self.decompressor
+    }
+
+    /// The total uncompressed size of the archive.
+    pub fn len(&self) -> usize {
+        self.decompressor.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get<R>(&self, range: R) -> io::Result<Vec<u8>>
+    where
+        R: RangeBounds<usize>,
+    {
+        let mut buf = Vec::new();
+        self.get_into(&mut buf, range)?;
+        Ok(buf)
+    }
+
+    pub fn get_into<'o, R>(&self, buf: &'o mut Vec<u8>, range: R) -> io::Result<&'o [u8]>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.len())?;
+        buf.clear();
+        let mut frame_buf = self.frame_bufs.take_or_default();
+        self.decompressor.read_into(buf, range, &mut frame_buf)
+    }
+}
+
+impl<'b> From<Decompressor<'b>> for ConcurrentDecompressor<'b> {
+    fn from(decompressor: Decompressor<'b>) -> Self {
+        Self::new(decompressor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Checksum, Compressor};
+
+    #[test]
+    fn test_concurrent_reads() {
+        let input: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .compress(&input)
+            .unwrap();
+        let d = ConcurrentDecompressor::new(Decompressor::new(&compressed).unwrap());
+        assert_eq!(d.len(), input.len());
+
+        std::thread::scope(|scope| {
+            for thread in 0..8 {
+                let (d, input) = (&d, &input);
+                scope.spawn(move || {
+                    let mut buf = Vec::new();
+                    for i in 0..200 {
+                        let start = (thread * 7_919 + i * 4_999) % 98_500;
+                        let range = start..start + 1_500;
+                        assert_eq!(d.get_into(&mut buf, range.clone()).unwrap(), &input[range]);
+                    }
+                });
+            }
+        });
+        // at most one scratch buffer per thread
+        assert!((1..=8).contains(&d.frame_bufs.len()));
+
+        assert_eq!(d.get(..).unwrap(), input);
+        assert!(d.get(99_000..100_001).is_err());
+    }
+}
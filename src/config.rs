@@ -0,0 +1,328 @@
+use crate::{
+    zstd, Checksum, Compressor, Dictionary, Error, FrameSize, Strategy, DEFAULT_FRAME_SIZE,
+};
+
+/// The settings of a [`Compressor`], as plain data.
+///
+/// In contrast to the [`Compressor`] itself, a config holds no `zstd` context
+/// or callbacks, so it can be stored, compared and logged, and compressors can
+/// be created from it repeatedly with [`Compressor::from_config`]. The fields
+/// correspond to the builder methods of the same name, and `None` keeps the
+/// default of the compression level for the `zstd` parameters.
+///
+/// With the `serde` feature, the config implements `Serialize` and `Deserialize`,
+/// so services can persist it. Missing fields take their default, and the
+/// [`strategy`](Self::strategy) is named without its `ZSTD_` prefix, like `"btultra2"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[non_exhaustive]
+pub struct CompressorConfig {
+    pub level: i32,
+    pub frame_size: FrameSize,
+    pub checksum: Checksum,
+    pub adaptive_level: bool,
+    pub deterministic: bool,
+    pub wide_offsets: bool,
+    pub trailer_index: bool,
+    pub compact_index: bool,
+    /// The number of frames per entry of a [sparse index](Compressor::sparse_index).
+    pub sparse_index: usize,
+    pub frame_alignment: usize,
+    pub holes: bool,
+    pub window_log: Option<u32>,
+    pub search_log: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(with = "strategy"))]
+    pub strategy: Option<Strategy>,
+    pub target_length: Option<u32>,
+    pub long_distance_matching: Option<bool>,
+    #[cfg(feature = "zstdmt")]
+    pub workers: Option<u32>,
+    /// The raw bytes of the [`dictionary`](Compressor::dictionary), which is
+    /// embedded into the archive unless a `dictionary_id` is given.
+    pub dictionary: Option<Vec<u8>>,
+    pub dictionary_id: Option<u32>,
+    pub metadata: Vec<(String, Vec<u8>)>,
+    pub max_memory: Option<usize>,
+}
+
+impl Default for CompressorConfig {
+    fn default() -> Self {
+        Self {
+            level: 0,
+            frame_size: FrameSize::Fixed(DEFAULT_FRAME_SIZE),
+            checksum: Checksum::None,
+            adaptive_level: false,
+            deterministic: false,
+            wide_offsets: false,
+            trailer_index: false,
+            compact_index: false,
+            sparse_index: 1,
+            frame_alignment: 1,
+            holes: false,
+            window_log: None,
+            search_log: None,
+            strategy: None,
+            target_length: None,
+            long_distance_matching: None,
+            #[cfg(feature = "zstdmt")]
+            workers: None,
+            dictionary: None,
+            dictionary_id: None,
+            metadata: Vec::new(),
+            max_memory: None,
+        }
+    }
+}
+
+/// (De)serializes the [`Strategy`] of a config by its name, as the `zstd` type
+/// does not implement the `serde` traits.
+#[cfg(feature = "serde")]
+mod strategy {
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::Strategy;
+
+    const NAMES: [(&str, Strategy); 9] = [
+        ("fast", Strategy::ZSTD_fast),
+        ("dfast", Strategy::ZSTD_dfast),
+        ("greedy", Strategy::ZSTD_greedy),
+        ("lazy", Strategy::ZSTD_lazy),
+        ("lazy2", Strategy::ZSTD_lazy2),
+        ("btlazy2", Strategy::ZSTD_btlazy2),
+        ("btopt", Strategy::ZSTD_btopt),
+        ("btultra", Strategy::ZSTD_btultra),
+        ("btultra2", Strategy::ZSTD_btultra2),
+    ];
+
+    pub fn serialize<S: Serializer>(
+        strategy: &Option<Strategy>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let name = strategy.map(|strategy| {
+            let named = NAMES.iter().find(|(_, named)| *named == strategy);
+            named.expect("every strategy has a name").0
+        });
+        name.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Strategy>, D::Error> {
+        let Some(name) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        let (_, strategy) = NAMES
+            .iter()
+            .find(|(named, _)| *named == name)
+            .ok_or_else(|| D::Error::custom(format_args!("unknown strategy `{name}`")))?;
+        Ok(Some(*strategy))
+    }
+}
+
+impl<'d> Compressor<'d> {
+    /// Creates a compressor with the settings of `config`.
+    ///
+    /// Fails with [`Error::InvalidLevel`] or [`Error::InvalidFrameSize`] like
+    /// [`Compressor::try_level`] and [`Compressor::try_frame_size`].
+    pub fn from_config(config: &CompressorConfig) -> Result<Self, Error> {
+        let mut compressor = Compressor::new()
+            .try_level(config.level)?
+            .try_frame_size(config.frame_size)?
+            .checksum(config.checksum)
+            .adaptive_level(config.adaptive_level)
+            .deterministic(config.deterministic)
+            .wide_offsets(config.wide_offsets)
+            .trailer_index(config.trailer_index)
+            .compact_index(config.compact_index)
+            .sparse_index(config.sparse_index)
+            .frame_alignment(config.frame_alignment)
+            .holes(config.holes);
+        if let Some(window_log) = config.window_log {
+            compressor = compressor.window_log(window_log);
+        }
+        if let Some(search_log) = config.search_log {
+            compressor = compressor.search_log(search_log);
+        }
+        if let Some(strategy) = config.strategy {
+            compressor = compressor.strategy(strategy);
+        }
+        if let Some(target_length) = config.target_length {
+            compressor = compressor.target_length(target_length);
+        }
+        if let Some(enable) = config.long_distance_matching {
+            compressor = compressor.long_distance_matching(enable);
+        }
+        #[cfg(feature = "zstdmt")]
+        if let Some(workers) = config.workers {
+            compressor = compressor.workers(workers);
+        }
+        if let Some(dictionary) = &config.dictionary {
+            compressor = compressor.dictionary(dictionary);
+        }
+        if let Some(id) = config.dictionary_id {
+            compressor = compressor.dictionary_id(id);
+        }
+        for (key, value) in &config.metadata {
+            compressor = compressor.metadata(key.as_str(), value.as_slice());
+        }
+        if let Some(max_memory) = config.max_memory {
+            compressor = compressor.max_memory(max_memory);
+        }
+        Ok(compressor)
+    }
+
+    /// Returns the settings of this compressor as a [`CompressorConfig`].
+    ///
+    /// A [prepared dictionary](Compressor::dictionary_by_ref) can not be part of
+    /// the config, and neither can the [progress callback](Compressor::on_progress)
+    /// or the thread pool used for parallel compression.
+    pub fn config(&self) -> CompressorConfig {
+        let mut config = CompressorConfig {
+            level: self.level,
+            frame_size: self.frame_size,
+            checksum: self.checksum,
+            adaptive_level: self.adaptive_level,
+            deterministic: self.deterministic,
+            wide_offsets: self.wide_offsets,
+            trailer_index: self.trailer_index,
+            compact_index: self.compact_index,
+            sparse_index: self.group_size,
+            frame_alignment: self.frame_alignment,
+            holes: self.holes,
+            dictionary: match &self.dictionary {
+                Some(Dictionary::Embedded(dictionary)) => Some(dictionary.clone()),
+                _ => None,
+            },
+            dictionary_id: self.dictionary_id,
+            metadata: self.metadata.clone(),
+            max_memory: self.max_memory,
+            ..Default::default()
+        };
+        for param in &self.params {
+            match *param {
+                zstd::CParameter::WindowLog(window_log) => config.window_log = Some(window_log),
+                zstd::CParameter::SearchLog(search_log) => config.search_log = Some(search_log),
+                zstd::CParameter::Strategy(strategy) => config.strategy = Some(strategy),
+                zstd::CParameter::TargetLength(target_length) => {
+                    config.target_length = Some(target_length)
+                }
+                zstd::CParameter::EnableLongDistanceMatching(enable) => {
+                    config.long_distance_matching = Some(enable)
+                }
+                #[cfg(feature = "zstdmt")]
+                zstd::CParameter::NbWorkers(workers) => config.workers = Some(workers),
+                _ => {}
+            }
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Decompressor, Preset};
+
+    #[test]
+    fn test_config() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        assert_eq!(Compressor::new().config(), CompressorConfig::default());
+
+        let mut builder = Compressor::new()
+            .preset(Preset::Balanced)
+            .level(7)
+            .frame_size(1000)
+            .checksum(Checksum::Xxh3)
+            .search_log(4)
+            .frame_alignment(8)
+            .dictionary(&input[..1000])
+            .metadata("name", "test");
+        let config = builder.config();
+        assert_eq!(config.level, 7);
+        assert_eq!(config.frame_size, FrameSize::Fixed(1000));
+        assert_eq!(config.strategy, Some(Strategy::ZSTD_dfast));
+        assert_eq!(config.search_log, Some(4));
+        assert_eq!(config.window_log, None);
+        assert_eq!(config.dictionary.as_deref(), Some(&input[..1000]));
+
+        // compressors created from the config are equivalent to the builder
+        let mut compressor = Compressor::from_config(&config).unwrap();
+        assert_eq!(compressor.config(), config);
+        let compressed = compressor.compress(&input).unwrap();
+        assert_eq!(compressed, builder.compress(&input).unwrap());
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+        assert_eq!(d.metadata().get("name"), Some(&b"test"[..]));
+
+        let mut config = CompressorConfig {
+            trailer_index: true,
+            ..config
+        };
+        assert_ne!(compressor.config(), config);
+        let compressed = Compressor::from_config(&config)
+            .unwrap()
+            .compress(&input)
+            .unwrap();
+        assert_eq!(
+            Decompressor::new(&compressed).unwrap().get(..).unwrap(),
+            input
+        );
+
+        config.level = 100;
+        assert!(matches!(
+            Compressor::from_config(&config),
+            Err(Error::InvalidLevel(100))
+        ));
+        config.level = 3;
+        config.frame_size = FrameSize::Fixed(0);
+        assert!(matches!(
+            Compressor::from_config(&config),
+            Err(Error::InvalidFrameSize(0))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_config_serde() {
+        let config = Compressor::new()
+            .level(7)
+            .frame_size(1000)
+            .checksum(Checksum::Xxh3)
+            .strategy(Strategy::ZSTD_btultra2)
+            .dictionary(&b"dictionary"[..])
+            .metadata("name", "test")
+            .config();
+        let serialized = toml::to_string(&config).unwrap();
+        let table: toml::Table = toml::from_str(&serialized).unwrap();
+        assert_eq!(table["frame_size"].to_string(), "{ fixed = 1000 }");
+        assert_eq!(table["checksum"].as_str(), Some("xxh3"));
+        assert_eq!(table["strategy"].as_str(), Some("btultra2"));
+        assert_eq!(
+            table["metadata"].to_string(),
+            r#"[["name", [116, 101, 115, 116]]]"#
+        );
+        let deserialized: CompressorConfig = toml::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, config);
+
+        // missing fields keep their default
+        let serialized = "level = 3\nframe_size = \"auto\"\nstrategy = \"lazy2\"\n";
+        let config: CompressorConfig = toml::from_str(serialized).unwrap();
+        assert_eq!(
+            config,
+            CompressorConfig {
+                level: 3,
+                frame_size: FrameSize::Auto,
+                strategy: Some(Strategy::ZSTD_lazy2),
+                ..Default::default()
+            }
+        );
+        let config: CompressorConfig = toml::from_str("").unwrap();
+        assert_eq!(config, CompressorConfig::default());
+
+        let err = toml::from_str::<CompressorConfig>("strategy = \"slow\"").unwrap_err();
+        assert!(err.to_string().contains("unknown strategy `slow`"));
+        assert!(toml::from_str::<CompressorConfig>("checksum = \"md5\"").is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+
+use crate::cache::FrameBuf;
+use crate::pool::ZstdContext;
+use crate::Decompressor;
+
+/// A [`Read`] and [`Seek`] implementation over the uncompressed contents of an
+/// archive, created by [`Decompressor::cursor`].
+///
+/// This makes an archive usable with any API expecting a file-like object. The
+/// most recently decompressed frame is kept around, so sequential reads only
+/// decompress each frame once. The [`BufRead`] implementation hands out the
+/// remainder of that frame directly, without copying it.
+pub struct Cursor<'a, 'b> {
+    decompressor: &'a Decompressor<'b>,
+    /// The `zstd` context, which is lazily taken from the decompressor on the first read.
+    zstd: Option<ZstdContext<'a, 'b>>,
+    pos: u64,
+    frame_buf: FrameBuf,
+}
+
+impl std::fmt::Debug for Cursor<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cursor")
+            .field("pos", &self.pos)
+            .field("frame", &self.frame_buf.frame)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, 'b> Cursor<'a, 'b> {
+    pub(crate) fn new(decompressor: &'a Decompressor<'b>) -> Self {
+        Self {
+            decompressor,
+            zstd: None,
+            pos: 0,
+            frame_buf: FrameBuf::default(),
+        }
+    }
+
+    /// The current position within the uncompressed contents.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Returns the remaining contents of the frame at the current position,
+    /// decompressing it first if needed.
+    fn current_frame(&mut self) -> io::Result<&[u8]> {
+        let len = self.decompressor.archive.input_len as u64;
+        if self.pos >= len {
+            return Ok(&[]);
+        }
+        let frame_size = self.decompressor.archive.frame_size;
+        let idx = (self.pos / frame_size as u64) as usize;
+        let frame = self
+            .decompressor
+            .load_frame(&mut self.zstd, idx, &mut self.frame_buf)?;
+        let offset = (self.pos - (idx * frame_size) as u64) as usize;
+        Ok(&frame[offset.min(frame.len())..])
+    }
+}
+
+impl Read for Cursor<'_, '_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+        while read < buf.len() {
+            let frame = self.current_frame()?;
+            if frame.is_empty() {
+                break;
+            }
+            let len = frame.len().min(buf.len() - read);
+            buf[read..read + len].copy_from_slice(&frame[..len]);
+            read += len;
+            self.pos += len as u64;
+        }
+        Ok(read)
+    }
+}
+
+impl BufRead for Cursor<'_, '_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.current_frame()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+impl Seek for Cursor<'_, '_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(pos) => {
+                self.pos = pos;
+                return Ok(pos);
+            }
+            SeekFrom::End(offset) => (self.decompressor.archive.input_len as u64, offset),
+            SeekFrom::Current(offset) => (self.pos, offset),
+        };
+        self.pos = base.checked_add_signed(offset).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+
+    fn stream_position(&mut self) -> io::Result<u64> {
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Compressor;
+
+    #[test]
+    fn test_cursor() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let d = Decompressor::new(&compressed).unwrap();
+
+        let mut cursor = d.cursor();
+        let mut output = Vec::new();
+        cursor.read_to_end(&mut output).unwrap();
+        assert_eq!(output, input);
+        assert_eq!(cursor.read(&mut [0; 10]).unwrap(), 0);
+
+        let mut buf = [0; 1500];
+        cursor.seek(SeekFrom::Start(2500)).unwrap();
+        cursor.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, input[2500..4000]);
+        assert_eq!(cursor.seek(SeekFrom::Current(-100)).unwrap(), 3900);
+        cursor.read_exact(&mut buf[..200]).unwrap();
+        assert_eq!(buf[..200], input[3900..4100]);
+        assert_eq!(cursor.seek(SeekFrom::End(-10)).unwrap(), 9990);
+        assert_eq!(cursor.read(&mut buf).unwrap(), 10);
+        assert_eq!(buf[..10], input[9990..]);
+
+        cursor.seek(SeekFrom::Start(2500)).unwrap();
+        assert_eq!(cursor.fill_buf().unwrap(), &input[2500..3000]);
+        cursor.consume(400);
+        assert_eq!(cursor.fill_buf().unwrap(), &input[2900..3000]);
+        cursor.consume(100);
+        assert_eq!(cursor.fill_buf().unwrap(), &input[3000..4000]);
+
+        assert!(cursor.seek(SeekFrom::Current(-20_000)).is_err());
+        assert_eq!(cursor.position(), 3000);
+        cursor.seek(SeekFrom::Start(20_000)).unwrap();
+        assert_eq!(cursor.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_range_reader() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let d = Decompressor::new(&compressed).unwrap();
+
+        for range in [0..0, 0..1, 999..1001, 2500..7500, 0..10_000] {
+            let mut output = Vec::new();
+            d.range_reader(range.clone())
+                .unwrap()
+                .read_to_end(&mut output)
+                .unwrap();
+            assert_eq!(output, &input[range]);
+        }
+
+        let mut reader = d.range_reader(2500..4200).unwrap();
+        assert_eq!(reader.fill_buf().unwrap(), &input[2500..3000]);
+        reader.consume(500);
+        let mut buf = [0; 2000];
+        assert_eq!(reader.read(&mut buf).unwrap(), 1200);
+        assert_eq!(buf[..1200], input[3000..4200]);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert_eq!(reader.get_ref().position(), 4200);
+
+        assert!(d.range_reader(9_000..10_001).is_err());
+    }
+
+    #[test]
+    fn test_lines() {
+        let input: String = (0..1000).map(|i| format!("line {i}\n")).collect();
+        let compressed = Compressor::new()
+            .frame_size(100)
+            .compress(input.as_bytes())
+            .unwrap();
+        let d = Decompressor::new(&compressed).unwrap();
+
+        let lines: Vec<_> = d.cursor().lines().map(Result::unwrap).collect();
+        assert_eq!(lines.len(), 1000);
+        assert_eq!(lines[123], "line 123");
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::io;
+
+use crate::{Decompressor, Error};
+
+/// Resolves the external dictionaries that archives reference by id.
+///
+/// Archives created with a [`dictionary_id`](crate::Compressor::dictionary_id)
+/// do not embed their dictionary, so it has to be provided by a local store or
+/// service when opening them with [`Decompressor::dictionary_provider`].
+///
+/// This is implemented for closures, and for a [`HashMap`] of ids to dictionaries.
+pub trait DictionaryProvider {
+    /// Returns the raw bytes of the dictionary with the given `id`.
+    fn dictionary(&self, id: u32) -> io::Result<Vec<u8>>;
+}
+
+impl<F> DictionaryProvider for F
+where
+    F: Fn(u32) -> io::Result<Vec<u8>>,
+{
+    fn dictionary(&self, id: u32) -> io::Result<Vec<u8>> {
+        self(id)
+    }
+}
+
+impl DictionaryProvider for HashMap<u32, Vec<u8>> {
+    fn dictionary(&self, id: u32) -> io::Result<Vec<u8>> {
+        self.get(&id).cloned().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("unknown dictionary {id}"))
+        })
+    }
+}
+
+impl Decompressor<'_> {
+    /// Resolves the external dictionary the archive references by id, using
+    /// the given `provider`.
+    ///
+    /// This does nothing for archives that do not reference a dictionary by id.
+    /// Fails with [`Error::DictionaryUnavailable`] if the `provider` fails to
+    /// resolve the dictionary. Without a provider, reads of such archives fail
+    /// with the same error, unless a prepared dictionary was given to
+    /// [`Decompressor::with_dictionary_by_ref`].
+    pub fn dictionary_provider<P>(mut self, provider: &P) -> Result<Self, Error>
+    where
+        P: DictionaryProvider + ?Sized,
+    {
+        if let Some(id) = self.archive.dictionary_id {
+            let dictionary =
+                provider
+                    .dictionary(id)
+                    .map_err(|err| Error::DictionaryUnavailable {
+                        id,
+                        source: Some(err),
+                    })?;
+            self.resolved_dictionary = Some(dictionary);
+            // contexts created before have been created without the dictionary
+            self.zstd = Default::default();
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Checksum, Compressor, DecoderDictionary, EncoderDictionary};
+
+    #[test]
+    fn test_dictionary_provider() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let dictionary = &input[..1000];
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .dictionary(dictionary)
+            .dictionary_id(7)
+            .compress(&input)
+            .unwrap();
+        let embedded = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .dictionary(dictionary)
+            .compress(&input)
+            .unwrap();
+        // the dictionary is only referenced
+        assert_eq!(compressed.len() + dictionary.len(), embedded.len());
+
+        let mut d = Decompressor::new(&compressed).unwrap();
+        let err = d.get(..).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(matches!(
+            err.get_ref().and_then(|err| err.downcast_ref()),
+            Some(Error::DictionaryUnavailable {
+                id: 7,
+                source: None
+            })
+        ));
+
+        let dictionaries = HashMap::from([(7, dictionary.to_vec())]);
+        let mut d = d.dictionary_provider(&dictionaries).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+        d.verify().unwrap();
+
+        let provider = |id| {
+            assert_eq!(id, 7);
+            Ok(dictionary.to_vec())
+        };
+        let mut d = Decompressor::new(&compressed)
+            .unwrap()
+            .dictionary_provider(&provider)
+            .unwrap();
+        assert_eq!(d.get(2500..7500).unwrap(), &input[2500..7500]);
+
+        let err = Decompressor::new(&compressed)
+            .unwrap()
+            .dictionary_provider(&HashMap::new())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DictionaryUnavailable {
+                id: 7,
+                source: Some(_)
+            }
+        ));
+
+        // archives without a dictionary id ignore the provider
+        let plain = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let mut d = Decompressor::new(&plain)
+            .unwrap()
+            .dictionary_provider(&HashMap::new())
+            .unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+
+        // prepared dictionaries are referenced by id as well
+        let encoder_dictionary = EncoderDictionary::copy(dictionary, 3);
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .dictionary_by_ref(&encoder_dictionary)
+            .dictionary_id(7)
+            .trailer_index(true)
+            .compress(&input)
+            .unwrap();
+        let mut d = Decompressor::new(&compressed)
+            .unwrap()
+            .dictionary_provider(&dictionaries)
+            .unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+        let decoder_dictionary = DecoderDictionary::copy(dictionary);
+        let mut d = Decompressor::with_dictionary_by_ref(&compressed, &decoder_dictionary).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+    }
+}
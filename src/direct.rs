@@ -0,0 +1,177 @@
+use std::alloc::{self, Layout};
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::path::Path;
+
+use crate::{eof, Storage};
+
+/// The alignment of offsets and buffers that works with all common block sizes.
+const DEFAULT_ALIGNMENT: usize = 4 * (1 << 10);
+
+/// [`Storage`] reading a file with direct I/O, bypassing the page cache.
+///
+/// The file is opened with `O_DIRECT`, so every read goes to the disk, and none
+/// of the compressed frames are cached by the kernel. This keeps the memory used
+/// by reads deterministic, like in databases managing their own caches. Direct
+/// I/O needs offsets, lengths and buffers aligned to the block size of the device,
+/// so reads are extended to the alignment and go through an aligned buffer.
+///
+/// Archives created with a [`frame_alignment`](crate::Compressor::frame_alignment)
+/// of the same alignment have their frames start at aligned offsets, so that
+/// reads do not fetch any bytes of neighboring frames.
+///
+/// This is only available on Linux with the `direct-io` feature.
+#[derive(Debug)]
+pub struct DirectStorage {
+    file: File,
+    len: u64,
+    alignment: usize,
+}
+
+impl DirectStorage {
+    /// Opens the file at `path` for direct I/O, with an alignment of 4 KiB.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::with_alignment(path, DEFAULT_ALIGNMENT)
+    }
+
+    /// Opens the file at `path` for direct I/O, with the given `alignment`,
+    /// which has to be a power of two of at least the block size of the device.
+    pub fn with_alignment(path: impl AsRef<Path>, alignment: usize) -> io::Result<Self> {
+        if !alignment.is_power_of_two() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the alignment has to be a power of two",
+            ));
+        }
+        let file = File::options()
+            .read(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            file,
+            len,
+            alignment,
+        })
+    }
+}
+
+impl Storage for DirectStorage {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+        if range.start > range.end || range.end > self.len {
+            return Err(eof());
+        }
+        buf.clear();
+        if range.is_empty() {
+            return Ok(buf);
+        }
+        let alignment = self.alignment as u64;
+        let start = range.start & !(alignment - 1);
+        let end = range.end.next_multiple_of(alignment);
+        let len = usize::try_from(end - start).map_err(|_| eof())?;
+
+        let mut aligned = AlignedBuf::new(len, self.alignment)?;
+        // reads at the end of the file return fewer bytes than requested
+        let needed = usize::try_from(range.end - start).map_err(|_| eof())?;
+        let mut filled = 0;
+        while filled < needed {
+            match self
+                .file
+                .read_at(&mut aligned.as_mut_slice()[filled..], start + filled as u64)
+            {
+                Ok(0) => return Err(eof()),
+                Ok(read) => filled += read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        let offset = (range.start - start) as usize;
+        buf.extend_from_slice(&aligned.as_mut_slice()[offset..needed]);
+        Ok(buf)
+    }
+}
+
+/// A zeroed heap buffer with the given alignment.
+struct AlignedBuf {
+    ptr: *mut u8,
+    layout: Layout,
+}
+
+impl AlignedBuf {
+    fn new(len: usize, alignment: usize) -> io::Result<Self> {
+        let layout = Layout::from_size_align(len.max(1), alignment)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        // SAFETY: the layout has a non-zero size
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        if ptr.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        Ok(Self { ptr, layout })
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: the buffer is allocated and initialized for `layout.size()` bytes
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.layout.size()) }
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        // SAFETY: the buffer was allocated with this layout in `AlignedBuf::new`
+        unsafe { alloc::dealloc(self.ptr, self.layout) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Compressor, Decompressor};
+
+    #[test]
+    fn test_direct_storage() {
+        let input: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new()
+            .frame_size(10_000)
+            .frame_alignment(DEFAULT_ALIGNMENT)
+            .compress(&input)
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("seezee-test-direct-{}", std::process::id()));
+        std::fs::write(&path, &compressed).unwrap();
+
+        let storage = match DirectStorage::open(&path) {
+            Ok(storage) => storage,
+            // some file systems, like tmpfs, do not support direct I/O
+            Err(err) if err.kind() == io::ErrorKind::InvalidInput => {
+                std::fs::remove_file(&path).unwrap();
+                return;
+            }
+            Err(err) => panic!("{err}"),
+        };
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(storage.len(), compressed.len() as u64);
+        let mut buf = Vec::new();
+        assert_eq!(
+            storage.read(10..5000, &mut buf).unwrap(),
+            &compressed[10..5000]
+        );
+        let end = compressed.len() as u64;
+        assert_eq!(
+            storage.read(end - 3..end, &mut buf).unwrap(),
+            &compressed[compressed.len() - 3..]
+        );
+        assert!(storage.read(end - 3..end + 1, &mut buf).is_err());
+
+        let mut index = Vec::new();
+        let mut d = Decompressor::with_storage(storage, &mut index).unwrap();
+        assert_eq!(d.get(15_000..35_000).unwrap(), &input[15_000..35_000]);
+        assert_eq!(d.get(..).unwrap(), input);
+
+        assert!(DirectStorage::with_alignment(&path, 1000).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::Storage;
+
+/// The extension of the files holding cached ranges.
+const EXTENSION: &str = "range";
+
+/// [`Storage`] caching the ranges read from another storage in a local directory.
+///
+/// Every range read from the wrapped storage, like the index and compressed frames
+/// of an archive fetched by [`HttpStorage`](crate::HttpStorage), is persisted to
+/// a file in the cache directory. Subsequent reads of the same range, including
+/// reads by later processes, are served from that file instead of fetching it
+/// again. When the cached files exceed the size limit, the least recently used
+/// ones are removed.
+///
+/// The cache directory has to be dedicated to a single archive, as the files
+/// are only identified by their range. Failures to write to the cache are ignored.
+#[derive(Debug)]
+pub struct DiskCacheStorage<S> {
+    storage: S,
+    dir: PathBuf,
+    max_bytes: u64,
+    /// The total size of the cached files.
+    size: Mutex<u64>,
+    /// A counter to create unique names for temporary files.
+    tmp_files: AtomicU64,
+}
+
+impl<S: Storage> DiskCacheStorage<S> {
+    /// Wraps `storage`, caching up to `max_bytes` in files within `dir`, which
+    /// is created if it does not exist yet.
+    pub fn new(storage: S, dir: impl Into<PathBuf>, max_bytes: u64) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let size = cached_files(&dir)?.iter().map(|file| file.len).sum();
+        Ok(Self {
+            storage,
+            dir,
+            max_bytes,
+            size: Mutex::new(size),
+            tmp_files: AtomicU64::new(0),
+        })
+    }
+
+    /// Returns the wrapped storage.
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+
+    /// Reads the cached `range` from `path` into `buf`, marking it as recently used.
+    fn read_cached(&self, path: &Path, range: &Range<u64>, buf: &mut Vec<u8>) -> io::Result<()> {
+        let mut file = File::open(path)?;
+        buf.clear();
+        file.read_to_end(buf)?;
+        if buf.len() as u64 != range.end - range.start {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        // the modification time is used to evict the least recently used files
+        let _ = File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(SystemTime::now()));
+        Ok(())
+    }
+
+    /// Adds `bytes` to the cache as `path`, evicting other files if needed.
+    fn insert(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let len = bytes.len() as u64;
+        if len > self.max_bytes {
+            return Ok(());
+        }
+        // concurrent reads of the same range each write their own file first
+        let tmp = self.dir.join(format!(
+            "{}-{}.tmp",
+            std::process::id(),
+            self.tmp_files.fetch_add(1, Ordering::Relaxed)
+        ));
+        let written = File::create(&tmp).and_then(|mut file| file.write_all(bytes));
+        if let Err(err) = written.and_then(|_| fs::rename(&tmp, path)) {
+            let _ = fs::remove_file(&tmp);
+            return Err(err);
+        }
+
+        let mut size = self.size.lock().unwrap_or_else(|err| err.into_inner());
+        *size += len;
+        if *size > self.max_bytes {
+            *size = self.evict()?;
+        }
+        Ok(())
+    }
+
+    /// Removes the least recently used files until the cache fits into the
+    /// size limit, returning the remaining size.
+    fn evict(&self) -> io::Result<u64> {
+        let mut files = cached_files(&self.dir)?;
+        let mut size: u64 = files.iter().map(|file| file.len).sum();
+        files.sort_by_key(|file| file.used);
+        for file in files {
+            if size <= self.max_bytes {
+                break;
+            }
+            if fs::remove_file(&file.path).is_ok() {
+                size -= file.len;
+            }
+        }
+        Ok(size)
+    }
+}
+
+impl<S: Storage> Storage for DiskCacheStorage<S> {
+    fn len(&self) -> u64 {
+        self.storage.len()
+    }
+
+    fn prefetch(&self, ranges: &[Range<u64>]) {
+        self.storage.prefetch(ranges)
+    }
+
+    fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+        if range.start >= range.end {
+            return self.storage.read(range, buf);
+        }
+        let path = self
+            .dir
+            .join(format!("{}-{}.{EXTENSION}", range.start, range.end));
+        if self.read_cached(&path, &range, buf).is_ok() {
+            return Ok(buf);
+        }
+        let bytes = self.storage.read(range, buf)?;
+        // the cache is only an optimization, so failing to write it is fine
+        let _ = self.insert(&path, bytes);
+        Ok(bytes)
+    }
+}
+
+struct CachedFile {
+    path: PathBuf,
+    len: u64,
+    used: SystemTime,
+}
+
+/// Lists all cached files within `dir`.
+fn cached_files(dir: &Path) -> io::Result<Vec<CachedFile>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path
+            .extension()
+            .is_none_or(|extension| extension != EXTENSION)
+        {
+            continue;
+        }
+        // files may be removed concurrently
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        files.push(CachedFile {
+            path,
+            len: metadata.len(),
+            used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        });
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::{Compressor, Decompressor};
+
+    /// Storage counting the bytes read from it.
+    struct Counting {
+        bytes: Vec<u8>,
+        read: AtomicUsize,
+    }
+
+    impl Storage for Counting {
+        fn len(&self) -> u64 {
+            self.bytes.len() as u64
+        }
+
+        fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+            let bytes = self.bytes.read(range, buf)?;
+            self.read.fetch_add(bytes.len(), Ordering::Relaxed);
+            Ok(bytes)
+        }
+    }
+
+    #[test]
+    fn test_disk_cache() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let dir = std::env::temp_dir().join(format!("seezee-test-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let counting = Counting {
+            bytes: compressed.clone(),
+            read: AtomicUsize::new(0),
+        };
+
+        for _ in 0..2 {
+            let storage = DiskCacheStorage::new(&counting, &dir, 1 << 20).unwrap();
+            let mut index = Vec::new();
+            let mut d = Decompressor::with_storage(&storage, &mut index).unwrap();
+            assert_eq!(d.get(1500..3500).unwrap(), &input[1500..3500]);
+            assert_eq!(d.get(..).unwrap(), input);
+        }
+        // later passes are served from the cache entirely
+        let read = counting.read.load(Ordering::Relaxed);
+        assert!(read > 0);
+        let storage = DiskCacheStorage::new(&counting, &dir, 1 << 20).unwrap();
+        let mut index = Vec::new();
+        Decompressor::with_storage(&storage, &mut index)
+            .unwrap()
+            .get(..)
+            .unwrap();
+        assert_eq!(counting.read.load(Ordering::Relaxed), read);
+
+        // a small cache evicts files to stay within its limit
+        let storage = DiskCacheStorage::new(&counting, &dir, 1000).unwrap();
+        for start in (0..compressed.len() as u64 - 100).step_by(100) {
+            storage.read(start..start + 100, &mut Vec::new()).unwrap();
+        }
+        let size: u64 = cached_files(&dir)
+            .unwrap()
+            .iter()
+            .map(|file| file.len)
+            .sum();
+        assert!(size <= 1000);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
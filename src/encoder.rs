@@ -0,0 +1,88 @@
+use std::io;
+
+use crate::format::FrameInfo;
+use crate::zstd;
+use crate::Checksum;
+
+/// The compression level used to probe whether a frame is worth compressing harder.
+pub const PROBE_LEVEL: i32 = 1;
+
+/// Compresses individual frames, using the configured `zstd` context.
+pub struct FrameEncoder<'d> {
+    compressor: zstd::Compressor<'d>,
+    /// A fast context, used to probe the compressibility of each frame before
+    /// spending time on compressing it with `compressor`.
+    probe: Option<zstd::Compressor<'d>>,
+    checksum: Checksum,
+    /// Whether frames that do not compress are stored uncompressed.
+    stored_frames: bool,
+    /// Whether frames consisting only of zeroes are stored as holes.
+    holes: bool,
+}
+
+impl<'d> FrameEncoder<'d> {
+    pub fn new(
+        compressor: zstd::Compressor<'d>,
+        probe: Option<zstd::Compressor<'d>>,
+        checksum: Checksum,
+        stored_frames: bool,
+        holes: bool,
+    ) -> Self {
+        Self {
+            compressor,
+            probe,
+            checksum,
+            stored_frames,
+            holes,
+        }
+    }
+
+    /// Compresses a single frame, appending it to `buf`.
+    ///
+    /// Frames that would grow in size are stored uncompressed instead, unless
+    /// disabled. Frames consisting only of zeroes are not written at all when
+    /// storing holes.
+    pub fn compress_frame(&mut self, source: &[u8], buf: &mut Vec<u8>) -> io::Result<FrameInfo> {
+        let checksum = self.checksum.digest(source);
+        if self.holes && source.iter().all(|&byte| byte == 0) {
+            return Ok(FrameInfo {
+                stored: false,
+                checksum,
+            });
+        }
+        let stored = self.compress(source, buf)?;
+        Ok(FrameInfo { stored, checksum })
+    }
+
+    /// Compresses `source`, returning `true` if it was stored uncompressed.
+    fn compress(&mut self, source: &[u8], buf: &mut Vec<u8>) -> io::Result<bool> {
+        let start = buf.len();
+        buf.reserve(zstd::compress_bound(source.len()));
+
+        if let Some(probe) = &mut self.probe {
+            let mut destination = zstd::spare_capacity_buf(buf);
+            let bytes_written = probe.compress_to_buffer(source, &mut destination)?;
+            // content that barely compresses at all won't do much better at a
+            // higher level, so keep the probe result
+            if bytes_written >= source.len() - source.len() / 32 {
+                return Ok(self.stored_frames && store_if_larger(source, buf, start));
+            }
+            buf.truncate(start);
+        }
+
+        let mut destination = zstd::spare_capacity_buf(buf);
+        self.compressor
+            .compress_to_buffer(source, &mut destination)?;
+        Ok(self.stored_frames && store_if_larger(source, buf, start))
+    }
+}
+
+/// Replaces the frame compressed into `buf[start..]` with `source` if it did not compress.
+fn store_if_larger(source: &[u8], buf: &mut Vec<u8>, start: usize) -> bool {
+    if buf.len() - start < source.len() {
+        return false;
+    }
+    buf.truncate(start);
+    buf.extend_from_slice(source);
+    true
+}
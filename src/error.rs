@@ -0,0 +1,217 @@
+use std::ops::Range;
+use std::{fmt, io};
+
+/// An error that can happen when configuring a [`Compressor`](crate::Compressor),
+/// creating an archive, or reading from it.
+///
+/// Reads return an [`io::Error`] wrapping this error, which can be retrieved
+/// with [`io::Error::get_ref`] and `downcast_ref`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The compression level is outside of the range supported by `zstd`.
+    InvalidLevel(i32),
+    /// The frame size is either zero, or too large to be represented in an archive.
+    InvalidFrameSize(usize),
+    /// A section of an archive, like the metadata or the dictionary, is too large
+    /// to be represented in the archive format.
+    TooLarge,
+    /// The compression was cancelled by the [progress callback](crate::Compressor::on_progress),
+    /// or a read by [`Decompressor::cancel_when`](crate::Decompressor::cancel_when).
+    Cancelled,
+    /// The header of an archive ends before all of its fields.
+    TruncatedHeader,
+    /// The header of an archive contains an invalid value, like a frame size of zero.
+    InvalidHeader,
+    /// The archive uses a format version that is not supported.
+    UnsupportedVersion(u32),
+    /// The archive uses required features that are not supported, signaled by these flags.
+    UnsupportedFlags(u32),
+    /// The footer at the end of an archive with a trailing index is malformed.
+    InvalidFooter,
+    /// The seek table of an archive in the upstream `zstd` seekable format is malformed.
+    InvalidSeekTable,
+    /// The checksums section of an archive does not match its number of frames.
+    InvalidChecksums,
+    /// The metadata section of an archive is malformed.
+    InvalidMetadata,
+    /// The compressed frames of an archive opened with
+    /// [`Decompressor::new_strict`](crate::Decompressor::new_strict) span `actual`
+    /// bytes, while its offset table expects `expected` bytes.
+    LengthMismatch {
+        /// The length of the compressed frames according to the offset table.
+        expected: u64,
+        /// The actual length of the compressed frames.
+        actual: u64,
+    },
+    /// The archive declares more uncompressed data than its compressed frames
+    /// can possibly hold.
+    ImplausibleInputLen(u64),
+    /// The archive exceeds the [`Limits`](crate::Limits) it was opened with.
+    LimitExceeded,
+    /// The frame offset table of an archive is malformed, or points outside
+    /// of the compressed frames.
+    InvalidOffsetTable,
+    /// The compressed frame `frame` could not be decoded.
+    FrameDecodeFailed {
+        /// The index of the frame.
+        frame: usize,
+        /// The range of the compressed frame within the archive.
+        compressed: Range<u64>,
+        /// The error of `zstd`, if it failed to decompress the frame.
+        source: Option<io::Error>,
+    },
+    /// The compressed bytes of frame `frame` are missing, as the archive is truncated.
+    TruncatedFrame {
+        /// The index of the frame.
+        frame: usize,
+        /// The number of bytes missing from the end of the compressed frames.
+        missing: u64,
+        /// The uncompressed range at the start of the archive whose frames are complete.
+        readable: Range<usize>,
+    },
+    /// The uncompressed frame `frame` does not match its checksum.
+    ChecksumMismatch {
+        /// The index of the frame.
+        frame: usize,
+    },
+    /// The archive references the external dictionary `id`, which was not
+    /// resolved by a [`DictionaryProvider`](crate::DictionaryProvider).
+    DictionaryUnavailable {
+        /// The id of the dictionary.
+        id: u32,
+        /// The error of the provider, if it failed to resolve the dictionary.
+        source: Option<io::Error>,
+    },
+    /// The requested `range` exceeds the uncompressed length `len` of the archive.
+    RangeOutOfBounds {
+        /// The requested range.
+        range: Range<usize>,
+        /// The uncompressed length of the archive.
+        len: usize,
+    },
+    /// The uncompressed length `len` of an archive opened as a [`SeeZeeVec`](crate::SeeZeeVec)
+    /// is not a multiple of its `element_size`.
+    ElementSizeMismatch {
+        /// The uncompressed length of the archive.
+        len: usize,
+        /// The size of each element.
+        element_size: usize,
+    },
+    /// A range request of [`HttpStorage`](crate::HttpStorage) failed with this
+    /// HTTP status code.
+    HttpStatus(u16),
+    /// An underlying I/O or `zstd` error.
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidLevel(level) => write!(f, "invalid compression level {level}"),
+            Error::InvalidFrameSize(frame_size) => write!(f, "invalid frame size {frame_size}"),
+            Error::TooLarge => f.write_str("too large to be represented in an archive"),
+            Error::Cancelled => f.write_str("the operation was cancelled"),
+            Error::TruncatedHeader => f.write_str("the archive header is truncated"),
+            Error::InvalidHeader => f.write_str("invalid archive header"),
+            Error::UnsupportedVersion(version) => write!(f, "unsupported format version {version}"),
+            Error::UnsupportedFlags(flags) => write!(f, "unsupported required flags {flags:#x}"),
+            Error::InvalidFooter => f.write_str("invalid archive footer"),
+            Error::InvalidSeekTable => f.write_str("invalid seek table"),
+            Error::InvalidChecksums => f.write_str("invalid checksums section"),
+            Error::InvalidMetadata => f.write_str("invalid metadata section"),
+            Error::LengthMismatch { expected, actual } => write!(
+                f,
+                "the compressed frames span {actual} bytes instead of {expected} bytes"
+            ),
+            Error::ImplausibleInputLen(input_len) => {
+                write!(f, "implausible uncompressed length {input_len}")
+            }
+            Error::LimitExceeded => f.write_str("the archive exceeds the limits"),
+            Error::InvalidOffsetTable => f.write_str("invalid frame offset table"),
+            Error::FrameDecodeFailed {
+                frame, compressed, ..
+            } => write!(
+                f,
+                "failed to decode frame {frame} at compressed bytes {}..{}",
+                compressed.start, compressed.end
+            ),
+            Error::TruncatedFrame {
+                frame,
+                missing,
+                readable,
+            } => write!(
+                f,
+                "frame {frame} is truncated, missing {missing} bytes, only {}..{} is readable",
+                readable.start, readable.end
+            ),
+            Error::ChecksumMismatch { frame } => write!(f, "checksum mismatch in frame {frame}"),
+            Error::DictionaryUnavailable { id, .. } => {
+                write!(f, "dictionary {id} is not available")
+            }
+            Error::RangeOutOfBounds { range, len } => write!(
+                f,
+                "range {}..{} is out of bounds of {len} bytes",
+                range.start, range.end
+            ),
+            Error::ElementSizeMismatch { len, element_size } => write!(
+                f,
+                "the length {len} is not a multiple of the element size {element_size}"
+            ),
+            Error::HttpStatus(status) => write!(f, "range request failed with status {status}"),
+            Error::Io(_) => f.write_str("I/O error"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::FrameDecodeFailed {
+                source: Some(err), ..
+            }
+            | Error::DictionaryUnavailable {
+                source: Some(err), ..
+            } => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io(err) => err,
+            err @ (Error::Cancelled | Error::HttpStatus(_)) => io::Error::other(err),
+            err @ Error::DictionaryUnavailable { .. } => {
+                io::Error::new(io::ErrorKind::NotFound, err)
+            }
+            err @ (Error::RangeOutOfBounds { .. } | Error::TruncatedFrame { .. }) => {
+                io::Error::new(io::ErrorKind::UnexpectedEof, err)
+            }
+            err @ (Error::TruncatedHeader
+            | Error::InvalidHeader
+            | Error::UnsupportedVersion(_)
+            | Error::UnsupportedFlags(_)
+            | Error::InvalidFooter
+            | Error::InvalidSeekTable
+            | Error::InvalidChecksums
+            | Error::InvalidMetadata
+            | Error::LengthMismatch { .. }
+            | Error::ImplausibleInputLen(_)
+            | Error::LimitExceeded
+            | Error::InvalidOffsetTable
+            | Error::FrameDecodeFailed { .. }
+            | Error::ChecksumMismatch { .. }
+            | Error::ElementSizeMismatch { .. }) => io::Error::new(io::ErrorKind::InvalidData, err),
+            err => io::Error::new(io::ErrorKind::InvalidInput, err),
+        }
+    }
+}
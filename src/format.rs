@@ -0,0 +1,1533 @@
+//! The binary layout of an archive.
+//!
+//! An archive starts with a header, followed by the frame offset table and
+//! the compressed frames:
+//!
+//! - `magic: [u8; 4]`: The [`MAGIC`] bytes identifying an archive.
+//! - `version: u32`: The format [`VERSION`].
+//! - `flags: u32`: The features used by the archive, as described below.
+//! - `frame_size: u32`: The uncompressed size of each frame.
+//! - `input_len`: The total uncompressed size, as `u32`.
+//! - `frame_offsets`: `num_frames + 1` offsets as `u32`, pointing to the start
+//!   of each compressed frame, relative to the end of the header.
+//!
+//! The lower 16 bits of the `flags` are [`REQUIRED_FLAGS`], which change the
+//! layout of the archive in ways that a reader has to understand. Archives with
+//! unknown required flags are rejected. The upper 16 bits are optional flags,
+//! each of which adds a section following the offset table, in the order of the
+//! flag bits. Every optional section starts with its `u32` length, and is padded
+//! to a multiple of 4 bytes, so readers can skip sections they do not know about.
+//!
+//! With [`WIDE_OFFSETS`], `input_len` and all the `frame_offsets` are instead
+//! stored as `u64`.
+//!
+//! With [`SPARSE_OFFSETS`], `frame_size` is followed by a `u32` `group_size`.
+//! The offset table then only contains the offset of every `group_size`-th
+//! frame, followed by the end of the last frame. The frames within a group are
+//! located by parsing the headers of the compressed frames, so frames are never
+//! stored uncompressed.
+//!
+//! With [`PADDED_FRAMES`], `frame_size` and the `group_size` are followed by a
+//! `u32` `frame_alignment`. The header is then padded with zeroes so that the
+//! first frame starts at a multiple of `frame_alignment` bytes, relative to the
+//! start of the archive, and each frame is followed by zero padding up to the next
+//! multiple of `frame_alignment`. The frame offsets point to the padded frames,
+//! the actual end of a frame is determined by parsing its compressed frame header,
+//! or by the `frame_size` for stored frames.
+//!
+//! Any of the optional `u32` fields following `frame_size` are followed by 4 bytes
+//! of padding with [`WIDE_OFFSETS`] if necessary to keep everything properly aligned.
+//!
+//! With [`COMPACT_OFFSETS`], the `frame_offsets` are replaced by a `u32` length
+//! and the compressed size of each frame, encoded as a LEB128 varint, padded to a
+//! multiple of 4 bytes. The lowest bit of each size signals a stored frame, and the
+//! remaining bits hold the size itself. [`WIDE_OFFSETS`] then only affects `input_len`.
+//!
+//! With [`HOLES`], frames with a compressed size of zero consist entirely of
+//! zeroes, which do not take up any space in the archive.
+//!
+//! With [`STORED_FRAMES`], the most significant bit of each frame offset signals
+//! that the frame starting at that offset is stored uncompressed, as it would
+//! have otherwise grown in size.
+//!
+//! The optional [`CHECKSUMS`] section consists of the `u32` id of the
+//! [`Checksum`] algorithm, and a checksum of the uncompressed contents of each frame.
+//!
+//! The optional [`METADATA`] section consists of the encoded [`Metadata`] entries.
+//! Each entry is encoded as a `u32` length and the bytes of its key, followed by
+//! a `u32` length and the bytes of its value.
+//!
+//! With [`DICTIONARY`], the optional sections are followed by a `u32` length and
+//! the raw bytes of the dictionary that was used to compress all the frames.
+//!
+//! With [`DICTIONARY_ID`], the optional sections are instead followed by the `u32`
+//! id of an external dictionary that was used to compress all the frames, which
+//! readers have to resolve on their own. It can not be combined with [`DICTIONARY`].
+//!
+//! With [`TRAILER`], the header only consists of `magic`, `version` and the
+//! `flags` with only the [`TRAILER`] flag, followed by 4 bytes of padding. The
+//! compressed frames follow directly, and everything else that is usually part
+//! of the header is instead written after the frames, padded to a multiple of
+//! 8 bytes. The archive then ends with a footer, consisting of the `u64` offset
+//! of that trailing index, followed by `magic` and `version` once more. This way,
+//! archives can be written in a single pass, without knowing the number of
+//! frames up front.
+//!
+//! All integers are stored in little-endian byte order.
+//!
+//! Archives created before the introduction of the magic bytes and version start
+//! with `frame_size` directly, and are still supported. Archives in the upstream
+//! `zstd` seekable format are detected by the magic number at their very end.
+
+use std::io::{self, Read, Write};
+use std::mem;
+use std::ops::Range;
+
+use watto::Pod;
+
+use crate::checksum::{Checksum, Digest};
+use crate::metadata::{self, Metadata};
+use crate::seekable;
+use crate::storage::{self, Storage};
+use crate::{zstd, Error};
+
+/// The magic bytes at the start of every archive.
+pub const MAGIC: [u8; 4] = *b"SeeZ";
+/// The current format version.
+pub const VERSION: u32 = 1;
+
+/// Required flag signaling 64-bit `input_len` and `frame_offsets`.
+pub const WIDE_OFFSETS: u32 = 1 << 0;
+/// Required flag signaling an embedded dictionary.
+pub const DICTIONARY: u32 = 1 << 1;
+/// Required flag signaling that some frames are stored uncompressed.
+pub const STORED_FRAMES: u32 = 1 << 2;
+/// Required flag signaling that the index is located at the end of the archive.
+pub const TRAILER: u32 = 1 << 3;
+/// Required flag signaling varint encoded frame sizes instead of offsets.
+pub const COMPACT_OFFSETS: u32 = 1 << 4;
+/// Required flag signaling that only the offset of every n-th frame is stored.
+pub const SPARSE_OFFSETS: u32 = 1 << 5;
+/// Required flag signaling that frames are padded to a fixed alignment.
+pub const PADDED_FRAMES: u32 = 1 << 6;
+/// Required flag signaling that some frames are holes consisting only of zeroes.
+pub const HOLES: u32 = 1 << 7;
+/// Required flag signaling a reference to an external dictionary.
+pub const DICTIONARY_ID: u32 = 1 << 8;
+/// Optional flag signaling per-frame checksums.
+pub const CHECKSUMS: u32 = 1 << 16;
+/// Optional flag signaling a metadata section.
+pub const METADATA: u32 = 1 << 17;
+
+/// The flags that a reader has to understand to read an archive.
+pub const REQUIRED_FLAGS: u32 = 0xffff;
+/// The required flags supported by this version.
+const KNOWN_REQUIRED_FLAGS: u32 = WIDE_OFFSETS
+    | DICTIONARY
+    | STORED_FRAMES
+    | TRAILER
+    | COMPACT_OFFSETS
+    | SPARSE_OFFSETS
+    | PADDED_FRAMES
+    | HOLES
+    | DICTIONARY_ID;
+
+/// The maximum supported frame size.
+pub const MAX_FRAME_SIZE: usize = u32::MAX as usize;
+
+/// An upper bound of the ratio between the uncompressed and compressed size that
+/// `zstd` can achieve, which is around 32 768 for runs of a single byte.
+const MAX_COMPRESSION_RATIO: usize = 1 << 16;
+
+/// The number of bytes initially read from the start of an archive in a
+/// [`Storage`] to parse its header, which is doubled until the header fits.
+const HEADER_READ_SIZE: u64 = 4 * (1 << 10);
+
+/// The size of the header in front of the frames with the [`TRAILER`] layout.
+pub const TRAILER_HEADER_SIZE: usize = 16;
+/// The size of the footer at the end of archives with the [`TRAILER`] layout.
+const FOOTER_SIZE: usize = 16;
+
+/// Bit on a `u64` frame offset signaling that the frame is stored uncompressed.
+pub const STORED_FRAME: u64 = 1 << 63;
+const NARROW_STORED_FRAME: u32 = 1 << 31;
+
+/// Whether an archive of the given sizes needs [`WIDE_OFFSETS`].
+pub fn needs_wide_offsets(input_len: u64, compressed_len: u64) -> bool {
+    input_len > u32::MAX as u64 || compressed_len >= NARROW_STORED_FRAME as u64
+}
+
+/// Information about a single compressed frame.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameInfo {
+    /// Whether the frame is stored uncompressed.
+    pub stored: bool,
+    /// The checksum of the uncompressed frame, if enabled.
+    pub checksum: Option<Digest>,
+}
+
+/// The frame offset table and checksums of an archive that is being created.
+#[derive(Debug)]
+pub struct FrameTable {
+    /// The `num_frames + 1` frame offsets, with the [`STORED_FRAME`] bit.
+    pub offsets: Vec<u64>,
+    /// The concatenated checksums of all frames.
+    pub checksums: Vec<u8>,
+}
+
+impl FrameTable {
+    pub fn new() -> Self {
+        Self {
+            offsets: vec![0],
+            checksums: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.offsets.clear();
+        self.offsets.push(0);
+        self.checksums.clear();
+    }
+
+    pub fn reserve(&mut self, num_frames: usize) {
+        self.offsets.reserve(num_frames);
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Adds a frame ending at the given offset.
+    pub fn push(&mut self, end: u64, frame: FrameInfo) {
+        if frame.stored {
+            if let Some(start) = self.offsets.last_mut() {
+                *start |= STORED_FRAME;
+            }
+        }
+        self.offsets.push(end);
+        if let Some(checksum) = frame.checksum {
+            self.checksums.extend_from_slice(checksum.as_bytes());
+        }
+    }
+}
+
+impl Default for FrameTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The archive header, describing everything in front of the compressed frames.
+#[derive(Debug)]
+pub struct Header<'d> {
+    pub frame_size: usize,
+    pub input_len: u64,
+    pub wide: bool,
+    pub compact: bool,
+    /// The number of frames per offset table entry, with [`SPARSE_OFFSETS`] if larger than `1`.
+    pub group_size: usize,
+    /// The alignment of the frames, with [`PADDED_FRAMES`] if larger than `1`.
+    pub frame_alignment: usize,
+    pub checksum: Checksum,
+    pub metadata: &'d [(String, Vec<u8>)],
+    pub dictionary: Option<&'d [u8]>,
+    /// The id of an external dictionary, which is referenced instead of an embedded one.
+    pub dictionary_id: Option<u32>,
+}
+
+impl Header<'_> {
+    /// The size of the header and offset table for an archive with `num_frames`.
+    ///
+    /// Returns `None` for a [`COMPACT_OFFSETS`] table, as its size depends on
+    /// the sizes of the compressed frames.
+    pub fn size(&self, num_frames: usize) -> Option<usize> {
+        if self.compact {
+            return None;
+        }
+        let num_offsets = num_frames.div_ceil(self.group_size) + 1;
+        let mut size = MAGIC.len() + 3 * mem::size_of::<u32>();
+        if self.group_size > 1 {
+            size += mem::size_of::<u32>();
+        }
+        if self.frame_alignment > 1 {
+            size += mem::size_of::<u32>();
+        }
+        if self.wide {
+            size = size.next_multiple_of(mem::size_of::<u64>());
+            size += (num_offsets + 1) * mem::size_of::<u64>();
+        } else {
+            size += (num_offsets + 1) * mem::size_of::<u32>();
+        }
+        if self.checksum != Checksum::None {
+            size += 2 * mem::size_of::<u32>() + num_frames * self.checksum.size();
+        }
+        if !self.metadata.is_empty() {
+            size +=
+                mem::size_of::<u32>() + metadata::encoded_size(self.metadata).next_multiple_of(4);
+        }
+        if let Some(dictionary) = self.dictionary {
+            size += mem::size_of::<u32>() + dictionary.len();
+        } else if self.dictionary_id.is_some() {
+            size += mem::size_of::<u32>();
+        }
+        Some(size + padding(size as u64, self.frame_alignment))
+    }
+
+    /// Writes the archive header, followed by the frame offset table.
+    pub fn write<W: Write>(&self, writer: &mut W, table: &FrameTable) -> io::Result<()> {
+        let mut writer = CountingWriter {
+            inner: writer,
+            written: 0,
+        };
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        self.write_index(&mut writer, table)?;
+        let padding = padding(writer.written, self.frame_alignment);
+        write_padding(&mut writer, padding)
+    }
+
+    /// Writes the header of an archive with the [`TRAILER`] layout, which is
+    /// followed by the compressed frames.
+    pub fn write_trailer_header<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&TRAILER.to_le_bytes())?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        write_padding(writer, self.trailer_header_size() - TRAILER_HEADER_SIZE)
+    }
+
+    /// The size of the header of an archive with the [`TRAILER`] layout,
+    /// including the padding in front of the first frame.
+    pub fn trailer_header_size(&self) -> usize {
+        TRAILER_HEADER_SIZE + padding(TRAILER_HEADER_SIZE as u64, self.frame_alignment)
+    }
+
+    /// Writes the index and footer of an archive with the [`TRAILER`] layout,
+    /// which follows `frames_len` bytes of compressed frames.
+    pub fn write_trailer<W: Write>(
+        &self,
+        writer: &mut W,
+        table: &FrameTable,
+        frames_len: u64,
+    ) -> io::Result<()> {
+        let frames_start = self.trailer_header_size() as u64;
+        let frames_end = frames_start + frames_len;
+        let index_offset = frames_end.next_multiple_of(8);
+        writer.write_all(&[0; 7][..(index_offset - frames_end) as usize])?;
+
+        self.write_index(writer, table)?;
+
+        writer.write_all(&index_offset.to_le_bytes())?;
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())
+    }
+
+    /// Writes everything in the header following the `magic` and `version`.
+    fn write_index<W: Write>(&self, writer: &mut W, table: &FrameTable) -> io::Result<()> {
+        let frame_offsets = &table.offsets;
+        let num_frames = table.num_frames();
+        // the offsets at the start of each group, and the end of the last frame
+        let mut group_offsets = (0..num_frames)
+            .step_by(self.group_size)
+            .chain([num_frames])
+            .map(|idx| frame_offsets[idx]);
+
+        let mut flags = 0;
+        if self.wide {
+            flags |= WIDE_OFFSETS;
+        }
+        if self.dictionary.is_some() {
+            flags |= DICTIONARY;
+        } else if self.dictionary_id.is_some() {
+            flags |= DICTIONARY_ID;
+        }
+        if frame_offsets
+            .iter()
+            .any(|offset| offset & STORED_FRAME != 0)
+        {
+            flags |= STORED_FRAMES;
+        }
+        if frame_offsets
+            .windows(2)
+            .any(|frame| frame[0] & !STORED_FRAME == frame[1] & !STORED_FRAME)
+        {
+            flags |= HOLES;
+        }
+        if self.compact {
+            flags |= COMPACT_OFFSETS;
+        }
+        if self.group_size > 1 {
+            flags |= SPARSE_OFFSETS;
+        }
+        if self.frame_alignment > 1 {
+            flags |= PADDED_FRAMES;
+        }
+        if self.checksum != Checksum::None {
+            let checksums_len = num_frames * self.checksum.size();
+            debug_assert_eq!(table.checksums.len(), checksums_len);
+            flags |= CHECKSUMS;
+        }
+        if !self.metadata.is_empty() {
+            flags |= METADATA;
+        }
+
+        writer.write_all(&flags.to_le_bytes())?;
+        writer.write_all(&u32_field(self.frame_size)?.to_le_bytes())?;
+        let mut fields = 0;
+        if self.group_size > 1 {
+            writer.write_all(&u32_field(self.group_size)?.to_le_bytes())?;
+            fields += 1;
+        }
+        if self.frame_alignment > 1 {
+            writer.write_all(&u32_field(self.frame_alignment)?.to_le_bytes())?;
+            fields += 1;
+        }
+        if self.wide && fields % 2 == 1 {
+            writer.write_all(&0u32.to_le_bytes())?;
+        }
+        if self.wide {
+            writer.write_all(&self.input_len.to_le_bytes())?;
+        } else {
+            writer.write_all(&u32_field(self.input_len)?.to_le_bytes())?;
+        }
+
+        if self.compact {
+            let mut sizes = Vec::with_capacity(frame_offsets.len());
+            let mut start = group_offsets.next().unwrap_or_default();
+            for end in group_offsets {
+                let size = (end & !STORED_FRAME) - (start & !STORED_FRAME);
+                let stored = (start & STORED_FRAME != 0) as u64;
+                write_varint(&mut sizes, size << 1 | stored);
+                start = end;
+            }
+            let padding = sizes.len().next_multiple_of(4) - sizes.len();
+            writer.write_all(&u32_field(sizes.len())?.to_le_bytes())?;
+            writer.write_all(&sizes)?;
+            writer.write_all(&[0; 3][..padding])?;
+        } else if self.wide {
+            for offset in group_offsets {
+                writer.write_all(&offset.to_le_bytes())?;
+            }
+        } else {
+            for offset in group_offsets {
+                // the highest bit of narrow offsets marks stored frames
+                let mut narrow = u32_field(offset & !STORED_FRAME)?;
+                if narrow & NARROW_STORED_FRAME != 0 {
+                    return Err(Error::TooLarge.into());
+                }
+                if offset & STORED_FRAME != 0 {
+                    narrow |= NARROW_STORED_FRAME;
+                }
+                writer.write_all(&narrow.to_le_bytes())?;
+            }
+        }
+
+        if self.checksum != Checksum::None {
+            let checksums_len = mem::size_of::<u32>() + table.checksums.len();
+            writer.write_all(&u32_field(checksums_len)?.to_le_bytes())?;
+            writer.write_all(&self.checksum.id().to_le_bytes())?;
+            writer.write_all(&table.checksums)?;
+        }
+
+        if !self.metadata.is_empty() {
+            let metadata_len = metadata::encoded_size(self.metadata);
+            let padding = metadata_len.next_multiple_of(4) - metadata_len;
+            writer.write_all(&u32_field(metadata_len)?.to_le_bytes())?;
+            metadata::write(writer, self.metadata)?;
+            writer.write_all(&[0; 3][..padding])?;
+        }
+
+        if let Some(dictionary) = self.dictionary {
+            writer.write_all(&u32_field(dictionary.len())?.to_le_bytes())?;
+            writer.write_all(dictionary)?;
+        } else if let Some(id) = self.dictionary_id {
+            writer.write_all(&id.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts `value` to a `u32` field of the archive, failing with
+/// [`Error::TooLarge`] if it does not fit.
+pub(crate) fn u32_field<T: TryInto<u32>>(value: T) -> io::Result<u32> {
+    value.try_into().map_err(|_| Error::TooLarge.into())
+}
+
+/// The number of bytes needed to pad `len` to a multiple of `alignment`.
+pub fn padding(len: u64, alignment: usize) -> usize {
+    (len.next_multiple_of(alignment as u64) - len) as usize
+}
+
+/// Writes `len` zero bytes of padding.
+pub fn write_padding<W: Write>(writer: &mut W, len: usize) -> io::Result<()> {
+    io::copy(&mut io::repeat(0).take(len as u64), writer)?;
+    Ok(())
+}
+
+/// A writer that keeps track of the number of bytes written.
+struct CountingWriter<W> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Appends `value` as a LEB128 varint.
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Reads a LEB128 varint from the front of `bytes`.
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        value |= ((byte & 0x7f) as u64).checked_shl(shift)?;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Splits a `T` off the front of `bytes`, failing if the header ends before it.
+///
+/// The value is copied out of `bytes`, which does not need to be aligned.
+fn read_prefix<T: Pod>(bytes: &[u8]) -> Result<(T, &[u8]), Error> {
+    let (value, rest) = bytes
+        .split_at_checked(mem::size_of::<T>())
+        .ok_or(Error::TruncatedHeader)?;
+    // SAFETY: `value` holds the bytes of a `T`, and any bit pattern is a valid `Pod`
+    Ok((unsafe { value.as_ptr().cast::<T>().read_unaligned() }, rest))
+}
+
+/// Splits a table of `len` offsets of `width` bytes off the front of `bytes`.
+fn split_offsets(bytes: &[u8], len: usize, width: usize) -> Result<(&[u8], &[u8]), Error> {
+    len.checked_mul(width)
+        .and_then(|table_len| bytes.split_at_checked(table_len))
+        .ok_or(Error::TruncatedHeader)
+}
+
+/// Splits a section of `len` bytes, padded to a multiple of 4 bytes, off the
+/// front of `bytes`, returning it without its padding.
+fn split_section(bytes: &[u8], len: usize) -> Result<(&[u8], &[u8]), Error> {
+    let padded_len = len
+        .checked_next_multiple_of(4)
+        .ok_or(Error::TruncatedHeader)?;
+    if bytes.len() < padded_len {
+        return Err(Error::TruncatedHeader);
+    }
+    let (section, rest) = bytes.split_at(padded_len);
+    Ok((&section[..len], rest))
+}
+
+/// Decodes the varint encoded frame sizes of a [`COMPACT_OFFSETS`] table into
+/// `num_frames + 1` frame offsets.
+fn decode_compact_offsets(mut sizes: &[u8], num_frames: usize) -> Option<Vec<u64>> {
+    let mut offsets = Vec::with_capacity(num_frames.min(sizes.len()) + 1);
+    let mut offset = 0u64;
+    for _ in 0..num_frames {
+        let size = read_varint(&mut sizes)?;
+        let stored = if size & 1 != 0 { STORED_FRAME } else { 0 };
+        offsets.push(offset | stored);
+        offset = offset
+            .checked_add(size >> 1)
+            .filter(|o| o & STORED_FRAME == 0)?;
+    }
+    offsets.push(offset);
+    sizes.is_empty().then_some(offsets)
+}
+
+/// The frame offset table of an archive, which is read in place without
+/// requiring the archive to be aligned.
+#[derive(Debug, Clone)]
+enum FrameOffsets<'b> {
+    /// A table of little endian `u32` offsets.
+    Narrow(&'b [u8]),
+    /// A table of little endian `u64` offsets.
+    Wide(&'b [u8]),
+    /// Offsets decoded from a [`COMPACT_OFFSETS`] table or a seek table.
+    Decoded(Vec<u64>),
+}
+
+impl FrameOffsets<'_> {
+    /// Returns the offset at `idx`, including the [`STORED_FRAME`] bit.
+    fn get(&self, idx: usize) -> Option<u64> {
+        match self {
+            FrameOffsets::Narrow(offsets) => {
+                let offset = offsets.chunks_exact(4).nth(idx)?;
+                let offset = u32::from_le_bytes(offset.try_into().unwrap());
+                let stored = if offset & NARROW_STORED_FRAME != 0 {
+                    STORED_FRAME
+                } else {
+                    0
+                };
+                Some((offset & !NARROW_STORED_FRAME) as u64 | stored)
+            }
+            FrameOffsets::Wide(offsets) => {
+                let offset = offsets.chunks_exact(8).nth(idx)?;
+                Some(u64::from_le_bytes(offset.try_into().unwrap()))
+            }
+            FrameOffsets::Decoded(offsets) => offsets.get(idx).copied(),
+        }
+    }
+}
+
+/// The compressed bytes of a single frame.
+#[derive(Debug, Clone, Copy)]
+pub enum Frame<'b> {
+    Compressed(&'b [u8]),
+    Stored(&'b [u8]),
+    /// A frame of the given length consisting only of zeroes.
+    Hole(usize),
+}
+
+/// A parsed archive.
+#[derive(Debug, Clone)]
+pub struct Archive<'b> {
+    pub frame_size: usize,
+    pub input_len: usize,
+    pub dictionary: Option<&'b [u8]>,
+    /// The id of the external dictionary the frames were compressed with.
+    pub dictionary_id: Option<u32>,
+    pub metadata: Metadata<'b>,
+    /// The flags of the archive, including [`TRAILER`] for that layout, and
+    /// zero for the legacy and seekable formats.
+    pub flags: u32,
+    stored_frames: bool,
+    holes: bool,
+    frame_offsets: FrameOffsets<'b>,
+    /// The number of frames per entry in `frame_offsets`.
+    pub group_size: usize,
+    /// The alignment of the padded frames.
+    pub frame_alignment: usize,
+    pub checksum: Checksum,
+    checksums: &'b [u8],
+    /// Whether the frames are regular `zstd` frames including the magic bytes,
+    /// as in the upstream [seekable format](crate::seekable).
+    pub zstd_frames: bool,
+    /// The compressed frames, which are empty for an archive parsed by [`Archive::read_index`].
+    pub zstd_buf: &'b [u8],
+}
+
+impl<'b> Archive<'b> {
+    /// Parses an archive in any of the supported layouts.
+    ///
+    /// Archives declaring more uncompressed data than their compressed frames
+    /// can possibly hold are rejected, as reading them would allocate huge
+    /// buffers for data that does not exist.
+    pub fn parse(bytes: &'b [u8]) -> Result<Self, Error> {
+        let archive = Self::parse_layout(bytes)?;
+        archive.check_plausible_size(archive.zstd_buf.len())?;
+        Ok(archive)
+    }
+
+    /// Fails if the `input_len` exceeds what `frames_len` bytes of compressed
+    /// frames can decompress to.
+    fn check_plausible_size(&self, frames_len: usize) -> Result<(), Error> {
+        // holes do not take up any space, so their size is not bounded
+        if self.holes || self.input_len <= frames_len.saturating_mul(MAX_COMPRESSION_RATIO) {
+            return Ok(());
+        }
+        Err(Error::ImplausibleInputLen(self.input_len as u64))
+    }
+
+    /// Reads the index of the archive in `storage` into `buf` and parses it,
+    /// without reading any of the compressed frames.
+    ///
+    /// Returns the archive without its `zstd_buf`, along with the range of the
+    /// compressed frames within the `storage`.
+    pub fn read_index(
+        storage: &dyn Storage,
+        buf: &'b mut Vec<u8>,
+    ) -> io::Result<(Self, Range<u64>)> {
+        let len = storage.len();
+        let mut head = Vec::new();
+        storage::read_to_vec(storage, 0..len.min(TRAILER_HEADER_SIZE as u64), &mut head)?;
+        let mut tail = Vec::new();
+        storage::read_to_vec(
+            storage,
+            len.saturating_sub(FOOTER_SIZE as u64)..len,
+            &mut tail,
+        )?;
+
+        let Some(rest) = head.strip_prefix(&MAGIC) else {
+            if seekable::is_seekable(&tail) {
+                return Self::read_seek_table(storage, &tail);
+            }
+            return Self::read_front_index(storage, buf);
+        };
+        let mut fields = rest
+            .chunks_exact(4)
+            .map(|field| u32::from_le_bytes(field.try_into().unwrap()));
+        let (Some(version), Some(flags)) = (fields.next(), fields.next()) else {
+            return Err(Error::TruncatedHeader.into());
+        };
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion(version).into());
+        }
+        if flags & TRAILER == 0 {
+            return Self::read_front_index(storage, buf);
+        }
+
+        let Some((index_offset, footer)) = tail
+            .split_first_chunk::<8>()
+            .filter(|_| tail.len() == FOOTER_SIZE)
+        else {
+            return Err(Error::InvalidFooter.into());
+        };
+        if footer[..4] != MAGIC || footer[4..] != VERSION.to_le_bytes() {
+            return Err(Error::InvalidFooter.into());
+        }
+        let index_offset = u64::from_le_bytes(*index_offset);
+        let index_end = len - FOOTER_SIZE as u64;
+        if index_offset > index_end {
+            return Err(Error::InvalidFooter.into());
+        }
+        storage::read_to_vec(storage, index_offset..index_end, buf)?;
+        let mut archive = Self::parse_index(buf)?;
+        archive.flags |= TRAILER;
+        if !archive.zstd_buf.is_empty() {
+            return Err(Error::InvalidFooter.into());
+        }
+        let frames_start = (TRAILER_HEADER_SIZE
+            + padding(TRAILER_HEADER_SIZE as u64, archive.frame_alignment))
+            as u64;
+        Ok(Self::detach(archive, frames_start..index_offset)?)
+    }
+
+    /// Reads the index in front of the compressed frames into `buf`, growing the
+    /// part that is read until the whole index fits.
+    fn read_front_index(
+        storage: &dyn Storage,
+        buf: &'b mut Vec<u8>,
+    ) -> io::Result<(Self, Range<u64>)> {
+        let len = storage.len();
+        let mut end = len.min(HEADER_READ_SIZE);
+        loop {
+            storage::read_to_vec(storage, 0..end, buf)?;
+            if Archive::parse_front(buf).is_ok() || end == len {
+                break;
+            }
+            end = len.min(end * 2);
+        }
+        let buf: &'b [u8] = buf;
+        let archive = Self::parse_front(buf)?;
+        let header_len = (buf.len() - archive.zstd_buf.len()) as u64;
+        let frames_start = header_len + padding(header_len, archive.frame_alignment) as u64;
+        Ok(Self::detach(archive, frames_start..len)?)
+    }
+
+    /// Parses the index in front of the compressed frames, with or without
+    /// the `magic` and `version`.
+    fn parse_front(bytes: &'b [u8]) -> Result<Self, Error> {
+        match bytes.strip_prefix(&MAGIC) {
+            Some(rest) => {
+                let (_version, rest) = read_prefix::<u32>(rest)?;
+                Self::parse_index(rest)
+            }
+            None => {
+                let (frame_size, rest) = read_prefix::<u32>(bytes)?;
+                Self::parse_table(0, u32::from_le(frame_size), rest)
+            }
+        }
+    }
+
+    /// Reads the seek table at the end of an archive in the [seekable format](crate::seekable),
+    /// which ends with the given `tail`.
+    fn read_seek_table(storage: &dyn Storage, tail: &[u8]) -> io::Result<(Self, Range<u64>)> {
+        let len = storage.len();
+        let table_start = seekable::table_len(tail)
+            .and_then(|table_len| len.checked_sub(table_len as u64))
+            .ok_or(Error::InvalidSeekTable)?;
+        let mut table = Vec::new();
+        storage::read_to_vec(storage, table_start..len, &mut table)?;
+        let frames_len = table_start.try_into().unwrap_or(usize::MAX);
+        let (frame_size, input_len, frame_offsets) =
+            seekable::parse_table(&table, frames_len).ok_or(Error::InvalidSeekTable)?;
+        let archive = Self::from_zstd_frames(frame_size, input_len, frame_offsets, &[]);
+        Ok(Self::detach(archive, 0..table_start)?)
+    }
+
+    /// Removes the compressed frames from an archive parsed from its index
+    /// alone, which are located at `frames` instead.
+    fn detach(mut archive: Self, frames: Range<u64>) -> Result<(Self, Range<u64>), Error> {
+        let frames_len = frames
+            .end
+            .checked_sub(frames.start)
+            .ok_or(Error::TruncatedHeader)?;
+        archive.check_plausible_size(frames_len.try_into().unwrap_or(usize::MAX))?;
+        archive.zstd_buf = &[];
+        Ok((archive, frames))
+    }
+
+    fn parse_layout(bytes: &'b [u8]) -> Result<Self, Error> {
+        let Some(rest) = bytes.strip_prefix(&MAGIC) else {
+            if seekable::is_seekable(bytes) {
+                return seekable::parse(bytes).ok_or(Error::InvalidSeekTable);
+            }
+            // archives without magic use the legacy layout without any flags
+            let (frame_size, rest) = read_prefix::<u32>(bytes)?;
+            return Self::parse_table(0, u32::from_le(frame_size), rest);
+        };
+        let (version, rest) = read_prefix::<u32>(rest)?;
+        if u32::from_le(version) != VERSION {
+            return Err(Error::UnsupportedVersion(u32::from_le(version)));
+        }
+
+        let (flags, _) = read_prefix::<u32>(rest)?;
+        if u32::from_le(flags) & TRAILER == 0 {
+            let mut archive = Self::parse_index(rest)?;
+            let header_len = bytes.len() - archive.zstd_buf.len();
+            let padding = padding(header_len as u64, archive.frame_alignment);
+            archive.zstd_buf = archive
+                .zstd_buf
+                .get(padding..)
+                .ok_or(Error::TruncatedHeader)?;
+            return Ok(archive);
+        }
+
+        let footer_start = bytes
+            .len()
+            .checked_sub(FOOTER_SIZE)
+            .ok_or(Error::InvalidFooter)?;
+        let (bytes, footer) = bytes.split_at(footer_start);
+        let (index_offset, footer) = footer
+            .split_first_chunk::<8>()
+            .ok_or(Error::InvalidFooter)?;
+        let (magic, version) = footer
+            .split_first_chunk::<4>()
+            .ok_or(Error::InvalidFooter)?;
+        if *magic != MAGIC || version != VERSION.to_le_bytes() {
+            return Err(Error::InvalidFooter);
+        }
+        let index = usize::try_from(u64::from_le_bytes(*index_offset))
+            .ok()
+            .and_then(|index_offset| Some((index_offset, bytes.get(index_offset..)?)));
+        let (index_offset, index) = index.ok_or(Error::InvalidFooter)?;
+
+        let mut archive = Self::parse_index(index)?;
+        if !archive.zstd_buf.is_empty() {
+            return Err(Error::InvalidFooter);
+        }
+        archive.flags |= TRAILER;
+        let padding = padding(TRAILER_HEADER_SIZE as u64, archive.frame_alignment);
+        archive.zstd_buf = bytes
+            .get(TRAILER_HEADER_SIZE + padding..index_offset)
+            .ok_or(Error::TruncatedHeader)?;
+        Ok(archive)
+    }
+
+    /// Parses the archive at the start of `bytes`, which may be followed by
+    /// further data, returning it along with its length.
+    ///
+    /// Only archives with the index in front have a known length. Any other
+    /// archive is assumed to span all of `bytes`.
+    pub fn parse_prefix(bytes: &'b [u8]) -> Result<(Self, usize), Error> {
+        let has_front_index = bytes.strip_prefix(&MAGIC).is_some_and(|rest| {
+            rest.get(4..8)
+                .is_some_and(|flags| u32::from_le_bytes(flags.try_into().unwrap()) & TRAILER == 0)
+        });
+        let mut archive = Self::parse(bytes)?;
+        if !has_front_index {
+            return Ok((archive, bytes.len()));
+        }
+        let frames_len = archive.frames_len().ok_or(Error::InvalidOffsetTable)?;
+        let header_len = bytes.len() - archive.zstd_buf.len();
+        archive.zstd_buf = archive
+            .zstd_buf
+            .get(..frames_len)
+            .ok_or(Error::InvalidOffsetTable)?;
+        Ok((archive, header_len + frames_len))
+    }
+
+    /// Parses an archive like [`Archive::parse`], but rejects it with
+    /// [`Error::LengthMismatch`] unless its compressed frames end exactly where
+    /// the offset table says they do.
+    pub fn parse_strict(bytes: &'b [u8]) -> Result<Self, Error> {
+        let has_trailer = bytes.strip_prefix(&MAGIC).is_some_and(|rest| {
+            rest.get(4..8)
+                .is_some_and(|flags| u32::from_le_bytes(flags.try_into().unwrap()) & TRAILER != 0)
+        });
+        let archive = Self::parse(bytes)?;
+        let mut expected = archive.frames_len().ok_or(Error::InvalidOffsetTable)? as u64;
+        if has_trailer {
+            // the trailing index is aligned to 8 bytes, following the frames
+            let frames_start = (TRAILER_HEADER_SIZE
+                + padding(TRAILER_HEADER_SIZE as u64, archive.frame_alignment))
+                as u64;
+            expected = frames_start
+                .checked_add(expected)
+                .and_then(|frames_end| frames_end.checked_next_multiple_of(8))
+                .ok_or(Error::InvalidOffsetTable)?
+                - frames_start;
+        }
+        let actual = archive.zstd_buf.len() as u64;
+        if actual != expected {
+            return Err(Error::LengthMismatch { expected, actual });
+        }
+        Ok(archive)
+    }
+
+    /// Returns the end of the last compressed frame, according to the offset table.
+    pub fn frames_len(&self) -> Option<usize> {
+        let num_groups = self
+            .input_len
+            .div_ceil(self.frame_size)
+            .div_ceil(self.group_size);
+        let end = self.frame_offsets.get(num_groups)?;
+        usize::try_from(end & !STORED_FRAME).ok()
+    }
+
+    /// Parses everything in the header following the `magic` and `version`,
+    /// with the compressed frames following directly.
+    fn parse_index(bytes: &'b [u8]) -> Result<Self, Error> {
+        let (flags, bytes) = read_prefix::<u32>(bytes)?;
+        let (frame_size, bytes) = read_prefix::<u32>(bytes)?;
+        Self::parse_table(u32::from_le(flags), u32::from_le(frame_size), bytes)
+    }
+
+    /// Parses everything in the header following the `flags` and `frame_size`.
+    fn parse_table(flags: u32, frame_size: u32, bytes: &'b [u8]) -> Result<Self, Error> {
+        let unknown_flags = flags & REQUIRED_FLAGS & !KNOWN_REQUIRED_FLAGS;
+        if unknown_flags != 0 {
+            return Err(Error::UnsupportedFlags(unknown_flags));
+        }
+        if frame_size == 0 {
+            return Err(Error::InvalidHeader);
+        }
+        let wide = flags & WIDE_OFFSETS != 0;
+
+        let mut fields = 0;
+        let mut bytes = bytes;
+        let mut read_field = |flag: u32| -> Result<usize, Error> {
+            if flags & flag == 0 {
+                return Ok(1);
+            }
+            let (value, rest) = read_prefix::<u32>(bytes)?;
+            bytes = rest;
+            fields += 1;
+            Ok(u32::from_le(value).max(1) as usize)
+        };
+        let group_size = read_field(SPARSE_OFFSETS)?;
+        let frame_alignment = read_field(PADDED_FRAMES)?;
+        if wide && fields % 2 == 1 {
+            let (_padding, rest) = read_prefix::<u32>(bytes)?;
+            bytes = rest;
+        }
+
+        let (input_len, bytes) = if wide {
+            let (input_len, bytes) = read_prefix::<u64>(bytes)?;
+            (u64::from_le(input_len), bytes)
+        } else {
+            let (input_len, bytes) = read_prefix::<u32>(bytes)?;
+            (u32::from_le(input_len) as u64, bytes)
+        };
+        let num_frames: usize = input_len
+            .div_ceil(frame_size as u64)
+            .try_into()
+            .map_err(|_| Error::ImplausibleInputLen(input_len))?;
+        let num_groups = num_frames.div_ceil(group_size);
+        let num_offsets = num_groups
+            .checked_add(1)
+            .ok_or(Error::ImplausibleInputLen(input_len))?;
+
+        let (frame_offsets, mut bytes) = if flags & COMPACT_OFFSETS != 0 {
+            let (sizes_len, bytes) = read_prefix::<u32>(bytes)?;
+            let (sizes, bytes) = split_section(bytes, u32::from_le(sizes_len) as usize)?;
+            let frame_offsets =
+                decode_compact_offsets(sizes, num_groups).ok_or(Error::InvalidOffsetTable)?;
+            (FrameOffsets::Decoded(frame_offsets), bytes)
+        } else if wide {
+            let (frame_offsets, bytes) = split_offsets(bytes, num_offsets, 8)?;
+            (FrameOffsets::Wide(frame_offsets), bytes)
+        } else {
+            let (frame_offsets, bytes) = split_offsets(bytes, num_offsets, 4)?;
+            (FrameOffsets::Narrow(frame_offsets), bytes)
+        };
+
+        let mut checksum = Checksum::None;
+        let mut checksums = &[][..];
+        let mut metadata = Metadata::default();
+        let mut optional_flags = flags & !REQUIRED_FLAGS;
+        while optional_flags != 0 {
+            let flag = 1 << optional_flags.trailing_zeros();
+            optional_flags &= !flag;
+
+            let (section_len, rest) = read_prefix::<u32>(bytes)?;
+            let (section, rest) = split_section(rest, u32::from_le(section_len) as usize)?;
+            bytes = rest;
+
+            match flag {
+                CHECKSUMS => {
+                    let (id, section) =
+                        read_prefix::<u32>(section).map_err(|_| Error::InvalidChecksums)?;
+                    // checksums of an unknown algorithm can not be verified
+                    if let Some(algorithm) = Checksum::from_id(u32::from_le(id)) {
+                        if Some(section.len()) != num_frames.checked_mul(algorithm.size()) {
+                            return Err(Error::InvalidChecksums);
+                        }
+                        checksum = algorithm;
+                        checksums = section;
+                    }
+                }
+                METADATA => metadata = Metadata::parse(section).ok_or(Error::InvalidMetadata)?,
+                // sections of unknown optional features are skipped
+                _ => {}
+            }
+        }
+
+        let (dictionary, zstd_buf) = if flags & DICTIONARY != 0 {
+            let (dictionary_len, bytes) = read_prefix::<u32>(bytes)?;
+            let dictionary_len = u32::from_le(dictionary_len) as usize;
+            if bytes.len() < dictionary_len {
+                return Err(Error::TruncatedHeader);
+            }
+            let (dictionary, bytes) = bytes.split_at(dictionary_len);
+            (Some(dictionary), bytes)
+        } else {
+            (None, bytes)
+        };
+        let (dictionary_id, zstd_buf) = if flags & DICTIONARY_ID != 0 {
+            if dictionary.is_some() {
+                return Err(Error::InvalidHeader);
+            }
+            let (id, bytes) = read_prefix::<u32>(zstd_buf)?;
+            (Some(u32::from_le(id)), bytes)
+        } else {
+            (None, zstd_buf)
+        };
+
+        Ok(Self {
+            frame_size: frame_size as usize,
+            input_len: input_len
+                .try_into()
+                .map_err(|_| Error::ImplausibleInputLen(input_len))?,
+            dictionary,
+            dictionary_id,
+            metadata,
+            flags,
+            stored_frames: flags & STORED_FRAMES != 0,
+            holes: flags & HOLES != 0,
+            frame_offsets,
+            group_size,
+            frame_alignment,
+            checksum,
+            checksums,
+            zstd_frames: false,
+            zstd_buf,
+        })
+    }
+
+    /// Creates an archive of regular `zstd` frames, located at the given
+    /// `frame_offsets` within `zstd_buf`.
+    pub fn from_zstd_frames(
+        frame_size: usize,
+        input_len: usize,
+        frame_offsets: Vec<u64>,
+        zstd_buf: &'b [u8],
+    ) -> Self {
+        Self {
+            frame_size,
+            input_len,
+            dictionary: None,
+            dictionary_id: None,
+            metadata: Metadata::default(),
+            flags: 0,
+            stored_frames: false,
+            holes: false,
+            frame_offsets: FrameOffsets::Decoded(frame_offsets),
+            group_size: 1,
+            frame_alignment: 1,
+            checksum: Checksum::None,
+            checksums: &[],
+            zstd_frames: true,
+            zstd_buf,
+        }
+    }
+
+    /// Returns the checksum of the uncompressed frame `idx`, if the archive has checksums.
+    pub fn frame_checksum(&self, idx: usize) -> Option<&'b [u8]> {
+        let size = self.checksum.size();
+        let start = idx.checked_mul(size)?;
+        self.checksums
+            .get(start..start.checked_add(size)?)
+            .filter(|checksum| !checksum.is_empty())
+    }
+
+    /// Returns the compressed bytes of frame `idx`.
+    pub fn frame(&self, idx: usize) -> Option<Frame<'b>> {
+        let span = self.frame_span(idx)?;
+        self.frame_from(idx, self.zstd_buf.get(span)?)
+    }
+
+    /// Returns the range of the compressed frames holding frame `idx`.
+    ///
+    /// This includes the padding following the frame with [`PADDED_FRAMES`], and
+    /// the whole group of the frame with [`SPARSE_OFFSETS`], which are stripped
+    /// by [`Archive::frame_from`].
+    pub fn frame_span(&self, idx: usize) -> Option<Range<usize>> {
+        let group = idx / self.group_size;
+        let mut start = self.frame_offsets.get(group)?;
+        let mut end = self.frame_offsets.get(group + 1)?;
+        if self.stored_frames && self.group_size == 1 {
+            start &= !STORED_FRAME;
+            end &= !STORED_FRAME;
+        }
+        Some(start.try_into().ok()?..end.try_into().ok()?)
+    }
+
+    /// Returns frame `idx` within the `bytes` of its [`Archive::frame_span`].
+    pub fn frame_from<'a>(&self, idx: usize, mut bytes: &'a [u8]) -> Option<Frame<'a>> {
+        if self.group_size > 1 {
+            return self.sparse_frame(idx, bytes);
+        }
+        let stored = self.stored_frames && self.frame_offsets.get(idx)? & STORED_FRAME != 0;
+        if self.holes && bytes.is_empty() {
+            return Some(Frame::Hole(self.frame_len(idx)?));
+        }
+        if self.frame_alignment > 1 {
+            // strip the padding following the frame
+            let frame_len = if stored {
+                self.frame_len(idx)?
+            } else {
+                zstd::frame_compressed_size(bytes)?
+            };
+            bytes = bytes.get(..frame_len)?;
+        }
+        Some(if stored {
+            Frame::Stored(bytes)
+        } else {
+            Frame::Compressed(bytes)
+        })
+    }
+
+    /// Returns the uncompressed length of frame `idx`.
+    pub fn frame_len(&self, idx: usize) -> Option<usize> {
+        let frame_start = idx.checked_mul(self.frame_size)?;
+        Some(
+            self.input_len
+                .checked_sub(frame_start)?
+                .min(self.frame_size),
+        )
+    }
+
+    /// Returns frame `idx` with [`SPARSE_OFFSETS`] within the `bytes` of its
+    /// group, by skipping over the preceding frames of the group.
+    fn sparse_frame<'a>(&self, idx: usize, mut bytes: &'a [u8]) -> Option<Frame<'a>> {
+        for _ in 0..idx % self.group_size {
+            let frame_len = zstd::frame_compressed_size(bytes)?;
+            let padding = padding(frame_len as u64, self.frame_alignment);
+            bytes = bytes.get(frame_len + padding..)?;
+        }
+        let frame_len = zstd::frame_compressed_size(bytes)?;
+        Some(Frame::Compressed(&bytes[..frame_len]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes an archive with two single-byte frames, the second one being stored.
+    fn write_header(wide: bool) -> Vec<u8> {
+        let header = Header {
+            frame_size: 0x0102,
+            input_len: 0x0201,
+            wide,
+            compact: false,
+            group_size: 1,
+            frame_alignment: 1,
+            checksum: Checksum::None,
+            metadata: &[],
+            dictionary: None,
+            dictionary_id: None,
+        };
+        let mut buf = Vec::new();
+        let mut table = FrameTable::new();
+        let compressed = FrameInfo {
+            stored: false,
+            checksum: None,
+        };
+        table.push(1, compressed);
+        table.push(
+            2,
+            FrameInfo {
+                stored: true,
+                ..compressed
+            },
+        );
+        header.write(&mut buf, &table).unwrap();
+        assert_eq!(buf.len(), header.size(table.num_frames()).unwrap());
+        buf.extend_from_slice(&[0; 2]);
+        buf
+    }
+
+    #[test]
+    fn test_little_endian_header() {
+        let narrow = write_header(false);
+        #[rustfmt::skip]
+        assert_eq!(narrow[..28], [
+            b'S', b'e', b'e', b'Z', 1, 0, 0, 0,
+            0x04, 0, 0, 0, 0x02, 0x01, 0, 0,
+            0x01, 0x02, 0, 0, 0, 0, 0, 0,
+            1, 0, 0, 0x80,
+        ]);
+
+        let wide = write_header(true);
+        #[rustfmt::skip]
+        assert_eq!(wide[..40], [
+            b'S', b'e', b'e', b'Z', 1, 0, 0, 0,
+            0x05, 0, 0, 0, 0x02, 0x01, 0, 0,
+            0x01, 0x02, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+            1, 0, 0, 0, 0, 0, 0, 0x80,
+        ]);
+
+        for buf in [narrow, wide] {
+            let archive = Archive::parse(&buf).unwrap();
+            assert_eq!(archive.frame_size, 0x0102);
+            assert_eq!(archive.input_len, 0x0201);
+            assert!(matches!(archive.frame(0), Some(Frame::Compressed(&[0]))));
+            assert!(matches!(archive.frame(1), Some(Frame::Stored(&[0]))));
+            assert!(archive.frame(2).is_none());
+        }
+    }
+
+    #[test]
+    fn test_narrow_offset_overflow() {
+        let header = Header {
+            frame_size: 1 << 20,
+            input_len: 4 << 20,
+            wide: false,
+            compact: false,
+            group_size: 1,
+            frame_alignment: 1 << 30,
+            checksum: Checksum::None,
+            metadata: &[],
+            dictionary: None,
+            dictionary_id: None,
+        };
+        let frame = FrameInfo {
+            stored: false,
+            checksum: None,
+        };
+        let mut table = FrameTable::new();
+        table.push(1 << 30, frame);
+        table.push(2 << 30, frame);
+        // the offset would collide with the bit marking stored frames
+        let mut buf = Vec::new();
+        let err = header.write(&mut buf, &table).unwrap_err();
+        assert!(matches!(
+            err.get_ref().and_then(|err| err.downcast_ref()),
+            Some(Error::TooLarge)
+        ));
+
+        table.push(4 << 30, frame);
+        assert!(header.write(&mut Vec::new(), &table).is_err());
+        let header = Header {
+            wide: true,
+            ..header
+        };
+        let mut buf = Vec::new();
+        header.write_index(&mut buf, &table).unwrap();
+    }
+
+    #[test]
+    fn test_decompression_bomb() {
+        let mut buf = write_header(false);
+        // a single frame that claims to decompress to 4 GiB
+        buf[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        buf[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Archive::parse_layout(&buf).is_ok());
+        assert!(matches!(
+            Archive::parse(&buf),
+            Err(Error::ImplausibleInputLen(0xffff_ffff))
+        ));
+
+        // unless the frames are holes
+        buf[8] |= HOLES as u8;
+        assert!(Archive::parse(&buf).is_ok());
+
+        // a frame count that overflows the offset table
+        let mut wide = write_header(true);
+        wide[8] |= HOLES as u8;
+        wide[12..16].copy_from_slice(&1u32.to_le_bytes());
+        wide[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            Archive::parse(&wide),
+            Err(Error::ImplausibleInputLen(u64::MAX))
+        ));
+    }
+
+    #[test]
+    fn test_unaligned() {
+        use crate::{Compressor, Decompressor};
+
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressors: [fn() -> Compressor<'static>; 6] = [
+            || Compressor::new().checksum(Checksum::Crc32),
+            || Compressor::new().wide_offsets(true),
+            || Compressor::new().compact_index(true),
+            || Compressor::new().sparse_index(4).frame_alignment(64),
+            || {
+                Compressor::new()
+                    .trailer_index(true)
+                    .metadata("key", "value")
+            },
+            || Compressor::new().holes(true),
+        ];
+        for compressor in compressors {
+            let compressed = compressor().frame_size(1000).compress(&input).unwrap();
+            // archives are read in place at any address, not just aligned ones
+            let mut buf = vec![0; compressed.len() + 8];
+            for shift in 0..8 {
+                buf[shift..shift + compressed.len()].copy_from_slice(&compressed);
+                let bytes = &buf[shift..shift + compressed.len()];
+                let mut d = Decompressor::new(bytes).unwrap();
+                assert_eq!(d.get(..).unwrap(), input);
+                assert_eq!(d.get(2500..4500).unwrap(), &input[2500..4500]);
+                assert!(matches!(
+                    Decompressor::new(&bytes[..10]),
+                    Err(Error::TruncatedHeader)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn test_flags() {
+        let mut buf = write_header(false);
+        assert!(Archive::parse(&buf).is_ok());
+
+        // unknown required flags are rejected
+        let mut unknown = buf.clone();
+        unknown[9] |= 1 << 7;
+        assert!(matches!(
+            Archive::parse(&unknown),
+            Err(Error::UnsupportedFlags(0x8000))
+        ));
+
+        // sections of unknown optional flags are skipped
+        buf[10] |= 1 << 7;
+        let offsets_end = 8 + 4 * 6;
+        let section = [5, 0, 0, 0, 1, 2, 3, 4, 5, 0, 0, 0];
+        buf.splice(offsets_end..offsets_end, section);
+        let archive = Archive::parse(&buf).unwrap();
+        assert_eq!(archive.frame_size, 0x0102);
+        assert!(matches!(archive.frame(0), Some(Frame::Compressed(&[0]))));
+        assert!(matches!(archive.frame(1), Some(Frame::Stored(&[0]))));
+
+        // truncated optional sections are rejected
+        buf.truncate(offsets_end + 8);
+        assert!(matches!(Archive::parse(&buf), Err(Error::TruncatedHeader)));
+    }
+
+    #[test]
+    fn test_compact_offsets() {
+        let mut table = FrameTable::new();
+        let sizes = [1, 127, 128, 300, 70_000];
+        let mut end = 0;
+        for (i, size) in sizes.into_iter().enumerate() {
+            end += size;
+            let frame = FrameInfo {
+                stored: i % 2 == 1,
+                checksum: None,
+            };
+            table.push(end, frame);
+        }
+
+        for wide in [false, true] {
+            let header = Header {
+                frame_size: 1 << 20,
+                input_len: 5 << 20,
+                wide,
+                compact: true,
+                group_size: 1,
+                frame_alignment: 1,
+                checksum: Checksum::None,
+                metadata: &[],
+                dictionary: None,
+                dictionary_id: None,
+            };
+            assert_eq!(header.size(table.num_frames()), None);
+            let mut buf = Vec::new();
+            header.write(&mut buf, &table).unwrap();
+            // the varints take 1 + 2 + 2 + 2 + 3 bytes, plus 2 bytes of padding
+            let index_len = if wide { 20 } else { 16 };
+            assert_eq!(buf.len(), 8 + index_len + 12);
+            buf.resize(buf.len() + end as usize, 0);
+
+            let archive = Archive::parse(&buf).unwrap();
+            assert_eq!(archive.input_len, 5 << 20);
+            let mut start = 0;
+            for (i, size) in sizes.into_iter().enumerate() {
+                let frame = archive.frame(i).unwrap();
+                let (Frame::Compressed(bytes) | Frame::Stored(bytes)) = frame else {
+                    unreachable!();
+                };
+                assert_eq!(bytes.len() as u64, size);
+                assert_eq!(matches!(frame, Frame::Stored(_)), i % 2 == 1);
+                assert_eq!(
+                    bytes.as_ptr(),
+                    buf[buf.len() - end as usize + start..].as_ptr()
+                );
+                start += size as usize;
+            }
+            assert!(archive.frame(5).is_none());
+
+            // truncated varints are rejected
+            let sizes_start = 8 + index_len;
+            let mut truncated = buf.clone();
+            truncated[sizes_start - 4] -= 1;
+            assert!(matches!(
+                Archive::parse(&truncated),
+                Err(Error::InvalidOffsetTable)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_byte_order() {
+        let write_legacy = |to_bytes: fn(u32) -> [u8; 4]| {
+            let mut buf = Vec::new();
+            buf.extend_from_slice(&to_bytes(0x0100));
+            buf.extend_from_slice(&to_bytes(0x0100));
+            buf.extend_from_slice(&to_bytes(0));
+            buf.extend_from_slice(&to_bytes(1));
+            buf.push(0);
+            buf
+        };
+
+        let le = write_legacy(u32::to_le_bytes);
+        let archive = Archive::parse(&le).unwrap();
+        assert_eq!((archive.frame_size, archive.input_len), (0x0100, 0x0100));
+        assert!(matches!(archive.frame(0), Some(Frame::Compressed(&[0]))));
+
+        // an archive written in big-endian byte order is read as little-endian,
+        // independent of the byte order of the host
+        let be = write_legacy(u32::to_be_bytes);
+        let archive = Archive::parse(&be).unwrap();
+        assert_eq!(
+            (archive.frame_size, archive.input_len),
+            (0x010000, 0x010000)
+        );
+        assert!(archive.frame(0).is_none());
+    }
+
+    #[test]
+    fn test_parse_fuzz() {
+        use crate::{Compressor, Decompressor, Limits};
+
+        let input: Vec<u8> = (0..3000u32).map(|i| (i % 7 * i % 13) as u8).collect();
+        let compressors: [fn() -> Compressor<'static>; 7] = [
+            || Compressor::new().frame_size(500),
+            || Compressor::new().frame_size(500).checksum(Checksum::Crc32),
+            || Compressor::new().frame_size(500).metadata("key", "value"),
+            || Compressor::new().frame_size(500).trailer_index(true),
+            || Compressor::new().frame_size(500).compact_index(true),
+            || {
+                Compressor::new()
+                    .frame_size(500)
+                    .sparse_index(2)
+                    .frame_alignment(16)
+            },
+            || Compressor::new().frame_size(500).holes(true),
+        ];
+        let mut archives: Vec<_> = compressors
+            .iter()
+            .map(|compressor| compressor().compress(&input).unwrap())
+            .collect();
+        archives.push(
+            Compressor::new()
+                .frame_size(500)
+                .compress_seekable(&input)
+                .unwrap(),
+        );
+
+        // none of these may panic, and reads are bounded by the limits
+        let check = |bytes: &[u8]| {
+            let limits = Limits::new().max_len(1 << 16).max_frame_size(1 << 12);
+            if let Ok(mut d) = Decompressor::with_limits(bytes, limits) {
+                let _ = d.get(..);
+                let len = d.archive.input_len;
+                let _ = d.get(len / 3..len / 2);
+            }
+            let mut index = Vec::new();
+            if let Ok(mut d) = Decompressor::with_storage(bytes, &mut index) {
+                if d.archive.input_len <= 1 << 16 {
+                    let _ = d.get(..);
+                }
+            }
+            let _ = Archive::parse_prefix(bytes);
+        };
+
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for archive in &archives {
+            for len in 0..archive.len() {
+                check(&archive[..len]);
+            }
+            for pos in 0..archive.len() {
+                for value in [0, 1, 0x80, 0xff, random() as u8] {
+                    let mut mutated = archive.clone();
+                    mutated[pos] = value;
+                    check(&mutated);
+                }
+            }
+            for _ in 0..200 {
+                let mut mutated = archive.clone();
+                // overwrite a whole field with a random value
+                let pos = random() as usize % (archive.len() - 8);
+                mutated[pos..pos + 8].copy_from_slice(&random().to_le_bytes());
+                check(&mutated);
+            }
+        }
+        for len in 0..100 {
+            let garbage: Vec<u8> = (0..len).map(|_| random() as u8).collect();
+            check(&garbage);
+        }
+
+        // range bounds overflowing `usize` are out of bounds instead of wrapping
+        use std::ops::Bound::{Excluded, Unbounded};
+        let is_out_of_bounds = |err: io::Error| {
+            matches!(
+                err.get_ref().and_then(|err| err.downcast_ref()),
+                Some(Error::RangeOutOfBounds { .. })
+            )
+        };
+        let mut d = Decompressor::new(&archives[0]).unwrap();
+        assert!(is_out_of_bounds(d.get(0..=usize::MAX).unwrap_err()));
+        assert!(is_out_of_bounds(d.get(..=usize::MAX).unwrap_err()));
+        assert!(is_out_of_bounds(
+            d.get((Excluded(usize::MAX), Unbounded)).unwrap_err()
+        ));
+        let mut buf = [0; 16];
+        assert!(is_out_of_bounds(
+            d.get_to_slice(0..=usize::MAX, &mut buf).unwrap_err()
+        ));
+        let mut chunks = d.get_chunks(0..=usize::MAX, 100);
+        assert!(is_out_of_bounds(chunks.next().unwrap().unwrap_err()));
+        assert!(chunks.next().is_none());
+    }
+}
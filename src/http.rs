@@ -0,0 +1,403 @@
+use std::io;
+use std::ops::Range;
+
+use crate::{Error, Storage};
+
+/// The parts of a response to a ranged HTTP `GET` request that [`HttpStorage`] needs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// The status code, which is `206 Partial Content` for successful range requests.
+    pub status: u16,
+    /// The value of the `Content-Range` header, if present.
+    pub content_range: Option<String>,
+}
+
+/// A minimal HTTP client used by [`HttpStorage`].
+///
+/// This is implemented on top of an HTTP library like `ureq` or `reqwest`, which
+/// takes care of connections, TLS and authentication. The `reqwest` and `ureq`
+/// features implement it for `reqwest::blocking::Client` and `ureq::Agent`.
+/// Neither enables TLS, which is up to the features of those crates.
+///
+/// Errors keep the [`ErrorKind`](io::ErrorKind) of the I/O error causing them,
+/// or are mapped to [`TimedOut`](io::ErrorKind::TimedOut) for timeouts, so that
+/// [`RetryStorage`](crate::RetryStorage) retries them.
+pub trait HttpClient: Send + Sync {
+    /// Sends a `GET` request to `url` with the `Range` header set to `range`,
+    /// reading the response body into `body`, replacing its contents.
+    fn get(&self, url: &str, range: &str, body: &mut Vec<u8>) -> io::Result<HttpResponse>;
+}
+
+impl<C: HttpClient + ?Sized> HttpClient for &C {
+    fn get(&self, url: &str, range: &str, body: &mut Vec<u8>) -> io::Result<HttpResponse> {
+        (**self).get(url, range, body)
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl HttpClient for reqwest::blocking::Client {
+    fn get(&self, url: &str, range: &str, body: &mut Vec<u8>) -> io::Result<HttpResponse> {
+        let into_io = |err: reqwest::Error| {
+            let kind = if err.is_timeout() {
+                io::ErrorKind::TimedOut
+            } else if err.is_connect() {
+                io::ErrorKind::ConnectionRefused
+            } else {
+                source_kind(&err)
+            };
+            io::Error::new(kind, err)
+        };
+        let mut response = reqwest::blocking::Client::get(self, url)
+            .header(reqwest::header::RANGE, range)
+            .send()
+            .map_err(into_io)?;
+        let status = response.status().as_u16();
+        let content_range = response
+            .headers()
+            .get(reqwest::header::CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+        body.clear();
+        // the body of any other response, like the whole file, is not needed
+        if status == 206 {
+            response.copy_to(body).map_err(into_io)?;
+        }
+        Ok(HttpResponse {
+            status,
+            content_range,
+        })
+    }
+}
+
+#[cfg(feature = "ureq")]
+impl HttpClient for ureq::Agent {
+    fn get(&self, url: &str, range: &str, body: &mut Vec<u8>) -> io::Result<HttpResponse> {
+        let mut response = match ureq::Agent::get(self, url).header("Range", range).call() {
+            Ok(response) => response,
+            Err(ureq::Error::StatusCode(status)) => {
+                return Ok(HttpResponse {
+                    status,
+                    content_range: None,
+                })
+            }
+            Err(ureq::Error::Io(err)) => return Err(err),
+            Err(err) => {
+                let kind = match err {
+                    ureq::Error::Timeout(_) | ureq::Error::BodyStalled => io::ErrorKind::TimedOut,
+                    ureq::Error::ConnectionFailed => io::ErrorKind::ConnectionRefused,
+                    _ => source_kind(&err),
+                };
+                return Err(io::Error::new(kind, err));
+            }
+        };
+        let status = response.status().as_u16();
+        let content_range = response
+            .headers()
+            .get("Content-Range")
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+        body.clear();
+        // the body of any other response, like the whole file, is not needed
+        if status == 206 {
+            io::Read::read_to_end(&mut response.body_mut().as_reader(), body)?;
+        }
+        Ok(HttpResponse {
+            status,
+            content_range,
+        })
+    }
+}
+
+/// The kind of the first I/O error in the sources of `err`, or `Other`.
+#[cfg(any(feature = "reqwest", feature = "ureq"))]
+fn source_kind(err: &(dyn std::error::Error + 'static)) -> io::ErrorKind {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            return err.kind();
+        }
+        source = err.source();
+    }
+    io::ErrorKind::Other
+}
+
+/// [`Storage`] fetching an archive over HTTP, using `Range` requests.
+///
+/// Only the parts of the archive that are needed are fetched: its index when
+/// opening it, and the compressed frames covering each read after that. This
+/// makes archives in object storage or on a CDN readable without downloading
+/// them as a whole. Servers that do not support range requests result in an error.
+/// Any other unsuccessful response fails with [`Error::HttpStatus`].
+#[derive(Debug)]
+pub struct HttpStorage<C> {
+    client: C,
+    url: String,
+    len: u64,
+}
+
+impl<C: HttpClient> HttpStorage<C> {
+    /// Creates storage for the archive at `url`, fetching its first byte to
+    /// determine its total size.
+    pub fn new(client: C, url: impl Into<String>) -> io::Result<Self> {
+        let url = url.into();
+        let mut body = Vec::new();
+        let response = client.get(&url, "bytes=0-0", &mut body)?;
+        let (range, len) = check_response(&response)?;
+        let len = len.ok_or_else(|| invalid_response("missing total size in `Content-Range`"))?;
+        if range != (0..1) || body.len() != 1 {
+            return Err(invalid_response("unexpected `Content-Range`"));
+        }
+        Ok(Self { client, url, len })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl<C: HttpClient> Storage for HttpStorage<C> {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+        if range.start > range.end || range.end > self.len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.clear();
+        if range.is_empty() {
+            return Ok(buf);
+        }
+        let header = format!("bytes={}-{}", range.start, range.end - 1);
+        let response = self.client.get(&self.url, &header, buf)?;
+        let (received, _) = check_response(&response)?;
+        if received != range || buf.len() as u64 != range.end - range.start {
+            return Err(invalid_response("unexpected `Content-Range`"));
+        }
+        Ok(buf)
+    }
+}
+
+/// Checks that `response` is a successful range response, returning the
+/// received range and the total size from its `Content-Range`.
+fn check_response(response: &HttpResponse) -> io::Result<(Range<u64>, Option<u64>)> {
+    match response.status {
+        206 => {}
+        200 => {
+            return Err(invalid_response(
+                "the server does not support range requests",
+            ))
+        }
+        status => return Err(Error::HttpStatus(status).into()),
+    }
+    response
+        .content_range
+        .as_deref()
+        .and_then(parse_content_range)
+        .ok_or_else(|| invalid_response("missing or invalid `Content-Range`"))
+}
+
+/// Parses a `Content-Range` header like `bytes 0-99/1234` into the range it
+/// covers, and the total size, unless it is unknown.
+fn parse_content_range(value: &str) -> Option<(Range<u64>, Option<u64>)> {
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, last) = range.split_once('-')?;
+    let start: u64 = start.trim().parse().ok()?;
+    let end = last.trim().parse::<u64>().ok()?.checked_add(1)?;
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    (start < end).then_some((start..end, total))
+}
+
+fn invalid_response(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{Compressor, Decompressor};
+
+    /// A fake server for a single file, which optionally ignores the `Range` header.
+    struct Server {
+        file: Vec<u8>,
+        ranges: bool,
+        requests: AtomicUsize,
+    }
+
+    impl HttpClient for Server {
+        fn get(&self, url: &str, range: &str, body: &mut Vec<u8>) -> io::Result<HttpResponse> {
+            assert_eq!(url, "https://example.com/archive.seezee");
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            body.clear();
+            if !self.ranges {
+                body.extend_from_slice(&self.file);
+                return Ok(HttpResponse {
+                    status: 200,
+                    content_range: None,
+                });
+            }
+            let (start, last) = range
+                .strip_prefix("bytes=")
+                .unwrap()
+                .split_once('-')
+                .unwrap();
+            let (start, last): (usize, usize) = (start.parse().unwrap(), last.parse().unwrap());
+            body.extend_from_slice(&self.file[start..=last]);
+            Ok(HttpResponse {
+                status: 206,
+                content_range: Some(format!("bytes {start}-{last}/{}", self.file.len())),
+            })
+        }
+    }
+
+    #[test]
+    fn test_http_storage() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let url = "https://example.com/archive.seezee";
+
+        let server = Server {
+            file: compressed.clone(),
+            ranges: true,
+            requests: AtomicUsize::new(0),
+        };
+        let storage = HttpStorage::new(&server, url).unwrap();
+        assert_eq!(storage.len(), compressed.len() as u64);
+        let mut index = Vec::new();
+        let mut d = Decompressor::with_storage(storage, &mut index).unwrap();
+
+        let requests = server.requests.load(Ordering::Relaxed);
+        assert_eq!(d.get(1500..1600).unwrap(), &input[1500..1600]);
+        assert_eq!(server.requests.load(Ordering::Relaxed), requests + 1);
+        assert_eq!(d.get(..).unwrap(), input);
+        drop(d);
+
+        let server = Server {
+            ranges: false,
+            ..server
+        };
+        let err = HttpStorage::new(&server, url).map(drop).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        struct NotFound;
+        impl HttpClient for NotFound {
+            fn get(&self, _: &str, _: &str, _: &mut Vec<u8>) -> io::Result<HttpResponse> {
+                Ok(HttpResponse {
+                    status: 404,
+                    content_range: None,
+                })
+            }
+        }
+        let err = HttpStorage::new(NotFound, url).map(drop).unwrap_err();
+        assert!(matches!(
+            err.get_ref().and_then(|err| err.downcast_ref()),
+            Some(Error::HttpStatus(404))
+        ));
+    }
+
+    /// Serves `file` at `/archive.seezee` over HTTP on a local port, answering
+    /// each range request on its own connection, and returns its URL.
+    #[cfg(any(feature = "reqwest", feature = "ureq"))]
+    fn serve(file: Vec<u8>) -> String {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/archive.seezee", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut range = None;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                        let (start, last) = value.trim().split_once('-').unwrap();
+                        range = Some((start.parse().unwrap(), last.parse::<usize>().unwrap()));
+                    }
+                    line.clear();
+                }
+                let response = match range {
+                    Some((start, last)) if request.starts_with("GET /archive.seezee ") => {
+                        let body = &file[start..=last];
+                        let header = format!(
+                            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {start}-{last}/{}\r\n\
+                             Content-Length: {}\r\nConnection: close\r\n\r\n",
+                            file.len(),
+                            body.len(),
+                        );
+                        [header.as_bytes(), body].concat()
+                    }
+                    _ => {
+                        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_vec()
+                    }
+                };
+                stream.write_all(&response).unwrap();
+            }
+        });
+        url
+    }
+
+    /// Reads an archive served by [`serve`] with `client`.
+    #[cfg(any(feature = "reqwest", feature = "ureq"))]
+    fn check_client(client: impl HttpClient) {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let url = serve(compressed.clone());
+
+        let storage = HttpStorage::new(&client, &url).unwrap();
+        assert_eq!(storage.len(), compressed.len() as u64);
+        let mut index = Vec::new();
+        let mut d = Decompressor::with_storage(storage, &mut index).unwrap();
+        assert_eq!(d.get(1500..1600).unwrap(), &input[1500..1600]);
+        assert_eq!(d.get(..).unwrap(), input);
+
+        let url = url.replace("archive", "missing");
+        let err = HttpStorage::new(&client, url).map(drop).unwrap_err();
+        assert!(matches!(
+            err.get_ref().and_then(|err| err.downcast_ref()),
+            Some(Error::HttpStatus(404))
+        ));
+
+        // nothing is listening on the port anymore
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/archive.seezee", port.local_addr().unwrap());
+        drop(port);
+        let err = HttpStorage::new(&client, url).map(drop).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    #[cfg(feature = "reqwest")]
+    fn test_reqwest_client() {
+        check_client(reqwest::blocking::Client::new());
+    }
+
+    #[test]
+    #[cfg(feature = "ureq")]
+    fn test_ureq_client() {
+        check_client(ureq::Agent::new_with_defaults());
+    }
+
+    #[test]
+    fn test_content_range() {
+        assert_eq!(
+            parse_content_range("bytes 0-0/1234"),
+            Some((0..1, Some(1234)))
+        );
+        assert_eq!(
+            parse_content_range("bytes 100-199/*"),
+            Some((100..200, None))
+        );
+        assert_eq!(parse_content_range("bytes */1234"), None);
+        assert_eq!(parse_content_range("bytes 5-4/10"), None);
+        assert_eq!(parse_content_range("items 0-1/2"), None);
+    }
+}
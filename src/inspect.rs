@@ -0,0 +1,321 @@
+use std::io;
+use std::ops::Range;
+
+use crate::format::{
+    self, Archive, Frame, CHECKSUMS, COMPACT_OFFSETS, METADATA, REQUIRED_FLAGS, TRAILER,
+    WIDE_OFFSETS,
+};
+use crate::{seekable, Checksum, Decompressor, Error};
+
+/// The format of an inspected archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// The current format, starting with the magic bytes and `version`.
+    Versioned(u32),
+    /// The legacy format without magic bytes and version.
+    Legacy,
+    /// The upstream `zstd` seekable format.
+    ZstdSeekable,
+}
+
+/// How a frame is stored in an archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    /// A `zstd` compressed frame.
+    Compressed,
+    /// A frame that is stored uncompressed, as it did not compress.
+    Stored,
+    /// A frame consisting only of zeroes, which does not take up any space.
+    Hole,
+}
+
+/// A problem found by [`inspect`] in an archive that can still be opened.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Warning {
+    /// The archive is malformed in a way that reads tolerate, like trailing
+    /// bytes, or frames that can not be located.
+    Invalid(Error),
+    /// The archive has checksums of an unknown algorithm, which can not be verified.
+    UnknownChecksum,
+    /// The archive has sections of unknown optional flags, which are skipped.
+    UnknownFlags(u32),
+}
+
+/// A frame of an inspected archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameSummary {
+    /// The range of the uncompressed contents covered by the frame.
+    pub input: Range<usize>,
+    /// The range of the compressed frame within the archive, without its padding,
+    /// or `None` if it could not be located.
+    pub compressed: Option<Range<u64>>,
+    /// How the frame is stored, or `None` if it could not be located.
+    pub kind: Option<FrameKind>,
+}
+
+impl FrameSummary {
+    /// The compression ratio of the frame, as its uncompressed size divided by
+    /// its compressed size, or `None` if it could not be located.
+    pub fn ratio(&self) -> Option<f64> {
+        let compressed = self.compressed.as_ref()?;
+        Some(self.input.len() as f64 / (compressed.end - compressed.start) as f64)
+    }
+}
+
+/// The structure of an archive, as returned by [`inspect`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ArchiveInfo {
+    pub format: ArchiveFormat,
+    /// The raw flags of the archive, including unknown optional ones.
+    pub flags: u32,
+    /// The uncompressed size of each frame.
+    pub frame_size: usize,
+    /// The total uncompressed size.
+    pub input_len: usize,
+    /// The size of the whole archive.
+    pub compressed_len: u64,
+    /// The range of the compressed frames within the archive.
+    pub frames_range: Range<u64>,
+    pub wide_offsets: bool,
+    pub compact_index: bool,
+    pub trailer_index: bool,
+    /// The number of frames per offset table entry of a sparse index.
+    pub group_size: usize,
+    /// The alignment of the frames within the archive.
+    pub frame_alignment: usize,
+    /// The checksum algorithm, which is [`Checksum::None`] for checksums of an unknown algorithm.
+    pub checksum: Checksum,
+    /// The size of the embedded dictionary.
+    pub dictionary_len: Option<usize>,
+    /// The id of the referenced external dictionary.
+    pub dictionary_id: Option<u32>,
+    /// The keys of the metadata entries.
+    pub metadata_keys: Vec<String>,
+    pub frames: Vec<FrameSummary>,
+    pub warnings: Vec<Warning>,
+}
+
+impl ArchiveInfo {
+    /// The total compression ratio, as the uncompressed size divided by the size of the archive.
+    pub fn ratio(&self) -> f64 {
+        self.input_len as f64 / self.compressed_len as f64
+    }
+}
+
+/// Describes the structure of the archive in `bytes`, without decompressing anything.
+///
+/// Fails if the archive can not be opened at all. Anything else that is wrong
+/// with it is reported in the [`ArchiveInfo::warnings`]. Use
+/// [`Decompressor::verify`] to validate the contents of the frames as well.
+pub fn inspect(bytes: &[u8]) -> Result<ArchiveInfo, Error> {
+    let format = match bytes.strip_prefix(&format::MAGIC) {
+        Some(rest) => ArchiveFormat::Versioned(
+            rest.first_chunk::<4>()
+                .map_or(0, |version| u32::from_le_bytes(*version)),
+        ),
+        None if seekable::is_seekable(bytes) => ArchiveFormat::ZstdSeekable,
+        None => ArchiveFormat::Legacy,
+    };
+    let archive = Archive::parse(bytes)?;
+    let mut warnings = Vec::new();
+    if let Err(err) = Archive::parse_strict(bytes) {
+        warnings.push(Warning::Invalid(err));
+    }
+    if archive.flags & CHECKSUMS != 0 && archive.checksum == Checksum::None {
+        warnings.push(Warning::UnknownChecksum);
+    }
+    let unknown_flags = archive.flags & !REQUIRED_FLAGS & !(CHECKSUMS | METADATA);
+    if unknown_flags != 0 {
+        warnings.push(Warning::UnknownFlags(unknown_flags));
+    }
+
+    let d = Decompressor::from_archive(bytes, archive);
+    let archive = &d.archive;
+    let frame_size = archive.frame_size;
+    let mut frames = Vec::with_capacity(d.frame_count());
+    for idx in 0..d.frame_count() {
+        let input = idx * frame_size..(idx * frame_size + frame_size).min(archive.input_len);
+        let span = match d.storage_span(idx) {
+            Ok(span) => span,
+            Err(err) => {
+                warnings.push(Warning::Invalid(into_error(err)));
+                frames.push(FrameSummary {
+                    input,
+                    compressed: None,
+                    kind: None,
+                });
+                continue;
+            }
+        };
+        let span_bytes = &archive.zstd_buf[(span.start - d.frames.start) as usize..]
+            [..(span.end - span.start) as usize];
+        let (compressed, kind) = match archive.frame_from(idx, span_bytes) {
+            Some(Frame::Compressed(frame)) => (
+                offset_of(span.start, span_bytes, frame),
+                FrameKind::Compressed,
+            ),
+            Some(Frame::Stored(frame)) => {
+                (offset_of(span.start, span_bytes, frame), FrameKind::Stored)
+            }
+            Some(Frame::Hole(_)) => (span.start..span.start, FrameKind::Hole),
+            None => {
+                warnings.push(Warning::Invalid(into_error(
+                    d.frame_decode_failed(idx, None),
+                )));
+                frames.push(FrameSummary {
+                    input,
+                    compressed: None,
+                    kind: None,
+                });
+                continue;
+            }
+        };
+        frames.push(FrameSummary {
+            input,
+            compressed: Some(compressed),
+            kind: Some(kind),
+        });
+    }
+
+    Ok(ArchiveInfo {
+        format,
+        flags: archive.flags,
+        frame_size,
+        input_len: archive.input_len,
+        compressed_len: bytes.len() as u64,
+        frames_range: d.frames.clone(),
+        wide_offsets: archive.flags & WIDE_OFFSETS != 0,
+        compact_index: archive.flags & COMPACT_OFFSETS != 0,
+        trailer_index: archive.flags & TRAILER != 0,
+        group_size: archive.group_size,
+        frame_alignment: archive.frame_alignment,
+        checksum: archive.checksum,
+        dictionary_len: archive.dictionary.map(<[u8]>::len),
+        dictionary_id: archive.dictionary_id,
+        metadata_keys: archive.metadata.iter().map(|(key, _)| key.into()).collect(),
+        frames,
+        warnings,
+    })
+}
+
+/// Returns the range of `frame` within the archive, which is a part of the
+/// `span` bytes starting at offset `start`.
+fn offset_of(start: u64, span: &[u8], frame: &[u8]) -> Range<u64> {
+    let offset = start + (frame.as_ptr() as usize - span.as_ptr() as usize) as u64;
+    offset..offset + frame.len() as u64
+}
+
+/// Extracts the [`Error`] of a read.
+fn into_error(err: io::Error) -> Error {
+    if err.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+        let inner = err.into_inner().expect("checked above");
+        return *inner.downcast().expect("checked above");
+    }
+    Error::Io(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Compressor;
+
+    #[test]
+    fn test_inspect() {
+        let mut input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        // a frame of random bytes does not compress
+        let mut state = 1u32;
+        for byte in &mut input[3000..4000] {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            *byte = (state >> 16) as u8;
+        }
+        input[5000..6000].fill(0);
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .frame_alignment(8)
+            .holes(true)
+            .checksum(Checksum::Crc32)
+            .metadata("name", "test")
+            .compress(&input)
+            .unwrap();
+
+        let info = inspect(&compressed).unwrap();
+        assert_eq!(info.format, ArchiveFormat::Versioned(format::VERSION));
+        assert_eq!(info.frame_size, 1000);
+        assert_eq!(info.input_len, 10_000);
+        assert_eq!(info.compressed_len, compressed.len() as u64);
+        assert_eq!(info.frame_alignment, 8);
+        assert_eq!(info.checksum, Checksum::Crc32);
+        assert_eq!(info.metadata_keys, ["name"]);
+        assert_eq!(info.dictionary_len, None);
+        assert!(!info.trailer_index);
+        assert!(info.warnings.is_empty());
+        assert!(info.ratio() > 1.0);
+
+        assert_eq!(info.frames.len(), 10);
+        for (idx, frame) in info.frames.iter().enumerate() {
+            assert_eq!(frame.input, idx * 1000..idx * 1000 + 1000);
+            let range = frame.compressed.clone().unwrap();
+            assert!(info.frames_range.start <= range.start && range.end <= info.frames_range.end);
+            let bytes = &compressed[range.start as usize..range.end as usize];
+            match frame.kind.unwrap() {
+                FrameKind::Compressed => {
+                    assert_eq!(crate::zstd::frame_compressed_size(bytes), Some(bytes.len()));
+                    assert!(frame.ratio().unwrap() > 1.0);
+                }
+                FrameKind::Stored => {
+                    assert_eq!(idx, 3);
+                    assert_eq!(bytes, &input[frame.input.clone()]);
+                }
+                FrameKind::Hole => {
+                    assert_eq!(idx, 5);
+                    assert!(bytes.is_empty());
+                }
+            }
+        }
+
+        let trailer = Compressor::new()
+            .frame_size(1000)
+            .trailer_index(true)
+            .sparse_index(4)
+            .dictionary(&input[..1000])
+            .compress(&input)
+            .unwrap();
+        let info = inspect(&trailer).unwrap();
+        assert!(info.trailer_index);
+        assert_eq!(info.group_size, 4);
+        assert_eq!(info.dictionary_len, Some(1000));
+        assert!(info
+            .frames
+            .iter()
+            .all(|frame| frame.kind == Some(FrameKind::Compressed)));
+
+        let seekable = Compressor::new()
+            .frame_size(1000)
+            .compress_seekable(&input)
+            .unwrap();
+        let info = inspect(&seekable).unwrap();
+        assert_eq!(info.format, ArchiveFormat::ZstdSeekable);
+        assert_eq!(info.frames.len(), 10);
+
+        // trailing bytes and missing frames are reported as warnings
+        let mut trailing = compressed.clone();
+        trailing.extend_from_slice(&[0; 16]);
+        let info = inspect(&trailing).unwrap();
+        assert!(matches!(
+            info.warnings[..],
+            [Warning::Invalid(Error::LengthMismatch { .. })]
+        ));
+
+        let info = inspect(&compressed[..compressed.len() - 10]).unwrap();
+        assert!(info.frames[..9].iter().all(|frame| frame.kind.is_some()));
+        assert_eq!(info.frames[9].kind, None);
+        assert!(info.warnings.iter().any(|warning| matches!(
+            warning,
+            Warning::Invalid(Error::TruncatedFrame { frame: 9, .. })
+        )));
+
+        assert!(inspect(&compressed[..10]).is_err());
+    }
+}
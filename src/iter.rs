@@ -0,0 +1,174 @@
+use std::io;
+use std::ops::Range;
+
+use crate::cache::FrameBuf;
+use crate::pool::ZstdContext;
+use crate::{Decompressor, Error};
+
+/// An iterator over the decompressed chunks of a range, created by
+/// [`Decompressor::get_chunks`].
+#[derive(Debug)]
+pub struct Chunks<'a, 'b> {
+    decompressor: &'a mut Decompressor<'b>,
+    /// The remaining part of the range.
+    range: Range<usize>,
+    chunk_size: usize,
+    /// The error of a range whose bounds overflow, which is the only item.
+    error: Option<Error>,
+}
+
+impl<'a, 'b> Chunks<'a, 'b> {
+    pub(crate) fn new(
+        decompressor: &'a mut Decompressor<'b>,
+        range: Result<Range<usize>, Error>,
+        chunk_size: usize,
+    ) -> Self {
+        assert!(chunk_size != 0, "chunk size must be non-zero");
+        let (range, error) = match range {
+            Ok(range) => (range, None),
+            Err(err) => (0..0, Some(err)),
+        };
+        Self {
+            decompressor,
+            range,
+            chunk_size,
+            error,
+        }
+    }
+}
+
+impl Iterator for Chunks<'_, '_> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err.into()));
+        }
+        if self.range.start >= self.range.end {
+            return None;
+        }
+        let end = self.range.end.min(self.range.start + self.chunk_size);
+        let chunk = self.decompressor.get(self.range.start..end);
+        // stop after the first error
+        self.range.start = if chunk.is_ok() { end } else { self.range.end };
+        Some(chunk)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let chunks = self.range.len().div_ceil(self.chunk_size) + self.error.is_some() as usize;
+        (chunks.min(1), Some(chunks))
+    }
+}
+
+/// A sequential scan over all decompressed frames of an archive, created by
+/// [`Decompressor::frames`].
+///
+/// All frames are decompressed into the same internal buffer, so this is not an
+/// [`Iterator`]. Instead, [`Frames::next_frame`] returns a frame borrowing that
+/// buffer until the next call, and can be used in a `while let` loop.
+pub struct Frames<'a, 'b> {
+    decompressor: &'a Decompressor<'b>,
+    /// The `zstd` context, which is lazily taken from the decompressor on the first frame.
+    zstd: Option<ZstdContext<'a, 'b>>,
+    /// The index of the next frame.
+    next: usize,
+    num_frames: usize,
+    frame_buf: FrameBuf,
+}
+
+impl std::fmt::Debug for Frames<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Frames")
+            .field("next", &self.next)
+            .field("num_frames", &self.num_frames)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, 'b> Frames<'a, 'b> {
+    pub(crate) fn new(decompressor: &'a Decompressor<'b>) -> Self {
+        Self {
+            decompressor,
+            zstd: None,
+            next: 0,
+            num_frames: decompressor.frame_count(),
+            frame_buf: FrameBuf::default(),
+        }
+    }
+
+    /// The number of frames that have not been returned yet.
+    pub fn remaining(&self) -> usize {
+        self.num_frames - self.next
+    }
+
+    /// Decompresses and returns the next frame, or `None` after the last one.
+    ///
+    /// The scan stops after the first error.
+    pub fn next_frame(&mut self) -> Option<io::Result<&[u8]>> {
+        if self.next >= self.num_frames {
+            return None;
+        }
+        let idx = self.next;
+        let frame = self
+            .decompressor
+            .load_frame(&mut self.zstd, idx, &mut self.frame_buf);
+        // stop after the first error
+        self.next = if frame.is_ok() {
+            idx + 1
+        } else {
+            self.num_frames
+        };
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Compressor, Decompressor};
+
+    #[test]
+    fn test_chunks() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+
+        for chunk_size in [1, 700, 1000, 20_000] {
+            let chunks: Vec<_> = d
+                .get_chunks(1234..9876, chunk_size)
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(chunks.len(), (9876 - 1234usize).div_ceil(chunk_size));
+            assert!(chunks.iter().all(|chunk| chunk.len() <= chunk_size));
+            assert_eq!(chunks.concat(), &input[1234..9876]);
+        }
+        assert_eq!(d.get_chunks(.., 100).count(), 100);
+        assert_eq!(d.get_chunks(5..5, 100).count(), 0);
+
+        let mut chunks = d.get_chunks(9_000..10_500, 1000);
+        assert_eq!(chunks.next().unwrap().unwrap(), &input[9_000..]);
+        assert!(chunks.next().unwrap().is_err());
+        assert!(chunks.next().is_none());
+    }
+
+    #[test]
+    fn test_frames() {
+        let input: Vec<u8> = (0..10_500u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let d = Decompressor::new(&compressed).unwrap();
+
+        let mut frames = d.frames();
+        assert_eq!(frames.remaining(), 11);
+        let mut output = Vec::new();
+        while let Some(frame) = frames.next_frame() {
+            let frame = frame.unwrap();
+            assert!(frame.len() == 1000 || output.len() == 10_000);
+            output.extend_from_slice(frame);
+        }
+        assert_eq!(output, input);
+        assert_eq!(frames.remaining(), 0);
+
+        let empty = Compressor::new().compress(&[]).unwrap();
+        let d = Decompressor::new(&empty).unwrap();
+        assert!(d.frames().next_frame().is_none());
+    }
+}
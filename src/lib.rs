@@ -1,241 +1,3974 @@
-use std::mem;
-use std::ops::{Range, RangeBounds};
+use std::ops::{ControlFlow, Range, RangeBounds};
+use std::sync::Arc;
 
-use watto::Pod;
+use cache::{FrameBuf, FrameCache, Pool};
+use encoder::FrameEncoder;
+use format::{Archive, Frame, FrameTable};
+use pool::ZstdContext;
+use progress::ProgressFn;
+use writer::ArchiveOptions;
 
+mod aligned;
+mod archive;
+mod async_io;
+#[cfg(feature = "serde")]
+mod blob;
+mod cache;
+mod checksum;
+mod concat;
+mod concurrent;
+mod config;
+mod cursor;
+mod dictionary;
+#[cfg(all(feature = "direct-io", target_os = "linux"))]
+mod direct;
+mod disk_cache;
+mod encoder;
+mod error;
+mod format;
+mod http;
+mod inspect;
+mod iter;
+mod limits;
+mod metadata;
+#[cfg(all(feature = "mmap", unix))]
+mod mmap;
+#[cfg(feature = "object_store")]
+mod object;
+mod owned;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod payload;
+mod pipeline;
+mod pool;
+mod progress;
+mod recover;
+mod report;
+mod retry;
+mod scratch;
+mod seekable;
+mod storage;
+mod text;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
+mod vec;
+mod writer;
 mod zstd;
+mod zstd_index;
+
+pub use aligned::AlignedBuf;
+pub use archive::{ArchiveBuilder, ArchiveReader};
+pub use async_io::{
+    AsyncChunks, AsyncCompressWriter, AsyncCursor, AsyncDecompressor, AsyncSink, AsyncStorage,
+};
+#[cfg(feature = "serde")]
+pub use blob::SeezeeBytes;
+pub use checksum::Checksum;
+pub use concat::{concat, split, ConcatDecompressor};
+pub use concurrent::ConcurrentDecompressor;
+pub use config::CompressorConfig;
+pub use cursor::Cursor;
+pub use dictionary::DictionaryProvider;
+#[cfg(all(feature = "direct-io", target_os = "linux"))]
+pub use direct::DirectStorage;
+pub use disk_cache::DiskCacheStorage;
+pub use error::Error;
+pub use http::{HttpClient, HttpResponse, HttpStorage};
+pub use inspect::{inspect, ArchiveFormat, ArchiveInfo, FrameKind, FrameSummary, Warning};
+pub use iter::{Chunks, Frames};
+pub use limits::Limits;
+pub use metadata::{Metadata, MetadataIter};
+#[cfg(feature = "object_store")]
+pub use object::ObjectStorage;
+pub use owned::{OwnedBytes, OwnedDecompressor};
+pub use payload::PayloadDecompressor;
+pub use pipeline::{ArchivePart, CompressedFrame, PipelineReceiver, PipelineSender};
+pub use pool::DecompressorPool;
+pub use progress::ProgressInfo;
+pub use recover::{Gap, Recovered};
+pub use report::CompressionReport;
+pub use retry::RetryStorage;
+pub use scratch::Scratch;
+pub use storage::{ReaderStorage, Storage};
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub use uring::UringStorage;
+pub use vec::{SeeZeeVec, SeeZeeVecIter};
+pub use writer::CompressWriter;
+pub use zstd::{DecoderDictionary, EncoderDictionary, Strategy};
+pub use zstd_index::ZstdIndex;
 
 const DEFAULT_FRAME_SIZE: usize = 32 * (1 << 10);
+/// The smallest common page size, used to touch the pages of memory mapped archives.
+const PAGE_SIZE: usize = 4 * (1 << 10);
+
+/// The number of frames that [`FrameSize::Auto`] aims for.
+const AUTO_TARGET_FRAMES: usize = 256;
+const AUTO_MIN_FRAME_SIZE: usize = 4 * (1 << 10);
+const AUTO_MAX_FRAME_SIZE: usize = 1 << 20;
+
+/// Returns the version of the bundled `zstd` library, for example `"1.5.5"`.
+///
+/// Archives created in [`deterministic`](Compressor::deterministic) mode are only
+/// guaranteed to be byte-identical with the same `zstd` version.
+pub fn zstd_version() -> &'static str {
+    zstd::version_string()
+}
+
+/// Decompresses a regular `zstd` stream read from `reader`, and compresses it
+/// into a new archive with frames of `frame_size`.
+///
+/// This happens in a single pass, so existing `.zst` files can be converted
+/// without ever holding the uncompressed content in memory or writing it to disk.
+/// Streams of multiple concatenated frames are supported. Use
+/// [`Compressor::compress_reader`] on top of a [`zstd` decoder] for control over
+/// the other compression settings.
+///
+/// [`zstd` decoder]: https://docs.rs/zstd/latest/zstd/stream/read/struct.Decoder.html
+pub fn reframe_zstd<R: std::io::Read>(reader: R, frame_size: usize) -> std::io::Result<Vec<u8>> {
+    let compressor = Compressor::new().try_frame_size(frame_size)?;
+    let decoder = zstd::StreamDecoder::new(reader)?;
+    compressor.compress_reader(decoder, Vec::new())
+}
+
+/// Compresses the whole `input` into an archive with the default settings.
+///
+/// This is a shorthand for [`Compressor::compress`]. Use a [`Compressor`] to
+/// configure the frame size, compression level and other settings.
+pub fn compress(input: &[u8]) -> Result<Vec<u8>, Error> {
+    Compressor::new().compress(input)
+}
+
+/// Decompresses the whole archive in `bytes`.
+///
+/// This is a shorthand for [`Decompressor::get`] of the whole archive. Use a
+/// [`Decompressor`] to read only parts of the archive.
+pub fn decompress(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    Decompressor::new(bytes)?.get(..)
+}
+
+/// The uncompressed size of each frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum FrameSize {
+    /// Use frames of exactly this size.
+    Fixed(usize),
+    /// Pick a frame size based on the input length, aiming for a few hundred frames.
+    ///
+    /// The frame size is a power of two between 4 KiB and 1 MiB. When the input
+    /// length is not known up front, as with [`CompressWriter`], this falls back
+    /// to the default frame size of 32 KiB.
+    Auto,
+}
+
+impl FrameSize {
+    fn resolve(self, input_len: Option<usize>) -> usize {
+        match (self, input_len) {
+            (FrameSize::Fixed(frame_size), _) => frame_size,
+            (FrameSize::Auto, Some(input_len)) => (input_len / AUTO_TARGET_FRAMES)
+                .next_power_of_two()
+                .clamp(AUTO_MIN_FRAME_SIZE, AUTO_MAX_FRAME_SIZE),
+            (FrameSize::Auto, None) => DEFAULT_FRAME_SIZE,
+        }
+    }
+}
 
-pub struct Compressor {
+impl From<usize> for FrameSize {
+    fn from(frame_size: usize) -> Self {
+        FrameSize::Fixed(frame_size)
+    }
+}
+
+/// Predefined combinations of compression level, frame size and `zstd` parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Fast compression and low-latency random access, using small frames.
+    Fast,
+    /// A good compression ratio at moderate compression speed.
+    Balanced,
+    /// The best compression ratio, using large frames and a slow compression level.
+    ///
+    /// Reading small ranges is more expensive, as larger frames have to be decompressed.
+    Max,
+}
+
+enum Dictionary<'d> {
+    /// A dictionary that is embedded into the archive.
+    Embedded(Vec<u8>),
+    /// A prepared dictionary that is referenced by the `zstd` context.
+    ByRef(&'d EncoderDictionary<'d>),
+}
+
+pub struct Compressor<'d> {
     level: i32,
-    frame_size: usize,
+    adaptive_level: bool,
+    deterministic: bool,
+    checksum: Checksum,
+    frame_size: FrameSize,
+    wide_offsets: bool,
+    trailer_index: bool,
+    compact_index: bool,
+    /// The number of frames per entry in a sparse offset table.
+    group_size: usize,
+    frame_alignment: usize,
+    holes: bool,
+    /// Advanced `zstd` parameters, applied on top of the compression `level`.
+    params: Vec<zstd::CParameter>,
+    dictionary: Option<Dictionary<'d>>,
+    /// The id of the dictionary, which is recorded in the archive instead of embedding it.
+    dictionary_id: Option<u32>,
+    metadata: Vec<(String, Vec<u8>)>,
+    /// The memory budget of the `zstd` contexts.
+    max_memory: Option<usize>,
+    /// The `zstd` context, which is lazily created and reused across `compress` calls.
+    context: Option<FrameEncoder<'d>>,
+    #[cfg(feature = "rayon")]
+    parallel: Option<parallel::Parallelism>,
+    progress: Option<ProgressFn<'d>>,
 }
 
-impl Compressor {
+impl<'d> Compressor<'d> {
     pub fn new() -> Self {
         Self {
             level: 0,
-            frame_size: DEFAULT_FRAME_SIZE,
+            adaptive_level: false,
+            deterministic: false,
+            checksum: Checksum::None,
+            frame_size: FrameSize::Fixed(DEFAULT_FRAME_SIZE),
+            wide_offsets: false,
+            trailer_index: false,
+            compact_index: false,
+            group_size: 1,
+            frame_alignment: 1,
+            holes: false,
+            params: Vec::new(),
+            dictionary: None,
+            dictionary_id: None,
+            metadata: Vec::new(),
+            max_memory: None,
+            context: None,
+            #[cfg(feature = "rayon")]
+            parallel: None,
+            progress: None,
+        }
+    }
+
+    /// Configures the compression level, frame size and `zstd` parameters
+    /// according to the given [`Preset`].
+    ///
+    /// This resets any previously configured `zstd` parameters, and individual
+    /// settings can be further adjusted afterwards.
+    pub fn preset(mut self, preset: Preset) -> Self {
+        self.params.clear();
+        self.context = None;
+        match preset {
+            Preset::Fast => {
+                self.level = 1;
+                self.frame_size = FrameSize::Fixed(16 * (1 << 10));
+                self.set_param(zstd::CParameter::Strategy(zstd::Strategy::ZSTD_fast));
+            }
+            Preset::Balanced => {
+                self.level = 3;
+                self.frame_size = FrameSize::Fixed(64 * (1 << 10));
+                self.set_param(zstd::CParameter::Strategy(zstd::Strategy::ZSTD_dfast));
+            }
+            Preset::Max => {
+                self.level = 19;
+                self.frame_size = FrameSize::Fixed(256 * (1 << 10));
+                self.set_param(zstd::CParameter::Strategy(zstd::Strategy::ZSTD_btultra2));
+                // make sure the window covers a whole frame
+                self.set_param(zstd::CParameter::WindowLog(18));
+            }
         }
+        self
+    }
+
+    pub fn level(self, level: i32) -> Self {
+        self.try_level(level).unwrap_or_else(|err| panic!("{err}"))
     }
 
-    pub fn level(mut self, level: i32) -> Self {
-        assert!(zstd::compression_level_range().contains(&level));
+    /// Sets the compression level, returning an error if it is outside of the
+    /// range supported by `zstd`.
+    pub fn try_level(mut self, level: i32) -> Result<Self, Error> {
+        if !zstd::compression_level_range().contains(&level) {
+            return Err(Error::InvalidLevel(level));
+        }
         self.level = level;
+        self.context = None;
+        Ok(self)
+    }
+
+    /// Probes each frame with a fast compression level first, and only compresses
+    /// it using the configured level if the content turns out to be compressible.
+    ///
+    /// This avoids spending a lot of time on frames with random or already
+    /// compressed content, which is particularly worthwhile with high compression
+    /// levels. It has no effect with a [prepared dictionary](Self::dictionary_by_ref),
+    /// as that determines the compression level itself.
+    pub fn adaptive_level(mut self, enable: bool) -> Self {
+        self.adaptive_level = enable;
+        self.context = None;
+        self
+    }
+
+    /// Produces byte-identical archives across platforms and runs.
+    ///
+    /// Compression is deterministic by default, except for the multithreaded mode
+    /// of `workers`, and a few `zstd` internals which are chosen
+    /// heuristically and may change between `zstd` versions. This mode disables
+    /// `zstd` worker threads and pins those internals to fixed values.
+    ///
+    /// The same input and configuration produce the same archive as long as the
+    /// version of `seezee` and the bundled `zstd` library stay the same. The bundled
+    /// `zstd` version can be checked with [`zstd_version`]. Compressing frames in
+    /// parallel produces identical output as well.
+    pub fn deterministic(mut self, enable: bool) -> Self {
+        self.deterministic = enable;
+        self.context = None;
         self
     }
 
-    pub fn frame_size(mut self, frame_size: usize) -> Self {
-        assert!(frame_size >= 1);
-        assert!(frame_size < u32::MAX as usize);
+    pub fn frame_size(self, frame_size: impl Into<FrameSize>) -> Self {
+        self.try_frame_size(frame_size)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Sets the uncompressed frame size, returning an error if it is zero or
+    /// too large to be represented in an archive.
+    pub fn try_frame_size(mut self, frame_size: impl Into<FrameSize>) -> Result<Self, Error> {
+        let frame_size = frame_size.into();
+        if let FrameSize::Fixed(frame_size) = frame_size {
+            if !(1..=format::MAX_FRAME_SIZE).contains(&frame_size) {
+                return Err(Error::InvalidFrameSize(frame_size));
+            }
+        }
         self.frame_size = frame_size;
+        Ok(self)
+    }
+
+    /// Sets the maximum back-reference distance as a power of two.
+    ///
+    /// As frames are compressed independently, values that cover more than the
+    /// frame size have no effect.
+    pub fn window_log(mut self, window_log: u32) -> Self {
+        self.set_param(zstd::CParameter::WindowLog(window_log));
         self
     }
 
-    pub fn compress(self, input: &[u8]) -> std::io::Result<Vec<u8>> {
-        assert!(input.len() < u32::MAX as usize);
+    /// Sets the number of search attempts as a power of two.
+    pub fn search_log(mut self, search_log: u32) -> Self {
+        self.set_param(zstd::CParameter::SearchLog(search_log));
+        self
+    }
 
-        let num_frames = input.len().div_ceil(self.frame_size);
-        let mut compressor = zstd::Compressor::new(self.level)?;
-        compressor.include_checksum(false)?;
-        compressor.include_contentsize(false)?;
-        compressor.include_dictid(false)?;
-        compressor.include_magicbytes(false)?;
+    /// Sets the match finding [`Strategy`].
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.set_param(zstd::CParameter::Strategy(strategy));
+        self
+    }
 
-        let table_sizeof = (num_frames + 3) * mem::size_of::<u32>();
+    /// Sets the target match length, the meaning of which depends on the [`Strategy`].
+    pub fn target_length(mut self, target_length: u32) -> Self {
+        self.set_param(zstd::CParameter::TargetLength(target_length));
+        self
+    }
 
-        let reserve = table_sizeof + zstd::compress_bound(self.frame_size * 2);
-        let mut buf: Vec<u8> = Vec::with_capacity(reserve);
-        buf.resize(table_sizeof, 0);
-        set_u32(&mut buf, 0, self.frame_size as u32);
-        set_u32(&mut buf, 1, input.len() as u32);
+    /// Enables long distance matching, which finds repetitions far apart in the input.
+    ///
+    /// As every frame is compressed independently, this can only find matches
+    /// within a single frame. It is thus only useful in combination with large
+    /// frame sizes of multiple MiB, and a [`window_log`](Self::window_log) that
+    /// covers the whole frame. With the default frame size, it has no effect
+    /// other than slowing down compression.
+    pub fn long_distance_matching(mut self, enable: bool) -> Self {
+        self.set_param(zstd::CParameter::EnableLongDistanceMatching(enable));
+        self
+    }
 
-        let mut total_written = 0;
+    /// Sets the number of `zstd` worker threads used to compress each individual frame.
+    ///
+    /// `zstd` splits its input into jobs of at least 512 KiB, so this only has an
+    /// effect on frame sizes of multiple MiB. For smaller frames, compressing whole
+    /// frames in parallel is more effective. A value of `0` disables multithreading.
+    #[cfg(feature = "zstdmt")]
+    pub fn workers(mut self, workers: u32) -> Self {
+        self.set_param(zstd::CParameter::NbWorkers(workers));
+        self
+    }
 
-        for i in 0..num_frames {
-            let from = i * self.frame_size;
-            let to = ((i + 1) * self.frame_size).min(input.len());
-            let source = &input[from..to];
+    /// Limits the memory used by the `zstd` compression contexts to roughly `bytes`.
+    ///
+    /// The window, chain and hash logs implied by the compression level are
+    /// reduced until the estimated size of a context fits, which keeps high
+    /// compression levels from allocating hundreds of MiB, at the cost of a
+    /// worse compression ratio. `zstd` worker threads are disabled, and
+    /// [parallel compression](Self::parallel) splits the budget across its
+    /// threads, using fewer threads if the budget does not suffice for all of them.
+    ///
+    /// The limit is best-effort: the logs are never reduced below the minimums
+    /// supported by `zstd`, and it does not cover the dictionary, nor the input
+    /// and output buffers.
+    pub fn max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = Some(bytes);
+        self.context = None;
+        self
+    }
+
+    /// Compresses all frames using the given dictionary.
+    ///
+    /// The dictionary is embedded into the archive, so it is available to the
+    /// [`Decompressor`] without any further configuration. As each frame is
+    /// compressed independently, a dictionary can substantially improve the
+    /// compression ratio of small frames.
+    pub fn dictionary(mut self, dictionary: &[u8]) -> Self {
+        self.dictionary = Some(Dictionary::Embedded(dictionary.into()));
+        self.context = None;
+        self
+    }
 
-            buf.reserve(zstd::compress_bound(source.len()));
-            let mut destination = zstd::spare_capacity_buf(&mut buf);
+    /// Compresses all frames using the given prepared dictionary.
+    ///
+    /// The dictionary is only referenced, which avoids copying and loading it
+    /// again when it is shared across many compressors. It is *not* embedded into
+    /// the archive, so it has to be provided to [`Decompressor::with_dictionary_by_ref`].
+    ///
+    /// Note that the compression level of the prepared dictionary takes precedence.
+    pub fn dictionary_by_ref(mut self, dictionary: &'d EncoderDictionary<'d>) -> Self {
+        self.dictionary = Some(Dictionary::ByRef(dictionary));
+        self.context = None;
+        self
+    }
 
-            let bytes_written = compressor.compress_to_buffer(source, &mut destination)?;
+    /// References the [`dictionary`](Self::dictionary) by `id` instead of
+    /// embedding it into the archive.
+    ///
+    /// This keeps archives small when many of them share the same dictionary,
+    /// which is then resolved by the [`DictionaryProvider`] passed to
+    /// [`Decompressor::dictionary_provider`]. The `id` is also recorded for a
+    /// [prepared dictionary](Self::dictionary_by_ref).
+    pub fn dictionary_id(mut self, id: u32) -> Self {
+        self.dictionary_id = Some(id);
+        self
+    }
 
-            total_written += bytes_written;
-            set_u32(&mut buf, i + 3, total_written as u32);
-        }
+    /// Stores a checksum of each uncompressed frame in the archive, using the
+    /// given [`Checksum`] algorithm.
+    ///
+    /// The [`Decompressor`] verifies the checksum of every frame it decompresses,
+    /// and returns an [`InvalidData`](std::io::ErrorKind::InvalidData) error on a
+    /// mismatch, instead of silently returning corrupted data.
+    pub fn checksum(mut self, checksum: Checksum) -> Self {
+        self.checksum = checksum;
+        self.context = None;
+        self
+    }
 
-        Ok(buf)
+    /// Adds a key-value pair to the metadata of the archive, replacing a previous
+    /// value of the same `key`.
+    ///
+    /// The metadata is stored uncompressed, and is meant for small amounts of
+    /// application-specific data, like a schema version. It can be read back via
+    /// [`Decompressor::metadata`] without decompressing any frames.
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<Vec<u8>>) -> Self {
+        let key = key.into();
+        let value = value.into();
+        match self.metadata.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.metadata.push((key, value)),
+        }
+        self
     }
-}
 
-fn set_u32(buf: &mut [u8], i: usize, val: u32) {
-    let from = i * mem::size_of::<u32>();
-    let to = from + mem::size_of::<u32>();
-    buf[from..to].copy_from_slice(&val.to_ne_bytes())
-}
+    /// Always use 64-bit offsets in the archive.
+    ///
+    /// By default, 64-bit offsets are used automatically only when the input or
+    /// the compressed frames would exceed 4 GiB.
+    pub fn wide_offsets(mut self, wide_offsets: bool) -> Self {
+        self.wide_offsets = wide_offsets;
+        self
+    }
 
-impl Default for Compressor {
-    fn default() -> Self {
-        Self::new()
+    /// Registers a callback that is invoked after every compressed frame.
+    ///
+    /// The callback receives the current [`ProgressInfo`], and can abort the
+    /// compression by returning [`ControlFlow::Break`], in which case
+    /// [`Error::Cancelled`] is returned. With [`Compressor::compress_to_writer`],
+    /// progress is only reported during the second pass, while the compressed
+    /// frames are being written.
+    pub fn on_progress<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(ProgressInfo) -> ControlFlow<()> + Send + 'd,
+    {
+        self.progress = Some(Box::new(callback));
+        self
     }
-}
 
-#[derive(Debug)]
-pub struct Decompressor<'b> {
-    header: &'b Header,
-    frame_offsets: &'b [u32],
-    zstd_buf: &'b [u8],
-    read_buf: Vec<u8>,
-}
+    /// Writes the frame offset table and everything else usually found in the
+    /// header at the end of the archive.
+    ///
+    /// This allows writing an archive in a single streaming pass, as the number
+    /// and sizes of the compressed frames do not have to be known up front. The
+    /// [`CompressWriter`] then writes compressed frames to its underlying writer
+    /// right away instead of buffering them, and [`Compressor::compress_to_writer`]
+    /// only compresses everything once. The [`Decompressor`] detects the layout
+    /// automatically, but needs access to the end of the archive to find the index.
+    pub fn trailer_index(mut self, enable: bool) -> Self {
+        self.trailer_index = enable;
+        self
+    }
 
-#[repr(C)]
-#[derive(Debug)]
-struct Header {
-    frame_size: u32,
-    input_len: u32,
-}
+    /// Stores the frame index in a compact varint encoding.
+    ///
+    /// Instead of a fixed-size offset per frame, only the compressed size of each
+    /// frame is stored, which typically only takes 2 or 3 bytes. This makes the
+    /// index considerably smaller for archives with many small frames. The index
+    /// is decoded once when the archive is opened, which requires an allocation.
+    pub fn compact_index(mut self, enable: bool) -> Self {
+        self.compact_index = enable;
+        self
+    }
 
-unsafe impl watto::Pod for Header {}
+    /// Only stores the offset of every `group_size`-th frame in the frame index.
+    ///
+    /// This makes the index `group_size` times smaller, which is useful for archives
+    /// with millions of frames. Reading a frame then has to skip over the preceding
+    /// frames of its group, by parsing their `zstd` block headers. Frames are also
+    /// never stored uncompressed, as those could not be skipped, so incompressible
+    /// input makes the archive slightly larger than the input. A `group_size` of
+    /// `0` or `1` stores the offset of every frame, which is the default.
+    pub fn sparse_index(mut self, group_size: usize) -> Self {
+        self.group_size = group_size.max(1);
+        self.context = None;
+        self
+    }
 
-impl<'b> Decompressor<'b> {
-    pub fn new(bytes: &'b [u8]) -> Option<Self> {
-        let (header, bytes) = Header::ref_from_prefix(bytes)?;
-        let num_frames = header.input_len.div_ceil(header.frame_size) + 1;
-        let (frame_offsets, zstd_buf) = u32::slice_from_prefix(bytes, num_frames as usize)?;
-
-        Some(Self {
-            header,
-            frame_offsets,
-            zstd_buf,
-            read_buf: Vec::new(),
-        })
+    /// Pads each compressed frame with zeroes so that all frames start at a
+    /// multiple of `alignment` bytes, relative to the start of the archive.
+    ///
+    /// With an alignment matching the block size of the underlying storage, for
+    /// example 4 KiB, each frame can be read using aligned reads, as required by
+    /// `O_DIRECT`. This comes at the cost of a larger archive, so it is most useful
+    /// with large frames. An `alignment` of `0` or `1` disables padding.
+    pub fn frame_alignment(mut self, alignment: usize) -> Self {
+        self.frame_alignment = alignment.max(1);
+        self
     }
 
-    fn frame_size(&self) -> usize {
-        self.header.frame_size as usize
+    /// Stores frames consisting entirely of zeroes as holes, which do not take
+    /// up any space in the archive, and are read without decompressing anything.
+    ///
+    /// This is worthwhile for inputs that are mostly zeroes, like VM images or
+    /// core dumps. It has no effect with a [`sparse_index`](Self::sparse_index).
+    pub fn holes(mut self, enable: bool) -> Self {
+        self.holes = enable;
+        self.context = None;
+        self
     }
 
-    pub fn get<R>(&mut self, range: R) -> std::io::Result<Vec<u8>>
-    where
-        R: RangeBounds<usize>,
-    {
+    /// Compresses `input` into a new archive.
+    ///
+    /// The underlying `zstd` context is kept around, so calling this repeatedly
+    /// on the same [`Compressor`] avoids the context setup costs.
+    pub fn compress(&mut self, input: &[u8]) -> Result<Vec<u8>, Error> {
         let mut buf = Vec::new();
-        self.get_into(&mut buf, range)?;
+        self.compress_into(input, &mut buf, &mut FrameTable::new())?;
         Ok(buf)
     }
 
-    pub fn get_into<'o, R>(&mut self, buf: &'o mut Vec<u8>, range: R) -> std::io::Result<&'o [u8]>
-    where
-        R: RangeBounds<usize>,
-    {
-        let range = make_range(range, self.header.input_len as usize);
-        self.read_into(buf, range)
+    /// Compresses `input` into the output buffer of `scratch`, returning the archive.
+    ///
+    /// In contrast to [`Compressor::compress`], the output buffer and other
+    /// temporary allocations are reused across calls with the same [`Scratch`].
+    pub fn compress_with_scratch<'s>(
+        &mut self,
+        scratch: &'s mut Scratch,
+        input: &[u8],
+    ) -> Result<&'s [u8], Error> {
+        self.compress_into(input, &mut scratch.buf, &mut scratch.table)?;
+        Ok(&scratch.buf)
     }
 
-    fn read_into<'o>(
+    /// Compresses `input` into `buf`, replacing its previous contents.
+    fn compress_into(
         &mut self,
-        buf: &'o mut Vec<u8>,
-        range: Range<usize>,
-    ) -> std::io::Result<&'o [u8]> {
-        if range.start > range.end {
-            return Err(eof());
+        input: &[u8],
+        buf: &mut Vec<u8>,
+        table: &mut FrameTable,
+    ) -> Result<(), Error> {
+        buf.clear();
+        table.clear();
+
+        let frame_size = self.frame_size.resolve(Some(input.len()));
+        #[cfg(feature = "rayon")]
+        if self.parallel.is_some() {
+            return self.compress_parallel(input, frame_size, buf, table);
         }
-        let frame_size = self.frame_size();
-        let start = range.start / frame_size;
-        let end = range.end.div_ceil(frame_size);
-        let frame_offsets = self.frame_offsets.get(start..=end).ok_or_else(eof)?;
 
-        let mut decompressor = zstd::Decompressor::new()?;
-        decompressor.include_magicbytes(false)?;
+        let num_frames = input.len().div_ceil(frame_size);
+        let input_len = input.len() as u64;
+        let wide_offsets = self.wide_offsets;
+        let checksum = self.checksum;
+        let trailer_index = self.trailer_index;
+        let compact_index = self.compact_index;
+        let group_size = self.group_size;
+        let frame_alignment = self.frame_alignment;
+        let Context {
+            encoder,
+            dictionary,
+            dictionary_id,
+            metadata,
+            mut callback,
+        } = self.context()?;
+        let mut progress = ProgressInfo::new(Some(num_frames), Some(input_len));
 
-        buf.clear();
-        buf.reserve(range.len());
+        // the width of the offsets is only known after compression, as padding and
+        // frames that are not stored as-is can take more space than the input
+        let mut header = format::Header {
+            frame_size,
+            input_len,
+            wide: wide_offsets || format::needs_wide_offsets(input_len, input_len),
+            compact: compact_index,
+            group_size,
+            frame_alignment,
+            checksum,
+            metadata,
+            dictionary,
+            dictionary_id,
+        };
+        // the size of a compact table is only known after compression, so it is
+        // inserted in front of the frames afterwards
+        let table_sizeof = if trailer_index {
+            header.write_trailer_header(buf)?;
+            header.trailer_header_size()
+        } else {
+            header.size(num_frames).unwrap_or(0)
+        };
 
-        // FIXME: a stable `array_windows` would be nice
-        for (i, win) in frame_offsets.windows(2).enumerate() {
-            let &[start, end] = win else {
-                return Err(eof());
-            };
-            let source = &self
-                .zstd_buf
-                .get((start as usize)..(end as usize))
-                .ok_or_else(eof)?;
+        buf.reserve(table_sizeof + zstd::compress_bound(frame_size * 2));
+        buf.resize(table_sizeof, 0);
 
-            let is_end = i == frame_offsets.len() - 2;
-            if i == 0 || is_end {
-                self.read_buf.clear();
-                self.read_buf.reserve(frame_size);
-                let mut destination = zstd::spare_capacity_buf(&mut self.read_buf);
-                decompressor.decompress_to_buffer(source, &mut destination)?;
+        table.reserve(num_frames);
 
-                let start = if i == 0 { range.start % frame_size } else { 0 };
-                let end = (start + (range.len() - buf.len())).min(self.read_buf.len());
-                buf.extend_from_slice(&self.read_buf[start..end]);
+        for i in 0..num_frames {
+            let from = i * frame_size;
+            let to = ((i + 1) * frame_size).min(input.len());
+            let source = &input[from..to];
+
+            let written_before = buf.len();
+            let frame = encoder.compress_frame(source, buf)?;
+            progress.frame_done(
+                source.len(),
+                buf.len() - written_before,
+                callback.as_deref_mut(),
+            )?;
+
+            let padding = format::padding((buf.len() - table_sizeof) as u64, frame_alignment);
+            buf.resize(buf.len() + padding, 0);
+            let total_written = (buf.len() - table_sizeof) as u64;
+            table.push(total_written, frame);
+        }
+
+        if trailer_index {
+            let frames_len = (buf.len() - table_sizeof) as u64;
+            header.wide = wide_offsets || format::needs_wide_offsets(input_len, frames_len);
+            header.write_trailer(buf, table, frames_len)?;
+        } else if compact_index {
+            header.wide = wide_offsets || format::needs_wide_offsets(input_len, buf.len() as u64);
+            let mut index = Vec::new();
+            header.write(&mut index, table)?;
+            buf.splice(..0, index);
+        } else {
+            let frames_len = (buf.len() - table_sizeof) as u64;
+            let wide = wide_offsets || format::needs_wide_offsets(input_len, frames_len);
+            if wide == header.wide {
+                header.write(&mut &mut buf[..table_sizeof], table)?;
             } else {
-                let mut destination = zstd::spare_capacity_buf(buf);
-                let _bytes_written = decompressor.decompress_to_buffer(source, &mut destination)?;
+                // the offset table reserved in front of the frames has the wrong size
+                header.wide = wide;
+                let mut index = Vec::new();
+                header.write(&mut index, table)?;
+                buf.splice(..table_sizeof, index);
             }
         }
 
-        Ok(buf.as_slice())
+        Ok(())
+    }
+
+    /// Compresses `input`, writing the resulting archive to `writer`.
+    ///
+    /// In contrast to [`Compressor::compress`], this never holds more than a single
+    /// compressed frame in memory. As the frame offset table is located in front of
+    /// the compressed frames, this does a first pass over the `input` to determine the
+    /// compressed frame sizes, and thus compresses everything twice. With a
+    /// [`trailer_index`](Self::trailer_index), everything is compressed only once.
+    pub fn compress_to_writer<W: std::io::Write>(
+        &mut self,
+        input: &[u8],
+        writer: &mut W,
+    ) -> Result<(), Error> {
+        let frame_size = self.frame_size.resolve(Some(input.len()));
+        let wide_offsets = self.wide_offsets;
+        let checksum = self.checksum;
+        let trailer_index = self.trailer_index;
+        let compact_index = self.compact_index;
+        let group_size = self.group_size;
+        let frame_alignment = self.frame_alignment;
+        let Context {
+            encoder,
+            dictionary,
+            dictionary_id,
+            metadata,
+            mut callback,
+        } = self.context()?;
+        let mut buf = Vec::with_capacity(zstd::compress_bound(frame_size));
+        let input_len = input.len() as u64;
+
+        if trailer_index {
+            let mut header = format::Header {
+                frame_size,
+                input_len,
+                wide: wide_offsets,
+                compact: compact_index,
+                group_size,
+                frame_alignment,
+                checksum,
+                metadata,
+                dictionary,
+                dictionary_id,
+            };
+            header.write_trailer_header(writer)?;
+
+            let num_frames = input.len().div_ceil(frame_size);
+            let mut progress = ProgressInfo::new(Some(num_frames), Some(input_len));
+            let mut table = FrameTable::new();
+            let mut total_written = 0;
+            for source in input.chunks(frame_size) {
+                buf.clear();
+                let frame = encoder.compress_frame(source, &mut buf)?;
+                progress.frame_done(source.len(), buf.len(), callback.as_deref_mut())?;
+                buf.resize(
+                    buf.len() + format::padding(total_written + buf.len() as u64, frame_alignment),
+                    0,
+                );
+                writer.write_all(&buf)?;
+                total_written += buf.len() as u64;
+                table.push(total_written, frame);
+            }
+
+            header.wide |= format::needs_wide_offsets(input_len, total_written);
+            header.write_trailer(writer, &table, total_written)?;
+            return Ok(());
+        }
+
+        let mut table = FrameTable::new();
+        let mut total_written = 0;
+        for source in input.chunks(frame_size) {
+            buf.clear();
+            let frame = encoder.compress_frame(source, &mut buf)?;
+            total_written += buf.len() as u64;
+            total_written += format::padding(total_written, frame_alignment) as u64;
+            table.push(total_written, frame);
+        }
+
+        let header = format::Header {
+            frame_size,
+            input_len,
+            wide: wide_offsets || format::needs_wide_offsets(input_len, total_written),
+            compact: compact_index,
+            group_size,
+            frame_alignment,
+            checksum,
+            metadata,
+            dictionary,
+            dictionary_id,
+        };
+        header.write(writer, &table)?;
+
+        let num_frames = table.num_frames();
+        let mut progress = ProgressInfo::new(Some(num_frames), Some(input_len));
+        for source in input.chunks(frame_size) {
+            buf.clear();
+            encoder.compress_frame(source, &mut buf)?;
+            progress.frame_done(source.len(), buf.len(), callback.as_deref_mut())?;
+            buf.resize(
+                buf.len() + format::padding(buf.len() as u64, frame_alignment),
+                0,
+            );
+            writer.write_all(&buf)?;
+        }
+
+        Ok(())
+    }
+
+    /// Compresses everything read from `reader`, writing the resulting archive to `sink`.
+    ///
+    /// The input is read in chunks of `frame_size`, so it never has to be held in
+    /// memory as a whole. Only the compressed frames are buffered, as the archive can
+    /// only be written once the frame offset table is complete.
+    pub fn compress_reader<R: std::io::Read, W: std::io::Write>(
+        self,
+        reader: R,
+        sink: W,
+    ) -> std::io::Result<W> {
+        let mut writer = self.writer(sink)?;
+        writer.read_from(reader)?;
+        writer.finish()
+    }
+
+    /// Creates an [`AsyncCompressWriter`], the asynchronous counterpart of
+    /// [`Compressor::writer`], writing the resulting archive to `sink`.
+    pub fn async_writer<W: AsyncSink>(
+        self,
+        sink: W,
+    ) -> std::io::Result<AsyncCompressWriter<'d, W>> {
+        Ok(AsyncCompressWriter::new(self.writer(Vec::new())?, sink))
+    }
+
+    /// Creates a [`CompressWriter`] which compresses everything written to it,
+    /// writing the resulting archive to `inner` once it is finished.
+    ///
+    /// With a [`trailer_index`](Self::trailer_index), compressed frames are
+    /// written to `inner` as soon as they are complete.
+    pub fn writer<W: std::io::Write>(mut self, inner: W) -> std::io::Result<CompressWriter<'d, W>> {
+        let encoder = match self.context.take() {
+            Some(context) => context,
+            None => self.encoder()?,
+        };
+        let options = ArchiveOptions {
+            frame_size: self.frame_size.resolve(None),
+            wide_offsets: self.wide_offsets,
+            checksum: self.checksum,
+            trailer_index: self.trailer_index,
+            compact_index: self.compact_index,
+            group_size: self.group_size,
+            frame_alignment: self.frame_alignment,
+            dictionary: self.embedded_dictionary().map(Vec::from),
+            dictionary_id: self.dictionary_id,
+            metadata: self.metadata,
+        };
+        CompressWriter::new(inner, encoder, options, self.progress)
+    }
+
+    /// Sets an advanced `zstd` parameter, replacing a previous value of the same kind.
+    fn set_param(&mut self, param: zstd::CParameter) {
+        let kind = std::mem::discriminant(&param);
+        self.params.retain(|p| std::mem::discriminant(p) != kind);
+        self.params.push(param);
+        self.context = None;
+    }
+
+    /// Returns the lazily created `zstd` context, along with everything else
+    /// needed while compressing.
+    fn context(&mut self) -> std::io::Result<Context<'_, 'd>> {
+        if self.context.is_none() {
+            self.context = Some(self.encoder()?);
+        }
+        let dictionary = match &self.dictionary {
+            Some(Dictionary::Embedded(dictionary)) if self.dictionary_id.is_none() => {
+                Some(dictionary.as_slice())
+            }
+            _ => None,
+        };
+        Ok(Context {
+            encoder: self.context.as_mut().unwrap(),
+            dictionary,
+            dictionary_id: self.dictionary_id,
+            metadata: &self.metadata,
+            callback: self.progress.as_mut(),
+        })
+    }
+
+    /// Returns the dictionary to embed into the archive.
+    fn embedded_dictionary(&self) -> Option<&[u8]> {
+        match &self.dictionary {
+            Some(Dictionary::Embedded(dictionary)) if self.dictionary_id.is_none() => {
+                Some(dictionary)
+            }
+            _ => None,
+        }
+    }
+
+    fn encoder(&self) -> std::io::Result<FrameEncoder<'d>> {
+        self.encoder_settings().encoder()
+    }
+
+    fn encoder_settings(&self) -> EncoderSettings<'_, 'd> {
+        EncoderSettings {
+            level: self.level,
+            adaptive_level: self.adaptive_level,
+            deterministic: self.deterministic,
+            checksum: self.checksum,
+            stored_frames: self.group_size == 1,
+            holes: self.holes && self.group_size == 1,
+            zstd_frames: false,
+            params: &self.params,
+            dictionary: self.dictionary.as_ref(),
+            max_memory: self.max_memory,
+        }
     }
 }
 
-fn eof() -> std::io::Error {
-    std::io::ErrorKind::UnexpectedEof.into()
+/// The parts of a [`Compressor`] that are used while compressing.
+struct Context<'a, 'd> {
+    encoder: &'a mut FrameEncoder<'d>,
+    /// The dictionary to embed into the archive.
+    dictionary: Option<&'a [u8]>,
+    /// The id of the dictionary to reference in the archive.
+    dictionary_id: Option<u32>,
+    metadata: &'a [(String, Vec<u8>)],
+    callback: Option<&'a mut ProgressFn<'d>>,
 }
 
-fn make_range<R>(range: R, len: usize) -> Range<usize>
-where
-    R: RangeBounds<usize>,
-{
-    use std::ops::Bound::*;
+/// The settings needed to create a [`FrameEncoder`].
+///
+/// In contrast to the [`Compressor`] itself, these can be shared across threads.
+#[derive(Clone, Copy)]
+struct EncoderSettings<'a, 'd> {
+    level: i32,
+    adaptive_level: bool,
+    deterministic: bool,
+    checksum: Checksum,
+    /// Whether frames that do not compress may be stored uncompressed.
+    stored_frames: bool,
+    /// Whether frames consisting only of zeroes are stored as holes.
+    holes: bool,
+    /// Whether to write regular `zstd` frames, including magic bytes and a checksum.
+    zstd_frames: bool,
+    params: &'a [zstd::CParameter],
+    dictionary: Option<&'a Dictionary<'d>>,
+    /// The memory budget of each `zstd` context.
+    max_memory: Option<usize>,
+}
 
-    let start = match range.start_bound() {
-        Included(b) => *b,
-        Excluded(b) => *b,
-        Unbounded => 0,
-    };
-    let end = match range.end_bound() {
-        Included(b) => *b + 1,
-        Excluded(b) => *b,
-        Unbounded => len,
-    };
+impl<'d> EncoderSettings<'_, 'd> {
+    fn encoder(self) -> std::io::Result<FrameEncoder<'d>> {
+        let mut compressor = self.zstd_compressor(self.level)?;
+        for param in self.params {
+            compressor.set_parameter(*param)?;
+        }
+        if let Some(max_memory) = self.max_memory {
+            let (params, _size) = zstd::limit_memory(self.level, self.params, max_memory);
+            for param in params {
+                compressor.set_parameter(param)?;
+            }
+            // worker threads allocate buffers of their own
+            compressor.set_parameter(zstd::CParameter::NbWorkers(0))?;
+        }
+        self.pin_params(&mut compressor)?;
+
+        let probe = match self.dictionary {
+            Some(Dictionary::ByRef(_)) => None,
+            // level `0` means the `zstd` default level
+            _ if self.adaptive_level && (self.level == 0 || self.level > encoder::PROBE_LEVEL) => {
+                let mut probe = self.zstd_compressor(encoder::PROBE_LEVEL)?;
+                self.pin_params(&mut probe)?;
+                Some(probe)
+            }
+            _ => None,
+        };
+        Ok(FrameEncoder::new(
+            compressor,
+            probe,
+            self.checksum,
+            self.stored_frames,
+            self.holes,
+        ))
+    }
+
+    /// Pins parameters whose defaults depend on heuristics or the `zstd` version
+    /// in [`deterministic`](Compressor::deterministic) mode.
+    fn pin_params(self, compressor: &mut zstd::Compressor<'_>) -> std::io::Result<()> {
+        if !self.deterministic {
+            return Ok(());
+        }
+        // the multithreaded mode produces different output than the single-threaded one
+        compressor.set_parameter(zstd::CParameter::NbWorkers(0))?;
+        compressor.set_parameter(zstd::CParameter::UseRowMatchFinder(
+            zstd::ParamSwitch::Disable,
+        ))?;
+        compressor.set_parameter(zstd::CParameter::UseBlockSplitter(
+            zstd::ParamSwitch::Disable,
+        ))?;
+        compressor.set_parameter(zstd::CParameter::LiteralCompressionMode(
+            zstd::ParamSwitch::Enable,
+        ))?;
+        Ok(())
+    }
 
-    start..end
+    fn zstd_compressor(self, level: i32) -> std::io::Result<zstd::Compressor<'d>> {
+        let mut compressor = match self.dictionary {
+            Some(Dictionary::Embedded(dictionary)) => {
+                zstd::Compressor::with_dictionary(level, dictionary)?
+            }
+            Some(&Dictionary::ByRef(dictionary)) => {
+                zstd::Compressor::with_prepared_dictionary(dictionary)?
+            }
+            None => zstd::Compressor::new(level)?,
+        };
+        compressor.include_checksum(self.zstd_frames)?;
+        compressor.include_contentsize(self.zstd_frames)?;
+        compressor.include_dictid(false)?;
+        compressor.include_magicbytes(self.zstd_frames)?;
+        Ok(compressor)
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use proptest::prelude::*;
+impl Default for Compressor<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-    #[test]
-    fn test_compress() {
-        let input: Vec<u8> = (0..32).collect();
-        let compressed = Compressor::new().frame_size(16).compress(&input).unwrap();
+pub struct Decompressor<'b> {
+    archive: Archive<'b>,
+    /// The storage the compressed frames are read from, which is shared with clones.
+    storage: Arc<dyn Storage + 'b>,
+    /// The range of the compressed frames within the `storage`.
+    frames: Range<u64>,
+    dictionary: Option<&'b DecoderDictionary<'b>>,
+    /// The external dictionary referenced by the archive, as resolved by a [`DictionaryProvider`].
+    resolved_dictionary: Option<Vec<u8>>,
+    /// The most recently decompressed frame.
+    read_buf: FrameBuf,
+    /// The output of [`Decompressor::get_ref`] spanning multiple frames.
+    ref_buf: Vec<u8>,
+    /// The `zstd` contexts reused across reads, which are lazily created. This
+    /// is a pool instead of a single context, so concurrent reads through `&self`
+    /// can reuse contexts as well.
+    zstd: Pool<zstd::Decompressor<'b>>,
+    cache: FrameCache,
+    /// The pool this was opened with, which provides `zstd` contexts and gets
+    /// the `read_buf` back on drop.
+    pool: Option<&'b DecompressorPool>,
+    max_output_bytes: usize,
+    max_scratch_bytes: usize,
+    /// Returns whether reads should be aborted, checked before every frame.
+    cancel: Option<Arc<dyn Fn() -> bool + Send + Sync + 'b>>,
+}
 
-        let mut o = Vec::new();
-        let mut d = Decompressor::new(&compressed).unwrap();
+impl Drop for Decompressor<'_> {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool {
+            pool.put_buf(std::mem::take(&mut self.read_buf));
+        }
+    }
+}
 
-        #[allow(clippy::reversed_empty_ranges)]
-        {
-            assert_eq!(d.get_into(&mut o, 3..1).ok(), input.get(3..1));
+/// Clones share the storage, dictionaries and settings, but each clone has its
+/// own buffers, `zstd` contexts and frame cache, which start out empty.
+impl Clone for Decompressor<'_> {
+    fn clone(&self) -> Self {
+        Self {
+            archive: self.archive.clone(),
+            storage: Arc::clone(&self.storage),
+            frames: self.frames.clone(),
+            dictionary: self.dictionary,
+            resolved_dictionary: self.resolved_dictionary.clone(),
+            read_buf: FrameBuf::default(),
+            ref_buf: Vec::new(),
+            zstd: Default::default(),
+            cache: FrameCache::new(self.cache.budget()),
+            pool: self.pool,
+            max_output_bytes: self.max_output_bytes,
+            max_scratch_bytes: self.max_scratch_bytes,
+            cancel: self.cancel.clone(),
         }
+    }
+}
 
-        assert_eq!(d.get_into(&mut o, ..0).ok(), input.get(..0));
-        assert_eq!(d.get_into(&mut o, ..).ok(), input.get(..));
-        assert_eq!(d.get_into(&mut o, 0..32).ok(), input.get(0..32));
+/// Only prints the shape of the archive, not any of the buffers.
+impl std::fmt::Debug for Decompressor<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Decompressor")
+            .field("len", &self.len())
+            .field("frame_size", &self.frame_size())
+            .field("frame_count", &self.frame_count())
+            .field("compressed_len", &self.compressed_len())
+            .field("checksum", &self.archive.checksum)
+            .field("cached_frame", &self.read_buf.frame)
+            .finish_non_exhaustive()
+    }
+}
 
-        assert_eq!(d.get_into(&mut o, ..31).ok(), input.get(..31));
-        assert_eq!(d.get_into(&mut o, 1..).ok(), input.get(1..));
-        assert_eq!(d.get_into(&mut o, 1..31).ok(), input.get(1..31));
+impl<'b> TryFrom<&'b [u8]> for Decompressor<'b> {
+    type Error = Error;
 
-        assert_eq!(d.get_into(&mut o, 5..10).ok(), input.get(5..10));
-        assert_eq!(d.get_into(&mut o, 10..20).ok(), input.get(10..20));
+    /// Opens the archive in `bytes`, like [`Decompressor::new`].
+    fn try_from(bytes: &'b [u8]) -> Result<Self, Error> {
+        Self::new(bytes)
+    }
+}
+
+impl<'b> Decompressor<'b> {
+    /// Opens the archive in `bytes`, returning an [`Error`] describing why it
+    /// is invalid otherwise.
+    pub fn new(bytes: &'b [u8]) -> Result<Self, Error> {
+        let archive = Archive::parse(bytes)?;
+        Ok(Self::from_archive(bytes, archive))
+    }
+
+    /// Opens the archive in `bytes` like [`Decompressor::new`], but rejects it
+    /// with [`Error::LengthMismatch`] unless its compressed frames end exactly
+    /// where its offset table says they do.
+    ///
+    /// This detects truncated archives, and archives followed by trailing bytes.
+    /// Those are otherwise accepted as long as their index is intact, so that
+    /// truncated archives only fail when reading the missing frames.
+    pub fn new_strict(bytes: &'b [u8]) -> Result<Self, Error> {
+        let archive = Archive::parse_strict(bytes)?;
+        Ok(Self::from_archive(bytes, archive))
+    }
+
+    /// Opens an archive, rejecting it with [`Error::LimitExceeded`] if the
+    /// sizes it declares exceed the `limits`.
+    ///
+    /// Use this for archives from untrusted sources.
+    pub fn with_limits(bytes: &'b [u8], limits: Limits) -> Result<Self, Error> {
+        let archive = Archive::parse(bytes)?;
+        if !limits.check(&archive) {
+            return Err(Error::LimitExceeded);
+        }
+        Ok(Self::from_archive(bytes, archive))
+    }
+
+    /// Opens an archive in any [`Storage`], reading only its index into `index`.
+    ///
+    /// The compressed frames are read from the `storage` on demand, so archives
+    /// in files or remote locations do not have to be loaded into memory as a whole.
+    /// An invalid archive results in an error wrapping the [`Error`] that
+    /// describes the problem.
+    pub fn with_storage<S: Storage + 'b>(
+        storage: S,
+        index: &'b mut Vec<u8>,
+    ) -> std::io::Result<Self> {
+        let (archive, frames) = Archive::read_index(&storage, index)?;
+        let mut decompressor = Self::from_archive(&[], archive);
+        decompressor.storage = Arc::new(storage);
+        decompressor.frames = frames;
+        Ok(decompressor)
+    }
+
+    /// Opens an archive in a [`File`](std::fs::File), or any other [`Read`](std::io::Read)
+    /// + [`Seek`](std::io::Seek) source, reading only its index into `index`.
+    ///
+    /// The compressed frames are read on demand, instead of loading or memory
+    /// mapping the whole archive first. See [`ReaderStorage`] for details.
+    pub fn from_reader<R>(reader: R, index: &'b mut Vec<u8>) -> std::io::Result<Self>
+    where
+        R: std::io::Read + std::io::Seek + Send + 'b,
+    {
+        Self::with_storage(ReaderStorage::new(reader)?, index)
+    }
+
+    /// Creates a decompressor for the `archive` parsed from `bytes`.
+    fn from_archive(bytes: &'b [u8], archive: Archive<'b>) -> Self {
+        let frames_start =
+            (archive.zstd_buf.as_ptr() as usize).wrapping_sub(bytes.as_ptr() as usize);
+        let frames_start = if archive.zstd_buf.is_empty() {
+            0
+        } else {
+            frames_start as u64
+        };
+        Self {
+            storage: Arc::new(bytes),
+            frames: frames_start..frames_start + archive.zstd_buf.len() as u64,
+            archive,
+            dictionary: None,
+            resolved_dictionary: None,
+            read_buf: FrameBuf::default(),
+            ref_buf: Vec::new(),
+            zstd: Default::default(),
+            cache: FrameCache::default(),
+            pool: None,
+            max_output_bytes: usize::MAX,
+            max_scratch_bytes: usize::MAX,
+            cancel: None,
+        }
+    }
+
+    /// Opens an archive that was compressed using the given prepared dictionary.
+    ///
+    /// The dictionary is only referenced, which avoids copying and loading it again
+    /// when it is shared across many decompressors. It takes precedence over a
+    /// dictionary embedded in the archive, which means it can also be used to avoid
+    /// repeatedly loading an embedded dictionary.
+    pub fn with_dictionary_by_ref(
+        bytes: &'b [u8],
+        dictionary: &'b DecoderDictionary<'b>,
+    ) -> Result<Self, Error> {
+        let mut decompressor = Self::new(bytes)?;
+        decompressor.dictionary = Some(dictionary);
+        Ok(decompressor)
+    }
+
+    /// Enables a cache of up to `budget` bytes of decompressed frames.
+    ///
+    /// Reads of cached frames skip decompression entirely, which pays off when
+    /// the same few frames are read over and over, like for symbol lookups. The
+    /// least recently used frames are evicted first. Only compressed frames are
+    /// cached, and the cache is shared by all reads, including [`Decompressor::cursor`]
+    /// and concurrent [`Decompressor::read_at`] calls.
+    pub fn frame_cache(mut self, budget: usize) -> Self {
+        self.cache = FrameCache::new(budget);
+        self
+    }
+
+    /// Limits the size of the output a single read may allocate.
+    ///
+    /// Reads of larger ranges fail with [`InvalidInput`](std::io::ErrorKind::InvalidInput)
+    /// before allocating anything, instead of allocating whatever range a caller
+    /// derived from untrusted input asks for. This does not apply to reads into
+    /// caller provided buffers.
+    pub fn max_output_bytes(mut self, limit: usize) -> Self {
+        self.max_output_bytes = limit;
+        self
+    }
+
+    /// Limits the size of the internal buffer used for partially read frames.
+    ///
+    /// The buffer holds a whole decompressed frame, and the frame size is read from
+    /// the archive. Reads of larger frames fail with
+    /// [`InvalidData`](std::io::ErrorKind::InvalidData) instead. Only the actual
+    /// length of a frame counts, so inputs smaller than the frame size can still
+    /// be read.
+    pub fn max_scratch_bytes(mut self, limit: usize) -> Self {
+        self.max_scratch_bytes = limit;
+        self
+    }
+
+    /// Aborts reads once `should_cancel` returns `true`.
+    ///
+    /// This is checked before each frame, so long operations like reading the
+    /// whole archive, [`Decompressor::frames`] or [`Decompressor::to_standard_zstd`]
+    /// stop early, for example when the client of a server disconnects. Cancelled
+    /// reads fail with an error wrapping [`Error::Cancelled`].
+    pub fn cancel_when<F>(mut self, should_cancel: F) -> Self
+    where
+        F: Fn() -> bool + Send + Sync + 'b,
+    {
+        self.cancel = Some(Arc::new(should_cancel));
+        self
+    }
+
+    /// Returns an error if the read has been cancelled via [`Decompressor::cancel_when`].
+    fn check_cancelled(&self) -> std::io::Result<()> {
+        match &self.cancel {
+            Some(should_cancel) if should_cancel() => Err(Error::Cancelled.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that `len` bytes of output are within [`Decompressor::max_output_bytes`].
+    fn check_output_len(&self, len: usize) -> std::io::Result<()> {
+        if len > self.max_output_bytes {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the requested range exceeds the output limit",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the key-value [`Metadata`] stored in the archive.
+    pub fn metadata(&self) -> Metadata<'_> {
+        self.archive.metadata
+    }
+
+    /// The total uncompressed size of the archive.
+    pub fn len(&self) -> usize {
+        self.archive.input_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The uncompressed size of each frame, except for the last one, which may be shorter.
+    pub fn frame_size(&self) -> usize {
+        self.archive.frame_size
+    }
+
+    /// The number of frames in the archive.
+    pub fn frame_count(&self) -> usize {
+        self.len().div_ceil(self.frame_size())
+    }
+
+    /// The size of the whole compressed archive, including its header and index.
+    pub fn compressed_len(&self) -> u64 {
+        self.storage.len()
+    }
+
+    pub fn get<R>(&mut self, range: R) -> std::io::Result<Vec<u8>>
+    where
+        R: RangeBounds<usize>,
+    {
+        let mut buf = Vec::new();
+        self.get_into(&mut buf, range)?;
+        Ok(buf)
+    }
+
+    pub fn get_into<'o, R>(&mut self, buf: &'o mut Vec<u8>, range: R) -> std::io::Result<&'o [u8]>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.archive.input_len)?;
+        buf.clear();
+        self.with_read_buf(|this, frame_buf| this.read_into(buf, range, frame_buf))
+    }
+
+    /// Decompresses `range` into an internal buffer, returning a view of it that
+    /// is valid until the next call.
+    ///
+    /// This avoids copying the output into a caller provided buffer. Ranges
+    /// within a single frame are returned directly from the decompressed frame,
+    /// or even from the archive itself for frames stored uncompressed.
+    pub fn get_ref<R>(&mut self, range: R) -> std::io::Result<&[u8]>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.archive.input_len)?;
+        if range.start > range.end || range.end > self.archive.input_len {
+            return Err(self.out_of_bounds(range));
+        }
+        let frame_size = self.frame_size();
+        let idx = range.start / frame_size;
+        let frame_start = idx * frame_size;
+        if range.is_empty() || range.end - frame_start > frame_size {
+            let mut buf = std::mem::take(&mut self.ref_buf);
+            buf.clear();
+            let result = self.with_read_buf(|this, frame_buf| {
+                this.read_into(&mut buf, range, frame_buf).map(|_| ())
+            });
+            self.ref_buf = buf;
+            result?;
+            return Ok(&self.ref_buf);
+        }
+
+        let frame_range = range.start - frame_start..range.end - frame_start;
+        // stored frames of archives in memory are returned directly
+        if let Some(Frame::Stored(source)) = self.archive.frame(idx) {
+            self.verify_frame(idx, source)?;
+            return source
+                .get(frame_range)
+                .ok_or_else(|| self.frame_decode_failed(idx, None));
+        }
+        self.with_read_buf(|this, frame_buf| {
+            this.load_frame(&mut None, idx, frame_buf).map(|_| ())
+        })?;
+        self.read_buf
+            .buf
+            .get(frame_range)
+            .ok_or_else(|| self.frame_decode_failed(idx, None))
+    }
+
+    /// Decompresses `range` into the output buffer of `scratch`, returning it.
+    ///
+    /// In contrast to [`Decompressor::get`], all buffers are provided by the
+    /// caller. Once the buffers of the [`Scratch`] have grown to the largest read,
+    /// reads do not allocate at all anymore, except for a [`frame_cache`](Self::frame_cache).
+    /// As this only needs `&self`, each thread can read with its own [`Scratch`].
+    pub fn get_with_scratch<'s, R>(
+        &self,
+        scratch: &'s mut Scratch,
+        range: R,
+    ) -> std::io::Result<&'s [u8]>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.archive.input_len)?;
+        scratch.buf.clear();
+        // the scratch may have been used with another archive
+        scratch.frame_buf.frame = None;
+        self.read_into(&mut scratch.buf, range, &mut scratch.frame_buf)
+    }
+
+    /// Returns an iterator over the contents of `range`, in chunks of `chunk_size`.
+    ///
+    /// In contrast to [`Decompressor::get`], the range does not have to be
+    /// held in memory as a whole, as each chunk only decompresses the frames it
+    /// needs. All chunks but the last one are exactly `chunk_size` long.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn get_chunks<R>(&mut self, range: R, chunk_size: usize) -> Chunks<'_, 'b>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.archive.input_len);
+        Chunks::new(self, range, chunk_size)
+    }
+
+    /// Decompresses multiple `ranges` at once, returning their contents in the
+    /// same order as the `ranges`.
+    ///
+    /// The ranges are processed in the order of their start, and each frame is
+    /// decompressed at most once, even when it is covered by many of the ranges.
+    /// This is a lot faster than individual reads for many small, scattered
+    /// ranges, like symbol lookups.
+    pub fn get_many(&mut self, ranges: &[Range<usize>]) -> std::io::Result<Vec<Vec<u8>>> {
+        if let Some(range) = ranges
+            .iter()
+            .find(|range| range.start > range.end || range.end > self.archive.input_len)
+        {
+            return Err(self.out_of_bounds(range.clone()));
+        }
+        self.with_read_buf(|this, frame_buf| this.read_many(ranges, frame_buf))
+    }
+
+    /// Decompresses all `ranges`, which have to be in bounds, using `frame_buf`
+    /// for the decompressed frames.
+    fn read_many(
+        &self,
+        ranges: &[Range<usize>],
+        frame_buf: &mut FrameBuf,
+    ) -> std::io::Result<Vec<Vec<u8>>> {
+        let output_len = ranges
+            .iter()
+            .try_fold(0usize, |len, range| len.checked_add(range.len()));
+        self.check_output_len(output_len.unwrap_or(usize::MAX))?;
+        let mut results: Vec<_> = ranges
+            .iter()
+            .map(|range| Vec::with_capacity(range.len()))
+            .collect();
+        let mut order: Vec<_> = (0..ranges.len())
+            .filter(|&i| !ranges[i].is_empty())
+            .collect();
+        order.sort_by_key(|&i| ranges[i].start);
+
+        let frame_size = self.frame_size();
+        let mut decompressor = None;
+        // the ranges covering the current frame
+        let mut active = Vec::new();
+        let mut next = 0;
+        let mut frame_idx = 0;
+        loop {
+            if active.is_empty() {
+                let Some(&i) = order.get(next) else {
+                    return Ok(results);
+                };
+                frame_idx = frame_idx.max(ranges[i].start / frame_size);
+            }
+            let frame_start = frame_idx * frame_size;
+            let frame_end = frame_start.saturating_add(frame_size);
+            while let Some(&i) = order.get(next).filter(|&&i| ranges[i].start < frame_end) {
+                active.push(i);
+                next += 1;
+            }
+
+            let frame = self.load_frame(&mut decompressor, frame_idx, frame_buf)?;
+            for &i in &active {
+                let range = &ranges[i];
+                let start = range.start.max(frame_start) - frame_start;
+                let end = range.end.min(frame_end) - frame_start;
+                let frame = frame
+                    .get(start..end)
+                    .ok_or_else(|| self.frame_decode_failed(frame_idx, None))?;
+                results[i].extend_from_slice(frame);
+            }
+            active.retain(|&i| ranges[i].end > frame_end);
+            frame_idx += 1;
+        }
+    }
+
+    /// Decompresses `range`, appending it to `buf` instead of replacing its
+    /// contents like [`Decompressor::get_into`].
+    ///
+    /// This allows assembling multiple ranges into a single buffer. Returns the
+    /// appended part of `buf`. On error, `buf` may contain a part of the range.
+    pub fn append_into<'o, R>(
+        &mut self,
+        buf: &'o mut Vec<u8>,
+        range: R,
+    ) -> std::io::Result<&'o [u8]>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.archive.input_len)?;
+        self.with_read_buf(|this, frame_buf| this.read_into(buf, range, frame_buf))
+    }
+
+    /// Reads the uncompressed contents at `offset` into `buf`, returning the
+    /// number of bytes read.
+    ///
+    /// Like `FileExt::read_at` of the standard library, this does
+    /// not depend on any position or other mutable state, so many threads can
+    /// read from the same [`Decompressor`] concurrently. Frames covered by `buf`
+    /// as a whole are decompressed into it directly. Fewer bytes than requested
+    /// are only read at the end of the archive.
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(self.archive.input_len.saturating_sub(offset));
+        let mut frame_buf = FrameBuf::default();
+        self.read_to_uninit(offset, zstd::as_uninit(&mut buf[..len]), &mut frame_buf)?;
+        Ok(len)
+    }
+
+    /// Decompresses `range` into `buf`, returning the number of bytes written.
+    ///
+    /// In contrast to [`Decompressor::get_into`], this writes into a caller
+    /// provided slice, like a pooled buffer or memory owned by foreign code.
+    /// Frames covered by `buf` as a whole are decompressed into it directly.
+    /// An error is returned if the `range` is out of bounds, or `buf` is too small
+    /// to hold it.
+    pub fn get_to_slice<R>(&mut self, range: R, buf: &mut [u8]) -> std::io::Result<usize>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.archive.input_len)?;
+        if range.start > range.end || range.end > self.archive.input_len {
+            return Err(self.out_of_bounds(range));
+        }
+        let buf = buf.get_mut(..range.len()).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "buffer too small for the requested range",
+            )
+        })?;
+        self.with_read_buf(|this, frame_buf| {
+            this.read_to_uninit(range.start, zstd::as_uninit(buf), frame_buf)
+        })?;
+        Ok(range.len())
+    }
+
+    /// Decompresses `range` into the possibly uninitialized `buf`, returning
+    /// the initialized part.
+    ///
+    /// This is the same as [`Decompressor::get_to_slice`], but avoids having
+    /// to initialize the destination first, which is noticeable for large reads.
+    pub fn get_uninit<'o, R>(
+        &mut self,
+        range: R,
+        buf: &'o mut [std::mem::MaybeUninit<u8>],
+    ) -> std::io::Result<&'o mut [u8]>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.archive.input_len)?;
+        if range.start > range.end || range.end > self.archive.input_len {
+            return Err(self.out_of_bounds(range));
+        }
+        let buf = buf.get_mut(..range.len()).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "buffer too small for the requested range",
+            )
+        })?;
+        self.with_read_buf(|this, frame_buf| this.read_to_uninit(range.start, buf, frame_buf))
+    }
+
+    /// Decompresses `range` into `writer`, returning the number of bytes written.
+    ///
+    /// The range is written frame by frame, so only a single decompressed frame
+    /// is held in memory at a time, regardless of the size of the range. This
+    /// makes it possible to stream large ranges into a file, socket or hasher.
+    /// As nothing is allocated for the output, the [`max_output_bytes`](Self::max_output_bytes)
+    /// limit does not apply. On error, a part of the range may have already been
+    /// written.
+    pub fn get_to_writer<R, W>(&mut self, range: R, writer: &mut W) -> std::io::Result<u64>
+    where
+        R: RangeBounds<usize>,
+        W: std::io::Write + ?Sized,
+    {
+        let range = make_range(range, self.archive.input_len)?;
+        if range.start > range.end || range.end > self.archive.input_len {
+            return Err(self.out_of_bounds(range));
+        }
+        if range.is_empty() {
+            return Ok(0);
+        }
+        let frame_size = self.frame_size();
+        let frames = range.start / frame_size..range.end.div_ceil(frame_size);
+        // errors are reported once the affected frame is reached
+        let _ = self.prefetch_storage(frames.clone());
+
+        self.with_read_buf(|this, frame_buf| {
+            let mut decompressor = None;
+            for idx in frames {
+                let frame_start = idx * frame_size;
+                let start = range.start.max(frame_start) - frame_start;
+                let end = range.end.min(frame_start + frame_size) - frame_start;
+                let frame = this
+                    .load_frame(&mut decompressor, idx, frame_buf)?
+                    .get(start..end)
+                    .ok_or_else(|| this.frame_decode_failed(idx, None))?;
+                writer.write_all(frame)?;
+            }
+            Ok(range.len() as u64)
+        })
+    }
+
+    /// Fills `buf` with the uncompressed contents at `offset`, which have to be
+    /// in bounds, using `frame_buf` for partially covered frames.
+    fn read_to_uninit<'o>(
+        &self,
+        offset: usize,
+        buf: &'o mut [std::mem::MaybeUninit<u8>],
+        frame_buf: &mut FrameBuf,
+    ) -> std::io::Result<&'o mut [u8]> {
+        let len = buf.len();
+        let frame_size = self.frame_size();
+        let mut decompressor = None;
+
+        let mut written = 0;
+        while written < len {
+            self.check_cancelled()?;
+            let pos = offset + written;
+            let idx = pos / frame_size;
+            let frame_start = idx * frame_size;
+            let frame_len = frame_size.min(self.archive.input_len - frame_start);
+            let destination = &mut buf[written..];
+
+            if pos == frame_start && frame_len <= destination.len() {
+                let destination = &mut destination[..frame_len];
+                let cached = self.cache.get(idx, |cached| {
+                    zstd::copy_uninit(destination, cached);
+                });
+                if cached.is_some() {
+                    written += frame_len;
+                    continue;
+                }
+                let frame = self.frame(idx, &mut frame_buf.compressed)?;
+                let is_compressed = matches!(frame, Frame::Compressed(_));
+                let decompressed = match frame {
+                    Frame::Compressed(source) => {
+                        let decompressor = self.lazy_zstd_context(&mut decompressor)?;
+                        zstd::decompress_uninit(decompressor, source, destination)
+                            .map_err(|err| self.frame_decode_failed(idx, Some(err)))?
+                    }
+                    Frame::Stored(source) if source.len() == frame_len => {
+                        zstd::copy_uninit(destination, source)
+                    }
+                    Frame::Stored(_) => return Err(self.frame_decode_failed(idx, None)),
+                    Frame::Hole(_) => zstd::zero_uninit(destination),
+                };
+                if decompressed.len() != frame_len {
+                    return Err(self.frame_decode_failed(idx, None));
+                }
+                self.verify_frame(idx, decompressed)?;
+                if is_compressed {
+                    self.cache.insert(idx, decompressed);
+                }
+                written += frame_len;
+            } else {
+                let source = self
+                    .load_frame(&mut decompressor, idx, frame_buf)?
+                    .get(pos - frame_start..)
+                    .filter(|source| !source.is_empty())
+                    .ok_or_else(|| self.frame_decode_failed(idx, None))?;
+                let n = source.len().min(destination.len());
+                zstd::copy_uninit(destination, &source[..n]);
+                written += n;
+            }
+        }
+        // SAFETY: the loop above has initialized all of `buf`
+        Ok(unsafe { zstd::assume_init(buf) })
+    }
+
+    /// Returns a [`Cursor`] implementing [`Read`](std::io::Read) and
+    /// [`Seek`](std::io::Seek) over the uncompressed contents of the archive.
+    pub fn cursor(&self) -> Cursor<'_, 'b> {
+        Cursor::new(self)
+    }
+
+    /// Returns a reader that yields exactly the uncompressed bytes of `range`.
+    ///
+    /// This hands a section of the archive to a parser consuming a [`Read`](std::io::Read)
+    /// or [`BufRead`](std::io::BufRead), without decompressing the whole range
+    /// up front. It is a [`Cursor`] positioned at the start of the range, and
+    /// limited to its length. An error is returned if the `range` is out of bounds.
+    pub fn range_reader<R>(&self, range: R) -> std::io::Result<std::io::Take<Cursor<'_, 'b>>>
+    where
+        R: RangeBounds<usize>,
+    {
+        use std::io::{Read, Seek};
+
+        let range = make_range(range, self.archive.input_len)?;
+        if range.start > range.end || range.end > self.archive.input_len {
+            return Err(self.out_of_bounds(range));
+        }
+        let mut cursor = self.cursor();
+        cursor.seek(std::io::SeekFrom::Start(range.start as u64))?;
+        Ok(cursor.take(range.len() as u64))
+    }
+
+    /// Decompresses the frames covering `range` into the [`frame_cache`](Self::frame_cache)
+    /// ahead of time, so that subsequent reads of them are cache hits.
+    ///
+    /// This only needs `&self`, so it can run on a background thread while other
+    /// reads continue, for example within [`std::thread::scope`]. Without a frame
+    /// cache, this only touches the pages of the compressed frames, which brings
+    /// a memory mapped archive into memory.
+    pub fn prefetch<R>(&self, range: R) -> std::io::Result<()>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.archive.input_len)?;
+        if range.start > range.end || range.end > self.archive.input_len {
+            return Err(self.out_of_bounds(range));
+        }
+        let frame_size = self.frame_size();
+        let mut decompressor = None;
+        let (mut compressed, mut buf) = (Vec::new(), Vec::new());
+        for idx in range.start / frame_size..range.end.div_ceil(frame_size) {
+            self.check_cancelled()?;
+            if self.cache.get(idx, |_| ()).is_some() {
+                continue;
+            }
+            match self.frame(idx, &mut compressed)? {
+                Frame::Compressed(source) if self.cache.is_enabled() => {
+                    let decompressor = self.lazy_zstd_context(&mut decompressor)?;
+                    buf.clear();
+                    self.decompress_source(decompressor, idx, source, &mut buf)?;
+                }
+                Frame::Compressed(source) | Frame::Stored(source) => {
+                    for byte in source.iter().step_by(PAGE_SIZE) {
+                        std::hint::black_box(*byte);
+                    }
+                }
+                Frame::Hole(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the range of compressed bytes needed to read the uncompressed `range`.
+    ///
+    /// The returned range is relative to the start of the archive, or its
+    /// [`Storage`], and covers all frames overlapping `range`, so callers doing
+    /// their own I/O know exactly which bytes to fetch. Empty ranges need no
+    /// compressed bytes at all.
+    pub fn compressed_range_for<R>(&self, range: R) -> std::io::Result<Range<u64>>
+    where
+        R: RangeBounds<usize>,
+    {
+        let frames = self.compressed_frames_for(range)?;
+        Ok(match (frames.first(), frames.last()) {
+            (Some((_, first)), Some((_, last))) => first.start..last.end,
+            _ => 0..0,
+        })
+    }
+
+    /// Returns the index and the range of compressed bytes of each frame
+    /// overlapping the uncompressed `range`.
+    ///
+    /// This is the frame-granular variant of [`Decompressor::compressed_range_for`].
+    /// With a [`sparse_index`](Compressor::sparse_index), the range of a frame
+    /// covers its whole group, which is shared by all frames of the group.
+    pub fn compressed_frames_for<R>(&self, range: R) -> std::io::Result<Vec<(usize, Range<u64>)>>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.archive.input_len)?;
+        if range.start > range.end || range.end > self.archive.input_len {
+            return Err(self.out_of_bounds(range));
+        }
+        if range.is_empty() {
+            return Ok(Vec::new());
+        }
+        let frame_size = self.frame_size();
+        (range.start / frame_size..range.end.div_ceil(frame_size))
+            .map(|idx| Ok((idx, self.storage_span(idx)?)))
+            .collect()
+    }
+
+    /// Returns a [`Frames`] scan decompressing each frame of the archive in order.
+    pub fn frames(&self) -> Frames<'_, 'b> {
+        Frames::new(self)
+    }
+
+    /// Writes the whole archive as a regular `zstd` stream to `writer`, which
+    /// can be decompressed by any `zstd` decoder, like `zstd -d`.
+    ///
+    /// This does not recompress anything. Each compressed frame is written as-is
+    /// with the `zstd` magic bytes in front of it, and stored frames and holes
+    /// are written as frames of uncompressed blocks. Frames compressed with a
+    /// dictionary can only be decompressed with that same dictionary.
+    pub fn to_standard_zstd<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let num_frames = self.frame_count();
+        let mut buf = Vec::new();
+        for idx in 0..num_frames {
+            self.check_cancelled()?;
+            match self.frame(idx, &mut buf)? {
+                Frame::Compressed(source) => {
+                    if !self.archive.zstd_frames {
+                        writer.write_all(&zstd::MAGIC)?;
+                    }
+                    writer.write_all(source)?;
+                }
+                Frame::Stored(source) => zstd::write_stored_frame(writer, source)?,
+                Frame::Hole(len) => zstd::write_zero_frame(writer, len)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates the whole archive, by decompressing every frame and checking
+    /// its checksum, if the archive has checksums.
+    ///
+    /// Each frame has to decompress to exactly its expected length, so that
+    /// the total output matches the uncompressed length of the archive. The
+    /// frame cache is neither used nor filled, so every frame is actually read.
+    /// Use this before trusting archives from untrusted storage.
+    pub fn verify(&self) -> std::io::Result<()> {
+        let num_frames = self.frame_count();
+        let mut decompressor = None;
+        let (mut compressed, mut buf) = (Vec::new(), Vec::new());
+        for idx in 0..num_frames {
+            self.check_cancelled()?;
+            let frame_len = self.archive.frame_len(idx).unwrap_or(0);
+            if frame_len > self.max_scratch_bytes {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "the frame size exceeds the scratch limit",
+                ));
+            }
+            buf.clear();
+            match self.frame(idx, &mut compressed)? {
+                Frame::Compressed(source) => {
+                    let decompressor = self.lazy_zstd_context(&mut decompressor)?;
+                    buf.reserve(frame_len);
+                    let mut destination = zstd::spare_capacity_buf(&mut buf);
+                    decompressor
+                        .decompress_to_buffer(source, &mut destination)
+                        .map_err(|err| self.frame_decode_failed(idx, Some(err)))?;
+                }
+                Frame::Stored(source) => buf.extend_from_slice(source),
+                Frame::Hole(len) => buf.resize(len, 0),
+            }
+            if buf.len() != frame_len {
+                return Err(self.frame_decode_failed(idx, None));
+            }
+            self.verify_frame(idx, &buf)?;
+        }
+        Ok(())
+    }
+
+    /// Decompresses `range`, appending it to `buf`, using `frame_buf` for
+    /// partially covered frames.
+    fn read_into<'o>(
+        &self,
+        buf: &'o mut Vec<u8>,
+        range: Range<usize>,
+        frame_buf: &mut FrameBuf,
+    ) -> std::io::Result<&'o [u8]> {
+        if range.start > range.end || range.end > self.archive.input_len {
+            return Err(self.out_of_bounds(range));
+        }
+        let frame_size = self.frame_size();
+        let start = range.start / frame_size;
+        let end = range.end.div_ceil(frame_size);
+
+        self.check_output_len(range.len())?;
+        let mut decompressor = None;
+
+        let base = buf.len();
+        buf.reserve(range.len());
+        if end - start > 2 {
+            self.prefetch_storage(start..end)?;
+        }
+
+        for (i, frame_idx) in (start..end).enumerate() {
+            self.check_cancelled()?;
+            let is_end = frame_idx == end - 1;
+            if i == 0 || is_end {
+                // stored frames of archives in memory are used directly
+                let decompressed = match self.archive.frame(frame_idx) {
+                    Some(Frame::Stored(source)) => {
+                        self.verify_frame(frame_idx, source)?;
+                        source
+                    }
+                    _ => self.load_frame(&mut decompressor, frame_idx, frame_buf)?,
+                };
+
+                let start = if i == 0 { range.start % frame_size } else { 0 };
+                let remaining = range.len() - (buf.len() - base);
+                let end = (start + remaining).min(decompressed.len());
+                let decompressed = decompressed
+                    .get(start..end)
+                    .ok_or_else(|| self.frame_decode_failed(frame_idx, None))?;
+                buf.extend_from_slice(decompressed);
+            } else {
+                if self
+                    .cache
+                    .get(frame_idx, |cached| buf.extend_from_slice(cached))
+                    .is_some()
+                {
+                    continue;
+                }
+                let frame_start = buf.len();
+                match self.frame(frame_idx, &mut frame_buf.compressed)? {
+                    Frame::Compressed(source) => {
+                        let decompressor = self.lazy_zstd_context(&mut decompressor)?;
+                        self.decompress_source(decompressor, frame_idx, source, buf)?;
+                    }
+                    Frame::Stored(source) => {
+                        buf.extend_from_slice(source);
+                        self.verify_frame(frame_idx, source)?;
+                    }
+                    Frame::Hole(len) => {
+                        buf.resize(buf.len() + len, 0);
+                        self.verify_frame(frame_idx, &buf[frame_start..])?;
+                    }
+                }
+            }
+        }
+
+        Ok(&buf[base..])
+    }
+
+    /// Calls `f` with the [`Decompressor::read_buf`], which is temporarily taken
+    /// out of `self` so that `f` can still borrow `self`.
+    fn with_read_buf<T>(&mut self, f: impl FnOnce(&Self, &mut FrameBuf) -> T) -> T {
+        let mut frame_buf = std::mem::take(&mut self.read_buf);
+        let result = f(self, &mut frame_buf);
+        self.read_buf = frame_buf;
+        result
+    }
+
+    /// Returns the decompressed frame `idx`, reusing the contents of `frame_buf`
+    /// if it already holds that frame.
+    fn load_frame<'s, 'f>(
+        &'s self,
+        decompressor: &mut Option<ZstdContext<'s, 'b>>,
+        idx: usize,
+        frame_buf: &'f mut FrameBuf,
+    ) -> std::io::Result<&'f [u8]> {
+        self.check_cancelled()?;
+        if frame_buf.frame != Some(idx) {
+            // the last frame, and thus the frame of inputs smaller than the frame size, is shorter
+            if self.archive.frame_len(idx).unwrap_or(0) > self.max_scratch_bytes {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "the frame size exceeds the scratch limit",
+                ));
+            }
+            // a failed read leaves the buffer in an unknown state
+            frame_buf.frame = None;
+            let decompressor = self.lazy_zstd_context(decompressor)?;
+            self.decompress_frame(decompressor, idx, frame_buf)?;
+            frame_buf.frame = Some(idx);
+        }
+        Ok(&frame_buf.buf)
+    }
+
+    /// Returns the `zstd` context in `decompressor`, taking it on first use.
+    fn lazy_zstd_context<'s, 'z>(
+        &'s self,
+        decompressor: &'z mut Option<ZstdContext<'s, 'b>>,
+    ) -> std::io::Result<&'z mut ZstdContext<'s, 'b>> {
+        let context = match decompressor.take() {
+            Some(context) => context,
+            None => self.zstd_context()?,
+        };
+        Ok(decompressor.insert(context))
+    }
+
+    /// Takes a `zstd` context out of the shared [`DecompressorPool`], if possible,
+    /// or the own pool otherwise, creating a new one if all of them are in use.
+    fn zstd_context(&self) -> std::io::Result<ZstdContext<'_, 'b>> {
+        match self.pool {
+            Some(pool)
+                if self.dictionary.is_none()
+                    && self.archive.dictionary.is_none()
+                    && self.archive.dictionary_id.is_none() =>
+            {
+                pool.context(&self.archive)
+            }
+            _ => self.zstd.take(|| self.create_zstd()).map(ZstdContext::Own),
+        }
+    }
+
+    /// Creates a `zstd` context for the frames of the archive.
+    fn create_zstd(&self) -> std::io::Result<zstd::Decompressor<'b>> {
+        let raw_dictionary = self
+            .archive
+            .dictionary
+            .or(self.resolved_dictionary.as_deref());
+        let mut decompressor = match (self.dictionary, raw_dictionary, self.archive.dictionary_id) {
+            (Some(dictionary), _, _) => zstd::Decompressor::with_prepared_dictionary(dictionary)?,
+            (None, Some(dictionary), _) => zstd::Decompressor::with_dictionary(dictionary)?,
+            (None, None, Some(id)) => {
+                return Err(Error::DictionaryUnavailable { id, source: None }.into())
+            }
+            (None, None, None) => zstd::Decompressor::new()?,
+        };
+        decompressor.include_magicbytes(self.archive.zstd_frames)?;
+        Ok(decompressor)
+    }
+
+    /// Decompresses the whole frame `idx` into the `buf` of `frame_buf`,
+    /// replacing its contents.
+    fn decompress_frame(
+        &self,
+        decompressor: &mut ZstdContext<'_, '_>,
+        idx: usize,
+        frame_buf: &mut FrameBuf,
+    ) -> std::io::Result<()> {
+        let buf = &mut frame_buf.buf;
+        buf.clear();
+        if self
+            .cache
+            .get(idx, |cached| buf.extend_from_slice(cached))
+            .is_some()
+        {
+            return Ok(());
+        }
+        match self.frame(idx, &mut frame_buf.compressed)? {
+            Frame::Compressed(source) => self.decompress_source(decompressor, idx, source, buf),
+            Frame::Stored(source) => {
+                buf.extend_from_slice(source);
+                self.verify_frame(idx, buf)
+            }
+            Frame::Hole(len) => {
+                buf.resize(len, 0);
+                self.verify_frame(idx, buf)
+            }
+        }
+    }
+
+    /// Decompresses the compressed frame `idx` from `source`, appending it to
+    /// `buf`, and adds it to the frame cache.
+    fn decompress_source(
+        &self,
+        decompressor: &mut ZstdContext<'_, '_>,
+        idx: usize,
+        source: &[u8],
+        buf: &mut Vec<u8>,
+    ) -> std::io::Result<()> {
+        let frame_start = buf.len();
+        // the declared frame size may exceed the actual frame
+        buf.reserve(self.archive.frame_len(idx).unwrap_or(0));
+        let mut destination = zstd::spare_capacity_buf(buf);
+        decompressor
+            .decompress_to_buffer(source, &mut destination)
+            .map_err(|err| self.frame_decode_failed(idx, Some(err)))?;
+        // a frame of the wrong length would shift all of the following output
+        if self.archive.frame_len(idx) != Some(buf.len() - frame_start) {
+            return Err(self.frame_decode_failed(idx, None));
+        }
+        self.verify_frame(idx, &buf[frame_start..])?;
+        self.cache_frame(idx, &buf[frame_start..]);
+        Ok(())
+    }
+
+    /// Returns the compressed frame `idx`, reading it from the storage into
+    /// `buf` if it is not held in memory.
+    fn frame<'s>(&'s self, idx: usize, buf: &'s mut Vec<u8>) -> std::io::Result<Frame<'s>> {
+        let span = self.storage_span(idx)?;
+        let bytes = if span.is_empty() {
+            &[]
+        } else {
+            self.storage.read(span, buf)?
+        };
+        self.archive
+            .frame_from(idx, bytes)
+            .ok_or_else(|| self.frame_decode_failed(idx, None))
+    }
+
+    /// Returns the range of the storage holding the compressed frame `idx`.
+    fn storage_span(&self, idx: usize) -> std::io::Result<Range<u64>> {
+        let span = self
+            .archive
+            .frame_span(idx)
+            .ok_or(Error::InvalidOffsetTable)?;
+        let offset = |pos: usize| self.frames.start.checked_add(pos as u64);
+        match (offset(span.start), offset(span.end)) {
+            (Some(start), Some(end)) if start <= end && end <= self.frames.end => Ok(start..end),
+            (Some(start), Some(end)) if start <= end => Err(self.truncated(idx)),
+            _ => Err(Error::InvalidOffsetTable.into()),
+        }
+    }
+
+    /// Returns the error for frame `idx`, which ends after the compressed frames.
+    ///
+    /// This is a [`Error::TruncatedFrame`] if the offset table expects the frames
+    /// to extend further than they do, or an invalid offset table otherwise.
+    fn truncated(&self, idx: usize) -> std::io::Error {
+        let frames_len = self.frames.end - self.frames.start;
+        let missing = match self.archive.frames_len() {
+            Some(expected) if expected as u64 > frames_len => expected as u64 - frames_len,
+            _ => return Error::InvalidOffsetTable.into(),
+        };
+        // the frames in front of the truncated one are usually complete
+        let mut readable_frames = idx;
+        while readable_frames > 0
+            && self
+                .archive
+                .frame_span(readable_frames - 1)
+                .is_none_or(|span| span.end as u64 > frames_len)
+        {
+            readable_frames -= 1;
+        }
+        let readable_len = readable_frames.saturating_mul(self.frame_size());
+        Error::TruncatedFrame {
+            frame: idx,
+            missing,
+            readable: 0..readable_len.min(self.archive.input_len),
+        }
+        .into()
+    }
+
+    /// Hints the storage to fetch the compressed `frames` that are not cached
+    /// as a batch, ahead of decompressing them one by one.
+    fn prefetch_storage(&self, frames: Range<usize>) -> std::io::Result<()> {
+        let mut spans = Vec::with_capacity(frames.len());
+        for idx in frames {
+            let span = self.storage_span(idx)?;
+            if !span.is_empty() && self.cache.get(idx, |_| ()).is_none() {
+                // frames of the same group share their span
+                if spans.last() != Some(&span) {
+                    spans.push(span);
+                }
+            }
+        }
+        if spans.len() > 1 {
+            self.storage.prefetch(&spans);
+        }
+        Ok(())
+    }
+
+    /// Adds the verified, decompressed frame `idx` to the frame cache, unless it
+    /// has an unexpected length.
+    fn cache_frame(&self, idx: usize, decompressed: &[u8]) {
+        if self.archive.frame_len(idx) == Some(decompressed.len()) {
+            self.cache.insert(idx, decompressed);
+        }
+    }
+
+    /// Returns the error for the frame `idx`, which failed to decode.
+    fn frame_decode_failed(&self, idx: usize, source: Option<std::io::Error>) -> std::io::Error {
+        let compressed = self.storage_span(idx).unwrap_or_default();
+        decode_failed(idx, compressed, source)
+    }
+
+    /// Returns the error for a read of `range`, which is out of bounds.
+    fn out_of_bounds(&self, range: Range<usize>) -> std::io::Error {
+        Error::RangeOutOfBounds {
+            range,
+            len: self.archive.input_len,
+        }
+        .into()
+    }
+
+    /// Verifies the checksum of the uncompressed frame `idx`, if the archive has checksums.
+    fn verify_frame(&self, idx: usize, decompressed: &[u8]) -> std::io::Result<()> {
+        let Some(expected) = self.archive.frame_checksum(idx) else {
+            return Ok(());
+        };
+        match self.archive.checksum.digest(decompressed) {
+            Some(checksum) if checksum.as_bytes() != expected => {
+                Err(Error::ChecksumMismatch { frame: idx }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn eof() -> std::io::Error {
+    std::io::ErrorKind::UnexpectedEof.into()
+}
+
+fn decode_failed(
+    frame: usize,
+    compressed: Range<u64>,
+    source: Option<std::io::Error>,
+) -> std::io::Error {
+    Error::FrameDecodeFailed {
+        frame,
+        compressed,
+        source,
+    }
+    .into()
+}
+
+/// Resolves the bounds of `range` within `len` bytes, failing with
+/// [`Error::RangeOutOfBounds`] if an inclusive bound overflows.
+fn make_range<R>(range: R, len: usize) -> Result<Range<usize>, Error>
+where
+    R: RangeBounds<usize>,
+{
+    use std::ops::Bound::*;
+
+    let out_of_bounds = |start: usize| Error::RangeOutOfBounds {
+        range: start..usize::MAX,
+        len,
+    };
+    let start = match range.start_bound() {
+        Included(b) => *b,
+        Excluded(b) => b.checked_add(1).ok_or_else(|| out_of_bounds(*b))?,
+        Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Included(b) => b.checked_add(1).ok_or_else(|| out_of_bounds(start))?,
+        Excluded(b) => *b,
+        Unbounded => len,
+    };
+
+    Ok(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_compress() {
+        let input: Vec<u8> = (0..32).collect();
+        let compressed = Compressor::new().frame_size(16).compress(&input).unwrap();
+
+        let mut o = Vec::new();
+        let mut d = Decompressor::new(&compressed).unwrap();
+
+        #[allow(clippy::reversed_empty_ranges)]
+        {
+            assert_eq!(d.get_into(&mut o, 3..1).ok(), input.get(3..1));
+        }
+
+        assert_eq!(d.get_into(&mut o, ..0).ok(), input.get(..0));
+        assert_eq!(d.get_into(&mut o, ..).ok(), input.get(..));
+        assert_eq!(d.get_into(&mut o, 0..32).ok(), input.get(0..32));
+
+        assert_eq!(d.get_into(&mut o, ..31).ok(), input.get(..31));
+        assert_eq!(d.get_into(&mut o, 1..).ok(), input.get(1..));
+        assert_eq!(d.get_into(&mut o, 1..31).ok(), input.get(1..31));
+
+        assert_eq!(d.get_into(&mut o, 5..10).ok(), input.get(5..10));
+        assert_eq!(d.get_into(&mut o, 10..20).ok(), input.get(10..20));
+    }
+
+    #[test]
+    fn test_one_shot() {
+        let input: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = compress(&input).unwrap();
+        assert_eq!(compressed, Compressor::new().compress(&input).unwrap());
+        assert_eq!(decompress(&compressed).unwrap(), input);
+
+        assert_eq!(decompress(&compress(&[]).unwrap()).unwrap(), []);
+        let err = decompress(&compressed[..compressed.len() - 10]).unwrap_err();
+        assert!(matches!(
+            err.get_ref().and_then(|err| err.downcast_ref()),
+            Some(Error::TruncatedFrame { .. })
+        ));
+        assert!(decompress(b"SeeZ").is_err());
+    }
+
+    #[test]
+    fn test_compress_to_writer() {
+        let input: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(64).compress(&input).unwrap();
+
+        let mut written = Vec::new();
+        Compressor::new()
+            .frame_size(64)
+            .compress_to_writer(&input, &mut written)
+            .unwrap();
+
+        assert_eq!(written, compressed);
+    }
+
+    #[test]
+    fn test_reuse_compressor() {
+        let input_a: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let input_b: Vec<u8> = (0..500u32).map(|i| (i % 13) as u8).collect();
+
+        let mut compressor = Compressor::new().frame_size(64);
+        let compressed_a = compressor.compress(&input_a).unwrap();
+        let compressed_b = compressor.compress(&input_b).unwrap();
+
+        assert_eq!(
+            compressed_a,
+            Compressor::new().frame_size(64).compress(&input_a).unwrap()
+        );
+        assert_eq!(
+            compressed_b,
+            Compressor::new().frame_size(64).compress(&input_b).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_wide_offsets() {
+        let input: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let narrow = Compressor::new().frame_size(64).compress(&input).unwrap();
+        let wide = Compressor::new()
+            .frame_size(64)
+            .wide_offsets(true)
+            .compress(&input)
+            .unwrap();
+        assert!(wide.len() > narrow.len());
+
+        let mut d = Decompressor::new(&wide).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+        assert_eq!(d.get(100..300).unwrap(), &input[100..300]);
+    }
+
+    #[test]
+    fn test_presets() {
+        let input: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+
+        for preset in [Preset::Fast, Preset::Balanced, Preset::Max] {
+            let compressed = Compressor::new().preset(preset).compress(&input).unwrap();
+
+            let mut d = Decompressor::new(&compressed).unwrap();
+            assert_eq!(d.get(..).unwrap(), input);
+            assert_eq!(d.get(20_000..70_000).unwrap(), &input[20_000..70_000]);
+        }
+    }
+
+    #[test]
+    fn test_auto_frame_size() {
+        assert_eq!(FrameSize::Auto.resolve(Some(0)), AUTO_MIN_FRAME_SIZE);
+        assert_eq!(FrameSize::Auto.resolve(Some(1 << 20)), 4 * (1 << 10));
+        assert_eq!(FrameSize::Auto.resolve(Some(100 << 20)), 512 * (1 << 10));
+        assert_eq!(FrameSize::Auto.resolve(Some(1 << 40)), AUTO_MAX_FRAME_SIZE);
+        assert_eq!(FrameSize::Auto.resolve(None), DEFAULT_FRAME_SIZE);
+
+        let input: Vec<u8> = (0..(1 << 20) as u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new()
+            .frame_size(FrameSize::Auto)
+            .compress(&input)
+            .unwrap();
+
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.frame_size(), 4 * (1 << 10));
+        assert_eq!(d.get(5_000..70_000).unwrap(), &input[5_000..70_000]);
+    }
+
+    #[test]
+    fn test_accessors() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(3000).compress(&input).unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.len(), 10_000);
+        assert!(!d.is_empty());
+        assert_eq!(d.frame_size(), 3000);
+        assert_eq!(d.frame_count(), 4);
+        assert_eq!(d.compressed_len(), compressed.len() as u64);
+        assert_eq!(d.get(d.len() - 10..).unwrap(), &input[9_990..]);
+
+        let mut index = Vec::new();
+        let d = Decompressor::with_storage(compressed.as_slice(), &mut index).unwrap();
+        assert_eq!(d.compressed_len(), compressed.len() as u64);
+        assert_eq!(d.frame_count(), 4);
+
+        let compressed = Compressor::new().compress(&[]).unwrap();
+        let d = Decompressor::new(&compressed).unwrap();
+        assert!(d.is_empty());
+        assert_eq!(d.frame_count(), 0);
+    }
+
+    #[test]
+    fn test_trait_impls() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let mut d = Decompressor::try_from(compressed.as_slice())
+            .unwrap()
+            .frame_cache(1 << 20)
+            .max_output_bytes(5000);
+        assert_eq!(d.get(..5000).unwrap(), &input[..5000]);
+        assert!(matches!(
+            Decompressor::try_from(&compressed[..10]),
+            Err(Error::TruncatedHeader)
+        ));
+
+        let debug = format!("{d:?}");
+        assert!(debug.contains("len: 10000"), "{debug}");
+        assert!(debug.len() < 200, "{debug}");
+
+        // clones share the settings, but not the buffers
+        let mut clone = d.clone();
+        assert_eq!(clone.get(5000..10_000).unwrap(), &input[5000..]);
+        assert!(clone.get(..).is_err());
+        drop(d);
+        assert_eq!(clone.get(1000..2000).unwrap(), &input[1000..2000]);
+
+        let owned = Decompressor::from_owned(compressed.clone()).unwrap();
+        let mut clone = owned.clone();
+        drop(owned);
+        assert_eq!(clone.get(2500..7500).unwrap(), &input[2500..7500]);
+    }
+
+    #[test]
+    fn test_empty_and_small_inputs() {
+        let compressors = || {
+            [
+                Compressor::new(),
+                Compressor::new().frame_size(1000),
+                Compressor::new().frame_size(1000).trailer_index(true),
+                Compressor::new().frame_size(1000).compact_index(true),
+                Compressor::new().frame_size(1000).sparse_index(4),
+                Compressor::new().frame_size(1000).frame_alignment(64),
+                Compressor::new().frame_size(1000).wide_offsets(true),
+                Compressor::new().frame_size(1000).checksum(Checksum::Xxh3),
+                Compressor::new().frame_size(1).holes(true),
+            ]
+        };
+        for input in [vec![], vec![1], vec![7; 999], vec![3; 1000], vec![5; 1001]] {
+            let len = input.len();
+            for (mut compressor, streaming) in compressors().into_iter().zip(compressors()) {
+                let compressed = compressor.compress(&input).unwrap();
+                let mut written = Vec::new();
+                compressor.compress_to_writer(&input, &mut written).unwrap();
+                let mut writer = streaming.writer(Vec::new()).unwrap();
+                std::io::Write::write_all(&mut writer, &input).unwrap();
+                let streamed = writer.finish().unwrap();
+
+                for archive in [&compressed, &written, &streamed] {
+                    let mut d = Decompressor::new_strict(archive).unwrap();
+                    assert_eq!(d.get(..).unwrap(), input);
+                    assert_eq!(d.get_ref(..).unwrap(), input);
+                    assert_eq!(d.get(len..).unwrap(), []);
+                    assert!(d.get(len..len + 1).is_err());
+                    assert_eq!(d.read_at(len, &mut [0; 4]).unwrap(), 0);
+                    d.verify().unwrap();
+
+                    let mut frames = d.frames();
+                    assert_eq!(frames.remaining(), len.div_ceil(d.frame_size()));
+                    let mut frames_len = 0;
+                    while let Some(frame) = frames.next_frame() {
+                        frames_len += frame.unwrap().len();
+                    }
+                    assert_eq!(frames_len, len);
+                    assert_eq!(
+                        d.compressed_frames_for(..).unwrap().len(),
+                        len.div_ceil(d.frame_size())
+                    );
+                }
+            }
+        }
+
+        // only the actual length of the single frame counts towards the scratch limit
+        let compressed = Compressor::new().compress(&[1; 100]).unwrap();
+        let mut d = Decompressor::new(&compressed)
+            .unwrap()
+            .max_scratch_bytes(100);
+        assert!(d.frame_size() > 100);
+        assert_eq!(d.get(10..20).unwrap(), [1; 10]);
+        d.verify().unwrap();
+    }
+
+    #[test]
+    fn test_advanced_params() {
+        let input: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+
+        let compressed = Compressor::new()
+            .level(12)
+            .window_log(16)
+            .search_log(4)
+            .strategy(crate::Strategy::ZSTD_lazy2)
+            .target_length(64)
+            .compress(&input)
+            .unwrap();
+
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+
+        let compressed = Compressor::new()
+            .frame_size(1 << 16)
+            .window_log(16)
+            .long_distance_matching(true)
+            .compress(&input)
+            .unwrap();
+
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+
+        // invalid parameters are reported by `zstd`
+        assert!(Compressor::new().window_log(100).compress(&input).is_err());
+    }
+
+    #[test]
+    fn test_get_to_writer() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .compress(&input)
+            .unwrap();
+        let mut d = Decompressor::new(&compressed)
+            .unwrap()
+            .max_output_bytes(100);
+
+        let mut output = Vec::new();
+        for range in [0..0, 0..1, 999..1001, 2500..7500, 0..10_000, 9_999..10_000] {
+            output.clear();
+            let written = d.get_to_writer(range.clone(), &mut output).unwrap();
+            assert_eq!(written, range.len() as u64);
+            assert_eq!(output, &input[range]);
+        }
+        output.clear();
+        d.get_to_writer(.., &mut output as &mut dyn std::io::Write)
+            .unwrap();
+        assert_eq!(output, input);
+        assert!(d.get_to_writer(9_000..10_001, &mut output).is_err());
+
+        // errors of the writer are passed through
+        let mut full = [0u8; 1500];
+        let err = d.get_to_writer(.., &mut &mut full[..]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::WriteZero);
+        assert_eq!(full, &input[..1500]);
+
+        let mut d = Decompressor::new(&compressed[..compressed.len() - 10]).unwrap();
+        output.clear();
+        assert!(d.get_to_writer(8_500.., &mut output).is_err());
+        assert_eq!(output, &input[8_500..9_000]);
+    }
+
+    #[test]
+    fn test_max_memory() {
+        let input: Vec<u8> = (0..1 << 16)
+            .flat_map(|i: u32| (i.wrapping_mul(2_654_435_761) % 1013).to_le_bytes())
+            .collect();
+        // the window log of the first frame, which is written without a content size
+        let window_log = |compressed: &[u8]| {
+            let d = Decompressor::new(compressed).unwrap();
+            let frame = d.compressed_frames_for(..).unwrap()[0].1.start as usize;
+            10 + (compressed[frame + 1] >> 3)
+        };
+
+        let unlimited = Compressor::new()
+            .level(19)
+            .frame_size(input.len())
+            .compress(&input)
+            .unwrap();
+        assert_eq!(window_log(&unlimited), 18);
+        let mut compressor = Compressor::new()
+            .level(19)
+            .frame_size(input.len())
+            .max_memory(1 << 20);
+        let compressed = compressor.compress(&input).unwrap();
+        assert!(window_log(&compressed) <= 15);
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+
+        // an ample budget does not change anything
+        let mut compressor = compressor.max_memory(usize::MAX);
+        assert_eq!(compressor.compress(&input).unwrap(), unlimited);
+        let (params, size) = zstd::limit_memory(19, &[], usize::MAX);
+        assert!(params.is_empty());
+        let (params, limited_size) = zstd::limit_memory(19, &[], 4 << 20);
+        assert!(limited_size <= 4 << 20 && limited_size < size);
+        assert_eq!(params.len(), 3);
+        // the logs are never reduced below the minimums
+        let (_params, min_size) = zstd::limit_memory(19, &[], 0);
+        assert!(min_size > 0);
+    }
+
+    #[test]
+    fn test_dictionary() {
+        let input: Vec<u8> = (0..10_000u32)
+            .flat_map(|i| format!("line {i}: some repetitive content\n").into_bytes())
+            .collect();
+        let dictionary = &input[..1024];
+
+        let plain = Compressor::new().frame_size(256).compress(&input).unwrap();
+        let compressed = Compressor::new()
+            .frame_size(256)
+            .dictionary(dictionary)
+            .compress(&input)
+            .unwrap();
+        assert!(compressed.len() < plain.len());
+
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+        assert_eq!(d.get(1000..5000).unwrap(), &input[1000..5000]);
+
+        let mut writer = Compressor::new()
+            .frame_size(256)
+            .dictionary(dictionary)
+            .writer(Vec::new())
+            .unwrap();
+        std::io::Write::write_all(&mut writer, &input).unwrap();
+        assert_eq!(writer.finish().unwrap(), compressed);
+    }
+
+    #[test]
+    fn test_dictionary_by_ref() {
+        let input: Vec<u8> = (0..10_000u32)
+            .flat_map(|i| format!("line {i}: some repetitive content\n").into_bytes())
+            .collect();
+        let dictionary = &input[..1024];
+        let encoder_dictionary = EncoderDictionary::copy(dictionary, 3);
+        let decoder_dictionary = DecoderDictionary::copy(dictionary);
+
+        let embedded = Compressor::new()
+            .frame_size(256)
+            .dictionary(dictionary)
+            .compress(&input)
+            .unwrap();
+        let compressed = Compressor::new()
+            .frame_size(256)
+            .dictionary_by_ref(&encoder_dictionary)
+            .compress(&input)
+            .unwrap();
+        assert!(compressed.len() < embedded.len());
+
+        let mut d = Decompressor::with_dictionary_by_ref(&compressed, &decoder_dictionary).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+        assert_eq!(d.get(1000..5000).unwrap(), &input[1000..5000]);
+
+        let mut d = Decompressor::with_dictionary_by_ref(&embedded, &decoder_dictionary).unwrap();
+        assert_eq!(d.get(1000..5000).unwrap(), &input[1000..5000]);
+    }
+
+    #[test]
+    fn test_stored_frames() {
+        // a simple xorshift generator for incompressible data
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let random: Vec<u8> = std::iter::repeat_with(|| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .take(1000)
+        .collect();
+        let mut input = random.clone();
+        input.extend(std::iter::repeat_n(0, 1000));
+        input.extend_from_slice(&random);
+
+        let compressed = Compressor::new().frame_size(100).compress(&input).unwrap();
+        // the header and table, the random parts stored as-is, and the compressed zeroes
+        assert!(compressed.len() < 4 * 33 + 2000 + 200);
+
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+        assert_eq!(d.get(950..2050).unwrap(), &input[950..2050]);
+
+        let wide = Compressor::new()
+            .frame_size(100)
+            .wide_offsets(true)
+            .compress(&input)
+            .unwrap();
+        let mut d = Decompressor::new(&wide).unwrap();
+        assert_eq!(d.get(50..2950).unwrap(), &input[50..2950]);
+    }
+
+    #[test]
+    fn test_adaptive_level() {
+        let text: Vec<u8> = (0..2000u32)
+            .flat_map(|i| format!("line {} of some text\n", i % 37).into_bytes())
+            .collect();
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let random: Vec<u8> = std::iter::repeat_with(|| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .take(10_000)
+        .collect();
+
+        // compressible frames are escalated to the configured level
+        let mut adaptive = Compressor::new()
+            .level(19)
+            .frame_size(1000)
+            .adaptive_level(true);
+        let mut fixed = Compressor::new().level(19).frame_size(1000);
+        assert_eq!(
+            adaptive.compress(&text).unwrap(),
+            fixed.compress(&text).unwrap()
+        );
+
+        let mut input = random;
+        input.extend_from_slice(&text);
+        let compressed = adaptive.compress(&input).unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+        assert_eq!(d.get(9_500..10_500).unwrap(), &input[9_500..10_500]);
+
+        let dictionary = b"line of some text";
+        let compressed = Compressor::new()
+            .level(19)
+            .dictionary(dictionary)
+            .adaptive_level(true)
+            .compress(&input)
+            .unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+    }
+
+    #[cfg(feature = "zstdmt")]
+    #[test]
+    fn test_workers() {
+        let input: Vec<u8> = (0..1_000_000u32)
+            .flat_map(|i| (i % 100_003).to_le_bytes())
+            .collect();
+
+        let compressed = Compressor::new()
+            .frame_size(2 << 20)
+            .workers(2)
+            .compress(&input)
+            .unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+        assert_eq!(
+            d.get(1_000_000..3_000_000).unwrap(),
+            &input[1_000_000..3_000_000]
+        );
+    }
+
+    #[test]
+    fn test_progress() {
+        let input: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut reports = Vec::new();
+        let compressed = Compressor::new()
+            .frame_size(300)
+            .on_progress(|info| {
+                reports.push(info);
+                ControlFlow::Continue(())
+            })
+            .compress(&input)
+            .unwrap();
+        assert_eq!(reports.len(), 4);
+        let last = reports.last().unwrap();
+        assert_eq!(last.frames, 4);
+        assert_eq!(last.total_frames, Some(4));
+        assert_eq!(last.input_bytes, 1000);
+        assert_eq!(last.total_input_bytes, Some(1000));
+        assert!(last.output_bytes < compressed.len() as u64);
+
+        let mut compressor = Compressor::new().frame_size(300).on_progress(|info| {
+            if info.frames == 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert!(matches!(compressor.compress(&input), Err(Error::Cancelled)));
+        let mut out = Vec::new();
+        assert!(matches!(
+            compressor.compress_to_writer(&input, &mut out),
+            Err(Error::Cancelled)
+        ));
+
+        let mut writer = compressor.writer(Vec::new()).unwrap();
+        let err = std::io::Write::write_all(&mut writer, &input).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_compress_with_scratch() {
+        let mut compressor = Compressor::new().frame_size(100);
+        let mut scratch = Scratch::new();
+
+        for len in [1000, 10, 0, 555] {
+            let input: Vec<u8> = (0..len).map(|i| (i % 13) as u8).collect();
+            let expected = compressor.compress(&input).unwrap();
+
+            let compressed = compressor
+                .compress_with_scratch(&mut scratch, &input)
+                .unwrap();
+            assert_eq!(compressed, expected);
+
+            let mut d = Decompressor::new(compressed).unwrap();
+            assert_eq!(d.get(..).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let input: Vec<u8> = (0..300u32)
+            .flat_map(|i| format!("{} ", i % 17).into_bytes())
+            .collect();
+
+        let mut compressor = Compressor::new()
+            .level(5)
+            .frame_size(512)
+            .deterministic(true);
+        let compressed = compressor.compress(&input).unwrap();
+        assert_eq!(compressor.compress(&input).unwrap(), compressed);
+
+        // this pins the output of the bundled `zstd` version
+        assert_eq!(zstd_version(), "1.5.5");
+        let expected = [
+            83, 101, 101, 90, 1, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 208, 2, 0, 0, 0, 0, 0, 0, 55, 0,
+            0, 0, 110, 0, 0, 0, 0, 0, 149, 1, 0, 148, 2, 48, 32, 49, 32, 50, 32, 51, 32, 52, 32,
+            53, 32, 54, 32, 55, 32, 56, 32, 57, 32, 49, 48, 32, 49, 49, 32, 49, 50, 32, 49, 51, 32,
+            49, 52, 32, 49, 53, 32, 49, 54, 32, 1, 0, 161, 102, 170, 42, 3, 0, 0, 149, 1, 0, 148,
+            2, 49, 48, 32, 49, 49, 32, 49, 50, 32, 49, 51, 32, 49, 52, 32, 49, 53, 32, 49, 54, 32,
+            48, 32, 49, 32, 50, 32, 51, 32, 52, 32, 53, 32, 54, 32, 55, 32, 56, 32, 57, 32, 1, 0,
+            33, 49, 74, 149, 1,
+        ];
+        assert_eq!(compressed, expected);
+
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+    }
+
+    #[test]
+    fn test_verify() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let mut zeroes = input.clone();
+        zeroes[3000..6000].fill(0);
+        let error = |d: &Decompressor<'_>| {
+            let err = d.verify().unwrap_err();
+            err.get_ref()
+                .and_then(|err| err.downcast_ref::<Error>())
+                .map(|err| err.to_string())
+        };
+
+        for compressor in [
+            Compressor::new().frame_size(1000),
+            Compressor::new().frame_size(1000).checksum(Checksum::Xxh3),
+            Compressor::new().frame_size(1000).sparse_index(4),
+            Compressor::new().frame_size(1000).holes(true),
+        ]
+        .iter_mut()
+        {
+            let compressed = compressor.compress(&zeroes).unwrap();
+            let d = Decompressor::new(&compressed).unwrap().frame_cache(1 << 20);
+            d.verify().unwrap();
+            // the cache is not filled
+            assert!(d.cache.get(0, |_| ()).is_none());
+        }
+        let empty = Compressor::new().compress(&[]).unwrap();
+        Decompressor::new(&empty).unwrap().verify().unwrap();
+
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .compress(&input)
+            .unwrap();
+        let d = Decompressor::new(&compressed).unwrap();
+        let frame = d.compressed_range_for(4000..5000).unwrap();
+        let frames_start = d.compressed_range_for(0..1).unwrap().start as usize;
+
+        // a frame that does not decompress
+        let mut corrupted = compressed.clone();
+        corrupted[frame.start as usize..frame.end as usize].fill(0xff);
+        let d = Decompressor::new(&corrupted).unwrap();
+        assert!(error(&d).unwrap().starts_with("failed to decode frame 4"));
+
+        // a frame with a wrong checksum, as the checksums follow the offset table
+        let mut corrupted = compressed.clone();
+        let checksums = frames_start - 10 * 4;
+        corrupted[checksums + 4 * 4] ^= 1;
+        let d = Decompressor::new(&corrupted).unwrap();
+        assert_eq!(error(&d).unwrap(), "checksum mismatch in frame 4");
+
+        // the last frame decompresses to more than the declared length
+        let mut corrupted = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        corrupted[16..20].copy_from_slice(&9_999u32.to_le_bytes());
+        let d = Decompressor::new(&corrupted).unwrap();
+        assert!(error(&d).unwrap().starts_with("failed to decode frame 9"));
+
+        let truncated = &compressed[..compressed.len() - 1];
+        let d = Decompressor::new(truncated).unwrap();
+        assert!(error(&d).unwrap().starts_with("frame 9 is truncated"));
+    }
+
+    #[test]
+    fn test_short_frame() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        // frame 4 decodes to a byte less than the frame size
+        let mut frames = Vec::new();
+        let mut table = FrameTable::new();
+        for (idx, chunk) in input.chunks(1000).enumerate() {
+            let chunk = if idx == 4 { &chunk[..999] } else { chunk };
+            let single = Compressor::new().frame_size(1000).compress(chunk).unwrap();
+            let range = Decompressor::new(&single)
+                .unwrap()
+                .compressed_range_for(..)
+                .unwrap();
+            frames.extend_from_slice(&single[range.start as usize..range.end as usize]);
+            let frame = format::FrameInfo {
+                stored: false,
+                checksum: None,
+            };
+            table.push(frames.len() as u64, frame);
+        }
+        let header = format::Header {
+            frame_size: 1000,
+            input_len: 10_000,
+            wide: false,
+            compact: false,
+            group_size: 1,
+            frame_alignment: 1,
+            checksum: Checksum::None,
+            metadata: &[],
+            dictionary: None,
+            dictionary_id: None,
+        };
+        let mut archive = Vec::new();
+        header.write(&mut archive, &table).unwrap();
+        archive.extend_from_slice(&frames);
+
+        let mut d = Decompressor::new(&archive).unwrap().frame_cache(100_000);
+        assert_eq!(d.get(2500..3500).unwrap(), &input[2500..3500]);
+        // as the first, last and a middle frame of a range
+        for range in [4500..5500, 3500..4500, 3500..5500, 0..10_000] {
+            let err = d.get(range).unwrap_err();
+            assert!(matches!(
+                err.get_ref().and_then(|err| err.downcast_ref()),
+                Some(Error::FrameDecodeFailed { frame: 4, .. })
+            ));
+        }
+        assert!(d.verify().is_err());
+        // the frame is not cached either
+        assert!(d.get(3500..5500).is_err());
+    }
+
+    #[test]
+    fn test_new_strict() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressors: [fn() -> Compressor<'static>; 6] = [
+            || Compressor::new().frame_size(1000),
+            || Compressor::new().frame_size(1000).trailer_index(true),
+            || Compressor::new().frame_size(1000).compact_index(true),
+            || Compressor::new().frame_size(1000).sparse_index(3),
+            || Compressor::new().frame_size(1000).frame_alignment(64),
+            || {
+                Compressor::new()
+                    .frame_size(1000)
+                    .trailer_index(true)
+                    .frame_alignment(64)
+            },
+        ];
+        for compressor in compressors {
+            let compressed = compressor().compress(&input).unwrap();
+            let mut d = Decompressor::new_strict(&compressed).unwrap();
+            assert_eq!(d.get(..).unwrap(), input);
+            let empty = compressor().compress(&[]).unwrap();
+            assert!(Decompressor::new_strict(&empty).is_ok());
+        }
+        let seekable = Compressor::new()
+            .frame_size(1000)
+            .compress_seekable(&input)
+            .unwrap();
+        assert!(Decompressor::new_strict(&seekable).is_ok());
+
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let frames = Decompressor::new(&compressed).unwrap().frames.clone();
+        let frames_len = frames.end - frames.start;
+        // a truncated archive opens fine, and only fails to read the missing frames
+        let truncated = &compressed[..compressed.len() - 10];
+        let mut d = Decompressor::new(truncated).unwrap();
+        assert_eq!(d.get(..9000).unwrap(), &input[..9000]);
+        let err = d.get(9000..).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert_eq!(
+            err.to_string(),
+            "frame 9 is truncated, missing 10 bytes, only 0..9000 is readable"
+        );
+        assert!(matches!(
+            Decompressor::new_strict(truncated),
+            Err(Error::LengthMismatch { expected, actual })
+                if expected == frames_len && actual == frames_len - 10
+        ));
+
+        // the error reports the complete frames in front of the truncated ones
+        let frame = Decompressor::new(&compressed)
+            .unwrap()
+            .compressed_range_for(7000..8000)
+            .unwrap();
+        let truncated = &compressed[..frame.start as usize + 1];
+        let missing = compressed.len() as u64 - frame.start - 1;
+        let mut index = Vec::new();
+        let mut d = Decompressor::with_storage(truncated, &mut index).unwrap();
+        assert_eq!(d.get(6000..7000).unwrap(), &input[6000..7000]);
+        let err = d.get(6500..9500).unwrap_err();
+        assert!(matches!(
+            err.get_ref().and_then(|err| err.downcast_ref()),
+            Some(Error::TruncatedFrame { frame: 7, missing: m, readable })
+                if *m == missing && *readable == (0..7000)
+        ));
+        let err = d.get(9500..).unwrap_err();
+        assert!(matches!(
+            err.get_ref().and_then(|err| err.downcast_ref()),
+            Some(Error::TruncatedFrame { frame: 9, readable, .. }) if *readable == (0..7000)
+        ));
+
+        let mut trailing = compressed.clone();
+        trailing.extend_from_slice(b"garbage");
+        assert!(Decompressor::new(&trailing).is_ok());
+        let err = Decompressor::new_strict(&trailing).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::LengthMismatch { expected, actual }
+                if expected == frames_len && actual == frames_len + 7
+        ));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "the compressed frames span {} bytes instead of {frames_len} bytes",
+                frames_len + 7
+            )
+        );
+    }
+
+    #[test]
+    fn test_versioned_format() {
+        let input: Vec<u8> = (0..1000u32).map(|i| (i % 7) as u8).collect();
+        let compressed = Compressor::new().frame_size(100).compress(&input).unwrap();
+        assert_eq!(&compressed[..4], b"SeeZ");
+
+        // archives without magic, version and flags are still supported
+        assert_eq!(&compressed[8..12], &[0; 4]);
+        let legacy = &compressed[12..];
+        let mut d = Decompressor::new(legacy).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+
+        let mut unknown_version = compressed.clone();
+        unknown_version[4..8].copy_from_slice(&2u32.to_le_bytes());
+        assert!(matches!(
+            Decompressor::new(&unknown_version),
+            Err(Error::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn test_checksums() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut input: Vec<u8> = std::iter::repeat_with(|| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .take(500)
+        .collect();
+        input.extend((0..500u32).map(|i| (i % 7) as u8));
+
+        let without = Compressor::new().frame_size(100).compress(&input).unwrap();
+
+        for (checksum, size) in [
+            (Checksum::Crc32, 4),
+            (Checksum::Xxh3, 8),
+            (Checksum::Blake3, 32),
+        ] {
+            let mut compressor = Compressor::new().frame_size(100).checksum(checksum);
+            let compressed = compressor.compress(&input).unwrap();
+            assert_eq!(compressed.len(), without.len() + 8 + 10 * size);
+
+            let mut writer = compressor.writer(Vec::new()).unwrap();
+            std::io::Write::write_all(&mut writer, &input).unwrap();
+            assert_eq!(writer.finish().unwrap(), compressed);
+
+            let mut d = Decompressor::new(&compressed).unwrap();
+            assert_eq!(d.get(..).unwrap(), input);
+            assert_eq!(d.get(250..750).unwrap(), &input[250..750]);
+
+            // flip a bit in the first frame, which is stored uncompressed
+            let mut corrupted = compressed.clone();
+            // magic, version, frame size, input length, 11 offsets, the checksum
+            // algorithm and 10 checksums
+            let header_size = 8 + 13 * 4 + 4 + 10 * size;
+            corrupted[header_size + 10] ^= 1;
+            let mut d = Decompressor::new(&corrupted).unwrap();
+            let err = d.get(10..20).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            assert_eq!(err.to_string(), "checksum mismatch in frame 0");
+            assert_eq!(d.get(100..).unwrap(), &input[100..]);
+        }
+    }
+
+    #[test]
+    fn test_typed_errors() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        fn error(err: &std::io::Error) -> Option<&Error> {
+            err.get_ref().and_then(|err| err.downcast_ref())
+        }
+
+        let mut d = Decompressor::new(&compressed).unwrap();
+        let err = d.get(5000..10_001).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert!(matches!(
+            error(&err),
+            Some(Error::RangeOutOfBounds { range, len: 10_000 }) if *range == (5000..10_001)
+        ));
+        assert_eq!(
+            err.to_string(),
+            "range 5000..10001 is out of bounds of 10000 bytes"
+        );
+        let err = d.get_many(&[0..10, 9_990..10_010]).unwrap_err();
+        assert!(matches!(
+            error(&err),
+            Some(Error::RangeOutOfBounds { range, .. }) if *range == (9_990..10_010)
+        ));
+
+        // point the end of frame 5 past the compressed frames:
+        // magic, version, flags, frame size, input length, 6 offsets
+        let mut corrupted = compressed.clone();
+        corrupted[20 + 6 * 4..20 + 7 * 4].copy_from_slice(&0xff_ffffu32.to_le_bytes());
+        let mut d = Decompressor::new(&corrupted).unwrap();
+        let err = d.get(5500..5600).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(matches!(error(&err), Some(Error::InvalidOffsetTable)));
+        assert_eq!(d.get(..1000).unwrap(), &input[..1000]);
+
+        // overwrite frame 3 with garbage
+        let frame = Decompressor::new(&compressed)
+            .unwrap()
+            .compressed_range_for(3000..4000)
+            .unwrap();
+        let mut corrupted = compressed.clone();
+        corrupted[frame.start as usize..frame.end as usize].fill(0xff);
+        let mut d = Decompressor::new(&corrupted).unwrap();
+        for err in [
+            d.get(2500..3500).unwrap_err(),
+            d.get(..).unwrap_err(),
+            d.get_to_slice(3000..4000, &mut [0; 1000]).unwrap_err(),
+        ] {
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            let Some(Error::FrameDecodeFailed {
+                frame: 3,
+                compressed,
+                source: Some(_),
+            }) = error(&err)
+            else {
+                panic!("unexpected error {err:?}");
+            };
+            assert_eq!(*compressed, frame);
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "failed to decode frame 3 at compressed bytes {}..{}",
+                    frame.start, frame.end
+                )
+            );
+        }
+        assert_eq!(d.get(4000..).unwrap(), &input[4000..]);
+    }
+
+    #[test]
+    fn test_metadata() {
+        let input: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut compressor = Compressor::new()
+            .frame_size(100)
+            .metadata("schema", "v1")
+            .metadata("tag", vec![1, 2, 3])
+            .metadata("schema", "v2")
+            .dictionary(b"some dictionary");
+        let compressed = compressor.compress(&input).unwrap();
+
+        let mut writer = compressor.writer(Vec::new()).unwrap();
+        std::io::Write::write_all(&mut writer, &input).unwrap();
+        assert_eq!(writer.finish().unwrap(), compressed);
+
+        let mut d = Decompressor::new(&compressed).unwrap();
+        let metadata = d.metadata();
+        assert_eq!(metadata.get("schema"), Some(&b"v2"[..]));
+        assert_eq!(metadata.get("tag"), Some(&[1, 2, 3][..]));
+        assert_eq!(metadata.get("missing"), None);
+        let entries: Vec<_> = metadata.iter().collect();
+        assert_eq!(entries, [("schema", &b"v2"[..]), ("tag", &[1, 2, 3][..])]);
+        assert_eq!(d.get(..).unwrap(), input);
+
+        let compressed = Compressor::new().compress(&input).unwrap();
+        let d = Decompressor::new(&compressed).unwrap();
+        assert!(d.metadata().is_empty());
+    }
+
+    #[test]
+    fn test_trailer_index() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut compressor = Compressor::new()
+            .frame_size(1000)
+            .trailer_index(true)
+            .checksum(Checksum::Crc32)
+            .metadata("schema", "v1")
+            .dictionary(b"some dictionary");
+        let compressed = compressor.compress(&input).unwrap();
+        assert_eq!(&compressed[..4], b"SeeZ");
+        assert_eq!(&compressed[compressed.len() - 8..][..4], b"SeeZ");
+
+        let mut written = Vec::new();
+        compressor.compress_to_writer(&input, &mut written).unwrap();
+        assert_eq!(written, compressed);
+
+        let mut writer = compressor.writer(Vec::new()).unwrap();
+        for chunk in input.chunks(777) {
+            std::io::Write::write_all(&mut writer, chunk).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), compressed);
+
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.frame_size(), 1000);
+        assert_eq!(d.metadata().get("schema"), Some(&b"v1"[..]));
+        assert_eq!(d.get(..).unwrap(), input);
+        assert_eq!(d.get(1234..5678).unwrap(), &input[1234..5678]);
+
+        // the index is located at the end, so a truncated archive is rejected
+        assert!(matches!(
+            Decompressor::new(&compressed[..compressed.len() - 1]),
+            Err(Error::InvalidFooter)
+        ));
+
+        let mut compressor = Compressor::new().trailer_index(true);
+        let compressed = compressor.compress(&[]).unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(..).unwrap(), &[]);
+        let mut written = Vec::new();
+        compressor.compress_to_writer(&[], &mut written).unwrap();
+        assert_eq!(written, compressed);
+    }
+
+    #[test]
+    fn test_compact_index() {
+        let input: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let plain = Compressor::new().frame_size(64).compress(&input).unwrap();
+
+        for trailer_index in [false, true] {
+            let mut compressor = Compressor::new()
+                .frame_size(64)
+                .compact_index(true)
+                .trailer_index(trailer_index)
+                .checksum(Checksum::Crc32)
+                .dictionary(b"some dictionary");
+            let compressed = compressor.compress(&input).unwrap();
+
+            let mut written = Vec::new();
+            compressor.compress_to_writer(&input, &mut written).unwrap();
+            assert_eq!(written, compressed);
+
+            let mut writer = compressor.writer(Vec::new()).unwrap();
+            std::io::Write::write_all(&mut writer, &input).unwrap();
+            assert_eq!(writer.finish().unwrap(), compressed);
+
+            let mut d = Decompressor::new(&compressed).unwrap();
+            assert_eq!(d.get(..).unwrap(), input);
+            assert_eq!(d.get(12_345..23_456).unwrap(), &input[12_345..23_456]);
+        }
+
+        let mut compressor = Compressor::new().frame_size(64).compact_index(true);
+        let compressed = compressor.compress(&input).unwrap();
+        // each of the 1563 frames needs 2 bytes instead of 4
+        assert!(plain.len() - compressed.len() > 3000);
+
+        let compressed = Compressor::new().compact_index(true).compress(&[]).unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(..).unwrap(), &[]);
+    }
+
+    #[test]
+    fn test_sparse_index() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let random: Vec<u8> = std::iter::repeat_with(|| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .take(1000)
+        .collect();
+        let mut input = random.clone();
+        input.extend((0..10_000u32).map(|i| (i % 13) as u8));
+        input.extend_from_slice(&random);
+
+        for (compact_index, trailer_index, wide_offsets) in [
+            (false, false, false),
+            (false, false, true),
+            (true, false, false),
+            (false, true, true),
+        ] {
+            let mut compressor = Compressor::new()
+                .frame_size(100)
+                .sparse_index(16)
+                .compact_index(compact_index)
+                .trailer_index(trailer_index)
+                .wide_offsets(wide_offsets)
+                .checksum(Checksum::Crc32);
+            let compressed = compressor.compress(&input).unwrap();
+
+            let mut written = Vec::new();
+            compressor.compress_to_writer(&input, &mut written).unwrap();
+            assert_eq!(written, compressed);
+
+            let mut writer = compressor.writer(Vec::new()).unwrap();
+            std::io::Write::write_all(&mut writer, &input).unwrap();
+            assert_eq!(writer.finish().unwrap(), compressed);
+
+            let mut d = Decompressor::new(&compressed).unwrap();
+            assert_eq!(d.get(..).unwrap(), input);
+            for range in [0..1, 950..2050, 1550..1650, 11_999..12_000] {
+                assert_eq!(d.get(range.clone()).unwrap(), &input[range]);
+            }
+        }
+
+        // the 100 compressible frames only need 8 offsets instead of 101
+        let input = &input[1000..11_000];
+        let plain = Compressor::new().frame_size(100).compress(input).unwrap();
+        let sparse = Compressor::new()
+            .frame_size(100)
+            .sparse_index(16)
+            .compress(input)
+            .unwrap();
+        assert_eq!(plain.len() - sparse.len(), (101 - 8 - 1) * 4);
+
+        // incompressible frames are not stored as-is, so they take more space than the input
+        let sparse = Compressor::new()
+            .frame_size(100)
+            .sparse_index(4)
+            .compress(&random)
+            .unwrap();
+        let mut d = Decompressor::new(&sparse).unwrap();
+        let frames = d.compressed_frames_for(..).unwrap();
+        assert!(frames[9].1.end - frames[0].1.start > random.len() as u64);
+        assert_eq!(d.get(..).unwrap(), random);
+    }
+
+    #[test]
+    fn test_frame_alignment() {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut input: Vec<u8> = std::iter::repeat_with(|| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .take(3000)
+        .collect();
+        input.extend((0..20_000u32).map(|i| (i % 13) as u8));
+
+        for (alignment, trailer_index, compact_index, sparse_index) in [
+            (512, false, false, 1),
+            (4096, false, false, 1),
+            (100, true, false, 1),
+            (512, false, true, 1),
+            (512, true, false, 4),
+        ] {
+            let mut compressor = Compressor::new()
+                .frame_size(1000)
+                .frame_alignment(alignment)
+                .trailer_index(trailer_index)
+                .compact_index(compact_index)
+                .sparse_index(sparse_index)
+                .checksum(Checksum::Crc32)
+                .dictionary(b"some dictionary");
+            let compressed = compressor.compress(&input).unwrap();
+
+            let mut written = Vec::new();
+            compressor.compress_to_writer(&input, &mut written).unwrap();
+            assert_eq!(written, compressed);
+
+            #[cfg(feature = "rayon")]
+            let mut compressor = compressor.parallel(true);
+            #[cfg(feature = "rayon")]
+            assert_eq!(compressor.compress(&input).unwrap(), compressed);
+
+            let mut writer = compressor.writer(Vec::new()).unwrap();
+            std::io::Write::write_all(&mut writer, &input).unwrap();
+            assert_eq!(writer.finish().unwrap(), compressed);
+
+            let mut d = Decompressor::new(&compressed).unwrap();
+            for idx in 0..23 {
+                let (Frame::Compressed(frame) | Frame::Stored(frame)) =
+                    d.archive.frame(idx).unwrap()
+                else {
+                    unreachable!();
+                };
+                let offset = frame.as_ptr() as usize - compressed.as_ptr() as usize;
+                assert_eq!(offset % alignment, 0);
+            }
+            assert!(matches!(d.archive.frame(0), Some(Frame::Stored(_))) || sparse_index > 1);
+            assert_eq!(d.get(..).unwrap(), input);
+            assert_eq!(d.get(2500..7500).unwrap(), &input[2500..7500]);
+        }
+    }
+
+    #[test]
+    fn test_padded_offsets() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        for (compact_index, sparse_index) in [(false, 1), (true, 1), (false, 3)] {
+            let mut compressor = Compressor::new()
+                .frame_size(100)
+                .frame_alignment(4096)
+                .compact_index(compact_index)
+                .sparse_index(sparse_index);
+            let compressed = compressor.compress(&input).unwrap();
+            let mut written = Vec::new();
+            compressor.compress_to_writer(&input, &mut written).unwrap();
+            assert_eq!(written, compressed);
+
+            // the padded frames take a lot more space than the input
+            let mut d = Decompressor::new(&compressed).unwrap();
+            let frames = d.compressed_frames_for(..).unwrap();
+            assert_eq!(frames.len(), 100);
+            assert!(frames[99].1.end - frames[0].1.start > input.len() as u64);
+            for (idx, (frame, range)) in frames.iter().enumerate() {
+                assert_eq!(*frame, idx);
+                assert_eq!(range.start % 4096, 0);
+                assert!(range.end <= compressed.len() as u64);
+            }
+            assert_eq!(d.get(..).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn test_to_standard_zstd() {
+        // a simple xorshift generator for incompressible content
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut input: Vec<u8> = std::iter::repeat_with(|| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .take(300_000)
+        .collect();
+        input.resize(400_000, 0);
+        input.extend((0..100_000u32).map(|i| (i % 13) as u8));
+
+        for frame_size in [255, 256, 65_791, 65_792, 200_000] {
+            for holes in [false, true] {
+                let compressed = Compressor::new()
+                    .frame_size(frame_size)
+                    .holes(holes)
+                    .compress(&input)
+                    .unwrap();
+                let d = Decompressor::new(&compressed).unwrap();
+                assert!(matches!(d.archive.frame(0), Some(Frame::Stored(_))));
+                let mut zst = Vec::new();
+                d.to_standard_zstd(&mut zst).unwrap();
+                assert_eq!(::zstd::decode_all(&zst[..]).unwrap(), input);
+            }
+        }
+
+        let compressed = Compressor::new()
+            .sparse_index(4)
+            .compress(&input[..100_000])
+            .unwrap();
+        let mut zst = Vec::new();
+        let d = Decompressor::new(&compressed).unwrap();
+        d.to_standard_zstd(&mut zst).unwrap();
+        assert_eq!(::zstd::decode_all(&zst[..]).unwrap(), &input[..100_000]);
+
+        let compressed = Compressor::new()
+            .compress_seekable(&input[..100_000])
+            .unwrap();
+        let mut zst = Vec::new();
+        let d = Decompressor::new(&compressed).unwrap();
+        d.to_standard_zstd(&mut zst).unwrap();
+        assert_eq!(::zstd::decode_all(&zst[..]).unwrap(), &input[..100_000]);
+
+        let empty = Compressor::new().compress(&[]).unwrap();
+        let mut zst = Vec::new();
+        Decompressor::new(&empty)
+            .unwrap()
+            .to_standard_zstd(&mut zst)
+            .unwrap();
+        assert!(zst.is_empty());
+    }
+
+    #[test]
+    fn test_reframe_zstd() {
+        let input: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let mut zst = ::zstd::encode_all(&input[..60_000], 3).unwrap();
+        zst.extend(::zstd::encode_all(&input[60_000..], 3).unwrap());
+
+        let compressed = reframe_zstd(&zst[..], 1000).unwrap();
+        assert_eq!(
+            compressed,
+            Compressor::new().frame_size(1000).compress(&input).unwrap()
+        );
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(12_345..67_890).unwrap(), &input[12_345..67_890]);
+
+        assert!(reframe_zstd(&zst[..], 0).is_err());
+        assert!(reframe_zstd(&zst[..zst.len() - 10], 1000).is_err());
+    }
+
+    #[test]
+    fn test_read_at() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .compress(&input)
+            .unwrap();
+        let d = Decompressor::new(&compressed).unwrap();
+
+        std::thread::scope(|s| {
+            for thread in 0..4 {
+                let d = &d;
+                let input = &input;
+                s.spawn(move || {
+                    let mut buf = vec![0; 2500];
+                    for offset in (thread * 100..10_000).step_by(700) {
+                        let read = d.read_at(offset, &mut buf).unwrap();
+                        assert_eq!(read, buf.len().min(10_000 - offset));
+                        assert_eq!(buf[..read], input[offset..offset + read]);
+                    }
+                });
+            }
+        });
+
+        let mut buf = [0; 10];
+        assert_eq!(d.read_at(10_000, &mut buf).unwrap(), 0);
+        assert_eq!(d.read_at(20_000, &mut buf).unwrap(), 0);
+        assert_eq!(d.read_at(9_995, &mut buf).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_get_to_slice() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+
+        let mut buf = [0; 5000];
+        for range in [0..0, 0..1000, 999..3001, 5000..10_000, 9_999..10_000] {
+            let len = d.get_to_slice(range.clone(), &mut buf).unwrap();
+            assert_eq!(len, range.len());
+            assert_eq!(buf[..len], input[range]);
+        }
+        assert_eq!(d.get_to_slice(.., &mut [0; 10_000]).unwrap(), 10_000);
+        assert!(d.get_to_slice(.., &mut buf).is_err());
+        assert!(d.get_to_slice(9_000..10_001, &mut buf).is_err());
+    }
+
+    #[test]
+    fn test_get_uninit() {
+        let mut input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        input[3000..5000].fill(0);
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .holes(true)
+            .compress(&input)
+            .unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+
+        let mut buf = Vec::with_capacity(10_000);
+        for range in [0..0, 0..1000, 999..3001, 2500..5500, 9_999..10_000] {
+            let output = d
+                .get_uninit(range.clone(), buf.spare_capacity_mut())
+                .unwrap();
+            assert_eq!(output, &input[range]);
+        }
+        let output = d.get_uninit(.., buf.spare_capacity_mut()).unwrap();
+        assert_eq!(output, input);
+        assert!(d
+            .get_uninit(.., &mut buf.spare_capacity_mut()[..100])
+            .is_err());
+    }
+
+    #[test]
+    fn test_append_into() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+
+        let mut buf = b"header".to_vec();
+        assert_eq!(
+            d.append_into(&mut buf, 999..3001).unwrap(),
+            &input[999..3001]
+        );
+        assert_eq!(d.append_into(&mut buf, 5..5).unwrap(), &[]);
+        assert_eq!(d.append_into(&mut buf, 9_000..).unwrap(), &input[9_000..]);
+
+        let mut expected = b"header".to_vec();
+        expected.extend_from_slice(&input[999..3001]);
+        expected.extend_from_slice(&input[9_000..]);
+        assert_eq!(buf, expected);
+
+        assert!(d.append_into(&mut buf, 9_000..10_001).is_err());
+        assert_eq!(d.get_into(&mut buf, ..10).unwrap(), &input[..10]);
+    }
+
+    #[test]
+    fn test_get_ref() {
+        let mut input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        // an incompressible frame, which is stored uncompressed
+        let mut state = 0x2545_f491u32;
+        for byte in &mut input[5000..6000] {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            *byte = state as u8;
+        }
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .compress(&input)
+            .unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert!(matches!(d.archive.frame(5), Some(Frame::Stored(_))));
+
+        for range in [
+            0..0,
+            0..1000,
+            999..3001,
+            5100..5900,
+            4000..4500,
+            9_999..10_000,
+            0..10_000,
+        ] {
+            assert_eq!(d.get_ref(range.clone()).unwrap(), &input[range]);
+        }
+        assert!(d.get_ref(9_000..10_001).is_err());
+    }
+
+    #[test]
+    fn test_reuse_zstd_context() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let dictionary: Vec<u8> = (0..1000u32).map(|i| (i % 7) as u8).collect();
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .dictionary(&dictionary)
+            .compress(&input)
+            .unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.zstd.len(), 0);
+
+        for range in [0..10, 500..2500, 9_000..10_000] {
+            assert_eq!(d.get(range.clone()).unwrap(), &input[range]);
+            assert_eq!(d.zstd.len(), 1);
+        }
+
+        // a cursor holds on to its context until it is dropped, and concurrent
+        // reads use another one
+        let mut cursor = d.cursor();
+        let mut buf = [0; 10];
+        std::io::Read::read_exact(&mut cursor, &mut buf).unwrap();
+        assert_eq!(d.zstd.len(), 0);
+        assert_eq!(d.read_at(5_000, &mut buf).unwrap(), 10);
+        assert_eq!(buf, input[5_000..5_010]);
+        assert_eq!(d.zstd.len(), 1);
+        drop(cursor);
+        assert_eq!(d.zstd.len(), 2);
+    }
+
+    #[test]
+    fn test_frame_cache() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .compress(&input)
+            .unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap().frame_cache(2500);
+        let is_cached = |d: &Decompressor, idx| d.cache.get(idx, |_| ()).is_some();
+
+        assert_eq!(d.get(500..3500).unwrap(), &input[500..3500]);
+        // only the most recently used frames fit into the budget
+        assert!(!is_cached(&d, 0) && !is_cached(&d, 1));
+        assert!(is_cached(&d, 2) && is_cached(&d, 3));
+
+        // reads of cached frames, and reads through all other paths
+        assert_eq!(d.get(2000..4000).unwrap(), &input[2000..4000]);
+        assert_eq!(d.get_ref(3100..3200).unwrap(), &input[3100..3200]);
+        let mut buf = vec![0; 3000];
+        d.get_to_slice(1000..4000, &mut buf).unwrap();
+        assert_eq!(buf, &input[1000..4000]);
+        assert_eq!(d.read_at(7_500, &mut buf).unwrap(), 2500);
+        assert_eq!(buf[..2500], input[7_500..]);
+        let ranges = [3_000..3_010, 100..5_000];
+        let many = d.get_many(&ranges).unwrap();
+        assert_eq!(many[0], &input[3_000..3_010]);
+        assert_eq!(many[1], &input[100..5_000]);
+        let mut output = Vec::new();
+        std::io::Read::read_to_end(&mut d.cursor(), &mut output).unwrap();
+        assert_eq!(output, input);
+        assert!(is_cached(&d, 8) && is_cached(&d, 9));
+    }
+
+    #[test]
+    fn test_prefetch() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let d = Decompressor::new(&compressed).unwrap();
+        d.prefetch(..).unwrap();
+        assert!(d.prefetch(..10_001).is_err());
+
+        let mut d = Decompressor::new(&compressed).unwrap().frame_cache(10_000);
+        std::thread::scope(|scope| {
+            scope.spawn(|| d.prefetch(2500..4500).unwrap());
+            let mut buf = [0; 100];
+            d.read_at(9_000, &mut buf).unwrap();
+            assert_eq!(buf, input[9_000..9_100]);
+        });
+        for idx in 0..10 {
+            assert_eq!(
+                d.cache.get(idx, |_| ()).is_some(),
+                (2..5).contains(&idx) || idx == 9
+            );
+        }
+        assert_eq!(d.get(2500..4500).unwrap(), &input[2500..4500]);
+    }
+
+    #[test]
+    fn test_compressed_range_for() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        for compressor in [
+            Compressor::new(),
+            Compressor::new().trailer_index(true),
+            Compressor::new().sparse_index(4),
+        ] {
+            let compressed = compressor.frame_size(1000).compress(&input).unwrap();
+            let d = Decompressor::new(&compressed).unwrap();
+
+            let frames = d.compressed_frames_for(1500..3500).unwrap();
+            assert_eq!(
+                frames.iter().map(|(idx, _)| *idx).collect::<Vec<_>>(),
+                [1, 2, 3]
+            );
+            let range = d.compressed_range_for(1500..3500).unwrap();
+            assert_eq!(range.start, frames[0].1.start);
+            assert_eq!(range.end, frames[2].1.end);
+
+            // the index and the returned bytes alone are enough to read the range
+            let all = d.compressed_range_for(..).unwrap();
+            let mut partial = vec![0; compressed.len()];
+            for range in [0..all.start, all.end..compressed.len() as u64, range] {
+                let range = range.start as usize..range.end as usize;
+                partial[range.clone()].copy_from_slice(&compressed[range]);
+            }
+            let mut index = Vec::new();
+            let mut partial = Decompressor::with_storage(partial, &mut index).unwrap();
+            assert_eq!(partial.get(1500..3500).unwrap(), &input[1500..3500]);
+
+            assert_eq!(d.compressed_range_for(100..100).unwrap(), 0..0);
+            assert!(d.compressed_range_for(..10_001).is_err());
+        }
+    }
+
+    #[test]
+    fn test_cancel_when() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let cancelled = AtomicBool::new(false);
+        let mut d = Decompressor::new(&compressed)
+            .unwrap()
+            .cancel_when(|| cancelled.load(Ordering::Relaxed));
+
+        let mut frames = d.frames();
+        assert!(frames.next_frame().unwrap().is_ok());
+        cancelled.store(true, Ordering::Relaxed);
+        assert!(frames.next_frame().unwrap().is_err());
+        assert!(frames.next_frame().is_none());
+        drop(frames);
+
+        let err = d.get(..).unwrap_err();
+        assert!(matches!(
+            err.get_ref().and_then(|err| err.downcast_ref()),
+            Some(Error::Cancelled)
+        ));
+        assert!(d.get_to_slice(.., &mut vec![0; 10_000]).is_err());
+        assert!(d.to_standard_zstd(&mut Vec::new()).is_err());
+        assert!(d.prefetch(..).is_err());
+
+        cancelled.store(false, Ordering::Relaxed);
+        assert_eq!(d.get(..).unwrap(), input);
+    }
+
+    #[test]
+    fn test_memory_limits() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+
+        let mut d = Decompressor::new(&compressed)
+            .unwrap()
+            .max_output_bytes(2000);
+        assert_eq!(d.get(..2000).unwrap(), &input[..2000]);
+        let err = d.get(..2001).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(d.get_many(&[0..1500, 5000..5600]).is_err());
+        assert!(d.get_many(&[0..1000, 5000..6000]).is_ok());
+        // the output of these is provided by the caller
+        let mut buf = vec![0; 5000];
+        d.get_to_slice(..5000, &mut buf).unwrap();
+
+        let mut d = Decompressor::new(&compressed)
+            .unwrap()
+            .max_scratch_bytes(999);
+        let err = d.get(500..600).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        let mut d = Decompressor::new(&compressed)
+            .unwrap()
+            .max_scratch_bytes(1000);
+        assert_eq!(d.get(500..600).unwrap(), &input[500..600]);
+    }
+
+    #[test]
+    fn test_get_with_scratch() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let other = Compressor::new()
+            .frame_size(1000)
+            .compress(&input[5000..])
+            .unwrap();
+        let d = Decompressor::new(&compressed).unwrap();
+        let mut scratch = Scratch::new();
+
+        assert_eq!(
+            d.get_with_scratch(&mut scratch, 500..3500).unwrap(),
+            &input[500..3500]
+        );
+        let (ptr, frame_ptr) = (scratch.buf.as_ptr(), scratch.frame_buf.buf.as_ptr());
+        for start in [0, 1234, 2000, 6500] {
+            let range = start..start + 3000;
+            assert_eq!(
+                d.get_with_scratch(&mut scratch, range.clone()).unwrap(),
+                &input[range]
+            );
+        }
+        // the buffers have not been reallocated
+        assert_eq!(scratch.buf.as_ptr(), ptr);
+        assert_eq!(scratch.frame_buf.buf.as_ptr(), frame_ptr);
+
+        // frames of another archive are not reused
+        let other = Decompressor::new(&other).unwrap();
+        assert_eq!(
+            other.get_with_scratch(&mut scratch, 500..600).unwrap(),
+            &input[5500..5600]
+        );
+        assert_eq!(
+            d.get_with_scratch(&mut scratch, 500..600).unwrap(),
+            &input[500..600]
+        );
+        assert!(d.get_with_scratch(&mut scratch, 9_000..10_001).is_err());
+    }
+
+    #[test]
+    fn test_sequential_reads() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .compress(&input)
+            .unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+
+        // the frame at the end of each read is kept for the next one
+        for start in (0..10_000).step_by(700) {
+            let range = start..(start + 700).min(10_000);
+            assert_eq!(d.get(range.clone()).unwrap(), &input[range.clone()]);
+            assert_eq!(d.read_buf.frame, Some((range.end - 1) / 1000));
+        }
+
+        // all kinds of reads share the same frame
+        assert_eq!(d.get_ref(1200..1300).unwrap(), &input[1200..1300]);
+        assert_eq!(d.read_buf.frame, Some(1));
+        let mut buf = [0; 100];
+        d.get_to_slice(1900..2000, &mut buf).unwrap();
+        assert_eq!(buf, input[1900..2000]);
+        assert_eq!(d.get(1500..1600).unwrap(), &input[1500..1600]);
+        assert_eq!(
+            d.get_many(&[1000..1001, 1999..2001]).unwrap()[1],
+            &input[1999..2001]
+        );
+        assert_eq!(d.read_buf.frame, Some(2));
+        assert_eq!(d.get_ref(2000..2100).unwrap(), &input[2000..2100]);
+    }
+
+    #[test]
+    fn test_get_many() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+
+        let ranges = [
+            9_000..9_010,
+            0..5_000,
+            100..200,
+            4_999..5_001,
+            7..7,
+            150..160,
+            5_500..9_500,
+            9_999..10_000,
+        ];
+        let results = d.get_many(&ranges).unwrap();
+        assert_eq!(results.len(), ranges.len());
+        for (result, range) in results.iter().zip(ranges) {
+            assert_eq!(result, &input[range]);
+        }
+
+        assert!(d.get_many(&[]).unwrap().is_empty());
+        assert!(d.get_many(&[0..10, 9_990..10_001]).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn test_get_many_ranges(ranges in prop::collection::vec((0..1000usize, 0..300usize), 0..20)) {
+            let input: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+            let compressed = Compressor::new().frame_size(64).compress(&input).unwrap();
+            let mut d = Decompressor::new(&compressed).unwrap();
+
+            let ranges: Vec<_> = ranges
+                .into_iter()
+                .map(|(start, len)| start..(start + len).min(1000))
+                .collect();
+            let results = d.get_many(&ranges).unwrap();
+            for (result, range) in results.iter().zip(ranges) {
+                prop_assert_eq!(result, &input[range]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_holes() {
+        let mut input = vec![0; 10_000];
+        input.extend((0..5_000u32).map(|i| (i % 13) as u8));
+        input.resize(30_000, 0);
+        input[25_000] = 1;
+
+        let without = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        for frame_alignment in [1, 64] {
+            let mut compressor = Compressor::new()
+                .frame_size(1000)
+                .holes(true)
+                .frame_alignment(frame_alignment)
+                .checksum(Checksum::Crc32);
+            let compressed = compressor.compress(&input).unwrap();
+            if frame_alignment == 1 {
+                assert!(compressed.len() + 200 < without.len());
+            }
+
+            let mut written = Vec::new();
+            compressor.compress_to_writer(&input, &mut written).unwrap();
+            assert_eq!(written, compressed);
+
+            let mut writer = compressor.writer(Vec::new()).unwrap();
+            std::io::Write::write_all(&mut writer, &input).unwrap();
+            assert_eq!(writer.finish().unwrap(), compressed);
+
+            let mut d = Decompressor::new(&compressed).unwrap();
+            assert!(matches!(d.archive.frame(0), Some(Frame::Hole(1000))));
+            assert!(matches!(d.archive.frame(10), Some(Frame::Compressed(_))));
+            assert!(matches!(d.archive.frame(25), Some(Frame::Compressed(_))));
+            assert_eq!(d.get(..).unwrap(), input);
+            for range in [
+                0..1,
+                500..1500,
+                9_500..10_500,
+                14_999..26_000,
+                29_000..30_000,
+            ] {
+                assert_eq!(d.get(range.clone()).unwrap(), &input[range]);
+            }
+        }
+
+        // the holes only need to be understood by readers if there are any
+        let input: Vec<u8> = (0..5_000u32).map(|i| (i % 13) as u8).collect();
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .holes(true)
+            .compress(&input)
+            .unwrap();
+        assert_eq!(
+            compressed,
+            Compressor::new().frame_size(1000).compress(&input).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(matches!(
+            Compressor::new().try_level(1000),
+            Err(Error::InvalidLevel(1000))
+        ));
+        assert!(matches!(
+            Compressor::new().try_frame_size(0),
+            Err(Error::InvalidFrameSize(0))
+        ));
+        assert!(Compressor::new().try_level(3).is_ok());
+        assert!(Compressor::new().try_frame_size(1).is_ok());
     }
 
     proptest! {
@@ -0,0 +1,86 @@
+use crate::format::Archive;
+
+/// Limits on the sizes declared by an archive, which are checked when opening
+/// it with [`Decompressor::with_limits`](crate::Decompressor::with_limits).
+///
+/// Archives are always rejected if they declare more uncompressed data than their
+/// compressed frames can possibly hold. However, archives with holes can declare
+/// arbitrary amounts of zeroes, and archives with very large frames need large
+/// buffers to read them. These limits reject such archives from untrusted sources
+/// right away, instead of failing with a huge allocation later on.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    max_len: usize,
+    max_frame_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_len: usize::MAX,
+            max_frame_size: usize::MAX,
+        }
+    }
+}
+
+impl Limits {
+    /// Creates limits that accept any archive.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the total uncompressed size of the archive.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Limits the uncompressed size of a single frame.
+    ///
+    /// This is the size of the buffer needed to read partial frames. Archives
+    /// smaller than their frame size only need a buffer of their total size.
+    pub fn max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// Returns `true` if `archive` is within the limits.
+    pub(crate) fn check(&self, archive: &Archive<'_>) -> bool {
+        archive.input_len <= self.max_len
+            && archive.frame_size.min(archive.input_len) <= self.max_frame_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Compressor, Decompressor};
+
+    #[test]
+    fn test_limits() {
+        let zeroes = vec![0; 100_000];
+        let compressed = Compressor::new()
+            .frame_size(10_000)
+            .holes(true)
+            .compress(&zeroes)
+            .unwrap();
+        assert!(compressed.len() < 1000);
+
+        let open = |limits| Decompressor::with_limits(&compressed, limits).is_ok();
+        assert!(open(Limits::new()));
+        assert!(open(Limits::new().max_len(100_000).max_frame_size(10_000)));
+        assert!(!open(Limits::new().max_len(99_999)));
+        assert!(!open(Limits::new().max_frame_size(9_999)));
+        assert!(matches!(
+            Decompressor::with_limits(&compressed, Limits::new().max_len(99_999)),
+            Err(crate::Error::LimitExceeded)
+        ));
+
+        // the frame size is limited by the total size
+        let small = Compressor::new()
+            .frame_size(10_000)
+            .compress(b"hello")
+            .unwrap();
+        assert!(Decompressor::with_limits(&small, Limits::new().max_frame_size(5)).is_ok());
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::{self, Write};
+use std::mem;
+
+use crate::format::u32_field;
+
+/// Application-defined key-value pairs stored in an archive.
+///
+/// The metadata is stored uncompressed in front of the compressed frames, so it
+/// can be read without decompressing anything.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Metadata<'b> {
+    /// The encoded entries, which are known to be valid.
+    entries: &'b [u8],
+}
+
+impl<'b> Metadata<'b> {
+    /// Validates the encoded metadata `entries`.
+    pub(crate) fn parse(entries: &'b [u8]) -> Option<Self> {
+        let mut rest = entries;
+        while !rest.is_empty() {
+            let (key, tail) = read_bytes(rest)?;
+            std::str::from_utf8(key).ok()?;
+            let (_value, tail) = read_bytes(tail)?;
+            rest = tail;
+        }
+        Some(Self { entries })
+    }
+
+    /// Returns the value of the first entry with the given `key`.
+    pub fn get(&self, key: &str) -> Option<&'b [u8]> {
+        self.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// Iterates over all the entries, in the order they were added.
+    pub fn iter(&self) -> MetadataIter<'b> {
+        MetadataIter { rest: self.entries }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<'b> IntoIterator for Metadata<'b> {
+    type Item = (&'b str, &'b [u8]);
+    type IntoIter = MetadataIter<'b>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of [`Metadata`].
+#[derive(Debug, Clone)]
+pub struct MetadataIter<'b> {
+    rest: &'b [u8],
+}
+
+impl<'b> Iterator for MetadataIter<'b> {
+    type Item = (&'b str, &'b [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, tail) = read_bytes(self.rest)?;
+        let (value, tail) = read_bytes(tail)?;
+        self.rest = tail;
+        // the entries were validated when parsing
+        Some((std::str::from_utf8(key).ok()?, value))
+    }
+}
+
+/// Reads a `u32` length prefixed byte string.
+fn read_bytes(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (len, bytes) = bytes.split_first_chunk::<4>()?;
+    let len = u32::from_le_bytes(*len) as usize;
+    if bytes.len() < len {
+        return None;
+    }
+    Some(bytes.split_at(len))
+}
+
+/// The size of the encoded `entries`, without the padding.
+pub(crate) fn encoded_size(entries: &[(String, Vec<u8>)]) -> usize {
+    entries
+        .iter()
+        .map(|(key, value)| 2 * mem::size_of::<u32>() + key.len() + value.len())
+        .sum()
+}
+
+/// Writes the encoded `entries`.
+pub(crate) fn write<W: Write>(writer: &mut W, entries: &[(String, Vec<u8>)]) -> io::Result<()> {
+    for (key, value) in entries {
+        writer.write_all(&u32_field(key.len())?.to_le_bytes())?;
+        writer.write_all(key.as_bytes())?;
+        writer.write_all(&u32_field(value.len())?.to_le_bytes())?;
+        writer.write_all(value)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::format::Archive;
+use crate::{Decompressor, Storage};
+
+/// A read-only memory map of a whole file.
+struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// SAFETY: the map is read-only and never changes its address
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    fn map(file: &File) -> io::Result<Self> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large to map"))?;
+        if len == 0 {
+            // empty maps are not supported
+            return Ok(Self {
+                ptr: std::ptr::NonNull::dangling().as_ptr(),
+                len,
+            });
+        }
+        // SAFETY: this maps a new region, which does not alias any existing memory
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { ptr, len })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        // SAFETY: the region is mapped for as long as `self` lives
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            // SAFETY: the region was mapped in `Mmap::map`
+            unsafe { libc::munmap(self.ptr, self.len) };
+        }
+    }
+}
+
+impl Storage for Mmap {
+    fn len(&self) -> u64 {
+        self.len as u64
+    }
+
+    fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+        self.as_slice().read(range, buf)
+    }
+}
+
+impl Decompressor<'static> {
+    /// Opens the archive in the file at `path` by memory mapping it.
+    ///
+    /// The returned [`Decompressor`] owns the memory map, so it is not tied to
+    /// the lifetime of a separately held buffer. Only the pages of the frames
+    /// that are actually read are loaded from disk. The file must not be modified
+    /// while it is open, which would change the contents of the archive underneath.
+    ///
+    /// This is only available on Unix with the `mmap` feature.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let map = Mmap::map(&File::open(path)?)?;
+        // SAFETY: the map is owned by the decompressor, and stays at the same
+        // address until it and all of its clones are dropped. The decompressor never
+        // hands out references to the archive that outlive itself.
+        let bytes: &'static [u8] =
+            unsafe { std::slice::from_raw_parts(map.ptr as *const u8, map.len) };
+        let archive = Archive::parse(bytes)?;
+        let mut decompressor = Self::from_archive(bytes, archive);
+        decompressor.storage = Arc::new(map);
+        Ok(decompressor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Checksum, Compressor};
+
+    #[test]
+    fn test_open() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .metadata("key", "value")
+            .compress(&input)
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("seezee-test-open-{}", std::process::id()));
+        std::fs::write(&path, &compressed).unwrap();
+
+        let mut d = Decompressor::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(d.metadata().get("key"), Some(&b"value"[..]));
+        assert_eq!(d.get(..).unwrap(), input);
+        assert_eq!(d.get_ref(1500..1600).unwrap(), &input[1500..1600]);
+        // the decompressor can be moved freely along with its map
+        let d = std::thread::spawn(move || d.get(5000..7000).unwrap());
+        assert_eq!(d.join().unwrap(), &input[5000..7000]);
+
+        let empty = std::env::temp_dir().join(format!("seezee-test-empty-{}", std::process::id()));
+        std::fs::write(&empty, []).unwrap();
+        let err = Decompressor::open(&empty).unwrap_err();
+        std::fs::remove_file(&empty).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(Decompressor::open(&empty).is_err());
+    }
+}
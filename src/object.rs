@@ -0,0 +1,294 @@
+use std::io;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Duration;
+
+use object_store::path::Path;
+use object_store::{GetOptions, ObjectStore};
+
+use crate::{eof, AsyncStorage};
+
+/// [`AsyncStorage`] reading an archive from an [`ObjectStore`], like S3, GCS,
+/// or Azure Blob Storage, with ranged GET requests.
+///
+/// Opening the object only requests its size. Each read is then a GET request
+/// of its range, so an [`AsyncDecompressor`](crate::AsyncDecompressor) only
+/// downloads the index and the compressed frames of the ranges it reads.
+///
+/// Failed requests are retried up to [`max_retries`](Self::max_retries) times,
+/// waiting for the [`backoff`](Self::backoff) in between, which doubles after
+/// each attempt, like with [`RetryStorage`](crate::RetryStorage). Only generic
+/// errors of the store are retried, which cover network errors and server errors.
+/// A missing object, denied permissions, and other errors are returned right
+/// away. The backoff uses the timer of `tokio`, which the remote stores of
+/// `object_store` run on anyway.
+///
+/// This is only available with the `object_store` feature.
+#[derive(Debug)]
+pub struct ObjectStorage {
+    store: Arc<dyn ObjectStore>,
+    location: Path,
+    len: u64,
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl ObjectStorage {
+    /// Opens the object at `location` in `store`, requesting its size, and
+    /// retrying up to 3 times, with a backoff starting at 100ms.
+    pub async fn open(store: Arc<dyn ObjectStore>, location: Path) -> io::Result<Self> {
+        let mut storage = Self {
+            store,
+            location,
+            len: 0,
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        };
+        let options = GetOptions::new().with_head(true);
+        storage.len = storage.get(options).await?.meta.size;
+        Ok(storage)
+    }
+
+    /// Sets the number of retries after the first failed request.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the time to wait before the first retry, which doubles for each
+    /// further retry up to `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Sends a GET request for the object, retrying generic errors.
+    async fn get(&self, options: GetOptions) -> io::Result<object_store::GetResult> {
+        let mut backoff = self.initial_backoff;
+        let mut retries = 0;
+        loop {
+            match self.store.get_opts(&self.location, options.clone()).await {
+                Ok(result) => return Ok(result),
+                Err(object_store::Error::Generic { .. }) if retries < self.max_retries => {}
+                Err(err) => return Err(into_io(err)),
+            }
+            retries += 1;
+            if !backoff.is_zero() {
+                tokio::time::sleep(backoff).await;
+            }
+            backoff = (backoff * 2).min(self.max_backoff);
+        }
+    }
+}
+
+impl AsyncStorage for ObjectStorage {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    async fn read(&self, range: Range<u64>) -> io::Result<Vec<u8>> {
+        if range.start > range.end || range.end > self.len {
+            return Err(eof());
+        }
+        // stores reject empty ranges
+        if range.is_empty() {
+            return Ok(Vec::new());
+        }
+        let result = self.get(GetOptions::new().with_range(Some(range))).await?;
+        // the body is only read after the response, so its errors are not retried
+        let bytes = result.bytes().await.map_err(into_io)?;
+        Ok(bytes.into())
+    }
+}
+
+fn into_io(err: object_store::Error) -> io::Error {
+    let kind = match err {
+        object_store::Error::NotFound { .. } => io::ErrorKind::NotFound,
+        object_store::Error::PermissionDenied { .. }
+        | object_store::Error::Unauthenticated { .. } => io::ErrorKind::PermissionDenied,
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use futures_util::stream::BoxStream;
+    use object_store::memory::InMemory;
+    use object_store::{
+        CopyOptions, GetResult, ListResult, MultipartUpload, ObjectMeta, ObjectStoreExt,
+        PutMultipartOptions, PutOptions, PutPayload, PutResult,
+    };
+
+    use super::*;
+    use crate::async_io::tests::block_on;
+    use crate::{AsyncDecompressor, Compressor};
+
+    /// Store failing every GET request for the given number of attempts.
+    #[derive(Debug)]
+    struct Flaky {
+        store: InMemory,
+        failures: u32,
+        attempts: AtomicU32,
+    }
+
+    impl std::fmt::Display for Flaky {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "Flaky({})", self.store)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl ObjectStore for Flaky {
+        async fn put_opts(
+            &self,
+            location: &Path,
+            payload: PutPayload,
+            opts: PutOptions,
+        ) -> object_store::Result<PutResult> {
+            self.store.put_opts(location, payload, opts).await
+        }
+
+        async fn put_multipart_opts(
+            &self,
+            location: &Path,
+            opts: PutMultipartOptions,
+        ) -> object_store::Result<Box<dyn MultipartUpload>> {
+            self.store.put_multipart_opts(location, opts).await
+        }
+
+        async fn get_opts(
+            &self,
+            location: &Path,
+            options: GetOptions,
+        ) -> object_store::Result<GetResult> {
+            let attempt = self.attempts.fetch_add(1, Ordering::Relaxed);
+            if attempt % (self.failures + 1) < self.failures {
+                let source = io::Error::from(io::ErrorKind::ConnectionReset);
+                return Err(object_store::Error::Generic {
+                    store: "Flaky",
+                    source: source.into(),
+                });
+            }
+            self.store.get_opts(location, options).await
+        }
+
+        fn delete_stream(
+            &self,
+            locations: BoxStream<'static, object_store::Result<Path>>,
+        ) -> BoxStream<'static, object_store::Result<Path>> {
+            self.store.delete_stream(locations)
+        }
+
+        fn list(
+            &self,
+            prefix: Option<&Path>,
+        ) -> BoxStream<'static, object_store::Result<ObjectMeta>> {
+            self.store.list(prefix)
+        }
+
+        async fn list_with_delimiter(
+            &self,
+            prefix: Option<&Path>,
+        ) -> object_store::Result<ListResult> {
+            self.store.list_with_delimiter(prefix).await
+        }
+
+        async fn copy_opts(
+            &self,
+            from: &Path,
+            to: &Path,
+            options: CopyOptions,
+        ) -> object_store::Result<()> {
+            self.store.copy_opts(from, to, options).await
+        }
+    }
+
+    #[test]
+    fn test_object_storage() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let location = Path::from("archives/test.sz");
+        let flaky = |failures| {
+            let store = InMemory::new();
+            block_on(store.put(&location, compressed.clone().into())).unwrap();
+            Arc::new(Flaky {
+                store,
+                failures,
+                // the request of the size succeeds, which retries with the default backoff
+                attempts: AtomicU32::new(failures),
+            })
+        };
+        let open = |store: Arc<Flaky>| {
+            let storage = block_on(ObjectStorage::open(store, location.clone()))?;
+            Ok::<_, io::Error>(storage.backoff(Duration::ZERO, Duration::ZERO))
+        };
+
+        let storage = open(flaky(2)).unwrap();
+        assert_eq!(storage.len(), compressed.len() as u64);
+        assert_eq!(block_on(storage.read(10..20)).unwrap(), &compressed[10..20]);
+        assert_eq!(block_on(storage.read(20..20)).unwrap(), b"");
+        let end = compressed.len() as u64;
+        let err = block_on(storage.read(end - 3..end + 1)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut index = Vec::new();
+        let mut d = block_on(AsyncDecompressor::new(&storage, &mut index)).unwrap();
+        assert_eq!(block_on(d.get(2500..7500)).unwrap(), &input[2500..7500]);
+        drop(d);
+
+        // giving up after the last retry
+        let storage = open(flaky(4)).unwrap().max_retries(2);
+        let err = block_on(storage.read(10..20)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert!(matches!(
+            err.get_ref().and_then(|err| err.downcast_ref()),
+            Some(object_store::Error::Generic { .. })
+        ));
+        assert_eq!(block_on(storage.read(10..20)).unwrap(), &compressed[10..20]);
+
+        // missing objects are not retried
+        let store = flaky(0);
+        let missing = block_on(ObjectStorage::open(
+            Arc::clone(&store) as _,
+            Path::from("missing"),
+        ));
+        assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert_eq!(store.attempts.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_object_storage_backoff() {
+        let compressed = Compressor::new().compress(b"hello").unwrap();
+        let store = InMemory::new();
+        let location = Path::from("test.sz");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            store
+                .put(&location, compressed.clone().into())
+                .await
+                .unwrap();
+            let store = Arc::new(Flaky {
+                store,
+                failures: 2,
+                attempts: AtomicU32::new(0),
+            });
+            let start = std::time::Instant::now();
+            let storage = ObjectStorage::open(store, location)
+                .await
+                .unwrap()
+                .backoff(Duration::from_millis(10), Duration::from_millis(15));
+            // the open waited for 100ms and 200ms, the read for 10ms and 15ms
+            assert_eq!(storage.read(0..5).await.unwrap(), &compressed[..5]);
+            assert!(start.elapsed() >= Duration::from_millis(325));
+        });
+    }
+}
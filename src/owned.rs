@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use crate::{Decompressor, Error, Storage};
+
+/// A [`Decompressor`] owning the bytes of its archive.
+///
+/// This can be moved across threads and stored in long-lived structs, without
+/// being tied to the lifetime of a separately held buffer.
+pub type OwnedDecompressor = Decompressor<'static>;
+
+/// Owned bytes of an archive, which can be opened by [`Decompressor::from_owned`].
+///
+/// This is implemented for [`Vec<u8>`], [`Box<[u8]>`](Box) and [`Arc<[u8]>`](Arc),
+/// whose bytes stay at the same address when they are moved.
+pub trait OwnedBytes: Storage + sealed::Sealed + 'static {}
+
+impl OwnedBytes for Vec<u8> {}
+impl OwnedBytes for Box<[u8]> {}
+impl OwnedBytes for Arc<[u8]> {}
+
+mod sealed {
+    use std::sync::Arc;
+
+    pub trait Sealed {
+        fn as_bytes(&self) -> &[u8];
+    }
+
+    impl Sealed for Vec<u8> {
+        fn as_bytes(&self) -> &[u8] {
+            self
+        }
+    }
+
+    impl Sealed for Box<[u8]> {
+        fn as_bytes(&self) -> &[u8] {
+            self
+        }
+    }
+
+    impl Sealed for Arc<[u8]> {
+        fn as_bytes(&self) -> &[u8] {
+            self
+        }
+    }
+}
+
+impl Decompressor<'static> {
+    /// Opens the archive in the owned `bytes`.
+    ///
+    /// The returned [`OwnedDecompressor`] takes ownership of the `bytes`, so it
+    /// is not tied to the lifetime of a borrowed buffer. An [`Arc<[u8]>`](Arc)
+    /// can be shared by many decompressors without copying it.
+    pub fn from_owned<B: OwnedBytes>(bytes: B) -> Result<Self, Error> {
+        // SAFETY: the bytes are owned by the decompressor, and stay at the same
+        // address until it and all of its clones are dropped, as they live on the heap.
+        // The decompressor never hands out references to the archive that
+        // outlive itself.
+        let slice: &'static [u8] = unsafe { &*(bytes.as_bytes() as *const [u8]) };
+        let mut decompressor = Self::new(slice)?;
+        decompressor.storage = Arc::new(bytes);
+        Ok(decompressor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Checksum, Compressor};
+
+    #[test]
+    fn test_from_owned() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .metadata("key", "value")
+            .compress(&input)
+            .unwrap();
+
+        let mut d = Decompressor::from_owned(compressed.clone()).unwrap();
+        assert_eq!(d.metadata().get("key"), Some(&b"value"[..]));
+        assert_eq!(d.get_ref(1500..1600).unwrap(), &input[1500..1600]);
+        let d = std::thread::spawn(move || d.get(..).unwrap());
+        assert_eq!(d.join().unwrap(), input);
+
+        struct Holder {
+            decompressor: OwnedDecompressor,
+        }
+        let shared: Arc<[u8]> = compressed.into();
+        let mut holders: Vec<_> = (0..2)
+            .map(|_| Holder {
+                decompressor: Decompressor::from_owned(shared.clone()).unwrap(),
+            })
+            .collect();
+        for holder in &mut holders {
+            assert_eq!(
+                holder.decompressor.get(5000..7000).unwrap(),
+                &input[5000..7000]
+            );
+        }
+        assert_eq!(Arc::strong_count(&shared), 3);
+
+        let mut d = Decompressor::from_owned(shared.to_vec().into_boxed_slice()).unwrap();
+        assert_eq!(d.get(9_000..).unwrap(), &input[9_000..]);
+        assert!(Decompressor::from_owned(vec![0; 100]).is_err());
+    }
+}
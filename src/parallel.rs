@@ -0,0 +1,223 @@
+use std::sync::Mutex;
+
+use rayon::prelude::*;
+
+use crate::encoder::FrameEncoder;
+use crate::format::FrameTable;
+use crate::{format, zstd, Compressor, Error, ProgressInfo};
+
+/// How frames are distributed across threads.
+pub(crate) enum Parallelism {
+    /// Use the global `rayon` thread pool.
+    Global,
+    /// Use a dedicated thread pool with the given number of threads, which is
+    /// created on first use.
+    Threads(usize, Option<rayon::ThreadPool>),
+}
+
+impl<'d> Compressor<'d> {
+    /// Compresses frames in parallel, using the global `rayon` thread pool.
+    ///
+    /// As all frames are independent, this scales almost linearly with the number
+    /// of threads. The resulting archive is identical to the one created by
+    /// single-threaded compression. This only affects [`Compressor::compress`],
+    /// the streaming APIs always compress frames one at a time.
+    ///
+    /// The [progress callback](Compressor::on_progress) is invoked from the worker
+    /// threads, in the order in which frames are finished.
+    pub fn parallel(mut self, enable: bool) -> Self {
+        self.parallel = enable.then_some(Parallelism::Global);
+        self
+    }
+
+    /// Compresses frames in parallel, using a dedicated thread pool with `threads` threads.
+    ///
+    /// See [`Compressor::parallel`] for details.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.parallel = Some(Parallelism::Threads(threads, None));
+        self
+    }
+
+    pub(crate) fn compress_parallel(
+        &mut self,
+        input: &[u8],
+        frame_size: usize,
+        buf: &mut Vec<u8>,
+        table: &mut FrameTable,
+    ) -> Result<(), Error> {
+        if let Some(Parallelism::Threads(threads, pool @ None)) = &mut self.parallel {
+            let thread_pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(*threads)
+                .build()
+                .map_err(std::io::Error::other)?;
+            *pool = Some(thread_pool);
+        }
+
+        // the callback is put back once all frames are compressed
+        let mut callback = self.progress.take();
+        let num_frames = input.len().div_ceil(frame_size);
+        let progress = Mutex::new((
+            ProgressInfo::new(Some(num_frames), Some(input.len() as u64)),
+            callback.as_mut(),
+        ));
+
+        let mut settings = self.encoder_settings();
+        // the memory budget is split across the threads, which each use their own context
+        let mut limited_pool = None;
+        if let Some(max_memory) = self.max_memory {
+            let threads = match &self.parallel {
+                Some(Parallelism::Threads(_, Some(pool))) => pool.current_num_threads(),
+                _ => rayon::current_num_threads(),
+            };
+            let (_params, min_size) = zstd::limit_memory(self.level, &self.params, 0);
+            let threads_in_budget = (max_memory / min_size.max(1)).clamp(1, threads);
+            settings.max_memory = Some(max_memory / threads_in_budget);
+            if threads_in_budget < threads {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads_in_budget)
+                    .build()
+                    .map_err(std::io::Error::other)?;
+                limited_pool = Some(pool);
+            }
+        }
+        let compress = || {
+            input
+                .par_chunks(frame_size)
+                .map_init(
+                    || None::<FrameEncoder<'d>>,
+                    |encoder, source| {
+                        let encoder = match encoder {
+                            Some(encoder) => encoder,
+                            None => encoder.insert(settings.encoder()?),
+                        };
+                        let mut buf = Vec::with_capacity(zstd::compress_bound(source.len()));
+                        let frame = encoder.compress_frame(source, &mut buf)?;
+
+                        let mut progress = progress.lock().unwrap();
+                        let (progress, callback) = &mut *progress;
+                        progress.frame_done(source.len(), buf.len(), callback.as_deref_mut())?;
+
+                        Ok((buf, frame))
+                    },
+                )
+                .collect::<Result<Vec<_>, Error>>()
+        };
+        let frames = match (&limited_pool, &self.parallel) {
+            (Some(pool), _) | (None, Some(Parallelism::Threads(_, Some(pool)))) => {
+                pool.install(compress)
+            }
+            (None, _) => compress(),
+        };
+        self.progress = callback;
+        let frames = frames?;
+
+        let input_len = input.len() as u64;
+        table.reserve(frames.len());
+        let mut total_written = 0;
+        let padding = |frame: &[u8]| format::padding(frame.len() as u64, self.frame_alignment);
+        for (buf, frame) in &frames {
+            total_written += (buf.len() + padding(buf)) as u64;
+            table.push(total_written, *frame);
+        }
+
+        let header = format::Header {
+            frame_size,
+            input_len,
+            wide: self.wide_offsets || format::needs_wide_offsets(input_len, total_written),
+            compact: self.compact_index,
+            group_size: self.group_size,
+            frame_alignment: self.frame_alignment,
+            checksum: self.checksum,
+            metadata: &self.metadata,
+            dictionary: self.embedded_dictionary(),
+            dictionary_id: self.dictionary_id,
+        };
+        if self.trailer_index {
+            header.write_trailer_header(buf)?;
+            for (frame, _info) in &frames {
+                buf.extend_from_slice(frame);
+                buf.resize(buf.len() + padding(frame), 0);
+            }
+            header.write_trailer(buf, table, total_written)?;
+            return Ok(());
+        }
+
+        let table_sizeof = header.size(frames.len()).unwrap_or(0);
+
+        buf.reserve(table_sizeof + total_written as usize);
+        header.write(buf, table)?;
+        for (frame, _info) in &frames {
+            buf.extend_from_slice(frame);
+            buf.resize(buf.len() + padding(frame), 0);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Compressor, Decompressor};
+
+    #[test]
+    fn test_parallel() {
+        let input: Vec<u8> = (0..100_000u32)
+            .flat_map(|i| (i % 1013).to_le_bytes())
+            .collect();
+
+        let mut compressor = Compressor::new().frame_size(1000);
+        let expected = compressor.compress(&input).unwrap();
+
+        let mut compressor = Compressor::new().frame_size(1000).parallel(true);
+        assert_eq!(compressor.compress(&input).unwrap(), expected);
+        // the thread-local contexts are created again for each call
+        assert_eq!(compressor.compress(&input).unwrap(), expected);
+
+        let mut compressor = Compressor::new().frame_size(1000).threads(3);
+        let compressed = compressor.compress(&input).unwrap();
+        assert_eq!(compressed, expected);
+
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(12_345..67_890).unwrap(), &input[12_345..67_890]);
+
+        let mut frames = 0;
+        let mut compressor = Compressor::new()
+            .frame_size(1000)
+            .parallel(true)
+            .on_progress(|info| {
+                frames = info.frames;
+                std::ops::ControlFlow::Continue(())
+            });
+        assert_eq!(compressor.compress(&input).unwrap(), expected);
+        drop(compressor);
+        assert_eq!(frames, 400);
+
+        // the memory budget is split across the threads
+        let mut compressor = Compressor::new()
+            .level(19)
+            .frame_size(100_000)
+            .max_memory(1 << 20);
+        let expected = compressor.compress(&input).unwrap();
+        let mut compressor = Compressor::new()
+            .level(19)
+            .frame_size(100_000)
+            .max_memory(4 << 20)
+            .threads(4);
+        assert_eq!(compressor.compress(&input).unwrap(), expected);
+        // a tiny budget only allows for a single thread
+        let mut compressor = compressor.max_memory(1);
+        let compressed = compressor.compress(&input).unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+
+        let mut compressor = Compressor::new().frame_size(1000).trailer_index(true);
+        let expected = compressor.compress(&input).unwrap();
+        let mut compressor = compressor.parallel(true);
+        assert_eq!(compressor.compress(&input).unwrap(), expected);
+
+        let mut compressor = Compressor::new().parallel(true);
+        let compressed = compressor.compress(&[]).unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.get(..).unwrap(), &[]);
+    }
+}
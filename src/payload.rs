@@ -0,0 +1,267 @@
+use std::io;
+use std::mem::{self, MaybeUninit};
+use std::ops::{Range, RangeBounds};
+
+use watto::Pod;
+
+use crate::{make_range, Compressor, Decompressor, Error};
+
+/// The alignment of the archive following the header, which is required to
+/// parse the archive in place.
+const ALIGN: usize = mem::size_of::<u64>();
+
+impl Compressor<'_> {
+    /// Compresses `file`, keeping its first `header_len` bytes uncompressed.
+    ///
+    /// This is meant for binary formats that start with a fixed-size header,
+    /// like the ones parsed with [`watto`]: the header can still be read
+    /// directly from the output, while the variable-length payload following
+    /// it is compressed into an archive. The header is padded with zeroes to a
+    /// multiple of 8 bytes, so the archive can be parsed in place. Use
+    /// [`PayloadDecompressor`] to read the output using the offsets of `file`.
+    pub fn compress_payload(&mut self, file: &[u8], header_len: usize) -> Result<Vec<u8>, Error> {
+        let Some((header, payload)) = file.split_at_checked(header_len) else {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the header is longer than the file",
+            )));
+        };
+        let mut buf = header.to_vec();
+        buf.resize(header_len.next_multiple_of(ALIGN), 0);
+        buf.extend_from_slice(&self.compress(payload)?);
+        Ok(buf)
+    }
+}
+
+/// Reads a file written by [`Compressor::compress_payload`], consisting of an
+/// uncompressed header followed by a compressed payload.
+///
+/// All reads take offsets within the original file, and are translated into
+/// offsets within the payload as needed, so readers of the format do not need
+/// to care which part of the file is compressed. Reads spanning the end of the
+/// header are stitched together from both parts.
+#[derive(Debug)]
+pub struct PayloadDecompressor<'b> {
+    header: &'b [u8],
+    decompressor: Decompressor<'b>,
+}
+
+impl<'b> PayloadDecompressor<'b> {
+    /// Opens the file in `bytes`, which starts with a header of `header_len` bytes.
+    ///
+    /// Fails with [`Error::TruncatedHeader`] if `bytes` is too short to hold
+    /// the header, and like [`Decompressor::new`] if the payload is invalid.
+    pub fn new(bytes: &'b [u8], header_len: usize) -> Result<Self, Error> {
+        let payload_start = header_len
+            .checked_next_multiple_of(ALIGN)
+            .ok_or(Error::TruncatedHeader)?;
+        if bytes.len() < payload_start {
+            return Err(Error::TruncatedHeader);
+        }
+        Ok(Self {
+            header: &bytes[..header_len],
+            decompressor: Decompressor::new(&bytes[payload_start..])?,
+        })
+    }
+
+    /// The uncompressed header.
+    pub fn header(&self) -> &'b [u8] {
+        self.header
+    }
+
+    /// The length of the original file, including the header.
+    pub fn len(&self) -> usize {
+        self.header.len() + self.decompressor.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Translates an `offset` within the file into an offset within the
+    /// payload, or `None` if it points into the header.
+    pub fn payload_offset(&self, offset: usize) -> Option<usize> {
+        offset.checked_sub(self.header.len())
+    }
+
+    /// Translates an `offset` within the payload into an offset within the file.
+    pub fn file_offset(&self, offset: usize) -> usize {
+        self.header.len() + offset
+    }
+
+    /// Returns the `range` of the file.
+    pub fn get<R>(&mut self, range: R) -> io::Result<Vec<u8>>
+    where
+        R: RangeBounds<usize>,
+    {
+        let mut buf = Vec::new();
+        self.get_into(&mut buf, range)?;
+        Ok(buf)
+    }
+
+    /// Returns the `range` of the file, reusing the allocation of `buf`.
+    pub fn get_into<'o, R>(&mut self, buf: &'o mut Vec<u8>, range: R) -> io::Result<&'o [u8]>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = self.check_range(range)?;
+        self.decompressor.check_output_len(range.len())?;
+        buf.clear();
+        buf.reserve(range.len());
+
+        let (header, payload) = self.split_range(range);
+        buf.extend_from_slice(&self.header[header]);
+        if !payload.is_empty() {
+            self.decompressor.append_into(buf, payload)?;
+        }
+        Ok(buf.as_slice())
+    }
+
+    /// Reads the `range` of the file into `buf`, returning the number of bytes read.
+    ///
+    /// An error is returned if the `range` is out of bounds, or `buf` is too
+    /// small to hold it.
+    pub fn get_to_slice<R>(&mut self, range: R, buf: &mut [u8]) -> io::Result<usize>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = self.check_range(range)?;
+        let len = range.len();
+        let buf = buf.get_mut(..len).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "buffer too small for the requested range",
+            )
+        })?;
+
+        let (header, payload) = self.split_range(range);
+        let (header_buf, payload_buf) = buf.split_at_mut(header.len());
+        header_buf.copy_from_slice(&self.header[header]);
+        if !payload.is_empty() {
+            self.decompressor.get_to_slice(payload, payload_buf)?;
+        }
+        Ok(len)
+    }
+
+    /// Reads the [`Pod`] value stored at `offset` of the file, which may be
+    /// part of the header, the payload, or both.
+    pub fn get_as<T: Pod>(&mut self, offset: usize) -> io::Result<T> {
+        let end = offset.checked_add(mem::size_of::<T>());
+        let range = offset..end.unwrap_or(usize::MAX);
+        let mut value = MaybeUninit::<T>::zeroed();
+        // SAFETY: the value is zeroed, and any bit pattern is a valid `Pod`.
+        let bytes = unsafe {
+            std::slice::from_raw_parts_mut(value.as_mut_ptr().cast::<u8>(), mem::size_of::<T>())
+        };
+        self.get_to_slice(range, bytes)?;
+        // SAFETY: see above.
+        Ok(unsafe { value.assume_init() })
+    }
+
+    /// Returns the [`Decompressor`] of the payload, which uses payload offsets.
+    pub fn decompressor(&mut self) -> &mut Decompressor<'b> {
+        &mut self.decompressor
+    }
+
+    pub fn into_inner(self) -> Decompressor<'b> {
+        self.decompressor
+    }
+
+    fn check_range<R>(&self, range: R) -> io::Result<Range<usize>>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.len())?;
+        if range.start > range.end || range.end > self.len() {
+            return Err(Error::RangeOutOfBounds {
+                range,
+                len: self.len(),
+            }
+            .into());
+        }
+        Ok(range)
+    }
+
+    /// Splits a valid `range` of the file into a range of the header and a
+    /// range of the payload, either of which may be empty.
+    fn split_range(&self, range: Range<usize>) -> (Range<usize>, Range<usize>) {
+        let header_len = self.header.len();
+        let header = range.start.min(header_len)..range.end.min(header_len);
+        let payload = range.start.saturating_sub(header_len)..range.end.saturating_sub(header_len);
+        (header, payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_decompressor() {
+        let mut file = Vec::new();
+        file.extend_from_slice(b"FMT1");
+        file.extend_from_slice(&10_000u32.to_le_bytes());
+        file.extend_from_slice(&[0xff; 4]);
+        file.extend((0..10_000u32).map(|i| (i % 251) as u8));
+        let header_len = 12;
+
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .compress_payload(&file, header_len)
+            .unwrap();
+        assert_eq!(&compressed[..header_len], &file[..header_len]);
+        assert_eq!(&compressed[header_len..16], &[0; 4]);
+        assert!(compressed.len() < file.len());
+
+        let mut d = PayloadDecompressor::new(&compressed, header_len).unwrap();
+        assert_eq!(d.header(), &file[..header_len]);
+        assert_eq!(d.len(), file.len());
+        assert_eq!(d.payload_offset(4), None);
+        assert_eq!(d.payload_offset(1012), Some(1000));
+        assert_eq!(d.file_offset(1000), 1012);
+
+        for range in [
+            0..0,
+            0..4,
+            4..12,
+            8..20,
+            12..1012,
+            1000..1500,
+            0..file.len(),
+        ] {
+            assert_eq!(d.get(range.clone()).unwrap(), &file[range.clone()]);
+            let mut buf = vec![0; range.len() + 1];
+            assert_eq!(
+                d.get_to_slice(range.clone(), &mut buf).unwrap(),
+                range.len()
+            );
+            assert_eq!(&buf[..range.len()], &file[range]);
+        }
+        assert_eq!(d.get_as::<u32>(4).unwrap(), 10_000);
+        // a value straddling the end of the header
+        assert_eq!(
+            d.get_as::<u64>(8).unwrap(),
+            u64::from_ne_bytes(file[8..16].try_into().unwrap())
+        );
+        assert_eq!(d.decompressor().get(..10).unwrap(), &file[12..22]);
+
+        let err = d.get(0..file.len() + 1).unwrap_err();
+        assert!(matches!(
+            err.get_ref().and_then(|err| err.downcast_ref()),
+            Some(Error::RangeOutOfBounds { .. })
+        ));
+        assert!(d.get_to_slice(0..20, &mut [0; 10]).is_err());
+        assert!(d.get_as::<u32>(file.len() - 2).is_err());
+
+        assert!(Compressor::new().compress_payload(&file[..8], 12).is_err());
+        assert!(matches!(
+            PayloadDecompressor::new(&compressed[..8], header_len),
+            Err(Error::TruncatedHeader)
+        ));
+
+        // a file consisting of only the header
+        let compressed = Compressor::new().compress_payload(&file[..12], 12).unwrap();
+        let mut d = PayloadDecompressor::new(&compressed, 12).unwrap();
+        assert_eq!(d.get(..).unwrap(), &file[..12]);
+    }
+}
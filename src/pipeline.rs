@@ -0,0 +1,332 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Poll, Waker};
+
+use crate::format::STORED_FRAME;
+use crate::{CompressWriter, Compressor};
+
+/// A part of an archive produced by a compression pipeline, created by
+/// [`Compressor::pipeline`].
+///
+/// Writing the bytes of all parts in order results in the complete archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArchivePart {
+    /// The header in front of the compressed frames.
+    Header(Vec<u8>),
+    /// A compressed frame.
+    Frame(CompressedFrame),
+    /// The index and footer following the compressed frames, which completes the archive.
+    Index(Vec<u8>),
+}
+
+impl ArchivePart {
+    /// The bytes of this part of the archive.
+    pub fn bytes(&self) -> &[u8] {
+        match self {
+            ArchivePart::Header(bytes) | ArchivePart::Index(bytes) => bytes,
+            ArchivePart::Frame(frame) => &frame.bytes,
+        }
+    }
+}
+
+/// A compressed frame, along with its entry in the index of the archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedFrame {
+    /// The index of the frame.
+    pub idx: usize,
+    /// The range of the input covered by the frame.
+    pub input: Range<u64>,
+    /// The offset of the frame within the archive.
+    pub offset: u64,
+    /// The compressed frame, including the padding of a
+    /// [`frame_alignment`](Compressor::frame_alignment).
+    pub bytes: Vec<u8>,
+}
+
+impl CompressedFrame {
+    /// The range of the frame within the archive.
+    pub fn compressed_range(&self) -> Range<u64> {
+        self.offset..self.offset + self.bytes.len() as u64
+    }
+}
+
+/// Sends the input of a compression pipeline, created by [`Compressor::pipeline`].
+///
+/// The input is complete once the sender is dropped, or [`PipelineSender::finish`]ed.
+#[derive(Debug)]
+pub struct PipelineSender {
+    sender: mpsc::Sender<Vec<u8>>,
+}
+
+impl PipelineSender {
+    /// Adds `chunk` to the input, which is compressed in the background.
+    ///
+    /// This never blocks. Fails with [`BrokenPipe`](io::ErrorKind::BrokenPipe)
+    /// if the compression has stopped, because of an error or because the
+    /// [`PipelineReceiver`] has been dropped.
+    pub fn send(&self, chunk: impl Into<Vec<u8>>) -> io::Result<()> {
+        self.sender
+            .send(chunk.into())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the pipeline has stopped"))
+    }
+
+    /// Completes the input, so that the last frame and the index are produced.
+    pub fn finish(self) {}
+}
+
+/// Receives the parts of the archive produced by a compression pipeline,
+/// created by [`Compressor::pipeline`].
+#[derive(Debug)]
+pub struct PipelineReceiver {
+    queue: Arc<Queue>,
+}
+
+impl PipelineReceiver {
+    /// Returns the next part of the archive, once it is complete, or `None`
+    /// after the [`ArchivePart::Index`]. The pipeline stops after the first error.
+    pub fn recv(&mut self) -> impl Future<Output = Option<io::Result<ArchivePart>>> + '_ {
+        std::future::poll_fn(|cx| {
+            let mut state = self.queue.lock();
+            if let Some(part) = state.parts.pop_front() {
+                self.queue.popped.notify_one();
+                return Poll::Ready(Some(part));
+            }
+            if state.done {
+                return Poll::Ready(None);
+            }
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+    }
+}
+
+impl Drop for PipelineReceiver {
+    fn drop(&mut self) {
+        self.queue.lock().closed = true;
+        self.queue.popped.notify_one();
+    }
+}
+
+/// The bounded queue of parts between the compression thread and the receiver.
+#[derive(Debug)]
+struct Queue {
+    state: Mutex<QueueState>,
+    /// Notifies the compression thread when a part was taken from a full queue.
+    popped: Condvar,
+    capacity: usize,
+}
+
+#[derive(Debug)]
+struct QueueState {
+    parts: VecDeque<io::Result<ArchivePart>>,
+    /// Whether the compression thread has produced its last part.
+    done: bool,
+    /// Whether the receiver has been dropped.
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl Queue {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Adds `part` to the queue, waiting while it is full. Returns `false` if
+    /// the receiver has been dropped.
+    fn push(&self, part: io::Result<ArchivePart>) -> bool {
+        let mut state = self.lock();
+        while state.parts.len() >= self.capacity && !state.closed {
+            state = self
+                .popped
+                .wait(state)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+        state.parts.push_back(part);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        !state.closed
+    }
+
+    fn set_done(&self) {
+        let mut state = self.lock();
+        state.done = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Compressor<'static> {
+    /// Starts compressing on a background thread, which receives its input
+    /// from the returned [`PipelineSender`], and produces the parts of the
+    /// archive through the returned [`PipelineReceiver`].
+    ///
+    /// Each frame is sent as soon as it is compressed, along with its index
+    /// entry, so that an upload can start while later frames are still being
+    /// compressed. The archive always uses a [`trailer_index`](Self::trailer_index).
+    /// Up to `capacity` parts are buffered, after which the compression waits
+    /// for the receiver to catch up.
+    pub fn pipeline(self, capacity: usize) -> io::Result<(PipelineSender, PipelineReceiver)> {
+        let writer = self.trailer_index(true).writer(Vec::new())?;
+        let (sender, input) = mpsc::channel();
+        let queue = Arc::new(Queue {
+            state: Mutex::new(QueueState {
+                parts: VecDeque::new(),
+                done: false,
+                closed: false,
+                waker: None,
+            }),
+            popped: Condvar::new(),
+            capacity: capacity.max(1),
+        });
+        let output = Arc::clone(&queue);
+        std::thread::spawn(move || {
+            if let Err(err) = run_pipeline(writer, &input, &output) {
+                output.push(Err(err));
+            }
+            output.set_done();
+        });
+        Ok((PipelineSender { sender }, PipelineReceiver { queue }))
+    }
+}
+
+/// Compresses all chunks from `input` with `writer`, pushing the parts of
+/// the archive to `output`.
+fn run_pipeline(
+    mut writer: CompressWriter<'_, Vec<u8>>,
+    input: &mpsc::Receiver<Vec<u8>>,
+    output: &Queue,
+) -> io::Result<()> {
+    let header = std::mem::take(writer.get_mut());
+    let mut frames = Frames {
+        frames_start: header.len() as u64,
+        next: 0,
+        input_len: 0,
+    };
+    if !output.push(Ok(ArchivePart::Header(header))) {
+        return Ok(());
+    }
+    for chunk in input {
+        writer.write_all(&chunk)?;
+        frames.input_len += chunk.len() as u64;
+        if !frames.push_new(&mut writer, output) {
+            return Ok(());
+        }
+    }
+    writer.flush_partial_frame()?;
+    if !frames.push_new(&mut writer, output) {
+        return Ok(());
+    }
+    let index = writer.finish()?;
+    output.push(Ok(ArchivePart::Index(index)));
+    Ok(())
+}
+
+/// Keeps track of the frames of a pipeline that have been sent.
+struct Frames {
+    /// The offset of the first frame within the archive.
+    frames_start: u64,
+    /// The index of the next frame to send.
+    next: usize,
+    input_len: u64,
+}
+
+impl Frames {
+    /// Pushes the frames compressed by `writer` since the last call to `output`.
+    fn push_new(&mut self, writer: &mut CompressWriter<'_, Vec<u8>>, output: &Queue) -> bool {
+        let frame_size = writer.frame_size() as u64;
+        let offsets = writer.frame_table().offsets.clone();
+        let mut compressed = std::mem::take(writer.get_mut());
+        while self.next < offsets.len() - 1 {
+            let idx = self.next;
+            let start = offsets[idx] & !STORED_FRAME;
+            let end = offsets[idx + 1] & !STORED_FRAME;
+            let rest = compressed.split_off((end - start) as usize);
+            let frame = CompressedFrame {
+                idx,
+                input: idx as u64 * frame_size..((idx as u64 + 1) * frame_size).min(self.input_len),
+                offset: self.frames_start + start,
+                bytes: std::mem::replace(&mut compressed, rest),
+            };
+            self.next += 1;
+            if !output.push(Ok(ArchivePart::Frame(frame))) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::async_io::tests::block_on;
+    use crate::{Checksum, Decompressor};
+
+    #[test]
+    fn test_pipeline() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressor = || {
+            Compressor::new()
+                .frame_size(1000)
+                .checksum(Checksum::Crc32)
+                .frame_alignment(64)
+        };
+        let expected = compressor().trailer_index(true).compress(&input).unwrap();
+
+        let (sender, mut receiver) = compressor().pipeline(2).unwrap();
+        let producer = std::thread::spawn({
+            let input = input.clone();
+            move || {
+                for chunk in input.chunks(700) {
+                    sender.send(chunk).unwrap();
+                }
+                sender.finish();
+            }
+        });
+        let mut parts = Vec::new();
+        while let Some(part) = block_on(receiver.recv()) {
+            parts.push(part.unwrap());
+        }
+        producer.join().unwrap();
+
+        let archive: Vec<u8> = parts
+            .iter()
+            .flat_map(|part| part.bytes())
+            .copied()
+            .collect();
+        assert_eq!(archive, expected);
+        assert!(matches!(parts.first(), Some(ArchivePart::Header(_))));
+        assert!(matches!(parts.last(), Some(ArchivePart::Index(_))));
+        let d = Decompressor::new(&archive).unwrap();
+        for part in &parts[1..parts.len() - 1] {
+            let ArchivePart::Frame(frame) = part else {
+                panic!("expected a frame");
+            };
+            let range = frame.compressed_range();
+            assert_eq!(
+                d.compressed_frames_for(frame.input.start as usize..frame.input.end as usize)
+                    .unwrap(),
+                [(frame.idx, range)]
+            );
+        }
+        assert_eq!(parts.len(), 12);
+
+        // dropping the receiver stops the compression
+        let (sender, receiver) = compressor().pipeline(1).unwrap();
+        drop(receiver);
+        let mut stopped = false;
+        for _ in 0..1000 {
+            if sender.send(vec![0; 1000]).is_err() {
+                stopped = true;
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert!(stopped);
+    }
+}
@@ -0,0 +1,121 @@
+use std::io;
+
+use crate::cache::{FrameBuf, Pool, Pooled};
+use crate::format::Archive;
+use crate::{zstd, Decompressor, Error};
+
+/// A pool of `zstd` contexts and scratch buffers shared by many archives.
+///
+/// Services decoding many short-lived archives would otherwise create a new
+/// `zstd` context and scratch buffer for each of them. A [`Decompressor`]
+/// opened through [`DecompressorPool::open`] instead takes warm contexts out of
+/// the pool for each read, and returns them right after. Its scratch buffer is
+/// returned to the pool when it is dropped.
+///
+/// The pool can be shared by many threads. Archives compressed with a dictionary
+/// need a context of their own, and do not use the pooled contexts.
+#[derive(Default)]
+pub struct DecompressorPool {
+    contexts: Pool<zstd::Decompressor<'static>>,
+    bufs: Pool<FrameBuf>,
+}
+
+impl std::fmt::Debug for DecompressorPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DecompressorPool").finish_non_exhaustive()
+    }
+}
+
+impl DecompressorPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens an archive whose reads use the contexts and buffers of this pool.
+    pub fn open<'b>(&'b self, bytes: &'b [u8]) -> Result<Decompressor<'b>, Error> {
+        let mut decompressor = Decompressor::new(bytes)?;
+        decompressor.read_buf = self.bufs.take_or_default().into_inner();
+        decompressor.pool = Some(self);
+        Ok(decompressor)
+    }
+
+    /// Takes a context without a dictionary out of the pool for `archive`.
+    pub(crate) fn context<'b>(&self, archive: &Archive<'_>) -> io::Result<ZstdContext<'_, 'b>> {
+        let mut context = self.contexts.take(zstd::Decompressor::new)?;
+        // the contexts are shared by archives with different settings
+        context.include_magicbytes(archive.zstd_frames)?;
+        Ok(ZstdContext::Shared(context))
+    }
+
+    /// Returns the scratch buffer of a dropped [`Decompressor`] to the pool.
+    pub(crate) fn put_buf(&self, mut frame_buf: FrameBuf) {
+        // the buffer is reused for another archive
+        frame_buf.frame = None;
+        self.bufs.put(frame_buf);
+    }
+}
+
+/// A `zstd` context used for reads, taken either from the own pool of a
+/// [`Decompressor`], or from a shared [`DecompressorPool`].
+///
+/// The contexts of a [`Decompressor`] can reference its dictionary, while
+/// shared contexts can not, which is why they are of different types.
+pub enum ZstdContext<'a, 'b> {
+    Own(Pooled<'a, zstd::Decompressor<'b>>),
+    Shared(Pooled<'a, zstd::Decompressor<'static>>),
+}
+
+impl ZstdContext<'_, '_> {
+    pub fn decompress_to_buffer<C>(
+        &mut self,
+        source: &[u8],
+        destination: &mut C,
+    ) -> io::Result<usize>
+    where
+        C: zstd::WriteBuf + ?Sized,
+    {
+        match self {
+            Self::Own(context) => context.decompress_to_buffer(source, destination),
+            Self::Shared(context) => context.decompress_to_buffer(source, destination),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Checksum, Compressor};
+
+    #[test]
+    fn test_pool() {
+        let pool = DecompressorPool::new();
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let plain = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let seekable = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .compress_seekable(&input)
+            .unwrap();
+        let dictionary = vec![7; 1000];
+        let with_dictionary = Compressor::new()
+            .frame_size(1000)
+            .dictionary(&dictionary)
+            .compress(&input)
+            .unwrap();
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for compressed in [&plain, &seekable, &with_dictionary] {
+                        let mut d = pool.open(compressed).unwrap();
+                        assert_eq!(d.get(500..2500).unwrap(), &input[500..2500]);
+                        assert_eq!(d.get(..).unwrap(), input);
+                    }
+                });
+            }
+        });
+        assert!((1..=4).contains(&pool.contexts.len()));
+        assert!((1..=4).contains(&pool.bufs.len()));
+        assert!(pool.bufs.take_or_default().frame.is_none());
+    }
+}
@@ -0,0 +1,55 @@
+use std::ops::ControlFlow;
+
+use crate::Error;
+
+/// A callback that is invoked after every compressed frame.
+pub(crate) type ProgressFn<'d> = Box<dyn FnMut(ProgressInfo) -> ControlFlow<()> + Send + 'd>;
+
+/// The progress of an ongoing compression, as reported to [`Compressor::on_progress`].
+///
+/// [`Compressor::on_progress`]: crate::Compressor::on_progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ProgressInfo {
+    /// The number of frames compressed so far.
+    pub frames: usize,
+    /// The total number of frames, if the input length is known up front.
+    pub total_frames: Option<usize>,
+    /// The number of input bytes compressed so far.
+    pub input_bytes: u64,
+    /// The total input length, if it is known up front.
+    pub total_input_bytes: Option<u64>,
+    /// The number of compressed bytes produced so far.
+    pub output_bytes: u64,
+}
+
+impl ProgressInfo {
+    pub(crate) fn new(total_frames: Option<usize>, total_input_bytes: Option<u64>) -> Self {
+        Self {
+            frames: 0,
+            total_frames,
+            input_bytes: 0,
+            total_input_bytes,
+            output_bytes: 0,
+        }
+    }
+
+    /// Records a compressed frame, and reports the progress to `callback`.
+    ///
+    /// Returns [`Error::Cancelled`] if the callback asks to abort the compression.
+    pub(crate) fn frame_done(
+        &mut self,
+        input_len: usize,
+        output_len: usize,
+        callback: Option<&mut ProgressFn<'_>>,
+    ) -> Result<(), Error> {
+        self.frames += 1;
+        self.input_bytes += input_len as u64;
+        self.output_bytes += output_len as u64;
+
+        match callback.map(|callback| callback(*self)) {
+            Some(ControlFlow::Break(())) => Err(Error::Cancelled),
+            _ => Ok(()),
+        }
+    }
+}
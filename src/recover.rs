@@ -0,0 +1,155 @@
+use std::io;
+use std::ops::{Range, RangeBounds};
+
+use crate::{make_range, Decompressor, Error};
+
+/// The result of a best-effort read by [`Decompressor::recover`].
+#[derive(Debug)]
+pub struct Recovered {
+    /// The requested range, with the parts of the frames that failed to decode
+    /// replaced by the filler.
+    pub data: Vec<u8>,
+    /// The parts of the requested range that could not be decoded, in order.
+    pub gaps: Vec<Gap>,
+}
+
+/// A part of a [`Recovered`] range that could not be decoded.
+#[derive(Debug)]
+pub struct Gap {
+    /// The index of the frame that failed to decode.
+    pub frame: usize,
+    /// The range of the output covered by the frame, relative to the start of the archive.
+    pub range: Range<usize>,
+    /// The error of the frame.
+    pub error: io::Error,
+}
+
+impl Decompressor<'_> {
+    /// Decompresses `range` on a best-effort basis, skipping over frames that
+    /// fail to decode.
+    ///
+    /// The output of those frames is replaced by `filler` bytes, and reported
+    /// as [`Gap`]s, so that the intact parts of a partially corrupted or truncated
+    /// archive can still be read. Only an out of bounds `range`, a range exceeding
+    /// the [`max_output_bytes`](Self::max_output_bytes), and cancellation fail the
+    /// whole read.
+    pub fn recover<R>(&mut self, range: R, filler: u8) -> io::Result<Recovered>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.archive.input_len)?;
+        if range.start > range.end || range.end > self.archive.input_len {
+            return Err(self.out_of_bounds(range));
+        }
+        self.check_output_len(range.len())?;
+        let frame_size = self.frame_size();
+
+        self.with_read_buf(|this, frame_buf| {
+            let mut decompressor = None;
+            let mut recovered = Recovered {
+                data: Vec::with_capacity(range.len()),
+                gaps: Vec::new(),
+            };
+            for idx in range.start / frame_size..range.end.div_ceil(frame_size) {
+                let frame_start = idx * frame_size;
+                let start = range.start.max(frame_start);
+                let end = range.end.min(frame_start.saturating_add(frame_size));
+                let frame = this
+                    .load_frame(&mut decompressor, idx, frame_buf)
+                    .and_then(|frame| {
+                        frame
+                            .get(start - frame_start..end - frame_start)
+                            .ok_or_else(|| this.frame_decode_failed(idx, None))
+                    });
+                match frame {
+                    Ok(frame) => recovered.data.extend_from_slice(frame),
+                    Err(err) if is_cancelled(&err) => return Err(err),
+                    Err(error) => {
+                        recovered
+                            .data
+                            .resize(recovered.data.len() + end - start, filler);
+                        recovered.gaps.push(Gap {
+                            frame: idx,
+                            range: start..end,
+                            error,
+                        });
+                    }
+                }
+            }
+            Ok(recovered)
+        })
+    }
+}
+
+fn is_cancelled(err: &io::Error) -> bool {
+    err.get_ref()
+        .and_then(|err| err.downcast_ref())
+        .is_some_and(|err| matches!(err, Error::Cancelled))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::Compressor;
+
+    #[test]
+    fn test_recover() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let d = Decompressor::new(&compressed).unwrap();
+        let mut corrupted = compressed.clone();
+        for range in [4000..5000, 7000..8000] {
+            let frame = d.compressed_range_for(range).unwrap();
+            corrupted[frame.start as usize..frame.end as usize].fill(0xff);
+        }
+
+        let mut d = Decompressor::new(&corrupted).unwrap();
+        assert!(d.get(..).is_err());
+        let recovered = d.recover(.., 0).unwrap();
+        assert_eq!(recovered.data.len(), input.len());
+        let gaps: Vec<_> = recovered
+            .gaps
+            .iter()
+            .map(|gap| (gap.frame, gap.range.clone()))
+            .collect();
+        assert_eq!(gaps, [(4, 4000..5000), (7, 7000..8000)]);
+        let mut expected = input.clone();
+        expected[4000..5000].fill(0);
+        expected[7000..8000].fill(0);
+        assert_eq!(recovered.data, expected);
+        assert!(matches!(
+            recovered.gaps[0]
+                .error
+                .get_ref()
+                .and_then(|err| err.downcast_ref()),
+            Some(Error::FrameDecodeFailed { frame: 4, .. })
+        ));
+
+        let recovered = d.recover(4500..7500, 0xaa).unwrap();
+        let mut expected = input[4500..7500].to_vec();
+        expected[..500].fill(0xaa);
+        expected[2500..].fill(0xaa);
+        assert_eq!(recovered.data, expected);
+        assert_eq!(recovered.gaps.len(), 2);
+        assert_eq!(recovered.gaps[0].range, 4500..5000);
+        assert_eq!(recovered.gaps[1].range, 7000..7500);
+        assert!(d.recover(5000..7000, 0).unwrap().gaps.is_empty());
+        assert!(d.recover(9000..10_001, 0).is_err());
+
+        // the missing frames of a truncated archive are gaps
+        let mut d = Decompressor::new(&compressed[..compressed.len() - 10]).unwrap();
+        let recovered = d.recover(8500.., 0).unwrap();
+        assert_eq!(recovered.data[..500], input[8500..9000]);
+        assert_eq!(recovered.gaps.len(), 1);
+        assert_eq!(recovered.gaps[0].range, 9000..10_000);
+
+        // cancellation still fails the read
+        let cancelled = AtomicBool::new(true);
+        let mut d = Decompressor::new(&corrupted)
+            .unwrap()
+            .cancel_when(move || cancelled.load(Ordering::Relaxed));
+        assert!(d.recover(.., 0).is_err());
+    }
+}
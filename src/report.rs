@@ -0,0 +1,101 @@
+use std::time::{Duration, Instant};
+
+use crate::format::{FrameTable, STORED_FRAME};
+use crate::{Compressor, Error};
+
+/// Statistics about a compressed archive, returned by [`Compressor::compress_with_report`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct CompressionReport {
+    /// The uncompressed size of the input.
+    pub input_len: u64,
+    /// The size of the whole archive, including its header and index.
+    pub compressed_len: u64,
+    /// The compressed size of each frame, including the padding of a
+    /// [`frame_alignment`](Compressor::frame_alignment).
+    pub frame_sizes: Vec<u64>,
+    /// The number of frames that are stored uncompressed, as they did not compress.
+    pub stored_frames: usize,
+    /// The time spent compressing.
+    pub elapsed: Duration,
+}
+
+impl CompressionReport {
+    /// The compression ratio, as the uncompressed size divided by the size of the archive.
+    pub fn ratio(&self) -> f64 {
+        self.input_len as f64 / self.compressed_len as f64
+    }
+}
+
+impl Compressor<'_> {
+    /// Compresses `input`, returning the archive along with a [`CompressionReport`].
+    ///
+    /// This produces the same archive as [`Compressor::compress`], so tooling can
+    /// keep track of the compression efficiency without parsing the archive again.
+    pub fn compress_with_report(
+        &mut self,
+        input: &[u8],
+    ) -> Result<(Vec<u8>, CompressionReport), Error> {
+        let start = Instant::now();
+        let mut buf = Vec::new();
+        let mut table = FrameTable::new();
+        self.compress_into(input, &mut buf, &mut table)?;
+        let elapsed = start.elapsed();
+
+        let frame_sizes = table
+            .offsets
+            .windows(2)
+            .map(|frame| (frame[1] & !STORED_FRAME) - (frame[0] & !STORED_FRAME))
+            .collect();
+        let stored_frames = table.offsets[1..]
+            .iter()
+            .filter(|offset| *offset & STORED_FRAME != 0)
+            .count();
+        let report = CompressionReport {
+            input_len: input.len() as u64,
+            compressed_len: buf.len() as u64,
+            frame_sizes,
+            stored_frames,
+            elapsed,
+        };
+        Ok((buf, report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Decompressor;
+
+    #[test]
+    fn test_compress_with_report() {
+        let mut input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        // a frame of random bytes does not compress
+        let mut state = 1u32;
+        for byte in &mut input[3000..4000] {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            *byte = (state >> 16) as u8;
+        }
+        let mut compressor = Compressor::new().frame_size(1000).frame_alignment(8);
+        let (compressed, report) = compressor.compress_with_report(&input).unwrap();
+        assert_eq!(compressed, compressor.compress(&input).unwrap());
+
+        assert_eq!(report.input_len, 10_000);
+        assert_eq!(report.compressed_len, compressed.len() as u64);
+        assert_eq!(report.frame_sizes.len(), 10);
+        assert_eq!(report.stored_frames, 1);
+        assert_eq!(report.frame_sizes[3], 1000);
+        assert!(report.ratio() > 1.0);
+
+        let d = Decompressor::new(&compressed).unwrap();
+        let frames = d.compressed_frames_for(..).unwrap();
+        for ((_, range), size) in frames.iter().zip(&report.frame_sizes) {
+            assert_eq!(range.end - range.start, *size);
+            assert_eq!(size % 8, 0);
+        }
+        assert_eq!(
+            report.frame_sizes.iter().sum::<u64>(),
+            frames.last().unwrap().1.end - frames[0].1.start
+        );
+    }
+}
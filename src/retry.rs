@@ -0,0 +1,178 @@
+use std::io;
+use std::ops::Range;
+use std::time::Duration;
+
+use crate::{Error, Storage};
+
+/// [`Storage`] retrying failed reads of another storage with exponential backoff.
+///
+/// Reads from remote storage, like [`HttpStorage`](crate::HttpStorage) or an
+/// object store, occasionally fail with transient network errors. Those reads are
+/// retried up to [`max_retries`](Self::max_retries) times, sleeping between the
+/// attempts for the [`backoff`](Self::backoff), which doubles after each attempt.
+/// Errors of kind [`Interrupted`](io::ErrorKind::Interrupted), [`WouldBlock`](io::ErrorKind::WouldBlock),
+/// [`TimedOut`](io::ErrorKind::TimedOut), and connection errors are considered
+/// transient, as are [`Error::HttpStatus`] errors with a `5xx` or `429 Too Many Requests`
+/// status. Any other error is returned right away.
+#[derive(Debug)]
+pub struct RetryStorage<S> {
+    storage: S,
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl<S: Storage> RetryStorage<S> {
+    /// Wraps `storage`, retrying up to 3 times, with a backoff starting at 100ms.
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+
+    /// Sets the number of retries after the first failed attempt.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the time to sleep before the first retry, which doubles for each
+    /// further retry up to `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Returns the wrapped storage.
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+}
+
+impl<S: Storage> Storage for RetryStorage<S> {
+    fn len(&self) -> u64 {
+        self.storage.len()
+    }
+
+    fn prefetch(&self, ranges: &[Range<u64>]) {
+        self.storage.prefetch(ranges)
+    }
+
+    fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+        let mut backoff = self.initial_backoff;
+        // all but the last attempt read into a separate buffer, as the borrow of
+        // `buf` by a successful read could not be returned from within the loop
+        let mut scratch = Vec::new();
+        for _ in 0..self.max_retries {
+            match self.storage.read(range.clone(), &mut scratch) {
+                Ok(bytes) => {
+                    buf.clear();
+                    buf.extend_from_slice(bytes);
+                    return Ok(buf);
+                }
+                Err(err) if is_transient(&err) => {}
+                Err(err) => return Err(err),
+            }
+            std::thread::sleep(backoff);
+            backoff = (backoff * 2).min(self.max_backoff);
+        }
+        self.storage.read(range, buf)
+    }
+}
+
+fn is_transient(err: &io::Error) -> bool {
+    use io::ErrorKind::*;
+
+    if let Some(Error::HttpStatus(status)) = err.get_ref().and_then(|err| err.downcast_ref()) {
+        return *status == 429 || (500..600).contains(status);
+    }
+    matches!(
+        err.kind(),
+        Interrupted
+            | WouldBlock
+            | TimedOut
+            | ConnectionRefused
+            | ConnectionReset
+            | ConnectionAborted
+            | NotConnected
+            | BrokenPipe
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+    use crate::{Compressor, Decompressor};
+
+    /// Storage failing every read for the given number of attempts.
+    struct Flaky {
+        bytes: Vec<u8>,
+        failures: u32,
+        error: fn() -> io::Error,
+        attempts: AtomicU32,
+    }
+
+    impl Storage for Flaky {
+        fn len(&self) -> u64 {
+            self.bytes.len() as u64
+        }
+
+        fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+            let attempt = self.attempts.fetch_add(1, Ordering::Relaxed);
+            if attempt % (self.failures + 1) < self.failures {
+                return Err((self.error)());
+            }
+            self.bytes.read(range, buf)
+        }
+    }
+
+    #[test]
+    fn test_retry_storage() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let flaky = |failures| Flaky {
+            bytes: compressed.clone(),
+            failures,
+            error: || io::ErrorKind::ConnectionReset.into(),
+            attempts: AtomicU32::new(0),
+        };
+        let no_backoff =
+            |storage| RetryStorage::new(storage).backoff(Duration::ZERO, Duration::ZERO);
+
+        let mut index = Vec::new();
+        let storage = no_backoff(flaky(2));
+        let mut d = Decompressor::with_storage(&storage, &mut index).unwrap();
+        assert_eq!(d.get(1500..3500).unwrap(), &input[1500..3500]);
+        drop(d);
+
+        let mut index = Vec::new();
+        let storage = no_backoff(flaky(2)).max_retries(1);
+        let err = Decompressor::with_storage(&storage, &mut index)
+            .map(drop)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+
+        // out of bounds reads are not retried
+        let storage = no_backoff(flaky(0));
+        assert!(storage.read(0..u64::MAX, &mut Vec::new()).is_err());
+        assert_eq!(storage.into_inner().attempts.into_inner(), 1);
+
+        // only server errors and rate limiting are retried
+        let attempts = |error: fn() -> io::Error| {
+            let storage = no_backoff(Flaky { error, ..flaky(1) });
+            let result = storage.read(0..10, &mut Vec::new()).map(drop);
+            (result.is_ok(), storage.into_inner().attempts.into_inner())
+        };
+        assert_eq!(attempts(|| Error::HttpStatus(503).into()), (true, 2));
+        assert_eq!(attempts(|| Error::HttpStatus(429).into()), (true, 2));
+        assert_eq!(attempts(|| Error::HttpStatus(404).into()), (false, 1));
+        assert_eq!(attempts(|| Error::Cancelled.into()), (false, 1));
+        assert_eq!(attempts(|| io::Error::other("unknown")), (false, 1));
+    }
+}
@@ -0,0 +1,25 @@
+use crate::cache::FrameBuf;
+use crate::format::FrameTable;
+
+/// Reusable buffers for [`Compressor::compress_with_scratch`] and
+/// [`Decompressor::get_with_scratch`].
+///
+/// Compressing many small inputs in a loop allocates a new output buffer and
+/// frame offset table for each of them. Keeping a [`Scratch`] around instead
+/// recycles those allocations across calls. Likewise, reads with a [`Scratch`]
+/// reuse its output and frame buffers.
+///
+/// [`Compressor::compress_with_scratch`]: crate::Compressor::compress_with_scratch
+/// [`Decompressor::get_with_scratch`]: crate::Decompressor::get_with_scratch
+#[derive(Debug, Default)]
+pub struct Scratch {
+    pub(crate) buf: Vec<u8>,
+    pub(crate) table: FrameTable,
+    pub(crate) frame_buf: FrameBuf,
+}
+
+impl Scratch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
@@ -0,0 +1,289 @@
+//! Interoperability with the upstream `zstd` [seekable format].
+//!
+//! A seekable archive consists of regular `zstd` frames, including their magic
+//! bytes, followed by a seek table in a skippable frame:
+//!
+//! - `magic: u32`: The [`SKIPPABLE_MAGIC`] of the skippable frame.
+//! - `frame_size: u32`: The size of the remaining skippable frame.
+//! - For each frame, its `u32` compressed and `u32` decompressed size. With the
+//!   [`CHECKSUM_FLAG`], these are followed by the lower 32 bits of the XXH64
+//!   checksum of the decompressed frame.
+//! - `num_frames: u32`, followed by the `u8` descriptor and the [`SEEKABLE_MAGIC`].
+//!
+//! All integers are stored in little-endian byte order.
+//!
+//! The seekable format allows frames of arbitrary decompressed size. As archives
+//! use a fixed frame size, only seekable archives in which all frames but the
+//! last one have the same decompressed size can be read.
+//!
+//! [seekable format]: https://github.com/facebook/zstd/blob/dev/contrib/seekable_format/zstd_seekable_compression_format.md
+
+use std::io;
+
+use crate::format::Archive;
+use crate::{Checksum, Compressor, EncoderSettings, Error, ProgressInfo};
+
+/// The magic number of the skippable frame containing the seek table.
+const SKIPPABLE_MAGIC: u32 = 0x184D2A5E;
+/// The magic number at the very end of a seekable archive.
+const SEEKABLE_MAGIC: u32 = 0x8F92EAB1;
+/// The bit of the descriptor signalling that the seek table contains checksums.
+const CHECKSUM_FLAG: u8 = 1 << 7;
+/// The reserved bits of the descriptor, which have to be zero.
+const RESERVED_FLAGS: u8 = 0x7c;
+/// The size of the skippable frame header.
+const SKIPPABLE_HEADER_SIZE: usize = 8;
+/// The size of the seek table footer.
+const FOOTER_SIZE: usize = 9;
+
+impl Compressor<'_> {
+    /// Compresses `input` into the upstream `zstd` seekable format.
+    ///
+    /// In contrast to the native archive layout, the result is a valid `.zst`
+    /// file that any `zstd` decoder can decompress as a whole, and that other
+    /// implementations of the seekable format can read randomly. Each frame
+    /// includes the `zstd` magic bytes and a frame checksum, and frames are
+    /// never stored uncompressed. With a [`checksum`](Self::checksum), the seek
+    /// table contains XXH64 checksums of each frame, regardless of the configured
+    /// algorithm. Metadata and the other index options are not supported by the
+    /// seekable format, and are ignored.
+    ///
+    /// [`Decompressor::new`](crate::Decompressor::new) detects and reads the
+    /// seekable format automatically. An embedded [`dictionary`](Self::dictionary)
+    /// can not be represented, and results in an error.
+    pub fn compress_seekable(&mut self, input: &[u8]) -> Result<Vec<u8>, Error> {
+        if self.embedded_dictionary().is_some() {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the zstd seekable format can not embed a dictionary",
+            )));
+        }
+        let frame_size = self.frame_size.resolve(Some(input.len()));
+        let with_checksums = self.checksum != Checksum::None;
+        let mut encoder = EncoderSettings {
+            checksum: Checksum::None,
+            stored_frames: false,
+            holes: false,
+            zstd_frames: true,
+            ..self.encoder_settings()
+        }
+        .encoder()?;
+
+        let num_frames = input.len().div_ceil(frame_size);
+        let mut progress = ProgressInfo::new(Some(num_frames), Some(input.len() as u64));
+        let entry_size = if with_checksums { 12 } else { 8 };
+        let mut seek_table = Vec::with_capacity(num_frames * entry_size + FOOTER_SIZE);
+        let mut buf = Vec::with_capacity(zstd_bound(input.len(), frame_size));
+
+        for source in input.chunks(frame_size) {
+            let start = buf.len();
+            encoder.compress_frame(source, &mut buf)?;
+            let compressed_len = buf.len() - start;
+            progress.frame_done(source.len(), compressed_len, self.progress.as_mut())?;
+
+            seek_table.extend_from_slice(&frame_len(compressed_len)?.to_le_bytes());
+            seek_table.extend_from_slice(&frame_len(source.len())?.to_le_bytes());
+            if with_checksums {
+                let checksum = xxhash_rust::xxh64::xxh64(source, 0) as u32;
+                seek_table.extend_from_slice(&checksum.to_le_bytes());
+            }
+        }
+
+        seek_table.extend_from_slice(&frame_len(num_frames)?.to_le_bytes());
+        seek_table.push(if with_checksums { CHECKSUM_FLAG } else { 0 });
+        seek_table.extend_from_slice(&SEEKABLE_MAGIC.to_le_bytes());
+
+        buf.reserve(SKIPPABLE_HEADER_SIZE + seek_table.len());
+        buf.extend_from_slice(&SKIPPABLE_MAGIC.to_le_bytes());
+        buf.extend_from_slice(&frame_len(seek_table.len())?.to_le_bytes());
+        buf.extend_from_slice(&seek_table);
+        Ok(buf)
+    }
+}
+
+/// A rough upper bound of the compressed size of `input_len` bytes in frames of `frame_size`.
+fn zstd_bound(input_len: usize, frame_size: usize) -> usize {
+    crate::zstd::compress_bound(frame_size.min(input_len)) * input_len.div_ceil(frame_size)
+}
+
+/// Converts a length to the `u32` used by the seek table.
+fn frame_len(len: usize) -> Result<u32, Error> {
+    len.try_into().map_err(|_| {
+        Error::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "frame too large for the zstd seekable format",
+        ))
+    })
+}
+
+/// Returns `true` if `bytes` end with the magic number of the seekable format.
+pub fn is_seekable(bytes: &[u8]) -> bool {
+    bytes
+        .last_chunk::<4>()
+        .is_some_and(|magic| u32::from_le_bytes(*magic) == SEEKABLE_MAGIC)
+}
+
+/// Parses the seek table at the end of a seekable archive.
+///
+/// The checksums of the seek table are not verified. `zstd` verifies the
+/// checksums of the frames themselves instead, if present.
+pub fn parse(bytes: &[u8]) -> Option<Archive<'_>> {
+    let table_start = bytes.len().checked_sub(table_len(bytes)?)?;
+    let (zstd_buf, table) = bytes.split_at(table_start);
+    let (frame_size, input_len, frame_offsets) = parse_table(table, zstd_buf.len())?;
+    Some(Archive::from_zstd_frames(
+        frame_size,
+        input_len,
+        frame_offsets,
+        zstd_buf,
+    ))
+}
+
+/// Returns the number of entries in the seek table, and the size of each entry,
+/// from the footer at the end of `bytes`.
+fn parse_footer(bytes: &[u8]) -> Option<(usize, usize)> {
+    let footer = bytes.last_chunk::<FOOTER_SIZE>()?;
+    let num_frames = u32::from_le_bytes(footer[..4].try_into().ok()?) as usize;
+    let descriptor = footer[4];
+    if descriptor & RESERVED_FLAGS != 0 {
+        return None;
+    }
+    let entry_size = if descriptor & CHECKSUM_FLAG != 0 {
+        12
+    } else {
+        8
+    };
+    Some((num_frames, entry_size))
+}
+
+/// Returns the size of the skippable frame holding the seek table, from the
+/// footer at the end of `bytes`.
+pub fn table_len(bytes: &[u8]) -> Option<usize> {
+    let (num_frames, entry_size) = parse_footer(bytes)?;
+    num_frames
+        .checked_mul(entry_size)?
+        .checked_add(SKIPPABLE_HEADER_SIZE + FOOTER_SIZE)
+}
+
+/// Parses the skippable frame holding the seek table, which follows `frames_len`
+/// bytes of frames.
+///
+/// Returns the frame size, the total decompressed size and the frame offsets.
+pub fn parse_table(table: &[u8], frames_len: usize) -> Option<(usize, usize, Vec<u64>)> {
+    let (num_frames, entry_size) = parse_footer(table)?;
+    let entries_len = num_frames.checked_mul(entry_size)?;
+    let (header, rest) = table.split_first_chunk::<SKIPPABLE_HEADER_SIZE>()?;
+    let entries = rest.get(..entries_len)?;
+    let (magic, len) = header.split_first_chunk::<4>()?;
+    if u32::from_le_bytes(*magic) != SKIPPABLE_MAGIC
+        || u32::from_le_bytes(len.try_into().ok()?) as usize != entries_len + FOOTER_SIZE
+        || rest.len() != entries_len + FOOTER_SIZE
+    {
+        return None;
+    }
+
+    let mut frame_offsets = Vec::with_capacity(num_frames + 1);
+    let mut offset = 0u64;
+    let mut input_len = 0usize;
+    let mut frame_size = 0;
+    for (i, entry) in entries.chunks_exact(entry_size).enumerate() {
+        let compressed_len = u32::from_le_bytes(entry[..4].try_into().ok()?);
+        let decompressed_len = u32::from_le_bytes(entry[4..8].try_into().ok()?) as usize;
+        if i == 0 {
+            frame_size = decompressed_len;
+        }
+        // all frames but the last one have to be of the same size
+        let is_last = i == num_frames - 1;
+        if decompressed_len == 0
+            || decompressed_len > frame_size
+            || (!is_last && decompressed_len != frame_size)
+        {
+            return None;
+        }
+        frame_offsets.push(offset);
+        offset = offset.checked_add(compressed_len as u64)?;
+        input_len = input_len.checked_add(decompressed_len)?;
+    }
+    frame_offsets.push(offset);
+    if offset != frames_len as u64 {
+        return None;
+    }
+    Some((frame_size.max(1), input_len, frame_offsets))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Decompressor;
+
+    #[test]
+    fn test_seekable() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 13) as u8).collect();
+
+        for checksum in [Checksum::None, Checksum::Crc32] {
+            let compressed = Compressor::new()
+                .frame_size(1000)
+                .checksum(checksum)
+                .compress_seekable(&input)
+                .unwrap();
+            let footer = &compressed[compressed.len() - FOOTER_SIZE..];
+            assert_eq!(footer[..4], 10u32.to_le_bytes());
+            assert_eq!(footer[4] == CHECKSUM_FLAG, checksum != Checksum::None);
+
+            // the whole archive is a regular `zstd` stream
+            assert_eq!(::zstd::decode_all(&compressed[..]).unwrap(), input);
+
+            let mut d = Decompressor::new(&compressed).unwrap();
+            assert_eq!(d.frame_size(), 1000);
+            assert_eq!(d.get(..).unwrap(), input);
+            assert_eq!(d.get(1234..5678).unwrap(), &input[1234..5678]);
+            assert_eq!(d.get(9999..).unwrap(), &input[9999..]);
+        }
+
+        let empty = Compressor::new().compress_seekable(&[]).unwrap();
+        assert_eq!(::zstd::decode_all(&empty[..]).unwrap(), []);
+        let mut d = Decompressor::new(&empty).unwrap();
+        assert_eq!(d.get(..).unwrap(), []);
+
+        let dictionary = vec![0; 100];
+        let mut compressor = Compressor::new().dictionary(&dictionary);
+        assert!(compressor.compress_seekable(&input).is_err());
+    }
+
+    #[test]
+    fn test_variable_frame_sizes() {
+        let frames = [&b"hello "[..], b"world", b"!"];
+        let mut compressed = Vec::new();
+        let mut seek_table = Vec::new();
+        for frame in frames {
+            let frame = ::zstd::encode_all(frame, 0).unwrap();
+            compressed.extend_from_slice(&frame);
+            seek_table.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            seek_table.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        }
+        let write_archive = |seek_table: &[u8]| {
+            let mut archive = compressed.clone();
+            archive.extend_from_slice(&SKIPPABLE_MAGIC.to_le_bytes());
+            archive.extend_from_slice(&(seek_table.len() as u32 + 9).to_le_bytes());
+            archive.extend_from_slice(seek_table);
+            archive.extend_from_slice(&3u32.to_le_bytes());
+            archive.push(0);
+            archive.extend_from_slice(&SEEKABLE_MAGIC.to_le_bytes());
+            archive
+        };
+
+        // the decompressed sizes do not match
+        let mut table = seek_table.clone();
+        for (i, frame) in frames.iter().enumerate() {
+            table[i * 8 + 4..i * 8 + 8].copy_from_slice(&(frame.len() as u32).to_le_bytes());
+        }
+        assert!(Decompressor::new(&write_archive(&table)).is_err());
+
+        let mut table = seek_table.clone();
+        for i in 0..3 {
+            let len = if i == 2 { 1 } else { 6 };
+            table[i * 8 + 4..i * 8 + 8].copy_from_slice(&(len as u32).to_le_bytes());
+        }
+        assert!(Decompressor::new(&write_archive(&table)).is_ok());
+    }
+}
@@ -0,0 +1,305 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use crate::eof;
+
+/// The storage backend holding the bytes of an archive.
+///
+/// A [`Decompressor`](crate::Decompressor) only reads the index of an archive
+/// up front, and then reads the compressed frames it needs from the storage on
+/// demand. In-memory archives are just a byte slice, but other backends can read
+/// from files or remote locations instead.
+///
+/// Storage is shared by all reads of a [`Decompressor`](crate::Decompressor),
+/// including concurrent reads through `&self`, which is why it has to be [`Sync`].
+pub trait Storage: Send + Sync {
+    /// The total size of the archive in bytes.
+    fn len(&self) -> u64;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the bytes in `range`.
+    ///
+    /// In-memory storage returns the bytes directly, while other backends read
+    /// them into `buf`, replacing its contents, and return that. Ranges that are
+    /// out of bounds result in an [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) error.
+    fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]>;
+
+    /// Hints that the compressed frames in `ranges` are about to be read, in order.
+    ///
+    /// This is called before reads spanning many frames, so that backends can
+    /// submit the reads as a batch, for example using `io_uring`, and overlap
+    /// them with the decompression of the preceding frames. The default does nothing.
+    fn prefetch(&self, ranges: &[Range<u64>]) {
+        let _ = ranges;
+    }
+}
+
+impl Storage for [u8] {
+    fn len(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read<'a>(&'a self, range: Range<u64>, _buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+        let start = usize::try_from(range.start).map_err(|_| eof())?;
+        let end = usize::try_from(range.end).map_err(|_| eof())?;
+        self.get(start..end).ok_or_else(eof)
+    }
+}
+
+impl Storage for Vec<u8> {
+    fn len(&self) -> u64 {
+        self.as_slice().len() as u64
+    }
+
+    fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+        self.as_slice().read(range, buf)
+    }
+}
+
+impl<S: Storage + ?Sized> Storage for &S {
+    fn len(&self) -> u64 {
+        (**self).len()
+    }
+
+    fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+        (**self).read(range, buf)
+    }
+
+    fn prefetch(&self, ranges: &[Range<u64>]) {
+        (**self).prefetch(ranges)
+    }
+}
+
+impl<S: Storage + ?Sized> Storage for Box<S> {
+    fn len(&self) -> u64 {
+        (**self).len()
+    }
+
+    fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+        (**self).read(range, buf)
+    }
+
+    fn prefetch(&self, ranges: &[Range<u64>]) {
+        (**self).prefetch(ranges)
+    }
+}
+
+impl<S: Storage + ?Sized> Storage for Arc<S> {
+    fn len(&self) -> u64 {
+        (**self).len()
+    }
+
+    fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+        (**self).read(range, buf)
+    }
+
+    fn prefetch(&self, ranges: &[Range<u64>]) {
+        (**self).prefetch(ranges)
+    }
+}
+
+/// [`Storage`] reading from a [`File`](std::fs::File), or any other [`Read`] + [`Seek`] source.
+///
+/// Every read seeks to the requested range and reads it into a buffer. The
+/// reader is behind a [`Mutex`], so concurrent reads are serialized, but the
+/// decompression itself still happens in parallel.
+#[derive(Debug)]
+pub struct ReaderStorage<R> {
+    reader: Mutex<R>,
+    len: u64,
+}
+
+impl<R: Read + Seek> ReaderStorage<R> {
+    /// Creates storage for the archive spanning all of `reader`.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            reader: Mutex::new(reader),
+            len,
+        })
+    }
+
+    /// Returns the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+            .into_inner()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl<R: Read + Seek + Send> Storage for ReaderStorage<R> {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+        if range.start > range.end || range.end > self.len {
+            return Err(eof());
+        }
+        let len = usize::try_from(range.end - range.start).map_err(|_| eof())?;
+        buf.clear();
+        buf.resize(len, 0);
+        // a reader that panicked can still be seeked to the correct position
+        let mut reader = self.reader.lock().unwrap_or_else(|err| err.into_inner());
+        reader.seek(SeekFrom::Start(range.start))?;
+        reader.read_exact(buf)?;
+        Ok(buf)
+    }
+}
+
+/// Reads `range` of `storage` into `buf`, replacing its contents.
+pub(crate) fn read_to_vec(
+    storage: &dyn Storage,
+    range: Range<u64>,
+    buf: &mut Vec<u8>,
+) -> io::Result<()> {
+    let mut scratch = Vec::new();
+    let bytes = storage.read(range, &mut scratch)?;
+    buf.clear();
+    buf.extend_from_slice(bytes);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Checksum, Compressor, Decompressor};
+
+    /// Storage that copies the bytes it reads, and keeps track of the ranges read
+    /// and prefetched.
+    struct Recording {
+        bytes: Vec<u8>,
+        reads: Mutex<Vec<Range<u64>>>,
+        prefetched: Mutex<Vec<Range<u64>>>,
+    }
+
+    impl Storage for Recording {
+        fn len(&self) -> u64 {
+            self.bytes.len() as u64
+        }
+
+        fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+            self.reads.lock().unwrap().push(range.clone());
+            read_to_vec(&self.bytes, range, buf)?;
+            Ok(buf)
+        }
+
+        fn prefetch(&self, ranges: &[Range<u64>]) {
+            self.prefetched.lock().unwrap().extend_from_slice(ranges);
+        }
+    }
+
+    #[test]
+    fn test_storage() {
+        // barely compressible, so that the frames are a lot larger than the index
+        let input: Vec<u8> = (0..100_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        let mut zeroes = input.clone();
+        zeroes[20_000..50_000].fill(0);
+        let compressors = [
+            Compressor::new(),
+            Compressor::new()
+                .checksum(Checksum::Crc32)
+                .wide_offsets(true),
+            Compressor::new().trailer_index(true).frame_alignment(512),
+            Compressor::new().compact_index(true).holes(true),
+            Compressor::new().sparse_index(4).frame_alignment(64),
+            Compressor::new()
+                .dictionary(&input[..1000])
+                .metadata("key", "value"),
+        ];
+
+        for compressor in compressors {
+            let mut compressor = compressor.frame_size(1000);
+            for compressed in [
+                compressor.compress(&zeroes).unwrap(),
+                compressor.compress_seekable(&input).unwrap_or_default(),
+            ] {
+                if compressed.is_empty() {
+                    continue;
+                }
+                let expected = Decompressor::new(&compressed).unwrap().get(..).unwrap();
+                let storage = Recording {
+                    bytes: compressed.clone(),
+                    reads: Mutex::default(),
+                    prefetched: Mutex::default(),
+                };
+                let mut index = Vec::new();
+                let mut d = Decompressor::with_storage(&storage, &mut index).unwrap();
+                // only the index has been read
+                let index_len: u64 = storage
+                    .reads
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|r| r.end - r.start)
+                    .sum();
+                assert!(index_len < compressed.len() as u64 / 2);
+
+                storage.reads.lock().unwrap().clear();
+                assert_eq!(d.get(12_345..12_500).unwrap(), &expected[12_345..12_500]);
+                let reads = std::mem::take(&mut *storage.reads.lock().unwrap());
+                // a single frame, or its whole group with a sparse index
+                assert_eq!(reads.len(), 1);
+                assert!(reads[0].end - reads[0].start < 4 * 1100);
+
+                assert!(storage.prefetched.lock().unwrap().is_empty());
+                assert_eq!(d.get(..).unwrap(), expected);
+                // the frames are read in the order they were prefetched
+                let prefetched = std::mem::take(&mut *storage.prefetched.lock().unwrap());
+                let mut reads = std::mem::take(&mut *storage.reads.lock().unwrap());
+                reads.dedup();
+                assert_eq!(prefetched, reads);
+                assert_eq!(d.get(99_000..).unwrap(), &expected[99_000..]);
+                assert!(d.get(99_000..100_001).is_err());
+            }
+        }
+
+        let mut index = Vec::new();
+        let invalid = vec![0; 100];
+        let err = Decompressor::from_reader(io::Cursor::new(&invalid), &mut index).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = Decompressor::with_storage(invalid.as_slice(), &mut index).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_reader_storage() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new()
+            .frame_size(1000)
+            .checksum(Checksum::Crc32)
+            .compress(&input)
+            .unwrap();
+        let mut index = Vec::new();
+        let d = Decompressor::from_reader(io::Cursor::new(&compressed), &mut index).unwrap();
+        let d = crate::ConcurrentDecompressor::new(d);
+
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let (d, input) = (&d, &input);
+                scope.spawn(move || {
+                    for i in 0..50 {
+                        let start = (thread * 997 + i * 499) % 8_500;
+                        let range = start..start + 1_500;
+                        assert_eq!(d.get(range.clone()).unwrap(), &input[range]);
+                    }
+                });
+            }
+        });
+        assert_eq!(d.get(..).unwrap(), input);
+
+        let storage = ReaderStorage::new(io::Cursor::new(&compressed)).unwrap();
+        assert_eq!(storage.len(), compressed.len() as u64);
+        let mut buf = Vec::new();
+        assert_eq!(storage.read(10..20, &mut buf).unwrap(), &compressed[10..20]);
+        assert!(storage
+            .read(10..compressed.len() as u64 + 1, &mut buf)
+            .is_err());
+    }
+}
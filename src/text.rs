@@ -0,0 +1,116 @@
+use std::io;
+use std::ops::RangeBounds;
+
+use crate::Decompressor;
+
+/// The maximum length of a UTF-8 encoded character.
+const MAX_CHAR_LEN: usize = 4;
+
+/// Whether `byte` continues a multi-byte UTF-8 character, instead of starting one.
+fn is_continuation(byte: u8) -> bool {
+    byte & 0b1100_0000 == 0b1000_0000
+}
+
+impl Decompressor<'_> {
+    /// Decompresses `range` as UTF-8 text.
+    ///
+    /// Fails with [`InvalidData`](io::ErrorKind::InvalidData) if the range is
+    /// not valid UTF-8, which includes ranges that split a character in half.
+    /// Use [`floor_char_boundary`](Self::floor_char_boundary) and
+    /// [`ceil_char_boundary`](Self::ceil_char_boundary) to snap the range to
+    /// character boundaries first.
+    pub fn get_str<R>(&mut self, range: R) -> io::Result<String>
+    where
+        R: RangeBounds<usize>,
+    {
+        String::from_utf8(self.get(range)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Returns the closest UTF-8 character boundary at or before `offset`.
+    ///
+    /// Offsets past the end of the archive are clamped to its length. Within
+    /// invalid UTF-8, the `offset` itself is returned.
+    pub fn floor_char_boundary(&mut self, offset: usize) -> io::Result<usize> {
+        if offset >= self.len() {
+            return Ok(self.len());
+        }
+        let start = offset.saturating_sub(MAX_CHAR_LEN - 1);
+        let bytes = self.get_ref(start..offset + 1)?;
+        Ok(bytes
+            .iter()
+            .rposition(|&byte| !is_continuation(byte))
+            .map_or(offset, |idx| start + idx))
+    }
+
+    /// Returns the closest UTF-8 character boundary at or after `offset`.
+    ///
+    /// Offsets past the end of the archive are clamped to its length. Within
+    /// invalid UTF-8, the `offset` itself is returned.
+    pub fn ceil_char_boundary(&mut self, offset: usize) -> io::Result<usize> {
+        let len = self.len();
+        if offset >= len {
+            return Ok(len);
+        }
+        let end = offset.saturating_add(MAX_CHAR_LEN).min(len);
+        let bytes = self.get_ref(offset..end)?;
+        Ok(
+            match bytes.iter().position(|&byte| !is_continuation(byte)) {
+                Some(idx) => offset + idx,
+                // the archive ends within the character
+                None if end == len => len,
+                None => offset,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Compressor;
+
+    #[test]
+    fn test_char_boundaries() {
+        let text = "héllo wörld, 日本語 🎉 ".repeat(100);
+        let compressed = Compressor::new()
+            .frame_size(7)
+            .compress(text.as_bytes())
+            .unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+
+        for offset in 0..=text.len() + 2 {
+            let floor = d.floor_char_boundary(offset).unwrap();
+            let ceil = d.ceil_char_boundary(offset).unwrap();
+            let expected_floor = (0..=offset.min(text.len()))
+                .rev()
+                .find(|&idx| text.is_char_boundary(idx))
+                .unwrap();
+            let expected_ceil = (offset.min(text.len())..=text.len())
+                .find(|&idx| text.is_char_boundary(idx))
+                .unwrap();
+            assert_eq!(floor, expected_floor);
+            assert_eq!(ceil, expected_ceil);
+        }
+
+        for (start, end) in [(0, 1), (2, 20), (15, 27), (100, 1000), (1, text.len())] {
+            let start = d.floor_char_boundary(start).unwrap();
+            let end = d.ceil_char_boundary(end).unwrap();
+            assert_eq!(d.get_str(start..end).unwrap(), &text[start..end]);
+        }
+        assert_eq!(d.get_str(..).unwrap(), text);
+
+        // the range splits the `é`
+        let err = d.get_str(0..2).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(d.get_str(..text.len() + 1).is_err());
+
+        // the archive ends within a character
+        let truncated = &"日本".as_bytes()[..5];
+        let compressed = Compressor::new().compress(truncated).unwrap();
+        let mut d = Decompressor::new(&compressed).unwrap();
+        assert_eq!(d.floor_char_boundary(4).unwrap(), 3);
+        assert_eq!(d.ceil_char_boundary(4).unwrap(), 5);
+        assert_eq!(d.get_str(..3).unwrap(), "日");
+    }
+}
@@ -0,0 +1,588 @@
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::ops::Range;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+use crate::{eof, Storage};
+
+/// The default number of prefetched reads that are in flight at once.
+const DEFAULT_QUEUE_DEPTH: u32 = 64;
+
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x800_0000;
+const IORING_OFF_SQES: libc::off_t = 0x1000_0000;
+const IORING_ENTER_GETEVENTS: libc::c_uint = 1;
+const IORING_OP_READ: u8 = 22;
+
+/// [`Storage`] reading a file with `io_uring`, which submits the reads of a
+/// [`prefetch`](Storage::prefetch) as a batch.
+///
+/// Reads spanning many frames hint the compressed frames they need up front.
+/// Instead of reading those frames one by one with `pread`, they are all
+/// submitted to the kernel at once, and are read in the background while the
+/// preceding frames are decompressed. Each read of a frame then only waits for
+/// its own read to complete.
+///
+/// Reads that were not prefetched, and prefetched reads that failed, use a
+/// regular `pread`. So do all reads if the kernel does not support `io_uring`,
+/// or it is disabled, like in some containers. Prefetched frames that were not
+/// read are discarded by the next prefetch.
+///
+/// The storage can be shared between threads. Waiting for a prefetched read
+/// does not block reads of other ranges, and reads skip the ring altogether
+/// while nothing is prefetched.
+///
+/// This is only available on Linux with the `io-uring` feature.
+pub struct UringStorage {
+    /// The ring, unless `io_uring` is not available.
+    ring: Option<Uring>,
+    file: File,
+    len: u64,
+}
+
+impl std::fmt::Debug for UringStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UringStorage")
+            .field("file", &self.file)
+            .field("len", &self.len)
+            .field("io_uring", &self.ring.is_some())
+            .finish()
+    }
+}
+
+impl UringStorage {
+    /// Opens the file at `path`, with up to 64 prefetched reads in flight.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::with_queue_depth(path, DEFAULT_QUEUE_DEPTH)
+    }
+
+    /// Opens the file at `path`, with up to `depth` prefetched reads in flight.
+    pub fn with_queue_depth(path: impl AsRef<Path>, depth: u32) -> io::Result<Self> {
+        if depth == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the queue depth has to be non-zero",
+            ));
+        }
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            ring: Ring::new(depth).ok().map(Uring::new),
+            file,
+            len,
+        })
+    }
+
+    fn pread(&self, range: Range<u64>, buf: &mut Vec<u8>) -> io::Result<()> {
+        let len = usize::try_from(range.end - range.start).map_err(|_| eof())?;
+        buf.clear();
+        buf.resize(len, 0);
+        self.file.read_exact_at(buf, range.start)
+    }
+}
+
+impl Storage for UringStorage {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read<'a>(&'a self, range: Range<u64>, buf: &'a mut Vec<u8>) -> io::Result<&'a [u8]> {
+        if range.start > range.end || range.end > self.len {
+            return Err(eof());
+        }
+        if let Some(bytes) = self.ring.as_ref().and_then(|ring| ring.take(&range)) {
+            *buf = bytes;
+            return Ok(buf);
+        }
+        self.pread(range, buf)?;
+        Ok(buf)
+    }
+
+    fn prefetch(&self, ranges: &[Range<u64>]) {
+        let Some(ring) = &self.ring else {
+            return;
+        };
+        let mut guard = lock(&ring.ring);
+        if !guard.waiting {
+            guard.reap();
+        }
+        // the completed reads of an earlier prefetch were not read in time
+        guard.pending.retain(|pending| pending.result.is_none());
+        let ranges: Vec<_> = ranges
+            .iter()
+            .filter(|range| {
+                range.start < range.end
+                    && range.end <= self.len
+                    && range.end - range.start <= i32::MAX as u64
+                    && !guard.pending.iter().any(|pending| pending.range == **range)
+            })
+            .cloned()
+            .collect();
+        // a failed submission leaves the reads to `pread`
+        let _ = guard.submit(self.file.as_raw_fd(), ranges);
+        ring.prefetched
+            .store(guard.pending.len(), Ordering::Release);
+    }
+}
+
+/// The ring shared by all reads of the storage.
+///
+/// The lock is only held to submit and reap reads, never while waiting for
+/// them to complete. One thread at a time waits in the kernel, and is the only
+/// one reaping completions meanwhile, so the completion it waits for cannot be
+/// reaped behind its back. The other threads wait for it to wake them up.
+struct Uring {
+    ring: Mutex<Ring>,
+    /// Notified when the waiting thread has reaped the completed reads.
+    reaped: Condvar,
+    /// The number of prefetched reads that were not taken or discarded yet.
+    prefetched: AtomicUsize,
+}
+
+impl Uring {
+    fn new(ring: Ring) -> Self {
+        Self {
+            ring: Mutex::new(ring),
+            reaped: Condvar::new(),
+            prefetched: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes the prefetched read of `range`, waiting for it to complete, or
+    /// returns `None` if it was not prefetched or failed.
+    fn take(&self, range: &Range<u64>) -> Option<Vec<u8>> {
+        if self.prefetched.load(Ordering::Acquire) == 0 {
+            return None;
+        }
+        let mut ring = lock(&self.ring);
+        loop {
+            if !ring.waiting {
+                ring.reap();
+            }
+            let idx = ring.pending.iter().position(|p| p.range == *range)?;
+            if ring.pending[idx].result.is_some() {
+                let pending = ring.pending.remove(idx);
+                self.prefetched.store(ring.pending.len(), Ordering::Release);
+                let len = pending.buf.len();
+                return (pending.result == Some(len as i32)).then_some(pending.buf);
+            }
+            if ring.waiting {
+                ring = self
+                    .reaped
+                    .wait(ring)
+                    .unwrap_or_else(|err| err.into_inner());
+                continue;
+            }
+            // reads the kernel did not consume yet would never complete
+            if ring.unsubmitted > 0 {
+                ring.enter(0).ok()?;
+                if ring.unsubmitted > 0 {
+                    return None;
+                }
+            }
+            ring.waiting = true;
+            let fd = ring.fd.as_raw_fd();
+            drop(ring);
+            // the ring, and with it `fd`, lives as long as `self`
+            let waited = enter(fd, 0, 1);
+            ring = lock(&self.ring);
+            ring.waiting = false;
+            ring.reap();
+            self.reaped.notify_all();
+            waited.ok()?;
+        }
+    }
+}
+
+/// Locks the ring, which stays usable after a panic.
+fn lock(ring: &Mutex<Ring>) -> MutexGuard<'_, Ring> {
+    ring.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// A read submitted to the ring, whose buffer the kernel writes into.
+struct Pending {
+    id: u64,
+    range: Range<u64>,
+    buf: Vec<u8>,
+    /// The number of bytes read or the negated error, once the read completed.
+    result: Option<i32>,
+}
+
+/// An `io_uring` instance with its mapped queues, and the reads submitted to it.
+struct Ring {
+    fd: OwnedFd,
+    params: Params,
+    sq: Mmap,
+    cq: Mmap,
+    sqes: Mmap,
+    /// The number of entries in the submission queue the kernel has not consumed yet.
+    unsubmitted: u32,
+    /// Whether a thread is waiting for completions without holding the lock,
+    /// in which case only that thread reaps them.
+    waiting: bool,
+    pending: Vec<Pending>,
+    next_id: u64,
+}
+
+impl Ring {
+    fn new(entries: u32) -> io::Result<Self> {
+        let mut params = Params::default();
+        // SAFETY: `params` is a valid `io_uring_params` struct
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_setup,
+                entries as libc::c_uint,
+                &mut params as *mut Params,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the syscall returned a new file descriptor
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * 4;
+        let cq_len =
+            params.cq_off.cqes as usize + params.cq_entries as usize * mem::size_of::<Cqe>();
+        let sqes_len = params.sq_entries as usize * mem::size_of::<Sqe>();
+        Ok(Self {
+            sq: Mmap::new(&fd, sq_len, IORING_OFF_SQ_RING)?,
+            cq: Mmap::new(&fd, cq_len, IORING_OFF_CQ_RING)?,
+            sqes: Mmap::new(&fd, sqes_len, IORING_OFF_SQES)?,
+            fd,
+            params,
+            unsubmitted: 0,
+            waiting: false,
+            pending: Vec::new(),
+            next_id: 0,
+        })
+    }
+
+    /// Submits reads of `ranges` of the file `fd`, as many as fit into the queue.
+    fn submit(&mut self, fd: RawFd, ranges: Vec<Range<u64>>) -> io::Result<()> {
+        let in_flight = self.pending.iter().filter(|p| p.result.is_none()).count();
+        let free = (self.params.sq_entries as usize).saturating_sub(in_flight);
+        let off = self.params.sq_off;
+        // SAFETY: the offsets are within the mapped submission queue
+        let mask = unsafe { *self.sq.at::<u32>(off.ring_mask) };
+        let tail = unsafe { &*self.sq.at::<AtomicU32>(off.tail) };
+        // only this side of the queue writes the tail
+        let mut next = tail.load(Ordering::Relaxed);
+        for range in ranges.into_iter().take(free) {
+            let mut buf = vec![0; (range.end - range.start) as usize];
+            let id = self.next_id;
+            self.next_id += 1;
+            let idx = next & mask;
+            let sqe = Sqe {
+                opcode: IORING_OP_READ,
+                fd,
+                off: range.start,
+                addr: buf.as_mut_ptr() as u64,
+                len: buf.len() as u32,
+                user_data: id,
+                ..Sqe::default()
+            };
+            // SAFETY: `idx` is within the mapped entries, and the buffer is
+            // kept alive in `pending` until the read completes
+            unsafe {
+                self.sqes
+                    .at::<Sqe>(idx * mem::size_of::<Sqe>() as u32)
+                    .write(sqe);
+                self.sq.at::<u32>(off.array + idx * 4).write(idx);
+            }
+            self.pending.push(Pending {
+                id,
+                range,
+                buf,
+                result: None,
+            });
+            next = next.wrapping_add(1);
+            self.unsubmitted += 1;
+        }
+        tail.store(next, Ordering::Release);
+        self.enter(0)
+    }
+
+    /// Records the results of all completed reads.
+    fn reap(&mut self) {
+        let off = self.params.cq_off;
+        // SAFETY: the offsets are within the mapped completion queue
+        let mask = unsafe { *self.cq.at::<u32>(off.ring_mask) };
+        let head = unsafe { &*self.cq.at::<AtomicU32>(off.head) };
+        let tail = unsafe { &*self.cq.at::<AtomicU32>(off.tail) }.load(Ordering::Acquire);
+        let mut next = head.load(Ordering::Relaxed);
+        while next != tail {
+            let idx = next & mask;
+            // SAFETY: the kernel has written the entries up to `tail`
+            let cqe = unsafe {
+                self.cq
+                    .at::<Cqe>(off.cqes + idx * mem::size_of::<Cqe>() as u32)
+                    .read()
+            };
+            if let Some(pending) = self.pending.iter_mut().find(|p| p.id == cqe.user_data) {
+                pending.result = Some(cqe.res);
+            }
+            next = next.wrapping_add(1);
+        }
+        head.store(next, Ordering::Release);
+    }
+
+    /// Submits the queued reads, and waits for `min_complete` reads to complete.
+    fn enter(&mut self, min_complete: u32) -> io::Result<()> {
+        self.unsubmitted -= enter(self.fd.as_raw_fd(), self.unsubmitted, min_complete)?;
+        Ok(())
+    }
+}
+
+/// Submits `to_submit` queued reads to the ring `fd`, and waits for
+/// `min_complete` reads to complete. Returns the number of submitted reads.
+fn enter(fd: RawFd, to_submit: u32, min_complete: u32) -> io::Result<u32> {
+    let flags = if min_complete > 0 {
+        IORING_ENTER_GETEVENTS
+    } else {
+        0
+    };
+    loop {
+        // SAFETY: the arguments match `io_uring_enter`, without a signal mask
+        let submitted = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_enter,
+                fd,
+                to_submit as libc::c_uint,
+                min_complete as libc::c_uint,
+                flags,
+                ptr::null::<libc::sigset_t>(),
+                0usize,
+            )
+        };
+        if submitted >= 0 {
+            return Ok(submitted as u32);
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        // the kernel may still write into the buffers of reads in flight
+        loop {
+            self.reap();
+            if self.pending.iter().all(|p| p.result.is_some()) {
+                break;
+            }
+            if self.enter(1).is_err() {
+                mem::forget(mem::take(&mut self.pending));
+                break;
+            }
+        }
+    }
+}
+
+/// A shared memory mapping of a queue of the ring.
+struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// SAFETY: the mapping is owned, and only accessed through the `Mutex` of the ring
+unsafe impl Send for Mmap {}
+
+impl Mmap {
+    fn new(fd: &OwnedFd, len: usize, offset: libc::off_t) -> io::Result<Self> {
+        // SAFETY: a new mapping does not alias any memory
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd.as_raw_fd(),
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { ptr, len })
+    }
+
+    /// Returns a pointer to the value at `offset`, which the caller has to keep in bounds.
+    fn at<T>(&self, offset: u32) -> *mut T {
+        self.ptr.cast::<u8>().wrapping_add(offset as usize).cast()
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: the mapping was created in `Mmap::new`
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
+
+/// `struct io_sqring_offsets`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct SqOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+/// `struct io_cqring_offsets`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct CqOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+/// `struct io_uring_params`
+#[repr(C)]
+#[derive(Debug, Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqOffsets,
+    cq_off: CqOffsets,
+}
+
+/// `struct io_uring_sqe`, with the fields used by reads.
+#[repr(C)]
+#[derive(Debug, Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    rw_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+/// `struct io_uring_cqe`
+#[repr(C)]
+#[derive(Debug)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+const _: () = assert!(mem::size_of::<Params>() == 120);
+const _: () = assert!(mem::size_of::<Sqe>() == 64);
+const _: () = assert!(mem::size_of::<Cqe>() == 16);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Compressor, Decompressor};
+
+    #[test]
+    fn test_uring_storage() {
+        let input: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(1000).compress(&input).unwrap();
+        let path = std::env::temp_dir().join(format!("seezee-test-uring-{}", std::process::id()));
+        std::fs::write(&path, &compressed).unwrap();
+        let storage = UringStorage::open(&path).unwrap();
+        let shallow = UringStorage::with_queue_depth(&path, 1).unwrap();
+        assert!(UringStorage::with_queue_depth(&path, 0).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(storage.len(), compressed.len() as u64);
+        let pending = |storage: &UringStorage| {
+            // some kernels or sandboxes do not support `io_uring`
+            let ring = storage.ring.as_ref()?;
+            Some(lock(&ring.ring).pending.len())
+        };
+
+        // prefetched reads are taken from the ring, in any order
+        let ranges = [10..500, 600..1200, 1200..1300];
+        storage.prefetch(&ranges);
+        assert!(pending(&storage).is_none_or(|pending| pending == 3));
+        let mut buf = Vec::new();
+        for range in ranges.iter().rev() {
+            let expected = &compressed[range.start as usize..range.end as usize];
+            assert_eq!(storage.read(range.clone(), &mut buf).unwrap(), expected);
+        }
+        assert!(pending(&storage).is_none_or(|pending| pending == 0));
+
+        assert_eq!(storage.read(20..30, &mut buf).unwrap(), &compressed[20..30]);
+        let end = compressed.len() as u64;
+        assert!(storage.read(end - 3..end + 1, &mut buf).is_err());
+
+        // only as many reads as the queue depth are in flight
+        shallow.prefetch(&ranges);
+        assert!(pending(&shallow).is_none_or(|pending| pending == 1));
+        for range in ranges.clone() {
+            let expected = &compressed[range.start as usize..range.end as usize];
+            assert_eq!(shallow.read(range, &mut buf).unwrap(), expected);
+        }
+
+        // large reads prefetch their frames
+        let mut index = Vec::new();
+        let mut d = Decompressor::with_storage(&storage, &mut index).unwrap();
+        assert_eq!(d.get(2500..7500).unwrap(), &input[2500..7500]);
+        assert_eq!(d.get(..).unwrap(), input);
+        drop(d);
+        assert!(pending(&storage).is_none_or(|pending| pending == 0));
+
+        // a read waiting for its prefetch does not hold up other reads
+        storage.prefetch(&ranges);
+        std::thread::scope(|scope| {
+            for range in ranges.iter().cycle().take(12) {
+                let (storage, compressed) = (&storage, &compressed);
+                scope.spawn(move || {
+                    let mut buf = Vec::new();
+                    let expected = &compressed[range.start as usize..range.end as usize];
+                    assert_eq!(storage.read(range.clone(), &mut buf).unwrap(), expected);
+                    assert_eq!(storage.read(20..30, &mut buf).unwrap(), &compressed[20..30]);
+                });
+            }
+        });
+        assert!(pending(&storage).is_none_or(|pending| pending == 0));
+        let prefetched = |ring: &Uring| ring.prefetched.load(Ordering::Acquire);
+        assert!(storage
+            .ring
+            .as_ref()
+            .is_none_or(|ring| prefetched(ring) == 0));
+
+        // unread prefetches are discarded
+        storage.prefetch(&[0..100, 100..200]);
+        storage.prefetch(&[200..300, 300..400]);
+        drop(storage);
+    }
+}
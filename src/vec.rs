@@ -0,0 +1,253 @@
+use std::io::{self, Read};
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::ops::RangeBounds;
+
+use watto::Pod;
+
+use crate::{make_range, Compressor, Cursor, Decompressor, Error};
+
+/// A compressed slice of fixed-size [`Pod`] records.
+///
+/// This takes care of translating element indices into byte ranges of the
+/// uncompressed contents, so random access into a compressed table of records
+/// only decompresses the frames covering the requested elements. The elements
+/// are stored in their native byte order.
+pub struct SeeZeeVec<'b, T> {
+    decompressor: Decompressor<'b>,
+    len: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> std::fmt::Debug for SeeZeeVec<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SeeZeeVec")
+            .field("len", &self.len)
+            .field("decompressor", &self.decompressor)
+            .finish()
+    }
+}
+
+impl<'b, T: Pod> SeeZeeVec<'b, T> {
+    /// Compresses `items` into a new archive using `compressor`, which can be
+    /// opened by [`SeeZeeVec::new`].
+    ///
+    /// A frame size that is a multiple of the element size avoids decompressing
+    /// two frames for elements that would otherwise straddle a frame boundary.
+    pub fn compress(compressor: &mut Compressor<'_>, items: &[T]) -> Result<Vec<u8>, Error> {
+        compressor.compress(items.as_bytes())
+    }
+
+    /// Opens the archive in `bytes` as a slice of `T`.
+    ///
+    /// Fails with [`Error::ElementSizeMismatch`] unless the uncompressed length
+    /// is a multiple of the size of `T`.
+    pub fn new(bytes: &'b [u8]) -> Result<Self, Error> {
+        Self::from_decompressor(Decompressor::new(bytes)?)
+    }
+
+    /// Wraps an already opened [`Decompressor`], like one with custom storage or limits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is zero-sized.
+    pub fn from_decompressor(decompressor: Decompressor<'b>) -> Result<Self, Error> {
+        let element_size = mem::size_of::<T>();
+        assert!(element_size > 0, "zero-sized elements are not supported");
+        if !decompressor.len().is_multiple_of(element_size) {
+            return Err(Error::ElementSizeMismatch {
+                len: decompressor.len(),
+                element_size,
+            });
+        }
+        Ok(Self {
+            len: decompressor.len() / element_size,
+            decompressor,
+            _marker: PhantomData,
+        })
+    }
+
+    /// The number of elements.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the element at `index`.
+    pub fn get(&mut self, index: usize) -> io::Result<T> {
+        if index >= self.len {
+            return Err(self.out_of_bounds(index..index.saturating_add(1)));
+        }
+        self.decompressor.get_as(index * mem::size_of::<T>())
+    }
+
+    /// Returns the elements in `range`.
+    pub fn slice<R>(&mut self, range: R) -> io::Result<Vec<T>>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.len)?;
+        if range.start > range.end || range.end > self.len {
+            return Err(self.out_of_bounds(range));
+        }
+        let element_size = mem::size_of::<T>();
+        let byte_len = range.len() * element_size;
+        self.decompressor.check_output_len(byte_len)?;
+
+        let mut items = Vec::<T>::with_capacity(range.len());
+        let spare = items.spare_capacity_mut();
+        // SAFETY: the spare capacity holds at least `byte_len` bytes, which are
+        // only read after `get_uninit` initialized them.
+        let bytes = unsafe {
+            std::slice::from_raw_parts_mut(spare.as_mut_ptr().cast::<MaybeUninit<u8>>(), byte_len)
+        };
+        let start = range.start * element_size;
+        self.decompressor
+            .get_uninit(start..start + byte_len, bytes)?;
+        // SAFETY: all bytes of the elements are initialized, and any bit pattern is a valid `Pod`.
+        unsafe { items.set_len(range.len()) };
+        Ok(items)
+    }
+
+    /// Returns an iterator over all elements, which decompresses each frame only once.
+    pub fn iter(&self) -> SeeZeeVecIter<'_, 'b, T> {
+        SeeZeeVecIter {
+            cursor: self.decompressor.cursor(),
+            remaining: self.len,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the underlying [`Decompressor`].
+    pub fn decompressor(&mut self) -> &mut Decompressor<'b> {
+        &mut self.decompressor
+    }
+
+    pub fn into_inner(self) -> Decompressor<'b> {
+        self.decompressor
+    }
+
+    /// Returns the error for a read of the elements in `range`, which is out of bounds.
+    fn out_of_bounds(&self, range: std::ops::Range<usize>) -> io::Error {
+        Error::RangeOutOfBounds {
+            range,
+            len: self.len,
+        }
+        .into()
+    }
+}
+
+impl<'a, 'b, T: Pod> IntoIterator for &'a SeeZeeVec<'b, T> {
+    type Item = io::Result<T>;
+    type IntoIter = SeeZeeVecIter<'a, 'b, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the elements of a [`SeeZeeVec`].
+#[derive(Debug)]
+pub struct SeeZeeVecIter<'a, 'b, T> {
+    cursor: Cursor<'a, 'b>,
+    remaining: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Pod> Iterator for SeeZeeVecIter<'_, '_, T> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let mut value = MaybeUninit::<T>::zeroed();
+        // SAFETY: the value is zeroed, and any bit pattern is a valid `Pod`.
+        let bytes = unsafe {
+            std::slice::from_raw_parts_mut(value.as_mut_ptr().cast::<u8>(), mem::size_of::<T>())
+        };
+        if let Err(err) = self.cursor.read_exact(bytes) {
+            // the iterator is fused after an error
+            self.remaining = 0;
+            return Some(Err(err));
+        }
+        self.remaining -= 1;
+        // SAFETY: see above.
+        Some(Ok(unsafe { value.assume_init() }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T: Pod> ExactSizeIterator for SeeZeeVecIter<'_, '_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Record {
+        id: u32,
+        value: f32,
+        offset: u64,
+    }
+    // SAFETY: the struct has a stable layout without padding, and its fields are `Pod`.
+    unsafe impl Pod for Record {}
+
+    #[test]
+    fn test_seezee_vec() {
+        let records: Vec<Record> = (0..10_000u32)
+            .map(|id| Record {
+                id,
+                value: id as f32 / 2.0,
+                offset: id as u64 * 100,
+            })
+            .collect();
+        let compressed =
+            SeeZeeVec::compress(&mut Compressor::new().frame_size(1000), &records).unwrap();
+        let mut vec = SeeZeeVec::<Record>::new(&compressed).unwrap();
+        assert_eq!(vec.len(), 10_000);
+        assert!(!vec.is_empty());
+
+        for index in [0, 62, 63, 5_000, 9_999] {
+            assert_eq!(vec.get(index).unwrap(), records[index]);
+        }
+        assert_eq!(vec.slice(60..70).unwrap(), &records[60..70]);
+        assert_eq!(vec.slice(..).unwrap(), records);
+        assert!(vec.slice(9_999..10_001).is_err());
+        let err = vec.get(10_000).unwrap_err();
+        assert!(matches!(
+            err.get_ref().and_then(|err| err.downcast_ref()),
+            Some(Error::RangeOutOfBounds { len: 10_000, .. })
+        ));
+        assert!(vec.get(usize::MAX).is_err());
+
+        let collected: Vec<_> = vec.iter().map(Result::unwrap).collect();
+        assert_eq!(collected, records);
+        assert_eq!(vec.iter().len(), 10_000);
+        assert_eq!((&vec).into_iter().nth(123).unwrap().unwrap(), records[123]);
+
+        // the length has to be a multiple of the element size
+        let compressed = Compressor::new().compress(&[0; 20]).unwrap();
+        assert!(matches!(
+            SeeZeeVec::<u64>::new(&compressed),
+            Err(Error::ElementSizeMismatch {
+                len: 20,
+                element_size: 8
+            })
+        ));
+        let mut vec = SeeZeeVec::<u32>::new(&compressed).unwrap();
+        assert_eq!(vec.slice(..).unwrap(), [0; 5]);
+
+        let empty = SeeZeeVec::compress(&mut Compressor::new(), &[] as &[u64]).unwrap();
+        let vec = SeeZeeVec::<u64>::new(&empty).unwrap();
+        assert!(vec.is_empty());
+        assert_eq!(vec.iter().count(), 0);
+    }
+}
@@ -0,0 +1,245 @@
+use std::io::{self, Read, Write};
+
+use crate::encoder::FrameEncoder;
+use crate::format::{self, FrameTable};
+use crate::progress::{ProgressFn, ProgressInfo};
+use crate::Checksum;
+
+/// A [`Write`] adapter that compresses its input into a seekable archive.
+///
+/// Input is buffered until a full frame is available, which is then compressed
+/// right away. As the frame offset table is located in front of the compressed
+/// frames, those are kept in memory until [`CompressWriter::finish`] writes the
+/// complete archive to the underlying writer. When using a
+/// [trailer index](crate::Compressor::trailer_index), compressed frames are
+/// instead written to the underlying writer right away.
+pub struct CompressWriter<'d, W> {
+    inner: W,
+    encoder: FrameEncoder<'d>,
+    options: ArchiveOptions,
+    frame: Vec<u8>,
+    table: FrameTable,
+    compressed: Vec<u8>,
+    compressed_len: u64,
+    input_len: u64,
+    progress: ProgressInfo,
+    callback: Option<ProgressFn<'d>>,
+}
+
+/// The settings of the archive created by a [`CompressWriter`].
+pub(crate) struct ArchiveOptions {
+    pub frame_size: usize,
+    pub wide_offsets: bool,
+    pub checksum: Checksum,
+    pub trailer_index: bool,
+    pub compact_index: bool,
+    pub group_size: usize,
+    pub frame_alignment: usize,
+    pub metadata: Vec<(String, Vec<u8>)>,
+    pub dictionary: Option<Vec<u8>>,
+    pub dictionary_id: Option<u32>,
+}
+
+impl<'d, W: Write> CompressWriter<'d, W> {
+    pub(crate) fn new(
+        inner: W,
+        encoder: FrameEncoder<'d>,
+        options: ArchiveOptions,
+        callback: Option<ProgressFn<'d>>,
+    ) -> io::Result<Self> {
+        let mut writer = Self {
+            inner,
+            encoder,
+            frame: Vec::with_capacity(options.frame_size),
+            options,
+            table: FrameTable::new(),
+            compressed: Vec::new(),
+            compressed_len: 0,
+            input_len: 0,
+            progress: ProgressInfo::new(None, None),
+            callback,
+        };
+        if writer.options.trailer_index {
+            header(&writer.options, 0, 0).write_trailer_header(&mut writer.inner)?;
+        }
+        Ok(writer)
+    }
+
+    /// Compresses any remaining buffered input, and writes the archive to the
+    /// underlying writer, which is returned.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.frame.is_empty() {
+            self.flush_frame()?;
+        }
+
+        let header = header(&self.options, self.input_len, self.compressed_len);
+        if self.options.trailer_index {
+            header.write_trailer(&mut self.inner, &self.table, self.compressed_len)?;
+        } else {
+            header.write(&mut self.inner, &self.table)?;
+            self.inner.write_all(&self.compressed)?;
+        }
+        self.inner.flush()?;
+
+        Ok(self.inner)
+    }
+
+    pub(crate) fn frame_size(&self) -> usize {
+        self.options.frame_size
+    }
+
+    /// Returns the table of the frames compressed so far.
+    pub(crate) fn frame_table(&self) -> &FrameTable {
+        &self.table
+    }
+
+    /// Compresses the buffered input as the last frame, which is usually
+    /// only done by [`CompressWriter::finish`].
+    pub(crate) fn flush_partial_frame(&mut self) -> io::Result<()> {
+        if !self.frame.is_empty() {
+            self.flush_frame()?;
+        }
+        Ok(())
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    pub(crate) fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Reads `reader` to its end, reading whole frames directly into the frame buffer.
+    pub(crate) fn read_from<R: Read>(&mut self, mut reader: R) -> io::Result<()> {
+        loop {
+            let missing = self.options.frame_size - self.frame.len();
+            let read = (&mut reader)
+                .take(missing as u64)
+                .read_to_end(&mut self.frame)?;
+            self.input_len += read as u64;
+
+            if self.frame.len() < self.options.frame_size {
+                return Ok(());
+            }
+            self.flush_frame()?;
+        }
+    }
+
+    fn flush_frame(&mut self) -> io::Result<()> {
+        let frame = std::mem::take(&mut self.frame);
+        self.compress_frame(&frame)?;
+        self.frame = frame;
+        self.frame.clear();
+        Ok(())
+    }
+
+    fn compress_frame(&mut self, source: &[u8]) -> io::Result<()> {
+        let written_before = self.compressed.len();
+        let frame = self.encoder.compress_frame(source, &mut self.compressed)?;
+        let frame_len = self.compressed.len() - written_before;
+        self.progress
+            .frame_done(source.len(), frame_len, self.callback.as_mut())?;
+        let padding = format::padding(frame_len as u64, self.options.frame_alignment);
+        self.compressed.resize(self.compressed.len() + padding, 0);
+        let frame_len = frame_len + padding;
+        if self.options.trailer_index {
+            self.inner.write_all(&self.compressed)?;
+            self.compressed.clear();
+        }
+        self.compressed_len += frame_len as u64;
+        self.table.push(self.compressed_len, frame);
+        Ok(())
+    }
+}
+
+/// Returns the archive header for the given `options` and input and compressed lengths.
+fn header(options: &ArchiveOptions, input_len: u64, compressed_len: u64) -> format::Header<'_> {
+    format::Header {
+        frame_size: options.frame_size,
+        input_len,
+        wide: options.wide_offsets || format::needs_wide_offsets(input_len, compressed_len),
+        compact: options.compact_index,
+        group_size: options.group_size,
+        frame_alignment: options.frame_alignment,
+        checksum: options.checksum,
+        metadata: &options.metadata,
+        dictionary: options.dictionary.as_deref(),
+        dictionary_id: options.dictionary_id,
+    }
+}
+
+impl<W: Write> Write for CompressWriter<'_, W> {
+    fn write(&mut self, mut buf: &[u8]) -> io::Result<usize> {
+        let written = buf.len();
+        self.input_len += written as u64;
+
+        // fill up a partially buffered frame first
+        if !self.frame.is_empty() {
+            let missing = self.options.frame_size - self.frame.len();
+            let (head, tail) = buf.split_at(missing.min(buf.len()));
+            self.frame.extend_from_slice(head);
+            buf = tail;
+
+            if self.frame.len() == self.options.frame_size {
+                self.flush_frame()?;
+            }
+        }
+
+        // full frames can be compressed without copying them into our buffer
+        while buf.len() >= self.options.frame_size {
+            let (frame, tail) = buf.split_at(self.options.frame_size);
+            self.compress_frame(frame)?;
+            buf = tail;
+        }
+
+        self.frame.extend_from_slice(buf);
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Partial frames can not be flushed, and without a trailer index,
+        // nothing is written to `inner` until the writer is finished.
+        if self.options.trailer_index {
+            self.inner.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Compressor, Decompressor};
+
+    #[test]
+    fn test_writer() {
+        let input: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(64).compress(&input).unwrap();
+
+        let mut writer = Compressor::new().frame_size(64).writer(Vec::new()).unwrap();
+        for chunk in input.chunks(23) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.write_all(&[]).unwrap();
+        let written = writer.finish().unwrap();
+
+        assert_eq!(written, compressed);
+
+        let mut d = Decompressor::new(&written).unwrap();
+        assert_eq!(d.get(..).unwrap(), input);
+    }
+
+    #[test]
+    fn test_compress_reader() {
+        let input: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Compressor::new().frame_size(64).compress(&input).unwrap();
+
+        let reader = (&input[..100]).chain(&input[100..]);
+        let mut written = Vec::new();
+        Compressor::new()
+            .frame_size(64)
+            .compress_reader(reader, &mut written)
+            .unwrap();
+
+        assert_eq!(written, compressed);
+    }
+}
@@ -1,8 +1,28 @@
-use std::io::Cursor;
+use std::io::{self, Cursor, Write};
+use std::mem::MaybeUninit;
+
+use crate::pool::ZstdContext;
 
 pub use zstd::bulk::{Compressor, Decompressor};
 pub use zstd::compression_level_range;
-pub use zstd::zstd_safe::compress_bound;
+pub use zstd::dict::{DecoderDictionary, EncoderDictionary};
+pub use zstd::stream::read::Decoder as StreamDecoder;
+use zstd::zstd_safe::zstd_sys;
+pub use zstd::zstd_safe::{
+    compress_bound, find_frame_compressed_size, get_frame_content_size, version_string, CParameter,
+    ParamSwitch, Strategy, WriteBuf,
+};
+
+/// The magic bytes at the start of a regular `zstd` frame.
+pub const MAGIC: [u8; 4] = 0xFD2FB528u32.to_le_bytes();
+/// The maximum uncompressed size of a single block.
+const MAX_BLOCK_SIZE: usize = 128 * (1 << 10);
+/// The minimum window log supported by `zstd`.
+const MIN_WINDOW_LOG: u32 = 10;
+/// The minimum chain and hash logs supported by `zstd`.
+const MIN_TABLE_LOG: u32 = 6;
+const BLOCK_RAW: u32 = 0;
+const BLOCK_RLE: u32 = 1;
 
 pub fn spare_capacity_buf(buf: &mut Vec<u8>) -> Cursor<&mut Vec<u8>> {
     let pos = buf.len() as u64;
@@ -10,3 +30,246 @@ pub fn spare_capacity_buf(buf: &mut Vec<u8>) -> Cursor<&mut Vec<u8>> {
     cursor.set_position(pos);
     cursor
 }
+
+/// A [`WriteBuf`] over possibly uninitialized memory.
+struct UninitBuf<'a> {
+    buf: &'a mut [MaybeUninit<u8>],
+    /// The number of bytes at the start of `buf` that are initialized.
+    filled: usize,
+}
+
+// SAFETY: `filled` only covers bytes that `zstd` has actually written.
+unsafe impl WriteBuf for UninitBuf<'_> {
+    fn as_slice(&self) -> &[u8] {
+        // SAFETY: the first `filled` bytes are initialized.
+        unsafe { std::slice::from_raw_parts(self.buf.as_ptr().cast(), self.filled) }
+    }
+
+    fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.buf.as_mut_ptr().cast()
+    }
+
+    unsafe fn filled_until(&mut self, n: usize) {
+        self.filled = n;
+    }
+}
+
+/// Decompresses the frame `source` into the possibly uninitialized `buf`,
+/// returning the initialized part.
+pub fn decompress_uninit<'a>(
+    decompressor: &mut ZstdContext<'_, '_>,
+    source: &[u8],
+    buf: &'a mut [MaybeUninit<u8>],
+) -> io::Result<&'a mut [u8]> {
+    let mut destination = UninitBuf { buf, filled: 0 };
+    let written = decompressor.decompress_to_buffer(source, &mut destination)?;
+    // SAFETY: `zstd` has initialized the first `written` bytes.
+    Ok(unsafe { assume_init(&mut destination.buf[..written]) })
+}
+
+/// Copies `source` to the start of the possibly uninitialized `buf`, returning
+/// the initialized part.
+pub fn copy_uninit<'a>(buf: &'a mut [MaybeUninit<u8>], source: &[u8]) -> &'a mut [u8] {
+    let buf = &mut buf[..source.len()];
+    for (byte, &value) in buf.iter_mut().zip(source) {
+        byte.write(value);
+    }
+    // SAFETY: all bytes have just been initialized.
+    unsafe { assume_init(buf) }
+}
+
+/// Fills the possibly uninitialized `buf` with zeroes.
+pub fn zero_uninit(buf: &mut [MaybeUninit<u8>]) -> &mut [u8] {
+    buf.fill(MaybeUninit::new(0));
+    // SAFETY: all bytes have just been initialized.
+    unsafe { assume_init(buf) }
+}
+
+/// Views an initialized buffer as possibly uninitialized memory.
+pub fn as_uninit(buf: &mut [u8]) -> &mut [MaybeUninit<u8>] {
+    // SAFETY: `MaybeUninit<u8>` has the same layout as `u8`, and callers only
+    // ever write initialized bytes into the buffer.
+    unsafe { &mut *(buf as *mut [u8] as *mut [MaybeUninit<u8>]) }
+}
+
+/// # Safety
+///
+/// All bytes of `buf` have to be initialized.
+pub unsafe fn assume_init(buf: &mut [MaybeUninit<u8>]) -> &mut [u8] {
+    unsafe { &mut *(buf as *mut [MaybeUninit<u8>] as *mut [u8]) }
+}
+
+/// Returns the size of the compressed frame at the start of `frame`, which
+/// is written without magic bytes.
+///
+/// Returns `None` if the frame is malformed or truncated.
+pub fn frame_compressed_size(frame: &[u8]) -> Option<usize> {
+    let &descriptor = frame.first()?;
+    let single_segment = descriptor & 0x20 != 0;
+    let has_checksum = descriptor & 0x04 != 0;
+    let window_size = if single_segment { 0 } else { 1 };
+    let dict_id_size = [0, 1, 2, 4][(descriptor & 0x03) as usize];
+    let content_size_size = match descriptor >> 6 {
+        0 if single_segment => 1,
+        0 => 0,
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    };
+    let mut pos = 1 + window_size + dict_id_size + content_size_size;
+
+    loop {
+        let header = frame.get(pos..pos + 3)?;
+        let header = u32::from_le_bytes([header[0], header[1], header[2], 0]);
+        let last_block = header & 1 != 0;
+        let block_size = match (header >> 1) & 0x03 {
+            // raw and compressed blocks
+            0 | 2 => (header >> 3) as usize,
+            // RLE blocks
+            1 => 1,
+            _ => return None,
+        };
+        pos += 3 + block_size;
+        if last_block {
+            break;
+        }
+    }
+    if has_checksum {
+        pos += 4;
+    }
+
+    (pos <= frame.len()).then_some(pos)
+}
+
+/// Reduces the window, chain and hash logs for compression `level` and `params`
+/// until the estimated size of a compression context fits into `max_memory`.
+///
+/// Returns the parameters for the reduced logs, along with the estimated size.
+/// The logs are never reduced below the minimums supported by `zstd`, so the
+/// estimated size may still exceed a tiny `max_memory`.
+pub fn limit_memory(
+    level: i32,
+    params: &[CParameter],
+    max_memory: usize,
+) -> (Vec<CParameter>, usize) {
+    // the input size is unknown, so the parameters are not reduced for small inputs
+    // SAFETY: this only computes the parameters for the level.
+    let mut cparams = unsafe { zstd_sys::ZSTD_getCParams(level, u64::MAX, 0) };
+    for param in params {
+        match *param {
+            CParameter::WindowLog(log) => cparams.windowLog = log,
+            CParameter::ChainLog(log) => cparams.chainLog = log,
+            CParameter::HashLog(log) => cparams.hashLog = log,
+            CParameter::SearchLog(log) => cparams.searchLog = log,
+            CParameter::MinMatch(min_match) => cparams.minMatch = min_match,
+            CParameter::TargetLength(target_length) => cparams.targetLength = target_length,
+            CParameter::Strategy(strategy) => cparams.strategy = strategy,
+            _ => {}
+        }
+    }
+
+    let estimate = |cparams| {
+        // SAFETY: these only compute the size, invalid parameters result in an error code.
+        let size = unsafe { zstd_sys::ZSTD_estimateCCtxSize_usingCParams(cparams) };
+        if unsafe { zstd_sys::ZSTD_isError(size) } != 0 {
+            usize::MAX
+        } else {
+            size
+        }
+    };
+    let initial = cparams;
+    let mut size = estimate(cparams);
+    while size > max_memory {
+        // the window is reduced first, as it usually dominates the memory usage
+        let log = if cparams.windowLog > MIN_WINDOW_LOG
+            && cparams.windowLog >= cparams.chainLog.max(cparams.hashLog)
+        {
+            &mut cparams.windowLog
+        } else if cparams.chainLog > MIN_TABLE_LOG && cparams.chainLog >= cparams.hashLog {
+            &mut cparams.chainLog
+        } else if cparams.hashLog > MIN_TABLE_LOG {
+            &mut cparams.hashLog
+        } else if cparams.windowLog > MIN_WINDOW_LOG {
+            &mut cparams.windowLog
+        } else {
+            break;
+        };
+        *log -= 1;
+        size = estimate(cparams);
+    }
+    let mut params = Vec::new();
+    if cparams.windowLog != initial.windowLog {
+        params.push(CParameter::WindowLog(cparams.windowLog));
+    }
+    if cparams.chainLog != initial.chainLog {
+        params.push(CParameter::ChainLog(cparams.chainLog));
+    }
+    if cparams.hashLog != initial.hashLog {
+        params.push(CParameter::HashLog(cparams.hashLog));
+    }
+    (params, size)
+}
+
+/// Writes `source` as a regular `zstd` frame of uncompressed blocks.
+pub fn write_stored_frame<W: Write>(writer: &mut W, source: &[u8]) -> io::Result<()> {
+    write_frame_header(writer, source.len())?;
+    let mut blocks = source.chunks(MAX_BLOCK_SIZE).peekable();
+    if blocks.peek().is_none() {
+        return write_block_header(writer, BLOCK_RAW, 0, true);
+    }
+    while let Some(block) = blocks.next() {
+        write_block_header(writer, BLOCK_RAW, block.len(), blocks.peek().is_none())?;
+        writer.write_all(block)?;
+    }
+    Ok(())
+}
+
+/// Writes `len` zeroes as a regular `zstd` frame of RLE blocks.
+pub fn write_zero_frame<W: Write>(writer: &mut W, len: usize) -> io::Result<()> {
+    write_frame_header(writer, len)?;
+    let mut remaining = len;
+    loop {
+        let block_len = remaining.min(MAX_BLOCK_SIZE);
+        remaining -= block_len;
+        write_block_header(writer, BLOCK_RLE, block_len, remaining == 0)?;
+        writer.write_all(&[0])?;
+        if remaining == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// Writes the magic bytes and a single segment frame header with the given content size.
+fn write_frame_header<W: Write>(writer: &mut W, len: usize) -> io::Result<()> {
+    writer.write_all(&MAGIC)?;
+    let len = len as u64;
+    match len {
+        0..=0xff => writer.write_all(&[0x20, len as u8]),
+        0x100..=0x100ff => {
+            writer.write_all(&[0x60])?;
+            writer.write_all(&((len - 0x100) as u16).to_le_bytes())
+        }
+        0x10100..=0xffff_ffff => {
+            writer.write_all(&[0xa0])?;
+            writer.write_all(&(len as u32).to_le_bytes())
+        }
+        _ => {
+            writer.write_all(&[0xe0])?;
+            writer.write_all(&len.to_le_bytes())
+        }
+    }
+}
+
+fn write_block_header<W: Write>(
+    writer: &mut W,
+    block_type: u32,
+    len: usize,
+    last: bool,
+) -> io::Result<()> {
+    let header = (len as u32) << 3 | block_type << 1 | last as u32;
+    writer.write_all(&header.to_le_bytes()[..3])
+}
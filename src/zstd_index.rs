@@ -0,0 +1,221 @@
+use std::io;
+use std::ops::{Range, RangeBounds};
+
+use crate::{decode_failed, make_range, zstd, Error};
+
+/// The mask of the magic numbers of skippable frames, which range from
+/// `0x184D2A50` to `0x184D2A5F`.
+const SKIPPABLE_MAGIC_MASK: u32 = 0xFFFFFFF0;
+const SKIPPABLE_MAGIC: u32 = 0x184D2A50;
+
+/// A single frame of a [`ZstdIndex`].
+#[derive(Debug, Clone, Copy)]
+struct IndexedFrame {
+    /// The range of the compressed frame within the file.
+    compressed: (usize, usize),
+    /// The offset of the decompressed frame.
+    start: usize,
+}
+
+/// Random access to a regular `zstd` file consisting of multiple frames.
+///
+/// Files written by `zstd --rsyncable`, or by writers emitting one frame per
+/// record, already consist of many independent frames. Instead of recompressing
+/// them into an archive, this scans the frame headers once to build an index of
+/// all frames, and then serves random reads directly from the original file.
+///
+/// In contrast to an archive, frames can have arbitrary decompressed sizes.
+/// Frames that do not declare their decompressed size in their header have to
+/// be decompressed once while scanning. Skippable frames are ignored, and frames
+/// compressed with a dictionary are not supported.
+#[derive(Debug)]
+pub struct ZstdIndex<'b> {
+    bytes: &'b [u8],
+    frames: Vec<IndexedFrame>,
+    len: usize,
+    read_buf: Vec<u8>,
+}
+
+impl<'b> ZstdIndex<'b> {
+    /// Scans all the frames of `bytes`, returning `None` if it is not a valid
+    /// `zstd` file.
+    pub fn new(bytes: &'b [u8]) -> Option<Self> {
+        let mut frames = Vec::new();
+        let mut len = 0usize;
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let frame = &bytes[pos..];
+            let compressed_len = zstd::find_frame_compressed_size(frame).ok()?;
+            let (magic, _) = frame.split_first_chunk::<4>()?;
+            if u32::from_le_bytes(*magic) & SKIPPABLE_MAGIC_MASK == SKIPPABLE_MAGIC {
+                pos += compressed_len;
+                continue;
+            }
+
+            let frame = &frame[..compressed_len];
+            let decompressed_len = match zstd::get_frame_content_size(frame).ok()? {
+                Some(decompressed_len) => decompressed_len.try_into().ok()?,
+                None => {
+                    let decoder = zstd::StreamDecoder::with_buffer(frame).ok()?;
+                    io::copy(&mut decoder.single_frame(), &mut io::sink())
+                        .ok()?
+                        .try_into()
+                        .ok()?
+                }
+            };
+            frames.push(IndexedFrame {
+                compressed: (pos, pos + compressed_len),
+                start: len,
+            });
+            len = len.checked_add(decompressed_len)?;
+            pos += compressed_len;
+        }
+
+        Some(Self {
+            bytes,
+            frames,
+            len,
+            read_buf: Vec::new(),
+        })
+    }
+
+    /// The total decompressed size of all frames.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of `zstd` frames, not counting skippable frames.
+    pub fn num_frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn get<R>(&mut self, range: R) -> io::Result<Vec<u8>>
+    where
+        R: RangeBounds<usize>,
+    {
+        let mut buf = Vec::new();
+        self.get_into(&mut buf, range)?;
+        Ok(buf)
+    }
+
+    pub fn get_into<'o, R>(&mut self, buf: &'o mut Vec<u8>, range: R) -> io::Result<&'o [u8]>
+    where
+        R: RangeBounds<usize>,
+    {
+        let range = make_range(range, self.len)?;
+        self.read_into(buf, range)
+    }
+
+    fn read_into<'o>(&mut self, buf: &'o mut Vec<u8>, range: Range<usize>) -> io::Result<&'o [u8]> {
+        if range.start > range.end || range.end > self.len {
+            return Err(Error::RangeOutOfBounds {
+                range,
+                len: self.len,
+            }
+            .into());
+        }
+        buf.clear();
+        buf.reserve(range.len());
+
+        // the first frame ending after the start of the range
+        let first = self
+            .frames
+            .partition_point(|frame| frame.start <= range.start)
+            .saturating_sub(1);
+        let mut decompressor = zstd::Decompressor::new()?;
+
+        for (idx, frame) in self.frames.iter().enumerate().skip(first) {
+            if frame.start >= range.end {
+                break;
+            }
+            let frame_end = self.frame_end(idx);
+            let source = &self.bytes[frame.compressed.0..frame.compressed.1];
+            let compressed = frame.compressed.0 as u64..frame.compressed.1 as u64;
+            let failed = |err| decode_failed(idx, compressed.clone(), Some(err));
+
+            if frame.start >= range.start && frame_end <= range.end {
+                let mut destination = zstd::spare_capacity_buf(buf);
+                decompressor
+                    .decompress_to_buffer(source, &mut destination)
+                    .map_err(failed)?;
+            } else {
+                self.read_buf.clear();
+                self.read_buf.reserve(frame_end - frame.start);
+                let mut destination = zstd::spare_capacity_buf(&mut self.read_buf);
+                decompressor
+                    .decompress_to_buffer(source, &mut destination)
+                    .map_err(failed)?;
+
+                let start = range.start.saturating_sub(frame.start);
+                let end = range.end.min(frame_end) - frame.start;
+                let decompressed = self
+                    .read_buf
+                    .get(start..end)
+                    .ok_or_else(|| decode_failed(idx, compressed.clone(), None))?;
+                buf.extend_from_slice(decompressed);
+            }
+        }
+
+        Ok(buf.as_slice())
+    }
+
+    /// Returns the decompressed end offset of frame `idx`.
+    fn frame_end(&self, idx: usize) -> usize {
+        self.frames
+            .get(idx + 1)
+            .map_or(self.len, |frame| frame.start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_frame() {
+        let input: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut zst = Vec::new();
+        let boundaries = [0, 1, 5_000, 5_001, 40_000, 99_000, 100_000];
+        for (i, bounds) in boundaries.windows(2).enumerate() {
+            let frame = &input[bounds[0]..bounds[1]];
+            if i % 2 == 0 {
+                zst.extend(::zstd::encode_all(frame, 3).unwrap());
+            } else {
+                // a streaming encoder does not write the content size
+                let mut encoder = ::zstd::Encoder::new(Vec::new(), 3).unwrap();
+                io::Write::write_all(&mut encoder, frame).unwrap();
+                zst.extend(encoder.finish().unwrap());
+            }
+        }
+        // a skippable frame
+        zst.extend(0x184D2A5Au32.to_le_bytes());
+        zst.extend(3u32.to_le_bytes());
+        zst.extend([1, 2, 3]);
+        zst.extend(::zstd::encode_all(&[][..], 3).unwrap());
+
+        let mut index = ZstdIndex::new(&zst).unwrap();
+        assert_eq!(index.num_frames(), 7);
+        assert_eq!(index.len(), input.len());
+        assert_eq!(index.get(..).unwrap(), input);
+        for range in [
+            0..0,
+            0..1,
+            1..2,
+            4_000..6_000,
+            5_000..5_001,
+            1..99_999,
+            99_999..100_000,
+        ] {
+            assert_eq!(index.get(range.clone()).unwrap(), &input[range]);
+        }
+        assert!(index.get(99_999..100_001).is_err());
+
+        assert!(ZstdIndex::new(&zst[..zst.len() - 1]).is_none());
+        assert!(ZstdIndex::new(&[]).unwrap().is_empty());
+    }
+}